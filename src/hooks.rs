@@ -14,8 +14,10 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::io::Read;
-use std::process::Command;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
+use crate::jj::{JjTimeout, RepoContext, jj_command, run_jj};
 use crate::session::{SessionId, format_precommit_message};
 
 /// Output structure for injecting additional context into Claude
@@ -34,8 +36,14 @@ pub struct HookResponse {
     pub continue_execution: bool,
     #[serde(rename = "stopReason", skip_serializing_if = "Option::is_none")]
     pub stop_reason: Option<String>,
+    /// Stable code for the stop reason (e.g. NOT_AT_HEAD), for tooling
+    #[serde(rename = "stopReasonCode", skip_serializing_if = "Option::is_none")]
+    pub reason_code: Option<String>,
     #[serde(rename = "hookSpecificOutput", skip_serializing_if = "Option::is_none")]
     pub hook_specific_output: Option<HookSpecificOutput>,
+    /// Message Claude Code shows to the user
+    #[serde(rename = "systemMessage", skip_serializing_if = "Option::is_none")]
+    pub system_message: Option<String>,
 }
 
 impl HookResponse {
@@ -44,7 +52,9 @@ impl HookResponse {
         Self {
             continue_execution: true,
             stop_reason: None,
+            reason_code: None,
             hook_specific_output: None,
+            system_message: None,
         }
     }
 
@@ -53,10 +63,20 @@ impl HookResponse {
         Self {
             continue_execution: true,
             stop_reason: None,
+            reason_code: None,
             hook_specific_output: Some(HookSpecificOutput {
                 hook_event_name: hook_event_name.into(),
                 additional_context: context.into(),
             }),
+            system_message: None,
+        }
+    }
+
+    /// Create a response that shows the user a message and continues
+    pub fn with_system_message(message: impl Into<String>) -> Self {
+        Self {
+            system_message: Some(message.into()),
+            ..Self::continue_execution()
         }
     }
 
@@ -65,8 +85,23 @@ impl HookResponse {
         Self {
             continue_execution: false,
             stop_reason: Some(reason.into()),
+            reason_code: None,
             hook_specific_output: None,
+            system_message: None,
+        }
+    }
+
+    /// Create a response that stops execution for a hook error,
+    /// with its stopReasonCode when the error is a HookStop
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        let mut response = Self::stop(error.to_string());
+        response.reason_code = match error.downcast_ref::<HookStop>() {
+            Some(stop) => Some(stop.code),
+            None if error.downcast_ref::<JjTimeout>().is_some() => Some(HookStop::JJ_TIMEOUT),
+            None => None,
         }
+        .map(str::to_string);
+        response
     }
 
     /// Output this response as JSON to stdout
@@ -77,6 +112,73 @@ impl HookResponse {
     }
 }
 
+/// A hook failure with a stable code, so tooling can tell stop reasons apart
+/// without matching on the message
+#[derive(Debug)]
+pub struct HookStop {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl HookStop {
+    /// @ has descendants
+    pub const NOT_AT_HEAD: &'static str = "NOT_AT_HEAD";
+    /// @ has unresolved conflicts
+    pub const HAS_CONFLICTS: &'static str = "HAS_CONFLICTS";
+    /// @ is itself a session change
+    pub const ON_SESSION_CHANGE: &'static str = "ON_SESSION_CHANGE";
+    /// Another session held the working copy lock until the timeout
+    pub const CONCURRENT_SESSION: &'static str = "CONCURRENT_SESSION";
+    /// @ doesn't resolve to exactly one commit (e.g. it is divergent)
+    pub const AMBIGUOUS_WORKING_COPY: &'static str = "AMBIGUOUS_WORKING_COPY";
+    /// A jj command failed unexpectedly
+    pub const JJ_FAILED: &'static str = "JJ_FAILED";
+    /// A jj command ran past JJAGENT_JJ_TIMEOUT_SECS and was killed
+    pub const JJ_TIMEOUT: &'static str = "JJ_TIMEOUT";
+
+    pub fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for HookStop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for HookStop {}
+
+/// How much the hooks report on stderr. Warnings and errors are printed either way
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Verbosity {
+    #[default]
+    Normal,
+    /// Skip informational messages, set with `--quiet` or JJAGENT_QUIET=1
+    Quiet,
+}
+
+impl Verbosity {
+    /// Quiet if JJAGENT_QUIET=1, normal otherwise
+    pub fn from_env() -> Self {
+        if std::env::var("JJAGENT_QUIET").unwrap_or_default() == "1" {
+            Self::Quiet
+        } else {
+            Self::Normal
+        }
+    }
+
+    /// Print an informational message to stderr, unless quiet
+    pub fn info(self, message: impl std::fmt::Display) {
+        if self == Self::Normal {
+            eprintln!("jjagent: {}", message);
+        }
+    }
+}
+
 /// Input structure for Claude Code hooks
 #[derive(Debug, Deserialize)]
 pub struct HookInput {
@@ -87,9 +189,21 @@ pub struct HookInput {
     pub hook_event_name: Option<String>,
     #[serde(default)]
     pub transcript_path: Option<String>,
+    /// The directory Claude is working in, which may not be where the hook was started
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// Not part of Claude's input: set by the caller from `--quiet` / JJAGENT_QUIET
+    #[serde(skip)]
+    pub verbosity: Verbosity,
 }
 
 impl HookInput {
+    /// Resolve the repo the hook should act on: the one containing `cwd` if Claude sent it,
+    /// otherwise the one containing the current directory
+    pub fn repo_context(&self) -> Option<RepoContext> {
+        RepoContext::discover_in(self.cwd.as_deref().map(Path::new))
+    }
+
     /// Read hook input from stdin
     pub fn from_stdin() -> Result<Self> {
         let mut buffer = String::new();
@@ -97,113 +211,253 @@ impl HookInput {
             .read_to_string(&mut buffer)
             .context("Failed to read hook input from stdin")?;
 
-        serde_json::from_str(&buffer).context("Failed to parse hook input JSON")
+        Self::from_json(&buffer)
+    }
+
+    /// Parse hook input from a JSON string
+    /// On failure, explains what's wrong with the input rather than returning serde's terse error
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).map_err(|e| describe_invalid_input(json, e))
+    }
+}
+
+/// Turn a hook input parse failure into a targeted error message
+fn describe_invalid_input(json: &str, error: serde_json::Error) -> anyhow::Error {
+    let value: serde_json::Value = match serde_json::from_str(json) {
+        Ok(value) => value,
+        Err(_) if json.trim().is_empty() => {
+            return anyhow::anyhow!("Hook input is empty, expected a JSON object on stdin");
+        }
+        Err(_) => return anyhow::anyhow!("Hook input is not valid JSON: {}", error),
+    };
+
+    let Some(object) = value.as_object() else {
+        return anyhow::anyhow!(
+            "Hook input must be a JSON object, got {}",
+            json_type_name(&value)
+        );
+    };
+
+    match object.get("session_id") {
+        None => anyhow::anyhow!("Hook input is missing required string field 'session_id'"),
+        Some(v) if !v.is_string() => anyhow::anyhow!(
+            "Hook input field 'session_id' must be a string, got {}",
+            json_type_name(v)
+        ),
+        Some(_) => {
+            // session_id is fine, so an optional field has the wrong type
+            for field in ["tool_name", "hook_event_name", "transcript_path", "cwd"] {
+                if let Some(v) = object.get(field)
+                    && !v.is_string()
+                    && !v.is_null()
+                {
+                    return anyhow::anyhow!(
+                        "Hook input field '{}' must be a string, got {}",
+                        field,
+                        json_type_name(v)
+                    );
+                }
+            }
+            anyhow::anyhow!("Failed to parse hook input JSON: {}", error)
+        }
+    }
+}
+
+fn json_type_name(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "a boolean",
+        serde_json::Value::Number(_) => "a number",
+        serde_json::Value::String(_) => "a string",
+        serde_json::Value::Array(_) => "an array",
+        serde_json::Value::Object(_) => "an object",
     }
 }
 
-/// Handle PreToolUse hook - acquires lock and creates a new precommit change
+/// Tools the hooks act on when JJAGENT_TRACK_TOOLS is unset
+pub const DEFAULT_TRACK_TOOLS: &str = "Edit,MultiEdit,Write,NotebookEdit,Bash";
+
+/// Whether a tool list (as in JJAGENT_TRACK_TOOLS) covers `tool_name`
+/// Entries are separated by `,` or `|`. `*` allows every tool and `!Name` denies one,
+/// taking precedence over any allow. A list of denials alone applies them to the defaults
+pub fn tool_is_tracked(list: &str, tool_name: &str) -> bool {
+    let entries = list
+        .split([',', '|'])
+        .map(str::trim)
+        .filter(|e| !e.is_empty());
+    let mut allowed = false;
+    let mut has_allow = false;
+    let mut has_deny = false;
+    for entry in entries {
+        match entry.strip_prefix('!') {
+            Some(denied) if denied.trim() == tool_name => return false,
+            Some(_) => has_deny = true,
+            None => {
+                has_allow = true;
+                allowed |= entry == "*" || entry == tool_name;
+            }
+        }
+    }
+    if has_deny && !has_allow {
+        return tool_is_tracked(DEFAULT_TRACK_TOOLS, tool_name);
+    }
+    allowed
+}
+
+/// Whether the hooks should act on this tool, per JJAGENT_TRACK_TOOLS
+/// Input without a tool name (e.g. from a replay) is always tracked
+fn should_track_tool(tool_name: Option<&str>) -> bool {
+    let Some(tool_name) = tool_name else {
+        return true;
+    };
+    let list = std::env::var("JJAGENT_TRACK_TOOLS")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_TRACK_TOOLS.to_string());
+    tool_is_tracked(&list, tool_name)
+}
+
+/// Handle PreToolUse hook - acquires lock and creates a new precommit change, or reuses
+/// the one on @ if this session already has it there
 pub fn handle_pretool_hook(input: HookInput) -> Result<()> {
-    // Check if we're in a jj repo - if not, this is a noop
-    if !crate::jj::is_jj_repo() {
-        eprintln!("jjagent: Not in a jj repository, skipping hook");
+    // Tools that never touch files don't need a precommit (or the lock)
+    if !should_track_tool(input.tool_name.as_deref()) {
         return Ok(());
     }
 
+    // Check if we're in a jj repo - if not, this is a noop
+    let Some(ctx) = input.repo_context() else {
+        input
+            .verbosity
+            .info("Not in a jj repository, skipping hook");
+        return Ok(());
+    };
+    let release_lock =
+        || crate::lock::release_lock_in(&input.session_id, &ctx.root, input.verbosity);
+
     // Acquire lock first - this will be held until PostToolUse/Stop
-    crate::lock::acquire_lock(&input.session_id).context("Failed to acquire working copy lock")?;
+    // Its errors already say what went wrong with the lock, so they're passed through as-is
+    crate::lock::acquire_lock_in(&input.session_id, &ctx.root, input.verbosity)
+        .map_err(|e| HookStop::new(HookStop::CONCURRENT_SESSION, format!("{:#}", e)))?;
 
     // Update stale working copy to sync with any operations that happened while waiting for lock
     // This is critical with watchman auto-snapshot to avoid divergence
-    let _output = Command::new("jj")
-        .args(["workspace", "update-stale"])
-        .output()
-        .context("Failed to update stale working copy")?;
+    let _output = run_jj(
+        jj_command()
+            .current_dir(&ctx.root)
+            .args(["workspace", "update-stale"]),
+    )
+    // Release lock on error, e.g. when jj timed out
+    .inspect_err(|_| {
+        let _ = release_lock();
+    })
+    .context("Failed to update stale working copy")?;
 
     // Note: update-stale succeeds with "Working copy already up to date" if not stale
     // so we don't need to check the output
 
+    // Invariant check: ensure @ resolves to exactly one commit before inspecting it
+    if let Err(e) = crate::jj::ensure_single_commit_in("@", ctx.path()) {
+        // Release lock on error
+        let _ = release_lock();
+        return Err(HookStop::new(HookStop::AMBIGUOUS_WORKING_COPY, e.to_string()).into());
+    }
+
+    // Gather the remaining invariants in one jj invocation, since this runs before every edit
+    let invariants = match crate::jj::check_pretool_invariants_in(ctx.path()) {
+        Ok(invariants) => invariants,
+        Err(e) => {
+            // Release lock on error
+            let _ = release_lock();
+            return Err(HookStop::new(
+                HookStop::JJ_FAILED,
+                format!("Failed to check working copy invariants: {}", e),
+            )
+            .into());
+        }
+    };
+
     // Invariant check: ensure we're not on a session change (has Claude-session-id trailer)
     // This prevents Claude from working directly on a session change
-    match crate::jj::get_current_commit_session_id() {
-        Ok(Some(session_id)) => {
-            // Release lock on error
-            let _ = crate::lock::release_lock(&input.session_id);
-            anyhow::bail!(
+    if let Some(session_id) = &invariants.session_id {
+        // Release lock on error
+        let _ = release_lock();
+        return Err(HookStop::new(
+            HookStop::ON_SESSION_CHANGE,
+            format!(
                 "Working copy (@) is a session change with Claude-session-id: {}. \
                  Cannot work directly on a session change. Please move to a different change.",
                 session_id
-            );
-        }
-        Err(e) => {
-            // Release lock on error
-            let _ = crate::lock::release_lock(&input.session_id);
-            anyhow::bail!(
-                "Failed to check if current commit is a session change: {}",
-                e
-            );
-        }
-        Ok(None) => {
-            // All good, we're not on a session change
-        }
+            ),
+        )
+        .into());
     }
 
     // Invariant check: ensure we're at a head (no descendants) before creating a new change
     // This prevents branching which jjagent aims to avoid
-    match crate::jj::is_at_head() {
-        Ok(false) => {
-            // Release lock on error
-            let _ = crate::lock::release_lock(&input.session_id);
-            anyhow::bail!(
-                "Working copy (@) is not at a head - it has descendants. \
-                 jjagent requires a linear history. Please resolve this before continuing."
-            );
-        }
-        Err(e) => {
-            // Release lock on error
-            let _ = crate::lock::release_lock(&input.session_id);
-            anyhow::bail!("Failed to check if at head: {}", e);
-        }
-        Ok(true) => {
-            // All good, we're at a head
-        }
+    if !invariants.at_head {
+        // Release lock on error
+        let _ = release_lock();
+        return Err(HookStop::new(
+            HookStop::NOT_AT_HEAD,
+            "Working copy (@) is not at a head - it has descendants. \
+             jjagent requires a linear history. Please resolve this before continuing.",
+        )
+        .into());
     }
 
     // Invariant check: ensure there are no conflicts in the working copy
     // This prevents Claude from working on a conflicted state
-    match crate::jj::has_conflicts() {
-        Ok(true) => {
-            // Release lock on error
-            let _ = crate::lock::release_lock(&input.session_id);
-            anyhow::bail!(
-                "Working copy (@) has conflicts. \
-                 Please resolve all conflicts before continuing."
-            );
-        }
-        Err(e) => {
-            // Release lock on error
-            let _ = crate::lock::release_lock(&input.session_id);
-            anyhow::bail!("Failed to check for conflicts: {}", e);
-        }
-        Ok(false) => {
-            // All good, no conflicts
-        }
+    if invariants.has_conflicts {
+        // Release lock on error
+        let _ = release_lock();
+        return Err(HookStop::new(
+            HookStop::HAS_CONFLICTS,
+            "Working copy (@) has conflicts. \
+             Please resolve all conflicts before continuing.",
+        )
+        .into());
+    }
+
+    // A second PreToolUse before PostToolUse (e.g. the first tool call was interrupted) keeps
+    // using the precommit that's already there instead of stacking another one on it
+    if invariants.precommit_session_id.as_deref() == Some(input.session_id.as_str()) {
+        input
+            .verbosity
+            .info("@ is already this session's precommit, reusing it");
+        return Ok(());
     }
 
     let session_id = SessionId::from_full(&input.session_id);
     let commit_message = format_precommit_message(&session_id);
 
-    let output = Command::new("jj")
-        .args(["new", "-m", &commit_message])
-        .output()
-        .context("Failed to execute jj new command")?;
+    if is_dry_run() {
+        report_dry_run("PreToolUse", &session_id, &["new", "-m", &commit_message]);
+        return Ok(());
+    }
+
+    let output = run_jj(
+        jj_command()
+            .current_dir(&ctx.root)
+            .args(["new", "-m", &commit_message]),
+    )
+    // Release lock on error, e.g. when jj timed out
+    .inspect_err(|_| {
+        let _ = release_lock();
+    })
+    .context("Failed to execute jj new command")?;
 
     if !output.status.success() {
         // Release lock on error
-        let _ = crate::lock::release_lock(&input.session_id);
-        anyhow::bail!(
-            "jj new command failed: {}",
-            String::from_utf8_lossy(&output.stderr)
-        );
+        let _ = release_lock();
+        return Err(HookStop::new(
+            HookStop::JJ_FAILED,
+            format!(
+                "jj new command failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            ),
+        )
+        .into());
     }
 
     // Lock remains held until PostToolUse or Stop
@@ -212,92 +466,352 @@ pub fn handle_pretool_hook(input: HookInput) -> Result<()> {
 
 /// Finalize a precommit by squashing it into the session change
 /// 1. Verifies @ is a precommit for this session (noop if not)
-/// 2. Finds or creates session change
+/// 2. Finds or creates session change, titled after the latest prompt in the transcript
 /// 3. Attempts to squash precommit into session
 /// 4. If conflicts occur, handles them by creating a new session part
-fn finalize_precommit(session_id: SessionId) -> Result<()> {
+/// 5. Records the tool in the Claude-tools trailer when JJAGENT_RECORD_TOOLS=1
+fn finalize_precommit(
+    ctx: &RepoContext,
+    session_id: SessionId,
+    tool_name: Option<&str>,
+    transcript_path: Option<&str>,
+) -> Result<()> {
+    let repo_path = ctx.path();
+
     // Update stale working copy before any jj operations
     // This prevents "stale working copy" errors during squash operations
     // especially when file watchers create automatic snapshots
-    let _output = Command::new("jj")
-        .args(["workspace", "update-stale"])
-        .output()
-        .context("Failed to update stale working copy")?;
+    let _output = run_jj(
+        jj_command()
+            .current_dir(&ctx.root)
+            .args(["workspace", "update-stale"]),
+    )
+    .context("Failed to update stale working copy")?;
 
     // Invariant check: ensure there are no conflicts in the working copy
     // This prevents finalizing changes with unresolved conflicts
-    if crate::jj::has_conflicts()? {
+    if crate::jj::has_conflicts_in(repo_path)? {
         anyhow::bail!(
             "Working copy (@) has conflicts. \
              Cannot finalize changes until conflicts are resolved."
         );
     }
 
+    // Invariant check: ensure @ resolves to exactly one commit before inspecting it
+    crate::jj::ensure_single_commit_in("@", repo_path)?;
+
     // Verify @ is a precommit for this session
     // If not (different session or not a precommit), this is a noop
-    if !crate::jj::is_current_commit_precommit_for_session(session_id.full())? {
+    if !crate::jj::is_current_commit_precommit_for_session_in(session_id.full(), repo_path)? {
         return Ok(());
     }
 
+    // Invariant check: uwc (@-) must be a single commit so it can be restored after the squash
+    crate::jj::ensure_single_commit_in("@-", repo_path)?;
+
+    // Keep excluded paths (e.g. .env) out of the session by leaving their changes in uwc
+    if let Ok(value) = std::env::var("JJAGENT_EXCLUDE_PATHS") {
+        let patterns = crate::jj::parse_exclude_patterns(&value);
+        let touched = crate::jj::move_excluded_paths_to_uwc_in(&patterns, repo_path)?;
+        if !touched.is_empty() {
+            eprintln!(
+                "jjagent: Warning - excluded path(s) edited, keeping them out of the session change: {}",
+                touched.join(", ")
+            );
+        }
+    }
+
     // Check if session change exists anywhere (not just in descendants)
-    let session_change = crate::jj::find_session_change_anywhere(session_id.full())?;
-    if session_change.is_none() {
-        crate::jj::create_session_change(&session_id)?;
+    if !crate::jj::session_exists_in(session_id.full(), repo_path)? {
+        // Title a new session after the prompt that started it, unless a title template is set
+        let template_set =
+            std::env::var("JJAGENT_SESSION_MESSAGE_TEMPLATE").is_ok_and(|t| !t.trim().is_empty());
+        let title = transcript_path
+            .filter(|_| !template_set)
+            .and_then(|path| crate::session::summarize_prompt(std::path::Path::new(path)));
+        // Optionally date the session change when the edit started rather than now
+        let author_timestamp =
+            if std::env::var("JJAGENT_PRESERVE_TIMESTAMP").unwrap_or_default() == "1" {
+                Some(crate::jj::get_author_timestamp_in("@", repo_path)?)
+            } else {
+                None
+            };
+        // Users with stacked working changes can place the session below a named base
+        let base = std::env::var("JJAGENT_SESSION_BASE")
+            .ok()
+            .filter(|base| !base.trim().is_empty());
+        crate::jj::create_session_change_titled_in(
+            &session_id,
+            title.as_deref(),
+            author_timestamp.as_deref(),
+            base.as_deref(),
+            repo_path,
+        )?;
     }
 
-    // Find the session change (either existing or just created)
-    let session_change_id = crate::jj::find_session_change_anywhere(session_id.full())?
-        .context("Session change should exist")?;
+    // Find the session's furthest part (or the change just created), so new edits
+    // land in the latest part rather than whichever part jj happens to list first
+    let session_change_id =
+        crate::jj::find_furthest_session_change_in(session_id.full(), repo_path)?
+            .context("Session change should exist")?
+            .change_id;
+
+    // Experimental: attribute hunks to the session parts that last touched them first
+    let strategy = match std::env::var("JJAGENT_SQUASH_STRATEGY") {
+        Ok(value) => crate::jj::SquashStrategy::parse(&value).unwrap_or_else(|e| {
+            eprintln!("jjagent: Warning - {}, using squash", e);
+            crate::jj::SquashStrategy::Squash
+        }),
+        Err(_) => crate::jj::SquashStrategy::Squash,
+    };
+    if strategy == crate::jj::SquashStrategy::Absorb
+        && !crate::jj::absorb_precommit_into_session_in(session_id.full(), repo_path)?
+    {
+        eprintln!("jjagent: Warning - jj absorb was skipped or undone, squashing instead");
+    }
 
-    // Get change IDs
+    // Get full change IDs, matching the form returned by find_session_change_anywhere
     // @ is currently at precommit (from pretool hook)
-    let precommit_id = crate::jj::get_change_id("@")?;
-    let uwc_id = crate::jj::get_change_id("@-")?;
+    let precommit_id = crate::jj::get_full_change_id_in("@", repo_path)?;
+    let uwc_id = crate::jj::get_full_change_id_in("@-", repo_path)?;
+
+    // Optionally keep notes written on the precommit, which the squash would otherwise drop
+    let precommit_notes =
+        if std::env::var("JJAGENT_KEEP_PRECOMMIT_NOTES").unwrap_or_default() == "1" {
+            crate::jj::precommit_notes(&crate::jj::get_commit_description_bytes_in(
+                &precommit_id,
+                repo_path,
+            )?)
+        } else {
+            None
+        };
+
+    // Remember where to roll back to if the squash introduces conflicts
+    let operation_before_squash = crate::jj::current_operation_id_in(repo_path)?;
 
     // Attempt to squash precommit into session
-    let new_conflicts =
-        crate::jj::squash_precommit_into_session(&precommit_id, &session_change_id, &uwc_id)?;
+    let new_conflicts = crate::jj::squash_precommit_into_session_in(
+        &precommit_id,
+        &session_change_id,
+        &uwc_id,
+        repo_path,
+    )?;
 
     // If conflicts were introduced, handle them
     if new_conflicts {
-        // Count existing session parts to determine the next part number
-        let existing_parts = crate::jj::count_session_parts(session_id.full())?;
-        let next_part = existing_parts + 1;
+        // Number the new part past the highest existing one, even if some were abandoned
+        let next_part = crate::jj::next_session_part_in(session_id.full(), repo_path)?;
+
+        // Record what conflicted while the squash is still in place, only if anyone's listening
+        // Logging is best-effort: a failure here must not stop the rollback below
+        let logger = crate::logger::logger();
+        let conflicted = if logger.is_enabled() {
+            crate::jj::list_conflicts_with_files_in(&session_change_id, repo_path)
+                .inspect_err(|e| {
+                    eprintln!(
+                        "jjagent: Warning - failed to collect conflict details: {}",
+                        e
+                    )
+                })
+                .ok()
+        } else {
+            None
+        };
+
+        crate::jj::handle_squash_conflicts_in(
+            &session_id,
+            next_part,
+            &uwc_id,
+            &operation_before_squash,
+            repo_path,
+        )?;
+
+        // The squash has been undone, so this is the count from before it
+        if let Some((conflicts_after, conflicted_files)) = conflicted
+            && let Ok(conflicts_before) =
+                crate::jj::count_conflicts_in(&session_change_id, repo_path)
+        {
+            logger.log_conflict(
+                session_id.full(),
+                next_part,
+                conflicts_before,
+                conflicts_after,
+                &conflicted_files,
+            );
+        }
+    }
 
-        crate::jj::handle_squash_conflicts(&session_id, next_part)?;
+    // The edits ended up in the latest part: the session change, or a new part on conflicts
+    if let Some(notes) = precommit_notes {
+        let part = crate::jj::find_furthest_session_change_in(session_id.full(), repo_path)?
+            .context("Session change should exist")?;
+        crate::jj::append_description_notes_in(&part.change_id, &notes, repo_path)?;
+    }
+
+    // Optionally record which tool produced this edit, for `session list --tool`
+    if let Some(tool_name) = tool_name
+        && std::env::var("JJAGENT_RECORD_TOOLS").unwrap_or_default() == "1"
+    {
+        crate::jj::record_session_tool_in(session_id.full(), tool_name, repo_path)?;
+    }
+
+    // Optionally keep git refs in sync for tools that read .git in a colocated repo
+    if std::env::var("JJAGENT_GIT_EXPORT").unwrap_or_default() == "1"
+        && ctx.is_colocated()
+        && let Err(e) = crate::jj::git_export_in(repo_path)
+    {
+        eprintln!("jjagent: Warning - {}", e);
     }
 
     Ok(())
 }
 
+/// How often to check the operation log while waiting for a file watcher
+const WATCHER_POLL_MS: u64 = 20;
+
+/// Wait up to `max_delay_ms` for a file watcher's snapshots to settle
+/// Returns immediately when fsmonitor isn't watchman, since nothing snapshots behind our back.
+/// Otherwise polls the operation log until it stops changing
+fn wait_for_file_watcher(ctx: &RepoContext, max_delay_ms: u64) {
+    if max_delay_ms == 0 {
+        return;
+    }
+    if ctx
+        .fsmonitor_backend()
+        .is_ok_and(|backend| backend != Some("watchman"))
+    {
+        return;
+    }
+
+    let deadline = Instant::now() + Duration::from_millis(max_delay_ms);
+    let mut last_operation = crate::jj::current_operation_id_in(ctx.path()).ok();
+    while Instant::now() < deadline {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        std::thread::sleep(remaining.min(Duration::from_millis(WATCHER_POLL_MS)));
+        let operation = crate::jj::current_operation_id_in(ctx.path()).ok();
+        if operation.is_some() && operation == last_operation {
+            return;
+        }
+        last_operation = operation;
+    }
+}
+
+/// Whether JJAGENT_DRY_RUN=1 is set, in which case hooks only report their mutating jj commands
+fn is_dry_run() -> bool {
+    std::env::var("JJAGENT_DRY_RUN").unwrap_or_default() == "1"
+}
+
+/// Report a jj command skipped in dry-run mode, on stderr and in the log
+fn report_dry_run(hook: &str, session_id: &SessionId, args: &[&str]) {
+    let shown: Vec<String> = args
+        .iter()
+        .map(|arg| {
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                format!("{:?}", arg)
+            } else {
+                arg.to_string()
+            }
+        })
+        .collect();
+    eprintln!("jjagent: Dry run - would run: jj {}", shown.join(" "));
+    let command: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    crate::logger::logger().log_dry_run(hook, session_id.full(), &command);
+}
+
+/// Report the jj commands finalize_precommit would run, using read-only queries only
+/// In a dry run PreToolUse never creates the precommit, so @ stands in for uwc unless
+/// it already is a precommit for this session
+fn preview_finalize_precommit(ctx: &RepoContext, session_id: &SessionId) -> Result<()> {
+    let repo_path = ctx.path();
+    let uwc =
+        if crate::jj::is_current_commit_precommit_for_session_in(session_id.full(), repo_path)? {
+            "@-"
+        } else {
+            "@"
+        };
+
+    let session_change =
+        match crate::jj::find_session_change_anywhere_in(session_id.full(), repo_path)? {
+            Some(change) => change.change_id,
+            None => {
+                let message = crate::session::format_session_message(session_id);
+                report_dry_run(
+                    "PostToolUse",
+                    session_id,
+                    &["new", "--insert-before", "@-", "--no-edit", "-m", &message],
+                );
+                "<new session change>".to_string()
+            }
+        };
+
+    let uwc_description = crate::jj::get_commit_description_in(uwc, repo_path)?;
+    report_dry_run(
+        "PostToolUse",
+        session_id,
+        &[
+            "squash",
+            "--into",
+            &session_change,
+            "--use-destination-message",
+        ],
+    );
+    report_dry_run(
+        "PostToolUse",
+        session_id,
+        &[
+            "squash",
+            "--from",
+            "@-",
+            "--into",
+            "@",
+            "-m",
+            &uwc_description,
+        ],
+    );
+    Ok(())
+}
+
 /// Handle PostToolUse hook - squashes changes and manages conflicts, then releases lock
 pub fn handle_posttool_hook(input: HookInput) -> Result<()> {
-    // Check if we're in a jj repo - if not, this is a noop
-    if !crate::jj::is_jj_repo() {
-        eprintln!("jjagent: Not in a jj repository, skipping hook");
+    // PreToolUse skipped this tool too, so there's no precommit to finalize or lock to release
+    if !should_track_tool(input.tool_name.as_deref()) {
         return Ok(());
     }
 
+    // Check if we're in a jj repo - if not, this is a noop
+    let Some(ctx) = input.repo_context() else {
+        input
+            .verbosity
+            .info("Not in a jj repository, skipping hook");
+        return Ok(());
+    };
+
     let session_id = SessionId::from_full(&input.session_id);
 
-    // Small delay to allow file watchers (watchman, fsmonitor) to complete their snapshots
+    // Give file watchers (watchman) time to finish their snapshots
     // This reduces the chance of concurrent operations creating divergent operation log branches
     // that can interfere with linearization and squashing
-    // Configurable via JJAGENT_POSTTOOL_DELAY_MS (default: 100ms)
-    let delay_ms = std::env::var("JJAGENT_POSTTOOL_DELAY_MS")
+    // Bounded by JJAGENT_POSTTOOL_DELAY_MS (default: 100ms)
+    let max_delay_ms = std::env::var("JJAGENT_POSTTOOL_DELAY_MS")
         .ok()
         .and_then(|s| s.parse::<u64>().ok())
         .unwrap_or(100);
-
-    if delay_ms > 0 {
-        std::thread::sleep(std::time::Duration::from_millis(delay_ms));
-    }
+    wait_for_file_watcher(&ctx, max_delay_ms);
 
     // Do the actual work
-    let result = finalize_precommit(session_id);
+    let result = if is_dry_run() {
+        preview_finalize_precommit(&ctx, &session_id)
+    } else {
+        finalize_precommit(
+            &ctx,
+            session_id,
+            input.tool_name.as_deref(),
+            input.transcript_path.as_deref(),
+        )
+    };
 
     // Always release lock, even on error
-    match crate::lock::release_lock(&input.session_id) {
+    match crate::lock::release_lock_in(&input.session_id, &ctx.root, input.verbosity) {
         Ok(()) => result,
         Err(e) => {
             eprintln!("jjagent: Warning - failed to release lock: {}", e);
@@ -306,24 +820,64 @@ pub fn handle_posttool_hook(input: HookInput) -> Result<()> {
     }
 }
 
+/// Whether this session holds the working copy lock, for hooks that finalize outside a tool call
+/// Returns None while another session holds a fresh lock: its tool is running, and @ can't be
+/// this session's precommit meanwhile, so the caller should leave jj alone
+/// A stale lock held by another session, or an unreadable one, counts as not owned
+fn lock_ownership(ctx: &RepoContext, session_id: &SessionId) -> Option<bool> {
+    match crate::lock::current_holder(&ctx.root).unwrap_or(None) {
+        Some(holder) if holder.session_id != session_id.full() => {
+            let holder_id = SessionId::from_full(&holder.session_id);
+            if !crate::lock::is_stale(holder.age_seconds) {
+                eprintln!(
+                    "jjagent: Warning - session {} holds the working copy lock, not finalizing or releasing it",
+                    holder_id.short()
+                );
+                return None;
+            }
+            eprintln!(
+                "jjagent: Warning - stale working copy lock held by session {}, leaving it for the next PreToolUse",
+                holder_id.short()
+            );
+            Some(false)
+        }
+        Some(_) => Some(true),
+        None => Some(false),
+    }
+}
+
 /// Handle Stop hook - finalizes any precommit and releases lock
 /// This hook runs when Claude exits (normally or interrupted).
 /// If @ is a precommit for this session, it finalizes the changes.
 /// Otherwise, it's a noop (user is already on uwc or another session is active).
+/// The lock is only released if this session holds it; while another session holds a
+/// fresh lock, nothing is done at all.
 pub fn handle_stop_hook(input: HookInput) -> Result<()> {
     // Check if we're in a jj repo - if not, this is a noop
-    if !crate::jj::is_jj_repo() {
-        eprintln!("jjagent: Not in a jj repository, skipping hook");
+    let Some(ctx) = input.repo_context() else {
+        input
+            .verbosity
+            .info("Not in a jj repository, skipping hook");
         return Ok(());
-    }
+    };
 
     let session_id = SessionId::from_full(&input.session_id);
 
+    // Only release a lock this session holds. Usually PostToolUse already released it, and
+    // a lock held by another session belongs to an edit still in progress
+    let Some(owns_lock) = lock_ownership(&ctx, &session_id) else {
+        return Ok(());
+    };
+
     // Do the actual work
-    let result = finalize_precommit(session_id);
+    let result = finalize_precommit(&ctx, session_id, None, input.transcript_path.as_deref());
+
+    if !owns_lock {
+        return result;
+    }
 
     // Always release lock, even on error
-    match crate::lock::release_lock(&input.session_id) {
+    match crate::lock::release_lock_in(&input.session_id, &ctx.root, input.verbosity) {
         Ok(()) => result,
         Err(e) => {
             eprintln!("jjagent: Warning - failed to release lock: {}", e);
@@ -332,6 +886,62 @@ pub fn handle_stop_hook(input: HookInput) -> Result<()> {
     }
 }
 
+/// Handle PreCompact hook - finalizes any in-flight precommit before Claude compacts its context.
+/// A tool interrupted between PreToolUse and PostToolUse would otherwise leave its edits in a
+/// precommit that the compacted session no longer knows about.
+/// Noop if @ is not a precommit for this session, or while another session holds a fresh lock.
+/// The lock is only released if this session holds it.
+pub fn handle_precompact_hook(input: &HookInput) -> Result<HookResponse> {
+    let Some(ctx) = input.repo_context() else {
+        input
+            .verbosity
+            .info("Not in a jj repository, skipping hook");
+        return Ok(HookResponse::continue_execution());
+    };
+
+    let session_id = SessionId::from_full(&input.session_id);
+    let Some(owns_lock) = lock_ownership(&ctx, &session_id) else {
+        return Ok(HookResponse::continue_execution());
+    };
+
+    let result = finalize_precommit(&ctx, session_id, None, input.transcript_path.as_deref());
+
+    // Release the lock an unfinished PreToolUse of this session may still hold
+    if owns_lock
+        && let Err(e) = crate::lock::release_lock_in(&input.session_id, &ctx.root, input.verbosity)
+    {
+        eprintln!("jjagent: Warning - failed to release lock: {}", e);
+    }
+
+    result.map(|()| HookResponse::continue_execution())
+}
+
+/// Handle Notification hook - tells the user when another session holds the working copy
+/// lock, so a session blocked waiting for it is visible instead of silently hanging
+pub fn handle_notification_hook(input: &HookInput) -> Result<HookResponse> {
+    let Some(ctx) = input.repo_context() else {
+        return Ok(HookResponse::continue_execution());
+    };
+
+    // An unreadable lock is usually one being written; there's no holder to report yet
+    match crate::lock::current_holder(&ctx.root).unwrap_or(None) {
+        Some(holder) if holder.session_id != input.session_id => Ok(
+            HookResponse::with_system_message(lock_wait_message(&holder)),
+        ),
+        _ => Ok(HookResponse::continue_execution()),
+    }
+}
+
+/// Message telling the user which session holds the working copy lock, and for how long
+fn lock_wait_message(holder: &crate::lock::LockInfo) -> String {
+    format!(
+        "jjagent: the working copy lock is held by session {} (pid {}) for {}s; edits wait until it's released",
+        SessionId::from_full(&holder.session_id).short(),
+        holder.pid,
+        holder.age_seconds
+    )
+}
+
 /// Handle UserPromptSubmit hook - injects session ID if it differs from the most recent one
 /// This runs before each user prompt, checking if the session ID has changed
 pub fn handle_user_prompt_submit_hook(input: &HookInput) -> Result<HookResponse> {
@@ -365,15 +975,28 @@ pub fn handle_user_prompt_submit_hook(input: &HookInput) -> Result<HookResponse>
 
     // If the session ID has changed (or wasn't found before), inject it
     if most_recent_session.as_ref() != Some(&input.session_id) {
-        let context_message = format!(
-            "System Note: The current session ID is {}. I must use this ID for session-specific tasks.",
-            input.session_id
-        );
         Ok(HookResponse::with_context(
             "UserPromptSubmit",
-            context_message,
+            session_id_context(&input.session_id),
         ))
     } else {
         Ok(HookResponse::continue_execution())
     }
 }
+
+/// Handle SessionStart hook - tells Claude its session ID up front, so session commands
+/// (describe, split, into) work before the first prompt has been submitted
+pub fn handle_session_start_hook(input: &HookInput) -> Result<HookResponse> {
+    Ok(HookResponse::with_context(
+        "SessionStart",
+        session_id_context(&input.session_id),
+    ))
+}
+
+/// Context message telling Claude which session ID to use
+fn session_id_context(session_id: &str) -> String {
+    format!(
+        "System Note: The current session ID is {}. I must use this ID for session-specific tasks.",
+        session_id
+    )
+}
@@ -8,43 +8,313 @@
 //! - Handling conflict resolution by creating numbered session parts
 
 use anyhow::{Context, Result};
-use std::path::Path;
-use std::process::Command;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::cell::OnceCell;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::session::SessionId;
 
-/// Check if the current directory is a jj repository
+const DEFAULT_JJ_TIMEOUT_SECS: u64 = 30;
+const MAX_JJ_POLL_MS: u64 = 25;
+
+/// Build a Command for the jj binary
+/// Uses JJAGENT_JJ_BIN if set (e.g. a wrapper script for counting spawns), otherwise `jj` on PATH
+/// `--no-pager` is always passed, since a pager would wait on a terminal no hook has
+pub fn jj_command() -> Command {
+    let program = std::env::var_os("JJAGENT_JJ_BIN").unwrap_or_else(|| "jj".into());
+    let mut cmd = Command::new(program);
+    cmd.arg("--no-pager");
+    cmd
+}
+
+/// Build a Command for a jj invocation that writes session changes
+/// With JJAGENT_SIGN=1, passes `--config signing.behavior=own` so those commits get signed
+/// with the user's configured signing backend and key
+pub fn jj_signing_command() -> Command {
+    let mut cmd = jj_command();
+    if std::env::var("JJAGENT_SIGN").is_ok_and(|v| v == "1") {
+        cmd.args(["--config", "signing.behavior=own"]);
+    }
+    cmd
+}
+
+/// How long a single jj invocation may run before it's killed
+/// Configurable via JJAGENT_JJ_TIMEOUT_SECS (default: 30s)
+fn jj_timeout() -> Duration {
+    let secs = std::env::var("JJAGENT_JJ_TIMEOUT_SECS")
+        .ok()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_JJ_TIMEOUT_SECS);
+    Duration::from_secs(secs)
+}
+
+/// A jj invocation that ran past JJAGENT_JJ_TIMEOUT_SECS and was killed
+#[derive(Debug)]
+pub struct JjTimeout {
+    /// The jj subcommand, e.g. "squash"
+    pub subcommand: String,
+    pub timeout: Duration,
+}
+
+impl fmt::Display for JjTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "jj {} timed out after {}s and was killed (set JJAGENT_JJ_TIMEOUT_SECS to allow longer)",
+            self.subcommand,
+            self.timeout.as_secs()
+        )
+    }
+}
+
+impl std::error::Error for JjTimeout {}
+
+/// The first argument of a jj command that isn't a global flag or its value
+fn jj_subcommand(cmd: &Command) -> String {
+    let mut args = cmd.get_args().map(|arg| arg.to_string_lossy());
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            args.next();
+        } else if !arg.starts_with('-') {
+            return arg.into_owned();
+        }
+    }
+    String::new()
+}
+
+/// Run a jj command to completion with its stdout and stderr captured
+/// Kills it and fails with a JjTimeout if it runs past the timeout
+pub fn run_jj(cmd: &mut Command) -> Result<Output> {
+    let child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    wait_jj_with_output(cmd, child)
+}
+
+/// Wait for a spawned jj, collecting whatever of its stdout and stderr is still piped
+/// Kills it and fails with a JjTimeout if it runs past the timeout
+pub fn wait_jj_with_output(cmd: &Command, mut child: Child) -> Result<Output> {
+    // Drain the pipes on their own threads so a chatty jj can't fill them and stall.
+    // On timeout these are left detached, since anything jj spawned may still hold the pipes
+    let stdout = child
+        .stdout
+        .take()
+        .map(|pipe| thread::spawn(|| read_pipe(pipe)));
+    let stderr = child
+        .stderr
+        .take()
+        .map(|pipe| thread::spawn(|| read_pipe(pipe)));
+
+    let status = wait_jj(cmd, &mut child)?;
+
+    let join = |reader: Option<thread::JoinHandle<Vec<u8>>>| {
+        reader
+            .map(|reader| reader.join().unwrap_or_default())
+            .unwrap_or_default()
+    };
+    Ok(Output {
+        status,
+        stdout: join(stdout),
+        stderr: join(stderr),
+    })
+}
+
+fn read_pipe(mut pipe: impl Read) -> Vec<u8> {
+    let mut buf = Vec::new();
+    let _ = pipe.read_to_end(&mut buf);
+    buf
+}
+
+/// Wait for a spawned jj to exit, killing it if it runs past the timeout
+/// Doesn't read its output, so anything piped must be drained elsewhere
+pub fn wait_jj(cmd: &Command, child: &mut Child) -> Result<ExitStatus> {
+    let timeout = jj_timeout();
+    let started = Instant::now();
+    // Poll quickly at first since most jj calls finish in a few milliseconds
+    let mut poll_ms = 1;
+    loop {
+        if let Some(status) = child.try_wait().context("Failed to wait for jj")? {
+            return Ok(status);
+        }
+        if started.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(JjTimeout {
+                subcommand: jj_subcommand(cmd),
+                timeout,
+            }
+            .into());
+        }
+        thread::sleep(Duration::from_millis(poll_ms));
+        poll_ms = (poll_ms * 2).min(MAX_JJ_POLL_MS);
+    }
+}
+
+/// Repo facts resolved once at the top of a hook and passed down to the jj helpers,
+/// so a single hook invocation doesn't keep rediscovering the repo or rereading its config
+#[derive(Debug)]
+pub struct RepoContext {
+    /// Workspace root; every jj helper runs there
+    pub root: PathBuf,
+    fsmonitor_backend: OnceCell<std::result::Result<Option<String>, String>>,
+}
+
+impl RepoContext {
+    /// Resolve the repo containing repo_path (or the current directory)
+    /// Returns None when it isn't inside a jj repo
+    pub fn discover_in(repo_path: Option<&Path>) -> Option<Self> {
+        let root = get_repo_root_in(repo_path).ok()?;
+        Some(Self {
+            root,
+            fsmonitor_backend: OnceCell::new(),
+        })
+    }
+
+    /// Resolve the repo containing the current directory
+    pub fn discover() -> Option<Self> {
+        Self::discover_in(None)
+    }
+
+    /// The repo root, in the form the `_in` helpers take
+    pub fn path(&self) -> Option<&Path> {
+        Some(&self.root)
+    }
+
+    /// The fsmonitor.backend setting, read from jj on first use
+    pub fn fsmonitor_backend(&self) -> Result<Option<&str>> {
+        let backend = self.fsmonitor_backend.get_or_init(|| {
+            get_config_value_in("fsmonitor.backend", self.path()).map_err(|e| e.to_string())
+        });
+        match backend {
+            Ok(backend) => Ok(backend.as_deref()),
+            Err(e) => Err(anyhow::anyhow!("{}", e)),
+        }
+    }
+
+    /// Check if the repo is colocated with git (a .git directory next to .jj)
+    pub fn is_colocated(&self) -> bool {
+        self.root.join(".git").is_dir()
+    }
+}
+
+/// Check if a directory is inside a jj repository
 /// Returns true if `jj root` succeeds, indicating we're in a jj repo
-pub fn is_jj_repo() -> bool {
-    Command::new("jj")
-        .args(["root"])
-        .output()
+/// If repo_path is provided, runs jj in that directory
+pub fn is_jj_repo_in(repo_path: Option<&Path>) -> bool {
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+
+    run_jj(cmd.args(["--ignore-working-copy", "root"]))
         .map(|output| output.status.success())
         .unwrap_or(false)
 }
 
+/// Check if the current directory is a jj repository
+pub fn is_jj_repo() -> bool {
+    is_jj_repo_in(None)
+}
+
+/// Get the root directory of the jj repo
+/// If repo_path is provided, runs jj in that directory
+pub fn get_repo_root_in(repo_path: Option<&Path>) -> Result<PathBuf> {
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+
+    let output =
+        run_jj(cmd.args(["root", "--ignore-working-copy"])).context("Failed to execute jj root")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj root failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(PathBuf::from(
+        String::from_utf8_lossy(&output.stdout).trim_end_matches(['\n', '\r']),
+    ))
+}
+
+/// Check if the repo is colocated with git (a .git directory next to .jj)
+/// If repo_path is provided, runs jj in that directory
+pub fn is_colocated_in(repo_path: Option<&Path>) -> Result<bool> {
+    let root = get_repo_root_in(repo_path)?;
+    Ok(root.join(".git").is_dir())
+}
+
+/// Export jj bookmarks and the working copy parent to the backing git repo
+/// If repo_path is provided, runs jj in that directory
+pub fn git_export_in(repo_path: Option<&Path>) -> Result<()> {
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+
+    let output = run_jj(cmd.args(["git", "export"])).context("Failed to execute jj git export")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj git export failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(())
+}
+
+/// Get a jj config value, or None if it isn't set
+/// If repo_path is provided, runs jj in that directory so repo config is included
+pub fn get_config_value_in(key: &str, repo_path: Option<&Path>) -> Result<Option<String>> {
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+
+    let output =
+        run_jj(cmd.args(["config", "get", key])).context("Failed to execute jj config get")?;
+
+    // jj exits non-zero when the key isn't set
+    if !output.status.success() {
+        return Ok(None);
+    }
+
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok((!value.is_empty()).then_some(value))
+}
+
 /// Check if the working copy (@) is at a head (has no descendants)
 /// Returns true if @ has no descendants, false otherwise
 /// If repo_path is provided, runs jj in that directory
 pub fn is_at_head_in(repo_path: Option<&Path>) -> Result<bool> {
-    let mut cmd = Command::new("jj");
+    let mut cmd = jj_command();
     if let Some(path) = repo_path {
         cmd.current_dir(path);
     }
 
-    let output = cmd
-        .args([
-            "log",
-            "-r",
-            "descendants(@) ~ @",
-            "--limit",
-            "1",
-            "-T",
-            "true",
-            "--no-graph",
-        ])
-        .output()
-        .context("Failed to execute jj log")?;
+    let output = run_jj(cmd.args([
+        "log",
+        "-r",
+        "descendants(@) ~ @",
+        "--limit",
+        "1",
+        "-T",
+        "true",
+        "--no-graph",
+    ]))
+    .context("Failed to execute jj log")?;
 
     if !output.status.success() {
         anyhow::bail!("jj log failed: {}", String::from_utf8_lossy(&output.stderr));
@@ -63,22 +333,20 @@ pub fn is_at_head() -> Result<bool> {
 /// Returns true if conflicts exist, false otherwise
 /// If repo_path is provided, runs jj in that directory
 pub fn has_conflicts_in(repo_path: Option<&Path>) -> Result<bool> {
-    let mut cmd = Command::new("jj");
+    let mut cmd = jj_command();
     if let Some(path) = repo_path {
         cmd.current_dir(path);
     }
 
-    let output = cmd
-        .args([
-            "log",
-            "-r",
-            "conflicts() & @",
-            "--no-graph",
-            "-T",
-            "change_id.short()",
-        ])
-        .output()
-        .context("Failed to execute jj log for conflict detection")?;
+    let output = run_jj(cmd.args([
+        "log",
+        "-r",
+        "conflicts() & @",
+        "--no-graph",
+        "-T",
+        "change_id.short()",
+    ]))
+    .context("Failed to execute jj log for conflict detection")?;
 
     if !output.status.success() {
         anyhow::bail!(
@@ -97,131 +365,343 @@ pub fn has_conflicts() -> Result<bool> {
     has_conflicts_in(None)
 }
 
-/// Find the closest descendant commit with the given session ID
-/// Returns the change ID if found, None otherwise
-/// Excludes immutable commits from the search results
-/// If repo_path is provided, runs jj in that directory
-pub fn find_session_change_in(
-    session_id: &str,
-    repo_path: Option<&Path>,
-) -> Result<Option<String>> {
-    // Use revset to filter candidates and template to check exact match
-    // Exclude immutable commits to prevent trying to squash into them
-    let revset = format!(
-        r#"(descendants(@) ~ @) & description(substring:"{}") & ~immutable()"#,
-        session_id
-    );
-    let template = format!(
-        r#"if(trailers.any(|t| t.key() == "Claude-session-id" && t.value() == "{}"), change_id.short() ++ "\n", "")"#,
-        session_id
-    );
+/// Facts about the working copy (@) that PreToolUse checks before creating a precommit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PretoolInvariants {
+    /// Claude-session-id trailer on @, if @ is a session change
+    pub session_id: Option<String>,
+    /// Whether @ has no descendants
+    pub at_head: bool,
+    /// Whether @ has conflicts
+    pub has_conflicts: bool,
+    /// Precommit trailer on @ (see PrecommitConfig), if @ is a precommit
+    pub precommit_session_id: Option<String>,
+}
+
+/// Template printing the session trailer, head flag, conflict flag and precommit trailer
+/// of a commit. The precommit trailer key depends on the PrecommitConfig
+fn pretool_invariants_template(precommit_trailer_key: &str) -> String {
+    format!(
+        concat!(
+            r#"trailers.map(|t| if({}, t.value(), "")).join("")"#,
+            r#" ++ "\x1f" ++ if(self.contained_in("visible_heads()"), "1", "0")"#,
+            r#" ++ "\x1f" ++ if(self.conflict(), "1", "0")"#,
+            r#" ++ "\x1f" ++ trailers.map(|t| if(t.key() == "{}", t.value(), "")).join("")"#,
+        ),
+        SESSION_TRAILER_KEY_CONDITION,
+        escape_string_literal(precommit_trailer_key)
+    )
+}
+
+/// Parse jj log output produced with pretool_invariants_template
+fn parse_pretool_invariants(output: &str) -> Result<PretoolInvariants> {
+    let fields: Vec<&str> = output
+        .trim_end_matches(['\n', '\r'])
+        .split('\x1f')
+        .collect();
+    let [session_id, at_head, has_conflicts, precommit_session_id] = fields[..] else {
+        anyhow::bail!("Unexpected jj log output for @: {:?}", output);
+    };
+    let trailer_value = |value: &str| {
+        let value = value.trim();
+        (!value.is_empty()).then(|| value.to_string())
+    };
+    Ok(PretoolInvariants {
+        session_id: trailer_value(session_id),
+        at_head: at_head == "1",
+        has_conflicts: has_conflicts == "1",
+        precommit_session_id: trailer_value(precommit_session_id),
+    })
+}
 
-    let mut cmd = Command::new("jj");
+/// Gather the session trailer, head, conflict state and precommit trailer of @ in a single
+/// jj invocation
+/// If repo_path is provided, runs jj in that directory
+pub fn check_pretool_invariants_in(repo_path: Option<&Path>) -> Result<PretoolInvariants> {
+    let mut cmd = jj_command();
     if let Some(path) = repo_path {
         cmd.current_dir(path);
     }
 
-    let output = cmd
-        .args([
-            "log",
-            "-r",
-            &revset,
-            "-T",
-            &template,
-            "--no-graph",
-            "--ignore-working-copy",
-        ])
-        .output()
-        .context("Failed to execute jj log")?;
+    let template =
+        pretool_invariants_template(&crate::session::PrecommitConfig::from_env().trailer_key());
+    let output = run_jj(cmd.args(["log", "-r", "@", "--no-graph", "-T", &template]))
+        .context("Failed to execute jj log to check working copy invariants")?;
 
     if !output.status.success() {
-        anyhow::bail!("jj log failed: {}", String::from_utf8_lossy(&output.stderr));
+        anyhow::bail!(
+            "jj log failed while checking working copy invariants: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let change_ids = parse_change_ids(&stdout);
+    parse_pretool_invariants(&String::from_utf8_lossy(&output.stdout))
+}
 
-    // Return the first match (closest descendant)
-    Ok(change_ids.into_iter().next())
+/// Gather the session trailer, head, conflict state and precommit trailer of @ in the
+/// current directory
+pub fn check_pretool_invariants() -> Result<PretoolInvariants> {
+    check_pretool_invariants_in(None)
 }
 
-/// Find the closest descendant commit with the given session ID in the current directory
-/// Returns the change ID if found, None otherwise
-pub fn find_session_change(session_id: &str) -> Result<Option<String>> {
-    find_session_change_in(session_id, None)
+/// A commit carrying a session's Claude-session-id trailer, as returned by the finders
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionChange {
+    /// Full change ID
+    pub change_id: String,
+    pub commit_id: String,
+    pub description: String,
+    /// N for a "pt. N" part created after a conflict, None for the first change
+    pub part: Option<usize>,
 }
 
-/// Find any commit with the given session ID (not limited to descendants)
-/// Returns the change ID if found, None otherwise
-/// Excludes immutable commits from the search results
+/// Template printing the fields of a SessionChange, one record per commit
+/// Fields are separated by \x1f and records by \x1e since descriptions span lines
+const SESSION_CHANGE_TEMPLATE: &str =
+    r#"change_id ++ "\x1f" ++ commit_id ++ "\x1f" ++ description ++ "\x1e""#;
+
+/// Parse jj log output produced with SESSION_CHANGE_TEMPLATE
+fn parse_session_changes(output: &str) -> Vec<SessionChange> {
+    output
+        .split('\x1e')
+        .filter_map(|record| {
+            let mut fields = record.trim_start_matches('\n').splitn(3, '\x1f');
+            let change_id = fields.next()?.trim();
+            let commit_id = fields.next()?.trim();
+            let description = fields.next()?;
+            if change_id.is_empty() {
+                return None;
+            }
+            Some(SessionChange {
+                change_id: change_id.to_string(),
+                commit_id: commit_id.to_string(),
+                description: description.to_string(),
+                part: parse_part_number(description),
+            })
+        })
+        .collect()
+}
+
+/// Get the part number from a "... pt. N" title, if there is one
+fn parse_part_number(description: &str) -> Option<usize> {
+    let title = description.lines().next()?;
+    let (_, part) = title.rsplit_once(" pt. ")?;
+    part.trim().parse().ok()
+}
+
+/// Session trailer key written by older jjagent versions
+/// Lookups still accept it until `jjagent migrate` has rewritten it to Claude-session-id
+pub const LEGACY_SESSION_TRAILER_KEY: &str = "Jjagent-claude-session-id";
+
+/// Template condition matching a trailer `t` that holds a session ID under either key
+const SESSION_TRAILER_KEY_CONDITION: &str =
+    r#"(t.key() == "Claude-session-id" || t.key() == "Jjagent-claude-session-id")"#;
+
+/// Whether a trailer line holds a session ID under either key
+fn is_session_trailer(trailer: &str) -> bool {
+    trailer.starts_with("Claude-session-id:")
+        || trailer
+            .strip_prefix(LEGACY_SESSION_TRAILER_KEY)
+            .is_some_and(|rest| rest.starts_with(':'))
+}
+
+/// Revset of the commits whose description carries a session's trailer, under either key
+/// Candidates match the whole trailer line (descriptions always end in a newline), so a
+/// `--limit` can't stop on a false positive. `escaped_id` must already be escaped
+fn session_candidates_revset(escaped_id: &str) -> String {
+    format!(
+        r#"all() & (description(substring:"Claude-session-id: {id}\n") | description(substring:"{legacy}: {id}\n"))"#,
+        id = escaped_id,
+        legacy = LEGACY_SESSION_TRAILER_KEY
+    )
+}
+
+/// Wrap `template` so it is only rendered for commits whose session trailer matches exactly
+/// `escaped_id` must already be escaped
+fn session_match_template(escaped_id: &str, template: &str) -> String {
+    format!(
+        r#"if(trailers.any(|t| {} && t.value() == "{}"), {})"#,
+        SESSION_TRAILER_KEY_CONDITION, escaped_id, template
+    )
+}
+
+/// Run `jj log` over the commits carrying a session's Claude-session-id trailer
+/// `wrap` turns the candidates (see session_candidates_revset) into the final revset
+/// (e.g. adding `~immutable()`), and `template` is rendered for each exact match
 /// If repo_path is provided, runs jj in that directory
-pub fn find_session_change_anywhere_in(
+fn query_session_changes_in(
     session_id: &str,
+    wrap: impl FnOnce(String) -> String,
+    template: &str,
+    limit: Option<usize>,
     repo_path: Option<&Path>,
-) -> Result<Option<String>> {
-    // Use revset to filter candidates and template to check exact match
-    // Exclude immutable commits to prevent trying to squash into them
-    let revset = format!(
-        r#"all() & description(substring:"{}") & ~immutable()"#,
-        session_id
-    );
-    let template = format!(
-        r#"if(trailers.any(|t| t.key() == "Claude-session-id" && t.value() == "{}"), change_id ++ "\n", "")"#,
-        session_id
-    );
-
-    let mut cmd = Command::new("jj");
+) -> Result<String> {
+    // Session IDs come from Claude, so escape them before building the query
+    let escaped_id = escape_string_literal(session_id);
+    let revset = wrap(session_candidates_revset(&escaped_id));
+    // The template still checks for an exact match as a safeguard
+    let template = session_match_template(&escaped_id, template);
+
+    let mut cmd = jj_command();
     if let Some(path) = repo_path {
         cmd.current_dir(path);
     }
+    cmd.args(["log", "-r", &revset, "-T", &template]);
+    if let Some(limit) = limit {
+        cmd.args(["--limit", &limit.to_string()]);
+    }
 
-    let output = cmd
-        .args([
-            "log",
-            "-r",
-            &revset,
-            "-T",
-            &template,
-            "--no-graph",
-            "--ignore-working-copy",
-        ])
-        .output()
+    let output = run_jj(cmd.args(["--no-graph", "--ignore-working-copy"]))
         .context("Failed to execute jj log")?;
 
     if !output.status.success() {
         anyhow::bail!("jj log failed: {}", String::from_utf8_lossy(&output.stderr));
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let change_ids = parse_change_ids(&stdout);
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Find a descendant commit of @ with the given session ID
+/// Returns the matching change (with its full change ID) if found, None otherwise
+/// Matches come in jj log order (newest first); see find_furthest_session_change_in for a
+/// session's latest part
+/// Excludes immutable commits from the search results
+/// If repo_path is provided, runs jj in that directory
+pub fn find_session_change_in(
+    session_id: &str,
+    repo_path: Option<&Path>,
+) -> Result<Option<SessionChange>> {
+    // Exclude immutable commits to prevent trying to squash into them
+    let stdout = query_session_changes_in(
+        session_id,
+        |candidates| format!("({}) & (descendants(@) ~ @) & ~immutable()", candidates),
+        SESSION_CHANGE_TEMPLATE,
+        None,
+        repo_path,
+    )?;
+
+    // Return the first match (newest in jj log order)
+    Ok(parse_session_changes(&stdout).into_iter().next())
+}
+
+/// Find a descendant commit of @ with the given session ID in the current directory
+/// Returns the matching change if found, None otherwise
+pub fn find_session_change(session_id: &str) -> Result<Option<SessionChange>> {
+    find_session_change_in(session_id, None)
+}
+
+/// Find any commit with the given session ID (not limited to descendants)
+/// Returns the matching change (with its full change ID) if found, None otherwise
+/// This is the newest match in jj log order; see find_furthest_session_change_in for the
+/// session's latest part
+/// Excludes immutable commits from the search results
+/// If repo_path is provided, runs jj in that directory
+pub fn find_session_change_anywhere_in(
+    session_id: &str,
+    repo_path: Option<&Path>,
+) -> Result<Option<SessionChange>> {
+    // Exclude immutable commits to prevent trying to squash into them
+    // Callers only need the first match, so don't let jj emit every matching commit
+    let stdout = query_session_changes_in(
+        session_id,
+        |candidates| format!("{} & ~immutable()", candidates),
+        SESSION_CHANGE_TEMPLATE,
+        Some(1),
+        repo_path,
+    )?;
 
     // Return the first match
-    Ok(change_ids.into_iter().next())
+    Ok(parse_session_changes(&stdout).into_iter().next())
 }
 
 /// Find any commit with the given session ID in the current directory
-/// Returns the change ID if found, None otherwise
-pub fn find_session_change_anywhere(session_id: &str) -> Result<Option<String>> {
+/// Returns the matching change if found, None otherwise
+pub fn find_session_change_anywhere(session_id: &str) -> Result<Option<SessionChange>> {
     find_session_change_anywhere_in(session_id, None)
 }
 
+/// Check whether any mutable commit carries the given session ID
+/// Runs the same query as find_session_change_anywhere_in, but only asks jj for a marker
+/// If repo_path is provided, runs jj in that directory
+pub fn session_exists_in(session_id: &str, repo_path: Option<&Path>) -> Result<bool> {
+    let stdout = query_session_changes_in(
+        session_id,
+        |candidates| format!("{} & ~immutable()", candidates),
+        r#""true""#,
+        Some(1),
+        repo_path,
+    )?;
+
+    Ok(stdout.trim() == "true")
+}
+
+/// Check whether any mutable commit carries the given session ID in the current directory
+pub fn session_exists(session_id: &str) -> Result<bool> {
+    session_exists_in(session_id, None)
+}
+
+/// Find any commit with the given session ID, including immutable ones
+/// For commands that refuse to rewrite immutable commits, so they can say why instead of
+/// reporting that the session doesn't exist
+/// If repo_path is provided, runs jj in that directory
+pub fn find_session_change_including_immutable_in(
+    session_id: &str,
+    repo_path: Option<&Path>,
+) -> Result<Option<SessionChange>> {
+    let stdout = query_session_changes_in(
+        session_id,
+        |candidates| candidates,
+        SESSION_CHANGE_TEMPLATE,
+        Some(1),
+        repo_path,
+    )?;
+
+    Ok(parse_session_changes(&stdout).into_iter().next())
+}
+
+/// Find the furthest descendant among a session's mutable commits (its latest part)
+/// This is where new edits are squashed, so parts created by conflicts and by
+/// `jjagent split` are treated alike regardless of their titles
+/// Returns the matching change (with its full change ID) if found, None otherwise
+/// If repo_path is provided, runs jj in that directory
+pub fn find_furthest_session_change_in(
+    session_id: &str,
+    repo_path: Option<&Path>,
+) -> Result<Option<SessionChange>> {
+    // heads() keeps only parts with no other part of the session above them
+    let stdout = query_session_changes_in(
+        session_id,
+        |candidates| format!("heads({} & ~immutable())", candidates),
+        SESSION_CHANGE_TEMPLATE,
+        None,
+        repo_path,
+    )?;
+
+    // If parts have diverged onto separate branches, take the newest head
+    Ok(parse_session_changes(&stdout).into_iter().next())
+}
+
+/// Find the latest part of a session in the current directory
+pub fn find_furthest_session_change(session_id: &str) -> Result<Option<SessionChange>> {
+    find_furthest_session_change_in(session_id, None)
+}
+
 /// Count how many commits exist with the given session ID
-/// This is used to determine the part number for conflict handling
+/// Used for the part count in the status line; see next_session_part_in for numbering parts
 /// If repo_path is provided, runs jj in that directory
 pub fn count_session_parts_in(session_id: &str, repo_path: Option<&Path>) -> Result<usize> {
+    // Session IDs come from Claude, so escape them before building the query
+    let escaped_id = escape_string_literal(session_id);
     // Use revset to filter candidates and template to check exact match
-    let revset = format!(r#"all() & description(substring:"{}")"#, session_id);
-    let template = format!(
-        r#"if(trailers.any(|t| t.key() == "Claude-session-id" && t.value() == "{}"), change_id.short() ++ "\n", "")"#,
-        session_id
-    );
+    let revset = session_candidates_revset(&escaped_id);
+    let template = session_match_template(&escaped_id, r#"change_id.short() ++ "\n""#);
 
-    let mut cmd = Command::new("jj");
+    let mut cmd = jj_command();
     if let Some(path) = repo_path {
         cmd.current_dir(path);
     }
 
-    let output = cmd
+    let mut child = cmd
         .args([
             "log",
             "-r",
@@ -231,17 +711,26 @@ pub fn count_session_parts_in(session_id: &str, repo_path: Option<&Path>) -> Res
             "--no-graph",
             "--ignore-working-copy",
         ])
-        .output()
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
         .context("Failed to execute jj log")?;
 
+    // Count matches as they stream in rather than collecting every change ID
+    let stdout = child
+        .stdout
+        .take()
+        .context("Failed to read jj log output")?;
+    let counter = thread::spawn(|| count_nonempty_lines(BufReader::new(stdout)));
+
+    let output = wait_jj_with_output(&cmd, child).context("Failed to wait for jj log")?;
     if !output.status.success() {
         anyhow::bail!("jj log failed: {}", String::from_utf8_lossy(&output.stderr));
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let change_ids = parse_change_ids(&stdout);
-
-    Ok(change_ids.len())
+    counter
+        .join()
+        .map_err(|_| anyhow::anyhow!("Failed to read jj log output"))?
 }
 
 /// Count how many commits exist with the given session ID in the current directory
@@ -249,57 +738,335 @@ pub fn count_session_parts(session_id: &str) -> Result<usize> {
     count_session_parts_in(session_id, None)
 }
 
-/// Create a new session change commit inserted before @-
-/// This creates the commit structure: @ -> uwc -> session -> base
+/// The number for a session's next "pt. N" part: one past the highest existing part, so
+/// numbers keep growing after a part is abandoned instead of repeating a live one
+/// Immutable changes count too, since their titles can't be renumbered
 /// If repo_path is provided, runs jj in that directory
-pub fn create_session_change_in(session_id: &SessionId, repo_path: Option<&Path>) -> Result<()> {
-    let message = crate::session::format_session_message(session_id);
+pub fn next_session_part_in(session_id: &str, repo_path: Option<&Path>) -> Result<usize> {
+    let stdout = query_session_changes_in(
+        session_id,
+        |candidates| candidates,
+        SESSION_CHANGE_TEMPLATE,
+        None,
+        repo_path,
+    )?;
+
+    Ok(next_part_number(&parse_session_changes(&stdout)))
+}
 
-    let mut cmd = Command::new("jj");
-    if let Some(path) = repo_path {
-        cmd.current_dir(path);
-    }
+/// One past the highest part number among a session's changes, the first change counting
+/// as part 1. Never less than one past the number of changes, so parts whose titles don't
+/// end in " pt. N" (e.g. from a custom title template) still get a fresh number
+fn next_part_number(changes: &[SessionChange]) -> usize {
+    let highest = changes
+        .iter()
+        .map(|change| change.part.unwrap_or(1))
+        .max()
+        .unwrap_or(0);
+    highest.max(changes.len()) + 1
+}
 
-    let output = cmd
-        .args(["new", "--insert-before", "@-", "--no-edit", "-m", &message])
-        .output()
-        .context("Failed to execute jj new")?;
+/// Find every commit with the given session ID (the session change and all its parts)
+/// Returns full change IDs in jj log order (newest first)
+/// If repo_path is provided, runs jj in that directory
+pub fn find_all_session_changes_in(
+    session_id: &str,
+    repo_path: Option<&Path>,
+) -> Result<Vec<String>> {
+    let stdout = query_session_changes_in(
+        session_id,
+        |candidates| candidates,
+        r#"change_id ++ "\n""#,
+        None,
+        repo_path,
+    )?;
+
+    Ok(parse_change_ids(&stdout))
+}
 
-    if !output.status.success() {
-        anyhow::bail!("jj new failed: {}", String::from_utf8_lossy(&output.stderr));
-    }
+/// Find every commit with the given session ID in the current directory
+pub fn find_all_session_changes(session_id: &str) -> Result<Vec<String>> {
+    find_all_session_changes_in(session_id, None)
+}
 
-    Ok(())
+/// List the files touched by any change carrying the given session ID
+/// Returns repo-relative paths, deduplicated and sorted; empty if the session has no changes
+/// If repo_path is provided, runs jj in that directory
+pub fn get_session_file_list_in(session_id: &str, repo_path: Option<&Path>) -> Result<Vec<String>> {
+    let stdout = query_session_changes_in(
+        session_id,
+        |candidates| candidates,
+        r#"self.diff().files().map(|f| f.path().display() ++ "\n").join("")"#,
+        None,
+        repo_path,
+    )
+    .context("Failed to list session files")?;
+
+    let files: BTreeSet<String> = stdout
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect();
+    Ok(files.into_iter().collect())
 }
 
-/// Create a new session change commit inserted before @- in the current directory
-pub fn create_session_change(session_id: &SessionId) -> Result<()> {
-    create_session_change_in(session_id, None)
+/// List the files touched by a session in the current directory
+pub fn get_session_file_list(session_id: &str) -> Result<Vec<String>> {
+    get_session_file_list_in(session_id, None)
 }
 
-/// Count conflicts on or after a specific change
-/// Uses the revset: conflicts() & (change_id:: | change_id)
-/// This counts conflicts in the specified change and all its descendants
-/// If repo_path is provided, runs jj in that directory
-pub fn count_conflicts_in(change_id: &str, repo_path: Option<&Path>) -> Result<usize> {
-    let revset = format!("conflicts() & ({}:: | {})", change_id, change_id);
+/// Size of a session's changes, summed over all of its parts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct SessionStats {
+    /// Distinct files touched by any part
+    pub files_changed: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
 
-    let mut cmd = Command::new("jj");
-    if let Some(path) = repo_path {
-        cmd.current_dir(path);
+/// Count the files, insertions and deletions across every change carrying the given session ID
+/// Returns zeros if the session has no changes
+/// If repo_path is provided, runs jj in that directory
+pub fn get_session_stats_in(session_id: &str, repo_path: Option<&Path>) -> Result<SessionStats> {
+    let change_ids = find_all_session_changes_in(session_id, repo_path)?;
+    if change_ids.is_empty() {
+        return Ok(SessionStats::default());
     }
 
-    let output = cmd
-        .args([
-            "log",
-            "-r",
-            &revset,
-            "--no-graph",
-            "-T",
-            "change_id.short()",
-        ])
-        .output()
-        .context("Failed to execute jj log for conflict counting")?;
+    // Parts can have other changes between them, which `jj diff -r` can't span,
+    // so each part is diffed on its own and the totals summed
+    let mut stats = SessionStats::default();
+    for change_id in &change_ids {
+        let mut cmd = jj_command();
+        if let Some(path) = repo_path {
+            cmd.current_dir(path);
+        }
+
+        let output = run_jj(cmd.args(["diff", "--stat", "-r", change_id, "--ignore-working-copy"]))
+            .context("Failed to execute jj diff")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj diff failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        let (insertions, deletions) =
+            parse_diff_stat_totals(&String::from_utf8_lossy(&output.stdout));
+        stats.insertions += insertions;
+        stats.deletions += deletions;
+    }
+
+    // A file edited in several parts is only counted once
+    stats.files_changed = get_session_file_list_in(session_id, repo_path)?.len();
+    Ok(stats)
+}
+
+/// Count the files, insertions and deletions of a session in the current directory
+pub fn get_session_stats(session_id: &str) -> Result<SessionStats> {
+    get_session_stats_in(session_id, None)
+}
+
+/// Parse the insertion and deletion counts from the summary line of `jj diff --stat`,
+/// e.g. "2 files changed, 3 insertions(+), 1 deletion(-)"
+fn parse_diff_stat_totals(output: &str) -> (usize, usize) {
+    let Some(summary) = output.lines().rev().find(|line| line.contains(" changed")) else {
+        return (0, 0);
+    };
+
+    let mut insertions = 0;
+    let mut deletions = 0;
+    for part in summary.split(", ") {
+        let mut words = part.split_whitespace();
+        let (Some(count), Some(kind)) = (words.next(), words.next()) else {
+            continue;
+        };
+        let Ok(count) = count.parse::<usize>() else {
+            continue;
+        };
+        if kind.starts_with("insertion") {
+            insertions = count;
+        } else if kind.starts_with("deletion") {
+            deletions = count;
+        }
+    }
+    (insertions, deletions)
+}
+
+/// Create a new session change commit inserted before `base` (default @-)
+/// This creates the commit structure: @ -> uwc -> session -> base's parents
+/// If repo_path is provided, runs jj in that directory
+pub fn create_session_change_in(
+    session_id: &SessionId,
+    base: Option<&str>,
+    repo_path: Option<&Path>,
+) -> Result<()> {
+    create_session_change_titled_in(session_id, None, None, base, repo_path)
+}
+
+/// Create a new session change commit inserted before `base` (default @-) with the given
+/// title, or the default session title if there is none
+/// With an author_timestamp (RFC 3339), the change is dated then instead of now
+/// A base other than @- must be a single proper ancestor of @
+/// If repo_path is provided, runs jj in that directory
+pub fn create_session_change_titled_in(
+    session_id: &SessionId,
+    title: Option<&str>,
+    author_timestamp: Option<&str>,
+    base: Option<&str>,
+    repo_path: Option<&Path>,
+) -> Result<()> {
+    let base = match base {
+        Some(base) => {
+            ensure_session_base_in(base, repo_path)?;
+            base
+        }
+        None => "@-",
+    };
+
+    let message = match title {
+        Some(title) => crate::session::format_session_message_with_title(session_id, title),
+        None => crate::session::format_session_message(session_id),
+    };
+
+    // Pin the base before inserting below it, so the new change can be found afterwards
+    let base = match author_timestamp {
+        Some(_) => get_full_change_id_in(base, repo_path)?,
+        None => base.to_string(),
+    };
+
+    let mut cmd = jj_signing_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+
+    let output = run_jj(cmd.args(["new", "--insert-before", &base, "--no-edit", "-m", &message]))
+        .context("Failed to execute jj new")?;
+
+    if !output.status.success() {
+        anyhow::bail!("jj new failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    // Only the author date moves; the committer date and operation log stay at now
+    if let Some(timestamp) = author_timestamp {
+        let mut cmd = jj_signing_command();
+        if let Some(path) = repo_path {
+            cmd.current_dir(path);
+        }
+        let output = run_jj(cmd.args([
+            "metaedit",
+            "--author-timestamp",
+            timestamp,
+            &format!("{}-", base),
+        ]))
+        .context("Failed to execute jj metaedit")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj metaedit failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Fail unless a session base resolves to one revision that is a proper ancestor of @
+fn ensure_session_base_in(base: &str, repo_path: Option<&Path>) -> Result<()> {
+    let change_id = get_full_change_id_in(base, repo_path)
+        .with_context(|| format!("Invalid session base '{}'", base))?;
+    if list_change_ids_in(&format!("{} & ::@-", change_id), repo_path)?.is_empty() {
+        anyhow::bail!(
+            "Session base '{}' is not an ancestor of the working copy (@)",
+            base
+        );
+    }
+    Ok(())
+}
+
+/// Create a new session change commit inserted before `base` (default @-) in the current
+/// directory
+pub fn create_session_change(session_id: &SessionId, base: Option<&str>) -> Result<()> {
+    create_session_change_in(session_id, base, None)
+}
+
+/// Create a new titled session change commit inserted before `base` (default @-) in the
+/// current directory
+pub fn create_session_change_titled(
+    session_id: &SessionId,
+    title: Option<&str>,
+    author_timestamp: Option<&str>,
+    base: Option<&str>,
+) -> Result<()> {
+    create_session_change_titled_in(session_id, title, author_timestamp, base, None)
+}
+
+/// Get the author timestamp of a revision in RFC 3339 form (e.g. 2024-01-01T10:00:00+00:00)
+/// If repo_path is provided, runs jj in that directory
+pub fn get_author_timestamp_in(revset: &str, repo_path: Option<&Path>) -> Result<String> {
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+
+    let output = run_jj(cmd.args([
+        "log",
+        "-r",
+        revset,
+        "--no-graph",
+        "-T",
+        r#"author.timestamp().format("%Y-%m-%dT%H:%M:%S%:z")"#,
+        "--ignore-working-copy",
+    ]))
+    .context("Failed to execute jj log to get author timestamp")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj log failed while getting author timestamp: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// List the conflicted changes on or after a specific change
+/// Uses the revset: conflicts() & (change_id:: | change_id)
+/// This covers the specified change and all its descendants
+/// Returns full change IDs in jj log order (newest first)
+/// If repo_path is provided, runs jj in that directory
+pub fn list_conflicts_in(change_id: &str, repo_path: Option<&Path>) -> Result<Vec<String>> {
+    list_conflicts_snapshotting_in(change_id, true, repo_path)
+}
+
+/// List conflicted changes on or after a change, optionally without snapshotting the
+/// working copy first
+fn list_conflicts_snapshotting_in(
+    change_id: &str,
+    snapshot: bool,
+    repo_path: Option<&Path>,
+) -> Result<Vec<String>> {
+    let revset = format!("conflicts() & ({}:: | {})", change_id, change_id);
+
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+    if !snapshot {
+        cmd.arg("--ignore-working-copy");
+    }
+
+    let output = run_jj(cmd.args([
+        "log",
+        "-r",
+        &revset,
+        "--no-graph",
+        "-T",
+        r#"change_id ++ "\n""#,
+    ]))
+    .context("Failed to execute jj log for conflict counting")?;
 
     if !output.status.success() {
         anyhow::bail!(
@@ -308,12 +1075,83 @@ pub fn count_conflicts_in(change_id: &str, repo_path: Option<&Path>) -> Result<u
         );
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let count = stdout
-        .lines()
-        .filter(|line| !line.trim().is_empty())
-        .count();
-    Ok(count)
+    Ok(parse_change_ids(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// List the conflicted changes on or after a specific change along with the paths that
+/// became conflicted in them, in a single jj log
+/// Returns the number of conflicted changes and the paths, deduplicated in jj log order
+/// If repo_path is provided, runs jj in that directory
+pub fn list_conflicts_with_files_in(
+    change_id: &str,
+    repo_path: Option<&Path>,
+) -> Result<(usize, Vec<String>)> {
+    let revset = format!("conflicts() & ({}:: | {})", change_id, change_id);
+    // A file shows up in a change's diff when the change made it conflicted
+    let template = concat!(
+        r#"change_id ++ "\x1f" ++ "#,
+        r#"self.diff().files().filter(|f| f.target().conflict())"#,
+        r#".map(|f| f.path().display()).join("\x1f") ++ "\n""#
+    );
+
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+    let output = run_jj(cmd.args([
+        "log",
+        "-r",
+        &revset,
+        "--no-graph",
+        "-T",
+        template,
+        "--ignore-working-copy",
+    ]))
+    .context("Failed to execute jj log for conflicted files")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj log failed while listing conflicted files: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(parse_conflicts_with_files(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+/// Parse jj log output of change IDs each followed by \x1f-separated conflicted paths
+fn parse_conflicts_with_files(output: &str) -> (usize, Vec<String>) {
+    let mut count = 0;
+    let mut files: Vec<String> = Vec::new();
+    for line in output.lines().filter(|l| !l.trim().is_empty()) {
+        count += 1;
+        for path in line.split('\x1f').skip(1).filter(|p| !p.is_empty()) {
+            if !files.iter().any(|f| f == path) {
+                files.push(path.to_string());
+            }
+        }
+    }
+    (count, files)
+}
+
+/// Count conflicts on or after a specific change
+/// This counts conflicts in the specified change and all its descendants
+/// If repo_path is provided, runs jj in that directory
+pub fn count_conflicts_in(change_id: &str, repo_path: Option<&Path>) -> Result<usize> {
+    Ok(list_conflicts_in(change_id, repo_path)?.len())
+}
+
+/// Count conflicts on or after a specific change as of the last snapshot, without
+/// snapshotting the working copy. For readers like the statusline that run alongside
+/// the hooks and must not create operations of their own
+/// If repo_path is provided, runs jj in that directory
+pub fn count_conflicts_without_snapshot_in(
+    change_id: &str,
+    repo_path: Option<&Path>,
+) -> Result<usize> {
+    Ok(list_conflicts_snapshotting_in(change_id, false, repo_path)?.len())
 }
 
 /// Count conflicts on or after a specific change in the current directory
@@ -321,27 +1159,27 @@ pub fn count_conflicts(change_id: &str) -> Result<usize> {
     count_conflicts_in(change_id, None)
 }
 
-/// Get the change ID of a specific revision
 /// Get the description of a given revision
+/// Invalid UTF-8 bytes are replaced with U+FFFD, so the result is for display and
+/// matching only - never feed it back into `jj describe`, use
+/// [`get_commit_description_bytes_in`] for rewrites instead
 /// If repo_path is provided, runs jj in that directory
 pub fn get_commit_description_in(revset: &str, repo_path: Option<&Path>) -> Result<String> {
-    let mut cmd = Command::new("jj");
+    let mut cmd = jj_command();
     if let Some(path) = repo_path {
         cmd.current_dir(path);
     }
 
-    let output = cmd
-        .args([
-            "log",
-            "-r",
-            revset,
-            "-T",
-            "description",
-            "--no-graph",
-            "--ignore-working-copy",
-        ])
-        .output()
-        .context("Failed to execute jj log")?;
+    let output = run_jj(cmd.args([
+        "log",
+        "-r",
+        revset,
+        "-T",
+        "description",
+        "--no-graph",
+        "--ignore-working-copy",
+    ]))
+    .context("Failed to execute jj log")?;
 
     if !output.status.success() {
         anyhow::bail!(
@@ -360,40 +1198,163 @@ pub fn get_commit_description(revset: &str) -> Result<String> {
     get_commit_description_in(revset, None)
 }
 
-/// Get the change ID of a given revision
+/// Get the raw description bytes of a given revision, without any UTF-8 conversion
+/// Leading and trailing ASCII whitespace is trimmed
 /// If repo_path is provided, runs jj in that directory
-pub fn get_change_id_in(revset: &str, repo_path: Option<&Path>) -> Result<String> {
-    let mut cmd = Command::new("jj");
+pub fn get_commit_description_bytes_in(revset: &str, repo_path: Option<&Path>) -> Result<Vec<u8>> {
+    let mut cmd = jj_command();
     if let Some(path) = repo_path {
         cmd.current_dir(path);
     }
 
-    let output = cmd
-        .args([
-            "log",
-            "-r",
+    let output = run_jj(cmd.args([
+        "log",
+        "-r",
+        revset,
+        "-T",
+        "description",
+        "--no-graph",
+        "--ignore-working-copy",
+    ]))
+    .context("Failed to execute jj log")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj log failed for revset '{}': {}",
             revset,
-            "-T",
-            "change_id.short()",
-            "--no-graph",
-            "--ignore-working-copy",
-        ])
-        .output()
-        .context("Failed to execute jj log to get change ID")?;
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(output.stdout.trim_ascii().to_vec())
+}
+
+/// Set a revision's description from raw bytes via `jj describe --stdin`
+/// Unlike `-m`, this does not require the message to be valid UTF-8
+/// If repo_path is provided, runs jj in that directory
+fn describe_with_bytes_in(revset: &str, message: &[u8], repo_path: Option<&Path>) -> Result<()> {
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+
+    let mut child = cmd
+        .args(["describe", "-r", revset, "--stdin"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to execute jj describe")?;
+
+    child
+        .stdin
+        .take()
+        .context("Failed to open jj describe stdin")?
+        .write_all(message)
+        .context("Failed to write description to jj describe")?;
+
+    let output = wait_jj_with_output(&cmd, child).context("Failed to wait for jj describe")?;
 
     if !output.status.success() {
         anyhow::bail!(
-            "jj log failed while getting change ID: {}",
+            "jj describe failed: {}",
             String::from_utf8_lossy(&output.stderr)
         );
     }
 
-    let change_id = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    if change_id.is_empty() {
-        anyhow::bail!("No change found for revset: {}", revset);
+    Ok(())
+}
+
+/// Decode jj output as UTF-8, failing instead of replacing invalid bytes
+/// Used where the decoded text is written back into a commit
+fn decode_utf8_strict(bytes: Vec<u8>, what: &str) -> Result<String> {
+    String::from_utf8(bytes).map_err(|e| {
+        anyhow::anyhow!(
+            "{} is not valid UTF-8 (invalid byte at offset {})",
+            what,
+            e.utf8_error().valid_up_to()
+        )
+    })
+}
+
+/// Get the short change ID of a given revision, for display and as a revset
+/// Use get_full_change_id_in when comparing against IDs from the find_session_* functions,
+/// which return full change IDs
+/// Fails if the revset resolves to no commits or to more than one commit
+/// If repo_path is provided, runs jj in that directory
+pub fn get_change_id_in(revset: &str, repo_path: Option<&Path>) -> Result<String> {
+    resolve_change_id_in(revset, r#"change_id.short() ++ "\n""#, repo_path)
+}
+
+/// Get the full change ID of a given revision
+/// Fails if the revset resolves to no commits or to more than one commit
+/// If repo_path is provided, runs jj in that directory
+pub fn get_full_change_id_in(revset: &str, repo_path: Option<&Path>) -> Result<String> {
+    resolve_change_id_in(revset, r#"change_id ++ "\n""#, repo_path)
+}
+
+/// Get the full change ID of a specific revision in the current directory
+pub fn get_full_change_id(revset: &str) -> Result<String> {
+    get_full_change_id_in(revset, None)
+}
+
+/// Check whether any commit in a revset is immutable
+/// If repo_path is provided, runs jj in that directory
+pub fn is_immutable_in(revset: &str, repo_path: Option<&Path>) -> Result<bool> {
+    let immutable = list_change_ids_in(&format!("({}) & immutable()", revset), repo_path)?;
+    Ok(!immutable.is_empty())
+}
+
+/// Fail with a clear error if the commit a revset resolves to is immutable, instead of
+/// letting a later rewrite fail with jj's own error
+/// If repo_path is provided, runs jj in that directory
+pub fn ensure_mutable_in(revset: &str, repo_path: Option<&Path>) -> Result<()> {
+    if is_immutable_in(revset, repo_path)? {
+        anyhow::bail!(
+            "Cannot modify immutable commit {}",
+            get_change_id_in(revset, repo_path)?
+        );
+    }
+    Ok(())
+}
+
+/// Resolve a revset to exactly one change ID, rendered with the given template
+fn resolve_change_id_in(revset: &str, template: &str, repo_path: Option<&Path>) -> Result<String> {
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
     }
 
-    Ok(change_id)
+    let output = run_jj(cmd.args([
+        "log",
+        "-r",
+        revset,
+        "-T",
+        template,
+        "--no-graph",
+        "--ignore-working-copy",
+    ]))
+    .context("Failed to execute jj log to get change ID")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj log failed while getting change ID: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut change_ids = parse_change_ids(&stdout);
+    match change_ids.len() {
+        0 => anyhow::bail!("No change found for revset: {}", revset),
+        1 => Ok(change_ids.remove(0)),
+        n => anyhow::bail!(
+            "Revset '{}' resolved to {} commits ({}), expected exactly one",
+            revset,
+            n,
+            change_ids.join(", ")
+        ),
+    }
 }
 
 /// Get the change ID of a specific revision in the current directory
@@ -401,33 +1362,72 @@ pub fn get_change_id(revset: &str) -> Result<String> {
     get_change_id_in(revset, None)
 }
 
+/// Check that a revision used by the hooks resolves to exactly one commit
+/// Unusual states (e.g. right after `jj op restore`, or a merge as the parent of @)
+/// would otherwise surface as cryptic failures in later jj commands
+/// If repo_path is provided, runs jj in that directory
+pub fn ensure_single_commit_in(revset: &str, repo_path: Option<&Path>) -> Result<()> {
+    get_change_id_in(revset, repo_path)
+        .map(|_| ())
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "'{}' does not resolve to exactly one commit ({}). \
+             jjagent needs a single working copy commit with a single parent. \
+             Run `jj log -r '{}'` to inspect the repo state.",
+                revset,
+                e,
+                revset
+            )
+        })
+}
+
+/// Check that a revision resolves to exactly one commit in the current directory
+pub fn ensure_single_commit(revset: &str) -> Result<()> {
+    ensure_single_commit_in(revset, None)
+}
+
 /// Check if the current commit (@) is a precommit for the given session
-/// Returns true if @ has a Claude-precommit-session-id trailer matching the session_id
+/// Returns true if @ has a precommit trailer (see PrecommitConfig) matching the session_id
 /// If repo_path is provided, runs jj in that directory
 pub fn is_current_commit_precommit_for_session_in(
     session_id: &str,
     repo_path: Option<&Path>,
 ) -> Result<bool> {
-    let template =
-        r#"trailers.map(|t| if(t.key() == "Claude-precommit-session-id", t.value(), "")).join("")"#;
+    is_current_commit_precommit_with_config_in(
+        session_id,
+        &crate::session::PrecommitConfig::from_env(),
+        repo_path,
+    )
+}
+
+/// Check if the current commit (@) is a precommit for the given session, looking for the
+/// trailer key from the given config
+/// If repo_path is provided, runs jj in that directory
+pub fn is_current_commit_precommit_with_config_in(
+    session_id: &str,
+    config: &crate::session::PrecommitConfig,
+    repo_path: Option<&Path>,
+) -> Result<bool> {
+    let template = format!(
+        r#"trailers.map(|t| if(t.key() == "{}", t.value(), "")).join("")"#,
+        escape_string_literal(&config.trailer_key())
+    );
 
-    let mut cmd = Command::new("jj");
+    let mut cmd = jj_command();
     if let Some(path) = repo_path {
         cmd.current_dir(path);
     }
 
-    let output = cmd
-        .args([
-            "log",
-            "-r",
-            "@",
-            "-T",
-            template,
-            "--no-graph",
-            "--ignore-working-copy",
-        ])
-        .output()
-        .context("Failed to execute jj log to check precommit")?;
+    let output = run_jj(cmd.args([
+        "log",
+        "-r",
+        "@",
+        "-T",
+        &template,
+        "--no-graph",
+        "--ignore-working-copy",
+    ]))
+    .context("Failed to execute jj log to check precommit")?;
 
     if !output.status.success() {
         anyhow::bail!(
@@ -456,26 +1456,26 @@ pub fn is_current_commit_precommit_for_session(session_id: &str) -> Result<bool>
 /// Returns the session ID if present, None otherwise
 /// If repo_path is provided, runs jj in that directory
 pub fn get_current_commit_session_id_in(repo_path: Option<&Path>) -> Result<Option<String>> {
-    let template =
-        r#"trailers.map(|t| if(t.key() == "Claude-session-id", t.value(), "")).join("")"#;
+    let template = format!(
+        r#"trailers.map(|t| if({}, t.value(), "")).join("")"#,
+        SESSION_TRAILER_KEY_CONDITION
+    );
 
-    let mut cmd = Command::new("jj");
+    let mut cmd = jj_command();
     if let Some(path) = repo_path {
         cmd.current_dir(path);
     }
 
-    let output = cmd
-        .args([
-            "log",
-            "-r",
-            "@",
-            "-T",
-            template,
-            "--no-graph",
-            "--ignore-working-copy",
-        ])
-        .output()
-        .context("Failed to execute jj log to check session ID")?;
+    let output = run_jj(cmd.args([
+        "log",
+        "-r",
+        "@",
+        "-T",
+        &template,
+        "--no-graph",
+        "--ignore-working-copy",
+    ]))
+    .context("Failed to execute jj log to check session ID")?;
 
     if !output.status.success() {
         anyhow::bail!(
@@ -506,26 +1506,26 @@ pub fn get_current_commit_session_id() -> Result<Option<String>> {
 pub fn get_session_id_in(revset: &str, repo_path: Option<&Path>) -> Result<Option<String>> {
     // Use jj template to extract only Claude-session-id trailer values
     // We get all of them and will pick the last one
-    let template =
-        r#"trailers.filter(|t| t.key() == "Claude-session-id").map(|t| t.value()).join("\n")"#;
+    let template = format!(
+        r#"trailers.filter(|t| {}).map(|t| t.value()).join("\n")"#,
+        SESSION_TRAILER_KEY_CONDITION
+    );
 
-    let mut cmd = Command::new("jj");
+    let mut cmd = jj_command();
     if let Some(path) = repo_path {
         cmd.current_dir(path);
     }
 
-    let output = cmd
-        .args([
-            "log",
-            "-r",
-            revset,
-            "-T",
-            template,
-            "--no-graph",
-            "--ignore-working-copy",
-        ])
-        .output()
-        .context("Failed to execute jj log to get session ID")?;
+    let output = run_jj(cmd.args([
+        "log",
+        "-r",
+        revset,
+        "-T",
+        &template,
+        "--no-graph",
+        "--ignore-working-copy",
+    ]))
+    .context("Failed to execute jj log to get session ID")?;
 
     if !output.status.success() {
         anyhow::bail!(
@@ -560,23 +1560,21 @@ pub fn get_session_id(revset: &str) -> Result<Option<String>> {
 pub fn get_commit_trailers_in(revset: &str, repo_path: Option<&Path>) -> Result<Vec<String>> {
     let template = r#"trailers.map(|t| t.key() ++ ": " ++ t.value()).join("\n")"#;
 
-    let mut cmd = Command::new("jj");
+    let mut cmd = jj_command();
     if let Some(path) = repo_path {
         cmd.current_dir(path);
     }
 
-    let output = cmd
-        .args([
-            "log",
-            "-r",
-            revset,
-            "-T",
-            template,
-            "--no-graph",
-            "--ignore-working-copy",
-        ])
-        .output()
-        .context("Failed to execute jj log to get trailers")?;
+    let output = run_jj(cmd.args([
+        "log",
+        "-r",
+        revset,
+        "-T",
+        template,
+        "--no-graph",
+        "--ignore-working-copy",
+    ]))
+    .context("Failed to execute jj log to get trailers")?;
 
     if !output.status.success() {
         anyhow::bail!(
@@ -585,7 +1583,10 @@ pub fn get_commit_trailers_in(revset: &str, repo_path: Option<&Path>) -> Result<
         );
     }
 
-    let trailers_str = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    // Trailers are written back by update_description_preserving_trailers_in,
+    // so refuse to decode them lossily
+    let trailers_str = decode_utf8_strict(output.stdout, "Commit trailers")?;
+    let trailers_str = trailers_str.trim();
 
     if trailers_str.is_empty() {
         Ok(Vec::new())
@@ -599,6 +1600,17 @@ pub fn get_commit_trailers(revset: &str) -> Result<Vec<String>> {
     get_commit_trailers_in(revset, None)
 }
 
+/// Get a commit's description with its trailer block removed
+/// If repo_path is provided, runs jj in that directory
+pub fn get_description_without_trailers_in(
+    revset: &str,
+    repo_path: Option<&Path>,
+) -> Result<String> {
+    let description = get_commit_description_bytes_in(revset, repo_path)?;
+    let (message, _) = split_description_and_trailers(&description);
+    Ok(String::from_utf8_lossy(message).trim_end().to_string())
+}
+
 /// Update a commit's description while preserving its trailers
 /// The new_message should not include trailers - they will be automatically appended
 /// If repo_path is provided, runs jj in that directory
@@ -618,14 +1630,12 @@ pub fn update_description_preserving_trailers_in(
     };
 
     // Update the commit description
-    let mut cmd = Command::new("jj");
+    let mut cmd = jj_command();
     if let Some(path) = repo_path {
         cmd.current_dir(path);
     }
 
-    let output = cmd
-        .args(["describe", "-r", revset, "-m", &complete_message])
-        .output()
+    let output = run_jj(cmd.args(["describe", "-r", revset, "-m", &complete_message]))
         .context("Failed to execute jj describe")?;
 
     if !output.status.success() {
@@ -643,6 +1653,223 @@ pub fn update_description_preserving_trailers(revset: &str, new_message: &str) -
     update_description_preserving_trailers_in(revset, new_message, None)
 }
 
+/// Parse a comma-separated list of globs (as in JJAGENT_EXCLUDE_PATHS)
+pub fn parse_exclude_patterns(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .map(|p| p.to_string())
+        .collect()
+}
+
+/// Build a jj fileset matching any of the globs, relative to the repo root
+fn exclude_fileset(patterns: &[String]) -> String {
+    patterns
+        .iter()
+        .map(|p| format!("root-glob:{:?}", p))
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+/// Move changes to excluded paths out of the precommit (@) and into uwc (@-)
+/// so they never end up in a session change. The files on disk are unchanged.
+/// Returns the excluded paths the precommit touched
+/// If repo_path is provided, runs jj in that directory
+pub fn move_excluded_paths_to_uwc_in(
+    patterns: &[String],
+    repo_path: Option<&Path>,
+) -> Result<Vec<String>> {
+    if patterns.is_empty() {
+        return Ok(Vec::new());
+    }
+    let fileset = exclude_fileset(patterns);
+
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+    let output = run_jj(cmd.args(["diff", "-r", "@", "--name-only", "--", &fileset]))
+        .context("Failed to execute jj diff")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj diff failed for excluded paths: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let touched = parse_change_ids(&String::from_utf8_lossy(&output.stdout));
+    if touched.is_empty() {
+        return Ok(touched);
+    }
+
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+    let output = run_jj(cmd.args([
+        "squash",
+        "--from",
+        "@",
+        "--into",
+        "@-",
+        "--use-destination-message",
+        "--",
+        &fileset,
+    ]))
+    .context("Failed to move excluded paths into uwc")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to move excluded paths into uwc: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(touched)
+}
+
+/// Move changes to excluded paths out of the precommit in the current directory
+pub fn move_excluded_paths_to_uwc(patterns: &[String]) -> Result<Vec<String>> {
+    move_excluded_paths_to_uwc_in(patterns, None)
+}
+
+/// How Claude's edits are folded into the session change
+/// Selected with JJAGENT_SQUASH_STRATEGY
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SquashStrategy {
+    /// Squash the whole precommit into the session change
+    #[default]
+    Squash,
+    /// Experimental: `jj absorb` hunks into the session parts that last touched them,
+    /// then squash whatever is left
+    Absorb,
+}
+
+impl SquashStrategy {
+    /// Parse a JJAGENT_SQUASH_STRATEGY value
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" | "squash" => Ok(Self::Squash),
+            "absorb" => Ok(Self::Absorb),
+            other => anyhow::bail!(
+                "Unknown JJAGENT_SQUASH_STRATEGY '{}', expected 'squash' or 'absorb'",
+                other
+            ),
+        }
+    }
+}
+
+/// Absorb the precommit (@) into the mutable parts of a session
+/// Each hunk moves into the part that last modified those lines; hunks no part touched
+/// stay in the precommit for the regular squash. The absorb is undone if it introduces
+/// conflicts. Returns true if the absorb was kept
+/// If repo_path is provided, runs jj in that directory
+pub fn absorb_precommit_into_session_in(
+    session_id: &str,
+    repo_path: Option<&Path>,
+) -> Result<bool> {
+    let parts = find_all_session_changes_in(session_id, repo_path)?;
+    if parts.is_empty() {
+        return Ok(false);
+    }
+    let into = format!("({}) & ~immutable()", parts.join(" | "));
+
+    let conflicts_before = count_conflicts_in("root()", repo_path)?;
+
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+    let output = run_jj(cmd.args(["absorb", "--from", "@", "--into", &into]))
+        .context("Failed to execute jj absorb")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj absorb failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    if count_conflicts_in("root()", repo_path)? > conflicts_before {
+        let mut cmd = jj_command();
+        if let Some(path) = repo_path {
+            cmd.current_dir(path);
+        }
+        let output = run_jj(cmd.args(["undo"])).context("Failed to execute jj undo")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to undo conflicting absorb: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
+/// Absorb the precommit into the session parts in the current directory
+pub fn absorb_precommit_into_session(session_id: &str) -> Result<bool> {
+    absorb_precommit_into_session_in(session_id, None)
+}
+
+/// How many times a squash is tried when it fails on a stale working copy or a
+/// concurrent operation, and the delay before the first retry (doubled after each)
+const SQUASH_ATTEMPTS: u32 = 3;
+const SQUASH_RETRY_DELAY_MS: u64 = 100;
+
+/// Whether jj's stderr says it failed because the working copy went stale or another
+/// operation ran at the same time (e.g. a watchman snapshot), which `jj workspace
+/// update-stale` and a retry resolve. jj commits nothing when it fails this way
+pub fn is_retryable_jj_error(stderr: &str) -> bool {
+    let stderr = stderr.to_lowercase();
+    [
+        "working copy is stale",
+        "concurrent operation",
+        "resolved to more than one operation",
+    ]
+    .iter()
+    .any(|pattern| stderr.contains(pattern))
+}
+
+/// Run a (signed, if configured) jj squash, retrying with backoff after an
+/// `update-stale` while it fails with a retryable error (see is_retryable_jj_error)
+/// Returns the last attempt's output
+fn run_squash_with_retry_in(args: &[&str], repo_path: Option<&Path>) -> Result<Output> {
+    let mut delay = Duration::from_millis(SQUASH_RETRY_DELAY_MS);
+    for attempt in 1.. {
+        let mut cmd = jj_signing_command();
+        if let Some(path) = repo_path {
+            cmd.current_dir(path);
+        }
+        let output = run_jj(cmd.args(args))?;
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if output.status.success() || attempt >= SQUASH_ATTEMPTS || !is_retryable_jj_error(&stderr)
+        {
+            return Ok(output);
+        }
+
+        eprintln!(
+            "jjagent: Warning - jj squash failed ({}), retrying",
+            stderr.lines().next().unwrap_or_default().trim()
+        );
+        thread::sleep(delay);
+        delay *= 2;
+
+        let mut cmd = jj_command();
+        if let Some(path) = repo_path {
+            cmd.current_dir(path);
+        }
+        run_jj(cmd.args(["workspace", "update-stale"]))
+            .context("Failed to update stale working copy")?;
+    }
+    unreachable!("the last attempt always returns")
+}
+
 /// Attempt to squash precommit into session change (happy path)
 /// Returns true if new conflicts were introduced, false otherwise
 /// If repo_path is provided, runs jj in that directory
@@ -667,14 +1894,11 @@ pub fn squash_precommit_into_session_in(
 
     // Squash precommit into session (from current position @ = precommit)
     // This leaves us on a new empty commit above uwc
-    let mut cmd = Command::new("jj");
-    if let Some(path) = repo_path {
-        cmd.current_dir(path);
-    }
-    let output = cmd
-        .args(["squash", "--into", session_id, "--use-destination-message"])
-        .output()
-        .context("Failed to execute jj squash")?;
+    let output = run_squash_with_retry_in(
+        &["squash", "--into", session_id, "--use-destination-message"],
+        repo_path,
+    )
+    .context("Failed to execute jj squash")?;
 
     if !output.status.success() {
         anyhow::bail!(
@@ -685,12 +1909,8 @@ pub fn squash_precommit_into_session_in(
 
     // Now we're on a new empty commit above uwc
     // Restore uwc by squashing it into the current empty commit
-    let mut cmd = Command::new("jj");
-    if let Some(path) = repo_path {
-        cmd.current_dir(path);
-    }
-    let output = cmd
-        .args([
+    let output = run_squash_with_retry_in(
+        &[
             "squash",
             "--from",
             "@-", // from uwc (which is now @-)
@@ -698,9 +1918,10 @@ pub fn squash_precommit_into_session_in(
             "@", // into current empty commit
             "-m",
             &uwc_description, // preserve uwc's description
-        ])
-        .output()
-        .context("Failed to restore uwc")?;
+        ],
+        repo_path,
+    )
+    .context("Failed to restore uwc")?;
 
     if !output.status.success() {
         anyhow::bail!(
@@ -716,6 +1937,67 @@ pub fn squash_precommit_into_session_in(
     Ok(conflicts_after > conflicts_before)
 }
 
+/// Notes a user wrote on a precommit: its description without the trailers and without the
+/// generated "jjagent: precommit" title, or None if that leaves nothing
+pub fn precommit_notes(description: &[u8]) -> Option<Vec<u8>> {
+    let (body, _) = split_description_and_trailers(description);
+    let body = body.trim_ascii();
+    let notes = if body.starts_with(b"jjagent: precommit") {
+        // Drop the generated title line
+        body.iter()
+            .position(|&b| b == b'\n')
+            .map_or(&[][..], |end| body[end..].trim_ascii())
+    } else {
+        body
+    };
+    (!notes.is_empty()).then(|| notes.to_vec())
+}
+
+/// Add a paragraph to a change's description, after its title and before its trailers
+/// If repo_path is provided, runs jj in that directory
+pub fn append_description_notes_in(
+    revset: &str,
+    notes: &[u8],
+    repo_path: Option<&Path>,
+) -> Result<()> {
+    append_to_description_body_in(revset, b"\n\n", notes, repo_path)
+}
+
+/// Add a line to a change's description, directly below its body and above its trailers
+/// The existing description is kept byte for byte, even if it isn't valid UTF-8
+/// If repo_path is provided, runs jj in that directory
+pub fn append_description_line_in(
+    revset: &str,
+    line: &str,
+    repo_path: Option<&Path>,
+) -> Result<()> {
+    append_to_description_body_in(revset, b"\n", line.as_bytes(), repo_path)
+}
+
+/// Append `text` to the body of a change's description, joined by `separator` unless the
+/// body is empty, and re-append the trailers
+fn append_to_description_body_in(
+    revset: &str,
+    separator: &[u8],
+    text: &[u8],
+    repo_path: Option<&Path>,
+) -> Result<()> {
+    let description = get_commit_description_bytes_in(revset, repo_path)?;
+    let (body, trailers) = split_description_and_trailers(&description);
+
+    let mut message = body.trim_ascii().to_vec();
+    if !message.is_empty() {
+        message.extend_from_slice(separator);
+    }
+    message.extend_from_slice(text);
+    if !trailers.is_empty() {
+        message.extend_from_slice(b"\n\n");
+        message.extend_from_slice(trailers.join("\n").as_bytes());
+    }
+
+    describe_with_bytes_in(revset, &message, repo_path)
+}
+
 /// Attempt to squash precommit into session change in the current directory
 pub fn squash_precommit_into_session(
     precommit_id: &str,
@@ -729,44 +2011,34 @@ pub fn squash_precommit_into_session(
 /// If repo_path is provided, runs jj in that directory
 ///
 /// This function:
-/// 1. Runs `jj undo` twice to revert both squash operations (precommit->session, uwc->@)
-/// 2. Renames precommit to "jjagent: session {short_id} pt. {part}"
+/// 1. Restores the operation recorded before the squash, reverting both squashes
+///    (precommit->session, uwc->@) and any retries or update-stale operations between them
+/// 2. Renames precommit to the session part title (e.g. "jjagent: session {short_id} pt. {part}")
 /// 3. Creates a new working copy on top
 /// 4. Attempts to move uwc to the tip by squashing it into the new working copy
+///
+/// uwc_id is the user's working copy the precommit sat on, as recorded before the squash.
+/// The undo restores it, so it's passed in rather than guessed from the ancestry,
+/// which can't tell uwc apart when several sessions' changes are interleaved.
+/// operation_before_squash is the current_operation_id_in from just before the squash
 pub fn handle_squash_conflicts_in(
     session_id: &SessionId,
     part: usize,
+    uwc_id: &str,
+    operation_before_squash: &str,
     repo_path: Option<&Path>,
 ) -> Result<()> {
-    // Undo twice: once for uwc restoration squash, once for precommit->session squash
-    for _ in 0..2 {
-        let mut cmd = Command::new("jj");
-        if let Some(path) = repo_path {
-            cmd.current_dir(path);
-        }
-        let output = cmd
-            .args(["undo"])
-            .output()
-            .context("Failed to execute jj undo")?;
-
-        if !output.status.success() {
-            anyhow::bail!(
-                "jj undo failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            );
-        }
-    }
+    // Counting `jj undo`s would miss the operations a squash retry adds, so go back by ID
+    restore_operation_in(operation_before_squash, repo_path)?;
 
     // Rename precommit to "pt. N" with trailer
     let message = crate::session::format_session_part_message(session_id, part);
-    let mut cmd = Command::new("jj");
+    let mut cmd = jj_signing_command();
     if let Some(path) = repo_path {
         cmd.current_dir(path);
     }
-    let output = cmd
-        .args(["describe", "-m", &message])
-        .output()
-        .context("Failed to execute jj describe")?;
+    let output =
+        run_jj(cmd.args(["describe", "-m", &message])).context("Failed to execute jj describe")?;
 
     if !output.status.success() {
         anyhow::bail!(
@@ -776,153 +2048,263 @@ pub fn handle_squash_conflicts_in(
     }
 
     // Create new working copy on top
-    let mut cmd = Command::new("jj");
+    let mut cmd = jj_command();
     if let Some(path) = repo_path {
         cmd.current_dir(path);
     }
-    let output = cmd
-        .args(["new"])
-        .output()
-        .context("Failed to execute jj new")?;
+    let output = run_jj(cmd.args(["new"])).context("Failed to execute jj new")?;
 
     if !output.status.success() {
         anyhow::bail!("jj new failed: {}", String::from_utf8_lossy(&output.stderr));
     }
 
-    // Try to move uwc to the tip
-    // Find the uwc by looking for the first non-session change in ancestors
-    // This should be the user's working copy that existed before the session changes
-    let mut cmd = Command::new("jj");
+    // Try to move uwc to the tip, first getting its description to preserve it
+    let mut cmd = jj_command();
     if let Some(path) = repo_path {
         cmd.current_dir(path);
     }
+    let desc_output = run_jj(cmd.args(["log", "-r", uwc_id, "--no-graph", "-T", "description"]))
+        .context("Failed to get uwc description")?;
 
-    // Use jj template to mark each commit as SESSION or OTHER based on trailer presence
-    let template = r#"if(trailers.any(|t| t.key() == "Claude-session-id"), "SESSION:", "OTHER:") ++ change_id ++ "\n""#;
-    let log_output = cmd
-        .args([
-            "log",
-            "-r",
-            "::@- & ~root()", // All ancestors of @- except root
-            "--no-graph",
-            "-T",
-            template,
-        ])
-        .output()
-        .context("Failed to get ancestor changes")?;
+    if !desc_output.status.success() {
+        anyhow::bail!(
+            "Failed to get uwc description: {}",
+            String::from_utf8_lossy(&desc_output.stderr)
+        );
+    }
+
+    let uwc_description = String::from_utf8_lossy(&desc_output.stdout)
+        .trim()
+        .to_string();
 
-    // Find a non-session change that appears to be "trapped" between session changes
-    let mut uwc_id = None;
-    if log_output.status.success() {
-        let output = String::from_utf8_lossy(&log_output.stdout);
-        let mut found_session = false;
+    // Count conflicts in the entire stack before attempting squash
+    // We need to check from root:: to catch all conflicts
+    let conflicts_before = count_conflicts_in("root()", repo_path)?;
 
-        for line in output.lines() {
-            let line = line.trim();
-            if line.is_empty() {
-                continue;
+    // Try to squash uwc into the new working copy, preserving uwc's description
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+    let squash_output = run_jj(cmd.args([
+        "squash",
+        "--from",
+        uwc_id,
+        "--into",
+        "@",
+        "-m",
+        &uwc_description,
+    ]))
+    .context("Failed to squash uwc to tip")?;
+
+    if squash_output.status.success() {
+        // Check if new conflicts were introduced anywhere in the stack
+        let conflicts_after = count_conflicts_in("root()", repo_path)?;
+
+        if conflicts_after > conflicts_before {
+            // New conflicts introduced, undo the squash
+            let mut cmd = jj_command();
+            if let Some(path) = repo_path {
+                cmd.current_dir(path);
             }
+            let undo_output = run_jj(cmd.args(["undo"])).context("Failed to undo uwc squash")?;
 
-            if line.starts_with("SESSION:") {
-                found_session = true;
-            } else if let Some(change_id) = line.strip_prefix("OTHER:")
-                && found_session
-            {
-                uwc_id = Some(change_id.to_string());
-                break;
+            if !undo_output.status.success() {
+                anyhow::bail!(
+                    "Failed to undo uwc squash: {}",
+                    String::from_utf8_lossy(&undo_output.stderr)
+                );
             }
         }
+        // If no new conflicts, we successfully moved uwc to the tip
+    }
+
+    Ok(())
+}
+
+/// A commit in an ancestry scan, tagged by whether it carries a Claude-session-id trailer
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AncestryEntry {
+    change_id: String,
+    is_session: bool,
+    /// Empty with no description, like a fresh `jj new`
+    is_blank: bool,
+}
+
+/// List the commits in a revset newest first, marking session changes
+/// Used to locate uwc relative to the session changes around it
+/// If repo_path is provided, runs jj in that directory
+fn scan_session_ancestry_in(revset: &str, repo_path: Option<&Path>) -> Result<Vec<AncestryEntry>> {
+    // Use jj template to mark each commit as SESSION or OTHER based on trailer presence
+    let template = format!(
+        concat!(
+            r#"if(trailers.any(|t| {}), "SESSION:", "OTHER:") ++ "#,
+            r#"if(empty && description == "", "BLANK:", "FULL:") ++ change_id ++ "\n""#
+        ),
+        SESSION_TRAILER_KEY_CONDITION
+    );
+
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+    let output = run_jj(cmd.args(["log", "-r", revset, "--no-graph", "-T", &template]))
+        .context("Failed to get ancestor changes")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to get ancestor changes: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(parse_session_ancestry(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+/// Parse the output of the scan_session_ancestry_in template
+fn parse_session_ancestry(output: &str) -> Vec<AncestryEntry> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (kind, rest) = line.trim().split_once(':')?;
+            let (fill, change_id) = rest.split_once(':')?;
+            Some(AncestryEntry {
+                change_id: change_id.to_string(),
+                is_session: kind == "SESSION",
+                is_blank: fill == "BLANK",
+            })
+        })
+        .collect()
+}
+
+/// Re-establish the uwc-on-top invariant after manual edits
+/// uwc is the newest non-session ancestor of @ (skipping @ itself if it's a blank new change).
+/// Any session changes that ended up above uwc are rebased to sit directly below it,
+/// keeping their order. If @ was one of them, @ moves back to uwc.
+/// The rebase is undone if it would introduce new conflicts.
+/// Returns the change IDs of the session changes that were moved (empty if already in order)
+/// If repo_path is provided, runs jj in that directory
+pub fn reflow_uwc_above_sessions_in(repo_path: Option<&Path>) -> Result<Vec<String>> {
+    let ancestry = scan_session_ancestry_in("::@ & ~root()", repo_path)?;
+    let at_is_session = ancestry.first().is_some_and(|entry| entry.is_session);
+    // A blank @ is just a fresh `jj new` on top, not the user's work
+    let skip_blank_at = ancestry.first().is_some_and(|entry| entry.is_blank) as usize;
+    let Some(uwc) = ancestry
+        .iter()
+        .skip(skip_blank_at)
+        .find(|entry| !entry.is_session)
+        .map(|entry| entry.change_id.clone())
+    else {
+        anyhow::bail!("Could not find a non-session change to treat as the user's working copy");
+    };
+
+    // Session changes anywhere above uwc are out of place
+    let misplaced: Vec<String> =
+        scan_session_ancestry_in(&format!("descendants({}) ~ {}", uwc, uwc), repo_path)?
+            .into_iter()
+            .filter(|entry| entry.is_session)
+            .map(|entry| entry.change_id)
+            .collect();
+
+    if misplaced.is_empty() {
+        return Ok(misplaced);
+    }
+
+    let conflicts_before = count_conflicts_in("root()", repo_path)?;
+
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+    let output = run_jj(cmd.args([
+        "rebase",
+        "-r",
+        &misplaced.join(" | "),
+        "--insert-before",
+        &uwc,
+    ]))
+    .context("Failed to execute jj rebase")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj rebase failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
     }
 
-    if let Some(uwc_id) = uwc_id {
-        // First get the uwc's description to preserve it
-        let mut cmd = Command::new("jj");
+    if count_conflicts_in("root()", repo_path)? > conflicts_before {
+        let mut cmd = jj_command();
         if let Some(path) = repo_path {
             cmd.current_dir(path);
         }
-        let desc_output = cmd
-            .args(["log", "-r", &uwc_id, "--no-graph", "-T", "description"])
-            .output()
-            .context("Failed to get uwc description")?;
-
-        if !desc_output.status.success() {
+        let output = run_jj(cmd.args(["undo"])).context("Failed to execute jj undo")?;
+        if !output.status.success() {
             anyhow::bail!(
-                "Failed to get uwc description: {}",
-                String::from_utf8_lossy(&desc_output.stderr)
+                "jj undo failed: {}",
+                String::from_utf8_lossy(&output.stderr)
             );
         }
+        anyhow::bail!(
+            "Moving session changes below {} would introduce conflicts; left the repo unchanged",
+            uwc
+        );
+    }
 
-        let uwc_description = String::from_utf8_lossy(&desc_output.stdout)
-            .trim()
-            .to_string();
-
-        // Count conflicts in the entire stack before attempting squash
-        // We need to check from root:: to catch all conflicts
-        let conflicts_before = count_conflicts_in("root()", repo_path)?;
-
-        // Try to squash uwc into the new working copy, preserving uwc's description
-        let mut cmd = Command::new("jj");
+    // If @ was a session change it moved below uwc with the others, so go back to uwc
+    if at_is_session {
+        let mut cmd = jj_command();
         if let Some(path) = repo_path {
             cmd.current_dir(path);
         }
-        let squash_output = cmd
-            .args([
-                "squash",
-                "--from",
-                &uwc_id,
-                "--into",
-                "@",
-                "-m",
-                &uwc_description,
-            ])
-            .output()
-            .context("Failed to squash uwc to tip")?;
-
-        if squash_output.status.success() {
-            // Check if new conflicts were introduced anywhere in the stack
-            let conflicts_after = count_conflicts_in("root()", repo_path)?;
-
-            if conflicts_after > conflicts_before {
-                // New conflicts introduced, undo the squash
-                let mut cmd = Command::new("jj");
-                if let Some(path) = repo_path {
-                    cmd.current_dir(path);
-                }
-                let undo_output = cmd
-                    .args(["undo"])
-                    .output()
-                    .context("Failed to undo uwc squash")?;
-
-                if !undo_output.status.success() {
-                    anyhow::bail!(
-                        "Failed to undo uwc squash: {}",
-                        String::from_utf8_lossy(&undo_output.stderr)
-                    );
-                }
-            }
-            // If no new conflicts, we successfully moved uwc to the tip
+        let output = run_jj(cmd.args(["edit", &uwc])).context("Failed to execute jj edit")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj edit failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
         }
     }
 
-    Ok(())
+    Ok(misplaced)
+}
+
+/// Re-establish the uwc-on-top invariant in the current directory
+pub fn reflow_uwc_above_sessions() -> Result<Vec<String>> {
+    reflow_uwc_above_sessions_in(None)
 }
 
 /// Handle squash conflicts in the current directory
-pub fn handle_squash_conflicts(session_id: &SessionId, part: usize) -> Result<()> {
-    handle_squash_conflicts_in(session_id, part, None)
+pub fn handle_squash_conflicts(
+    session_id: &SessionId,
+    part: usize,
+    uwc_id: &str,
+    operation_before_squash: &str,
+) -> Result<()> {
+    handle_squash_conflicts_in(session_id, part, uwc_id, operation_before_squash, None)
 }
 
 /// Split a change by inserting a new change before @ (working copy)
 /// The reference can be either a Claude session ID or a jj reference (change ID, revset, etc.)
 /// Session IDs are looked up first before treating as a jj ref
-/// The reference must be an ancestor of @
+/// The reference must be an ancestor of @, or @ itself, in which case the new change
+/// becomes a child of @ and @ moves onto it
 /// If the reference has a session ID, creates a new session part
-pub fn split_change(reference: &str, repo_path: Option<&Path>) -> Result<()> {
+/// The new part is titled "<first line of the reference> (split <timestamp>)" unless a
+/// message is given, which is used verbatim (plus the Claude-session-id trailer)
+/// Returns the full change ID of the new part
+pub fn split_change(
+    reference: &str,
+    message: Option<&str>,
+    repo_path: Option<&Path>,
+) -> Result<String> {
     // First, try to interpret reference as a Claude session ID
-    let actual_reference = match find_session_change_anywhere_in(reference, repo_path)? {
-        Some(change_id) => {
+    // Use its latest part, like the hooks squash into the furthest part of a session
+    let actual_reference = match find_furthest_session_change_in(reference, repo_path)? {
+        Some(change) => {
             // Found a session by ID, use the change_id
-            change_id
+            change.change_id
         }
         None => {
             // Not a session ID, treat as a jj reference
@@ -930,22 +2312,21 @@ pub fn split_change(reference: &str, repo_path: Option<&Path>) -> Result<()> {
         }
     };
 
-    // Check if reference is an ancestor of @
-    let mut cmd = Command::new("jj");
+    // Check that reference is @ or an ancestor of @. `reference..@` isn't enough: it is
+    // also non-empty when @ is on an unrelated branch
+    let mut cmd = jj_command();
     if let Some(path) = repo_path {
         cmd.current_dir(path);
     }
-    let output = cmd
-        .args([
-            "log",
-            "-r",
-            &format!("{}..@", actual_reference),
-            "--no-graph",
-            "-T",
-            "change_id.short()",
-        ])
-        .output()
-        .context("Failed to check if reference is an ancestor")?;
+    let output = run_jj(cmd.args([
+        "log",
+        "-r",
+        &format!("({}) & ::@", actual_reference),
+        "--no-graph",
+        "-T",
+        r#"if(current_working_copy, "@", "ancestor") ++ "\n""#,
+    ]))
+    .context("Failed to check if reference is an ancestor")?;
 
     if !output.status.success() {
         anyhow::bail!(
@@ -955,23 +2336,39 @@ pub fn split_change(reference: &str, repo_path: Option<&Path>) -> Result<()> {
     }
 
     let stdout = String::from_utf8_lossy(&output.stdout);
-    // If the output is empty, then reference is not a proper ancestor
+    let reference_is_working_copy = stdout.lines().any(|line| line == "@");
+    // If the output is empty, then @ doesn't descend from reference
     if stdout.trim().is_empty() {
-        anyhow::bail!("Reference '{}' is not an ancestor of @", reference);
+        anyhow::bail!(
+            "Reference '{}' is not an ancestor of @; the working copy must descend from the \
+             session change to split it",
+            reference
+        );
     }
+    ensure_mutable_in(&actual_reference, repo_path)?;
 
-    // Get the session ID from the reference commit using trailers
+    // Get the first description line and the session ID from the reference commit
     // We extract the first Claude-session-id trailer value
-    let template =
-        r#"trailers.map(|t| if(t.key() == "Claude-session-id", t.value(), "")).join("\n")"#;
-    let mut cmd = Command::new("jj");
+    let template = format!(
+        concat!(
+            r#"description.first_line() ++ "\n" ++ "#,
+            r#"trailers.map(|t| if({}, t.value(), "")).join("\n")"#
+        ),
+        SESSION_TRAILER_KEY_CONDITION
+    );
+    let mut cmd = jj_command();
     if let Some(path) = repo_path {
         cmd.current_dir(path);
     }
-    let output = cmd
-        .args(["log", "-r", &actual_reference, "--no-graph", "-T", template])
-        .output()
-        .context("Failed to get reference commit info")?;
+    let output = run_jj(cmd.args([
+        "log",
+        "-r",
+        &actual_reference,
+        "--no-graph",
+        "-T",
+        &template,
+    ]))
+    .context("Failed to get reference commit info")?;
 
     if !output.status.success() {
         anyhow::bail!(
@@ -980,26 +2377,67 @@ pub fn split_change(reference: &str, repo_path: Option<&Path>) -> Result<()> {
         );
     }
 
-    let session_id_output = String::from_utf8_lossy(&output.stdout);
-    let session_id = session_id_output
-        .lines()
+    let reference_output = String::from_utf8_lossy(&output.stdout);
+    let mut lines = reference_output.lines();
+    let first_line = lines.next().unwrap_or_default().trim();
+    let session_id = lines
         .find(|line| !line.trim().is_empty())
         .context("Reference commit does not have a Claude-session-id trailer")?;
 
     let session_id = SessionId::from_full(session_id);
 
-    // Count existing session parts
-    let next_part = count_session_parts_in(session_id.full(), repo_path)? + 1;
-
+    let message = match message {
+        Some(message) => format!(
+            "{}\n\nClaude-session-id: {}",
+            message.trim_end(),
+            session_id.full()
+        ),
+        None => {
+            let first_line = if first_line.is_empty() {
+                crate::session::format_session_title(&session_id, None)
+            } else {
+                first_line.to_string()
+            };
+            format!(
+                "{}\n\nClaude-session-id: {}",
+                crate::session::format_split_message(
+                    &first_line,
+                    &crate::session::split_timestamp()
+                ),
+                session_id.full()
+            )
+        }
+    };
+
+    // When @ is the session change itself, start the new part as its child and move @ there
+    if reference_is_working_copy {
+        let mut cmd = jj_command();
+        if let Some(path) = repo_path {
+            cmd.current_dir(path);
+        }
+        let output = run_jj(cmd.args(["new", "@", "-m", &message]))
+            .context("Failed to create new change")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to create new change: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        return get_full_change_id_in("@", repo_path);
+    }
+
+    let working_copy_before = get_full_change_id_in("@", repo_path)?;
+
     // Insert a new change before @, keeping @ as working copy
-    let message = crate::session::format_session_part_message(&session_id, next_part);
-    let mut cmd = Command::new("jj");
+    // jj snapshots the working copy into @ before inserting, so uncommitted edits stay
+    // in @ and the new part starts out empty
+    let mut cmd = jj_command();
     if let Some(path) = repo_path {
         cmd.current_dir(path);
     }
-    let output = cmd
-        .args(["new", "--insert-before", "@", "--no-edit", "-m", &message])
-        .output()
+    let output = run_jj(cmd.args(["new", "--insert-before", "@", "--no-edit", "-m", &message]))
         .context("Failed to insert new change")?;
 
     if !output.status.success() {
@@ -1009,135 +2447,1666 @@ pub fn split_change(reference: &str, repo_path: Option<&Path>) -> Result<()> {
         );
     }
 
+    // The split must never move @ (e.g. onto the inserted part)
+    let working_copy_after = get_full_change_id_in("@", repo_path)?;
+    if working_copy_after != working_copy_before {
+        anyhow::bail!(
+            "Split moved the working copy from {} to {}; run `jj edit {}` to restore it",
+            working_copy_before,
+            working_copy_after,
+            working_copy_before
+        );
+    }
+
+    get_full_change_id_in("@-", repo_path)
+}
+
+/// Verify that reference is a proper ancestor of @ (working copy)
+fn ensure_ancestor_of_working_copy_in(reference: &str, repo_path: Option<&Path>) -> Result<()> {
+    // Use ref..@ to check if there are descendants between ref and @
+    // If ref is @ itself, this will be empty, which means it's not a proper ancestor
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+    let output = run_jj(cmd.args([
+        "log",
+        "-r",
+        &format!("{}..@", reference),
+        "--no-graph",
+        "-T",
+        "change_id.short()",
+    ]))
+    .context("Failed to verify ancestry")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "'{}' is not an ancestor of the working copy: {}",
+            reference,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // If the output is empty, then reference is @ or is not an ancestor
+    if stdout.trim().is_empty() {
+        anyhow::bail!(
+            "'{}' is not an ancestor of the working copy (@); pick a change below @",
+            reference
+        );
+    }
+
+    Ok(())
+}
+
+/// Move session tracking to an existing jj revision
+/// Verifies the reference is an ancestor of @ and updates its description with the session ID trailer
+/// Returns the full change ID that received the trailer
+pub fn move_session_into(
+    session_id: &str,
+    reference: &str,
+    repo_path: Option<&Path>,
+) -> Result<String> {
+    ensure_ancestor_of_working_copy_in(reference, repo_path)?;
+    let change_id = get_full_change_id_in(reference, repo_path)?;
+    ensure_mutable_in(&change_id, repo_path)?;
+
+    // Get the current description of the target revision as raw bytes so that
+    // anything outside the trailers is written back exactly as it was
+    let current_description = get_commit_description_bytes_in(reference, repo_path)?;
+
+    // Replace any existing Claude-session-id trailers with the new one
+    let complete_message = rewrite_trailers(&current_description, |trailers| {
+        let mut new_trailers: Vec<String> = trailers
+            .into_iter()
+            .filter(|t| !is_session_trailer(t))
+            .collect();
+        new_trailers.push(format!("Claude-session-id: {}", session_id));
+        new_trailers
+    });
+
+    describe_with_bytes_in(&change_id, &complete_message, repo_path)?;
+    Ok(change_id)
+}
+
+/// Remove every trailer with the given key from a commit's description
+/// Other trailers and the title are left untouched
+/// If repo_path is provided, runs jj in that directory
+pub fn remove_trailer_in(revset: &str, key: &str, repo_path: Option<&Path>) -> Result<()> {
+    let current_description = get_commit_description_bytes_in(revset, repo_path)?;
+
+    let prefix = format!("{}:", key);
+    let complete_message = rewrite_trailers(&current_description, |trailers| {
+        trailers
+            .into_iter()
+            .filter(|t| !t.starts_with(&prefix))
+            .collect()
+    });
+
+    describe_with_bytes_in(revset, &complete_message, repo_path)
+}
+
+/// Set a trailer on a commit's description, replacing any existing trailers with the same key
+/// The title and all other trailers are left untouched
+/// If repo_path is provided, runs jj in that directory
+pub fn set_trailer_in(
+    revset: &str,
+    key: &str,
+    value: &str,
+    repo_path: Option<&Path>,
+) -> Result<()> {
+    let current_description = get_commit_description_bytes_in(revset, repo_path)?;
+
+    let prefix = format!("{}:", key);
+    let complete_message = rewrite_trailers(&current_description, |trailers| {
+        let mut new_trailers: Vec<String> = trailers
+            .into_iter()
+            .filter(|t| !t.starts_with(&prefix))
+            .collect();
+        new_trailers.push(format!("{}: {}", key, value));
+        new_trailers
+    });
+
+    describe_with_bytes_in(revset, &complete_message, repo_path)
+}
+
+/// Read the descriptions of several changes with a single `jj log`, in the order given
+/// Change IDs must be full IDs, as the session finders return them
+/// If repo_path is provided, runs jj in that directory
+fn get_commit_descriptions_in(
+    change_ids: &[String],
+    repo_path: Option<&Path>,
+) -> Result<Vec<Vec<u8>>> {
+    if change_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+
+    let output = run_jj(cmd.args([
+        "log",
+        "-r",
+        &change_ids.join(" | "),
+        "-T",
+        r#"change_id ++ "\x1f" ++ description ++ "\x1e""#,
+        "--no-graph",
+        "--ignore-working-copy",
+    ]))
+    .context("Failed to execute jj log")?;
+
+    if !output.status.success() {
+        anyhow::bail!("jj log failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    let descriptions = parse_descriptions(&output.stdout);
+    change_ids
+        .iter()
+        .map(|change_id| {
+            descriptions
+                .get(change_id)
+                .cloned()
+                .with_context(|| format!("No description found for {}", change_id))
+        })
+        .collect()
+}
+
+/// Parse `change_id \x1f description \x1e` records into a map of change ID to description
+fn parse_descriptions(output: &[u8]) -> HashMap<String, Vec<u8>> {
+    output
+        .split(|&b| b == 0x1e)
+        .filter_map(|record| {
+            let separator = record.iter().position(|&b| b == 0x1f)?;
+            let change_id = String::from_utf8_lossy(&record[..separator])
+                .trim()
+                .to_string();
+            Some((change_id, record[separator + 1..].trim_ascii().to_vec()))
+        })
+        .collect()
+}
+
+/// Set the descriptions of several changes in a single `jj describe`, so the batch is one
+/// operation. jj only takes per-revision messages through its editor, so the editor is
+/// scripted to copy in a prepared file with a `JJ: describe <commit>` section per change
+/// Fails without changing anything, naming the change, if one of them doesn't exist
+/// If repo_path is provided, runs jj in that directory
+pub fn describe_many_in(updates: &[(String, Vec<u8>)], repo_path: Option<&Path>) -> Result<()> {
+    match updates {
+        [] => return Ok(()),
+        [(change_id, message)] => {
+            return describe_with_bytes_in(change_id, message, repo_path)
+                .with_context(|| format!("Failed to describe {}", change_id));
+        }
+        _ => {}
+    }
+
+    let change_ids: Vec<String> = updates.iter().map(|(id, _)| id.clone()).collect();
+    let commit_ids = get_short_commit_ids_in(&change_ids, repo_path)?;
+
+    let mut edited = String::new();
+    for ((change_id, message), commit_id) in updates.iter().zip(&commit_ids) {
+        let message = std::str::from_utf8(message).with_context(|| {
+            format!(
+                "Failed to describe {}; its description is not valid UTF-8",
+                change_id
+            )
+        })?;
+        edited.push_str(&format!("JJ: describe {} -------\n", commit_id));
+        edited.push_str(message);
+        edited.push_str("\n\n");
+    }
+
+    let path =
+        std::env::temp_dir().join(format!("jjagent-describe-many-{}.txt", std::process::id()));
+    std::fs::write(&path, edited).context("Failed to write descriptions file")?;
+    let editor = format!(
+        r#"ui.editor=["cp", "{}"]"#,
+        toml_escape(&path.to_string_lossy())
+    );
+
+    let mut cmd = jj_command();
+    if let Some(repo) = repo_path {
+        cmd.current_dir(repo);
+    }
+    cmd.arg("describe")
+        .args(&change_ids)
+        .args(["--config", &editor]);
+    let output = run_jj(&mut cmd);
+    let _ = std::fs::remove_file(&path);
+    let output = output.context("Failed to execute jj describe")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj describe failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(())
+}
+
+/// Get the 12-character commit ID jj's editor uses for each of the given changes, in order
+/// Fails naming the first change that doesn't exist, before anything is described
+/// If repo_path is provided, runs jj in that directory
+fn get_short_commit_ids_in(change_ids: &[String], repo_path: Option<&Path>) -> Result<Vec<String>> {
+    let revset = change_ids
+        .iter()
+        .map(|id| format!("present({})", id))
+        .collect::<Vec<_>>()
+        .join(" | ");
+
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+    let output = run_jj(cmd.args([
+        "log",
+        "-r",
+        &revset,
+        "-T",
+        r#"change_id ++ " " ++ commit_id.short(12) ++ "\n""#,
+        "--no-graph",
+        "--ignore-working-copy",
+    ]))
+    .context("Failed to execute jj log")?;
+
+    if !output.status.success() {
+        anyhow::bail!("jj log failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let found: Vec<(&str, &str)> = stdout
+        .lines()
+        .filter_map(|line| line.trim().split_once(' '))
+        .collect();
+    change_ids
+        .iter()
+        .map(|change_id| {
+            found
+                .iter()
+                .find(|(full, _)| full.starts_with(change_id.as_str()))
+                .map(|(_, commit_id)| commit_id.to_string())
+                .with_context(|| {
+                    format!(
+                        "Failed to describe {}; no descriptions were changed: no such change",
+                        change_id
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Escape a string for use inside a double-quoted TOML string
+fn toml_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Reattribute every change of a session to a different session ID
+/// Only the matching Claude-session-id trailer is rewritten; titles and other trailers are
+/// kept. Refuses if any of the changes is immutable, and rolls every rewrite back if one
+/// of them fails
+/// Returns the change IDs that were updated
+/// If repo_path is provided, runs jj in that directory
+pub fn rename_session_in(
+    old_id: &str,
+    new_id: &str,
+    repo_path: Option<&Path>,
+) -> Result<Vec<String>> {
+    if new_id.trim().is_empty() {
+        anyhow::bail!("New session ID must not be empty");
+    }
+    if old_id == new_id {
+        anyhow::bail!("Old and new session IDs are the same: {}", old_id);
+    }
+
+    let changes = find_all_session_changes_in(old_id, repo_path)?;
+    if changes.is_empty() {
+        anyhow::bail!("No change found for session ID: {}", old_id);
+    }
+
+    let immutable = list_change_ids_in(
+        &format!("({}) & immutable()", changes.join(" | ")),
+        repo_path,
+    )?;
+    if !immutable.is_empty() {
+        anyhow::bail!(
+            "Session {} has immutable changes ({}); refusing to rename it",
+            old_id,
+            immutable.join(", ")
+        );
+    }
+
+    let old_trailer = format!("Claude-session-id: {}", old_id);
+    let old_legacy_trailer = format!("{}: {}", LEGACY_SESSION_TRAILER_KEY, old_id);
+    let new_trailer = format!("Claude-session-id: {}", new_id);
+
+    let descriptions = get_commit_descriptions_in(&changes, repo_path)?;
+    let updates: Vec<(String, Vec<u8>)> = changes
+        .iter()
+        .zip(descriptions)
+        .map(|(change_id, description)| {
+            let message = rewrite_trailers(&description, |trailers| {
+                trailers
+                    .into_iter()
+                    .map(|t| {
+                        if t == old_trailer || t == old_legacy_trailer {
+                            new_trailer.clone()
+                        } else {
+                            t
+                        }
+                    })
+                    .collect()
+            });
+            (change_id.clone(), message)
+        })
+        .collect();
+
+    describe_many_in(&updates, repo_path).context("Failed to rename session")?;
+
+    Ok(changes)
+}
+
+/// Trailer keys written by older jjagent versions, with the key that replaced each
+/// `Claude-session-id` is the canonical session trailer; the temp-change trailer becomes the
+/// precommit trailer key of the given config, including its namespace
+pub fn legacy_trailer_keys(
+    config: &crate::session::PrecommitConfig,
+) -> Vec<(&'static str, String)> {
+    vec![
+        (LEGACY_SESSION_TRAILER_KEY, "Claude-session-id".to_string()),
+        ("Jjagent-claude-temp-change", config.trailer_key()),
+    ]
+}
+
+/// Rename legacy trailer keys (as returned by legacy_trailer_keys) to their current names,
+/// dropping any that would duplicate a trailer already present
+fn migrate_trailer_keys(trailers: Vec<String>, keys: &[(&str, String)]) -> Vec<String> {
+    let mut migrated: Vec<String> = Vec::new();
+    for trailer in trailers {
+        let renamed = keys
+            .iter()
+            .find_map(|(old, new)| {
+                trailer
+                    .strip_prefix(old)
+                    .filter(|rest| rest.starts_with(':'))
+                    .map(|rest| format!("{}{}", new, rest))
+            })
+            .unwrap_or(trailer);
+        if !migrated.contains(&renamed) {
+            migrated.push(renamed);
+        }
+    }
+    migrated
+}
+
+/// Rewrite legacy jjagent trailers (see legacy_trailer_keys) to the current keys on every
+/// mutable commit, so older sessions are found by the current lookups
+/// The precommit key honors JJAGENT_PRECOMMIT_TRAILER_NAMESPACE (see PrecommitConfig)
+/// Returns the change IDs of the rewritten commits
+/// If repo_path is provided, runs jj in that directory
+pub fn migrate_trailers_in(repo_path: Option<&Path>) -> Result<Vec<String>> {
+    let keys = legacy_trailer_keys(&crate::session::PrecommitConfig::from_env());
+    let candidates = keys
+        .iter()
+        .map(|(old, _)| format!(r#"description(substring:"{}:")"#, old))
+        .collect::<Vec<_>>()
+        .join(" | ");
+    let changes = list_change_ids_in(&format!("({}) & ~immutable()", candidates), repo_path)?;
+
+    let descriptions = get_commit_descriptions_in(&changes, repo_path)?;
+    let mut updates = Vec::new();
+    for (change_id, description) in changes.into_iter().zip(descriptions) {
+        // The legacy key may only appear in the body, which is left alone
+        let (_, trailers) = split_description_and_trailers(&description);
+        if migrate_trailer_keys(trailers.clone(), &keys) == trailers {
+            continue;
+        }
+        let message = rewrite_trailers(&description, |trailers| {
+            migrate_trailer_keys(trailers, &keys)
+        });
+        updates.push((change_id, message));
+    }
+
+    describe_many_in(&updates, repo_path)?;
+    Ok(updates
+        .into_iter()
+        .map(|(change_id, _)| change_id)
+        .collect())
+}
+
+/// A change created by duplicating a session onto another revision
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicatedChange {
+    pub change_id: String,
+    pub has_conflict: bool,
+}
+
+/// Duplicate all of a session's changes onto a destination revision
+/// The original session changes are left in place (unlike move/into)
+/// If strip_trailer is true, the Claude-session-id trailer is removed from the copies
+/// so that session lookups keep resolving to the original change
+/// Returns the new change IDs along with whether each one ended up conflicted
+/// If repo_path is provided, runs jj in that directory
+pub fn duplicate_session_in(
+    session_id: &str,
+    destination: &str,
+    strip_trailer: bool,
+    repo_path: Option<&Path>,
+) -> Result<Vec<DuplicatedChange>> {
+    let originals = find_all_session_changes_in(session_id, repo_path)?;
+    if originals.is_empty() {
+        anyhow::bail!("No change found for session ID: {}", session_id);
+    }
+
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+
+    let output = run_jj(
+        cmd.arg("duplicate")
+            .args(&originals)
+            .args(["-d", destination]),
+    )
+    .context("Failed to execute jj duplicate")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj duplicate failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    // The copies carry the same trailer but have fresh change IDs,
+    // so anything that wasn't there before is one of the duplicates
+    let duplicates: Vec<String> = find_all_session_changes_in(session_id, repo_path)?
+        .into_iter()
+        .filter(|id| !originals.contains(id))
+        .collect();
+
+    if duplicates.is_empty() {
+        anyhow::bail!(
+            "jj duplicate did not create any changes for session {}",
+            session_id
+        );
+    }
+
+    let template = r#"change_id ++ " " ++ if(conflict, "conflict", "ok") ++ "\n""#;
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+
+    let output = run_jj(cmd.args([
+        "log",
+        "-r",
+        &duplicates.join(" | "),
+        "--no-graph",
+        "-T",
+        template,
+        "--ignore-working-copy",
+    ]))
+    .context("Failed to execute jj log for duplicated changes")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj log failed while inspecting duplicated changes: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let changes: Vec<DuplicatedChange> = stdout
+        .lines()
+        .filter_map(|line| {
+            let (change_id, status) = line.trim().split_once(' ')?;
+            Some(DuplicatedChange {
+                change_id: change_id.to_string(),
+                has_conflict: status == "conflict",
+            })
+        })
+        .collect();
+
+    if strip_trailer {
+        for change in &changes {
+            let description = get_commit_description_bytes_in(&change.change_id, repo_path)?;
+            let message = rewrite_trailers(&description, |trailers| {
+                trailers
+                    .into_iter()
+                    .filter(|t| !is_session_trailer(t))
+                    .collect()
+            });
+            describe_with_bytes_in(&change.change_id, &message, repo_path)?;
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Revset matching exactly the given changes
+pub fn session_changes_revset(change_ids: &[String]) -> String {
+    change_ids.join(" | ")
+}
+
+/// Revset of changes that descend from a session without belonging to it, leaving out @'s
+/// ancestors and descendants (which jj rebases along with the working copy)
+pub fn foreign_descendants_revset(change_ids: &[String]) -> String {
+    format!(
+        "descendants({0}) ~ ({0}) ~ ::@ ~ @::",
+        session_changes_revset(change_ids)
+    )
+}
+
+/// Refuse to abandon a session that other changes are stacked on
+fn ensure_no_foreign_descendants(session_id: &str, foreign: &[String]) -> Result<()> {
+    if !foreign.is_empty() {
+        anyhow::bail!(
+            "Session {} has descendants that are not part of the session ({}); refusing to abandon",
+            session_id,
+            foreign.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Abandon every change belonging to a session (the session change and all its parts)
+/// Refuses if @ is itself a session change. With keep_descendants set, also refuses if any
+/// change outside the session descends from one (other than @ and its ancestors/descendants,
+/// which jj rebases), instead of letting jj rebase it onto the session's parents
+/// With dry_run set, nothing is abandoned and the changes that would be are returned
+/// Returns the full change IDs of the abandoned changes
+/// If repo_path is provided, runs jj in that directory
+pub fn abandon_session_in(
+    session_id: &str,
+    keep_descendants: bool,
+    dry_run: bool,
+    repo_path: Option<&Path>,
+) -> Result<Vec<String>> {
+    let parts = find_all_session_changes_in(session_id, repo_path)?;
+    if parts.is_empty() {
+        anyhow::bail!("No change found for session ID: {}", session_id);
+    }
+    let parts_revset = session_changes_revset(&parts);
+
+    let immutable = list_change_ids_in(&format!("({}) & immutable()", parts_revset), repo_path)?;
+    if !immutable.is_empty() {
+        anyhow::bail!(
+            "Session {} has immutable changes ({}); refusing to abandon",
+            session_id,
+            immutable.join(", ")
+        );
+    }
+
+    let working_copy_before = get_full_change_id_in("@", repo_path)?;
+    if parts.contains(&working_copy_before) {
+        anyhow::bail!(
+            "The working copy is part of session {}; run `jj new` to move off it before abandoning",
+            session_id
+        );
+    }
+
+    // Changes stacked on the session that aren't on the working copy's line would be
+    // silently rebased onto the session's parents, so make the user deal with them first
+    if keep_descendants {
+        let foreign = list_change_ids_in(&foreign_descendants_revset(&parts), repo_path)?;
+        ensure_no_foreign_descendants(session_id, &foreign)?;
+    }
+
+    if dry_run {
+        return Ok(parts);
+    }
+
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+
+    let output = run_jj(cmd.arg("abandon").args(&parts)).context("Failed to execute jj abandon")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj abandon failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let working_copy_after = get_full_change_id_in("@", repo_path)?;
+    if working_copy_after != working_copy_before {
+        anyhow::bail!(
+            "Working copy changed from {} to {} while abandoning session {}",
+            working_copy_before,
+            working_copy_after,
+            session_id
+        );
+    }
+
+    Ok(parts)
+}
+
+/// A precommit left behind by a PreToolUse whose PostToolUse never ran
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OrphanedPrecommit {
+    /// Full change ID
+    pub change_id: String,
+    pub session_id: String,
+    pub empty: bool,
+    /// Seconds since the precommit was last rewritten
+    pub age_secs: u64,
+}
+
+/// Find precommits that `jjagent gc` would abandon: mutable commits carrying the precommit
+/// trailer (see PrecommitConfig) that are empty or at least `min_age_secs` old, skipping the
+/// session holding a fresh working copy lock since its tool may still be running
+/// If repo_path is provided, runs jj in that directory
+pub fn find_orphaned_precommits_in(
+    min_age_secs: u64,
+    repo_path: Option<&Path>,
+) -> Result<Vec<OrphanedPrecommit>> {
+    let active_session = match crate::lock::current_holder(&get_repo_root_in(repo_path)?) {
+        Ok(holder) => holder
+            .filter(|holder| !crate::lock::is_stale(holder.age_seconds))
+            .map(|holder| holder.session_id),
+        Err(_) => anyhow::bail!(
+            "The working copy lock can't be read, so a session may be mid-edit; try again shortly"
+        ),
+    };
+
+    let key = escape_string_literal(&crate::session::PrecommitConfig::from_env().trailer_key());
+    let revset = format!(
+        r#"all() & description(substring:"{}: ") & ~immutable()"#,
+        key
+    );
+    let template = format!(
+        r#"change_id ++ "\t" ++ trailers.map(|t| if(t.key() == "{}", t.value())).join("") ++ "\t" ++ if(empty, "empty", "") ++ "\t" ++ committer.timestamp().format("%s") ++ "\n""#,
+        key
+    );
+
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+
+    let output = run_jj(cmd.args([
+        "log",
+        "-r",
+        &revset,
+        "-T",
+        &template,
+        "--no-graph",
+        "--ignore-working-copy",
+    ]))
+    .context("Failed to execute jj log to find precommits")?;
+
+    if !output.status.success() {
+        anyhow::bail!("jj log failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    let now = Utc::now().timestamp();
+    Ok(
+        parse_precommits(&String::from_utf8_lossy(&output.stdout), now)
+            .into_iter()
+            .filter(|p| !p.session_id.is_empty())
+            .filter(|p| active_session.as_deref() != Some(p.session_id.as_str()))
+            .filter(|p| p.empty || p.age_secs >= min_age_secs)
+            .collect(),
+    )
+}
+
+/// Parse the output of the find_orphaned_precommits_in template, given the current Unix time
+fn parse_precommits(stdout: &str, now: i64) -> Vec<OrphanedPrecommit> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let change_id = fields.next()?.trim();
+            let session_id = fields.next()?.trim();
+            let empty = fields.next()? == "empty";
+            let committed_at = fields.next()?.trim().parse::<i64>().ok()?;
+            if change_id.is_empty() {
+                return None;
+            }
+            Some(OrphanedPrecommit {
+                change_id: change_id.to_string(),
+                session_id: session_id.to_string(),
+                empty,
+                age_secs: now.saturating_sub(committed_at).max(0) as u64,
+            })
+        })
+        .collect()
+}
+
+/// Abandon orphaned precommits (see find_orphaned_precommits_in)
+/// If @ is one of them, @ first moves to its parent, or is left alone (with a warning) when
+/// it has edits that would be dropped from the working copy or no single mutable parent
+/// With dry_run set, nothing is abandoned and the precommits that would be are returned
+/// Returns the abandoned precommits
+/// If repo_path is provided, runs jj in that directory
+pub fn gc_precommits_in(
+    min_age_secs: u64,
+    dry_run: bool,
+    repo_path: Option<&Path>,
+) -> Result<Vec<OrphanedPrecommit>> {
+    let mut precommits = find_orphaned_precommits_in(min_age_secs, repo_path)?;
+
+    let working_copy = get_full_change_id_in("@", repo_path)?;
+    if let Some(index) = precommits.iter().position(|p| p.change_id == working_copy) {
+        let parent_is_mutable = list_change_ids_in("@- & ~immutable()", repo_path)?.len() == 1
+            && list_change_ids_in("@-", repo_path)?.len() == 1;
+        if !precommits[index].empty || !parent_is_mutable {
+            let skipped = precommits.remove(index);
+            eprintln!(
+                "jjagent: Warning - leaving the working copy precommit {} alone; \
+                 `jj squash` keeps its edits in the parent, `jj abandon` drops them",
+                skipped.change_id
+            );
+        } else if !dry_run {
+            let mut cmd = jj_command();
+            if let Some(path) = repo_path {
+                cmd.current_dir(path);
+            }
+            let output = run_jj(cmd.args(["edit", "@-"])).context("Failed to execute jj edit")?;
+            if !output.status.success() {
+                anyhow::bail!(
+                    "jj edit failed: {}",
+                    String::from_utf8_lossy(&output.stderr)
+                );
+            }
+        }
+    }
+
+    if dry_run || precommits.is_empty() {
+        return Ok(precommits);
+    }
+
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+    let output = run_jj(
+        cmd.arg("abandon")
+            .args(precommits.iter().map(|p| p.change_id.as_str())),
+    )
+    .context("Failed to execute jj abandon")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj abandon failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(precommits)
+}
+
+/// Squash a session's later parts back into its first change
+/// The parts must form a linear chain of mutable changes, none of them @. Descriptions
+/// are combined (generated "pt. N" titles are dropped) and trailers are kept, including
+/// Claude-session-id. If squashing a part introduces conflicts, every squash is rolled
+/// back and the offending part is reported
+/// Returns the change ID of the merged session change and the parts folded into it
+/// If repo_path is provided, runs jj in that directory
+pub fn merge_session_parts_in(
+    session_id: &str,
+    repo_path: Option<&Path>,
+) -> Result<(String, Vec<String>)> {
+    let mut parts = find_all_session_changes_in(session_id, repo_path)?;
+    if parts.is_empty() {
+        anyhow::bail!("No change found for session ID: {}", session_id);
+    }
+    // jj log lists newest first; merge from the first change upwards
+    parts.reverse();
+    let primary = parts.remove(0);
+    if parts.is_empty() {
+        anyhow::bail!("Session {} has only one part, nothing to merge", session_id);
+    }
+
+    let all = format!("{} | {}", primary, parts.join(" | "));
+    let immutable = list_change_ids_in(&format!("({}) & immutable()", all), repo_path)?;
+    if !immutable.is_empty() {
+        anyhow::bail!(
+            "Session {} has immutable changes ({}); refusing to merge its parts",
+            session_id,
+            immutable.join(", ")
+        );
+    }
+
+    let working_copy = get_full_change_id_in("@", repo_path)?;
+    if working_copy == primary || parts.contains(&working_copy) {
+        anyhow::bail!(
+            "The working copy is part of session {}; run `jj new` to move off it before merging parts",
+            session_id
+        );
+    }
+
+    let mut previous = &primary;
+    for part in &parts {
+        let parent = get_full_change_id_in(&format!("{}-", part), repo_path).ok();
+        if parent.as_ref() != Some(previous) {
+            anyhow::bail!(
+                "Session {} parts are not a linear chain: {} is not a child of {}",
+                session_id,
+                part,
+                previous
+            );
+        }
+        previous = part;
+    }
+
+    // Read every description up front, the parts disappear as they're squashed
+    let primary_description = get_commit_description_bytes_in(&primary, repo_path)?;
+    let part_descriptions = parts
+        .iter()
+        .map(|part| get_commit_description_bytes_in(part, repo_path))
+        .collect::<Result<Vec<_>>>()?;
+
+    let start_operation = current_operation_id_in(repo_path)?;
+    let mut conflicts_before = count_conflicts_in("root()", repo_path)?;
+
+    for part in &parts {
+        let mut cmd = jj_command();
+        if let Some(path) = repo_path {
+            cmd.current_dir(path);
+        }
+        let output = run_jj(cmd.args([
+            "squash",
+            "--from",
+            part,
+            "--into",
+            &primary,
+            "--use-destination-message",
+        ]))
+        .context("Failed to execute jj squash")?;
+
+        if !output.status.success() {
+            restore_operation_in(&start_operation, repo_path)?;
+            anyhow::bail!(
+                "jj squash failed for part {}: {}",
+                part,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        let conflicts_after = count_conflicts_in("root()", repo_path)?;
+        if conflicts_after > conflicts_before {
+            restore_operation_in(&start_operation, repo_path)?;
+            anyhow::bail!(
+                "Squashing part {} into {} would introduce conflicts; left the repo unchanged",
+                part,
+                primary
+            );
+        }
+        conflicts_before = conflicts_after;
+    }
+
+    let message = combine_part_descriptions(&primary_description, &part_descriptions);
+    describe_with_bytes_in(&primary, &message, repo_path)?;
+
+    Ok((primary, parts))
+}
+
+/// Fold every change of a session into an existing ancestor of @
+/// The target keeps its own description and trailers, and the emptied session changes are
+/// abandoned. Rolls back if the squash introduces conflicts
+/// Returns the target's change ID and the session changes folded into it
+/// If repo_path is provided, runs jj in that directory
+pub fn squash_session_into_in(
+    session_id: &str,
+    reference: &str,
+    repo_path: Option<&Path>,
+) -> Result<(String, Vec<String>)> {
+    let mut changes = find_all_session_changes_in(session_id, repo_path)?;
+    if changes.is_empty() {
+        anyhow::bail!("No change found for session ID: {}", session_id);
+    }
+    changes.reverse();
+
+    ensure_ancestor_of_working_copy_in(reference, repo_path)?;
+    let target = get_full_change_id_in(reference, repo_path)?;
+    if changes.contains(&target) {
+        anyhow::bail!(
+            "'{}' is a change of session {}; squash into a commit outside the session",
+            reference,
+            session_id
+        );
+    }
+
+    let start_operation = current_operation_id_in(repo_path)?;
+    let conflicts_before = count_conflicts_in("root()", repo_path)?;
+
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+    let output = run_jj(cmd.args([
+        "squash",
+        "--from",
+        &changes.join(" | "),
+        "--into",
+        &target,
+        "--use-destination-message",
+    ]))
+    .context("Failed to execute jj squash")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj squash failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    if count_conflicts_in("root()", repo_path)? > conflicts_before {
+        restore_operation_in(&start_operation, repo_path)?;
+        anyhow::bail!(
+            "Squashing session {} into {} would introduce conflicts; left the repo unchanged",
+            session_id,
+            reference
+        );
+    }
+
+    Ok((target, changes))
+}
+
+/// Combine the descriptions of a session change and its later parts
+/// Part titles that are just the session title plus " pt. N" are dropped, other text is
+/// appended as its own paragraph, and every distinct trailer is kept in order
+fn combine_part_descriptions(primary: &[u8], parts: &[Vec<u8>]) -> Vec<u8> {
+    let (primary_title, mut trailers) = split_description_and_trailers(primary);
+    let primary_title = primary_title.trim_ascii();
+    let mut message = primary_title.to_vec();
+
+    for part in parts {
+        let (title, part_trailers) = split_description_and_trailers(part);
+        let title = title.trim_ascii();
+        if !title.is_empty() && !is_generated_part_title(title, primary_title) {
+            if !message.is_empty() {
+                message.extend_from_slice(b"\n\n");
+            }
+            message.extend_from_slice(title);
+        }
+        for trailer in part_trailers {
+            if !trailers.contains(&trailer) {
+                trailers.push(trailer);
+            }
+        }
+    }
+
+    if !trailers.is_empty() {
+        message.extend_from_slice(b"\n\n");
+        message.extend_from_slice(trailers.join("\n").as_bytes());
+    }
+    message
+}
+
+/// Check whether a part's description is only the session's first line plus " pt. N"
+fn is_generated_part_title(title: &[u8], primary_title: &[u8]) -> bool {
+    let title = String::from_utf8_lossy(title);
+    let primary_first_line = String::from_utf8_lossy(primary_title);
+    let primary_first_line = primary_first_line.lines().next().unwrap_or_default();
+
+    match title.rsplit_once(" pt. ") {
+        Some((base, part)) => {
+            !title.contains('\n')
+                && base == primary_first_line
+                && !part.is_empty()
+                && part.chars().all(|c| c.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+/// Get the ID of the current jj operation, for rolling back multi-step changes
+/// If repo_path is provided, runs jj in that directory
+pub fn current_operation_id_in(repo_path: Option<&Path>) -> Result<String> {
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+
+    let output = run_jj(cmd.args([
+        "op",
+        "log",
+        "--limit",
+        "1",
+        "--no-graph",
+        "-T",
+        r#"id ++ "\n""#,
+        "--ignore-working-copy",
+    ]))
+    .context("Failed to execute jj op log")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj op log failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Restore the repo to an earlier jj operation
+/// If repo_path is provided, runs jj in that directory
+fn restore_operation_in(operation_id: &str, repo_path: Option<&Path>) -> Result<()> {
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+
+    let output = run_jj(cmd.args(["op", "restore", operation_id]))
+        .context("Failed to execute jj op restore")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj op restore failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(())
+}
+
+/// Author and commit of a change, as a `git format-patch` header needs them
+/// Fields are separated by \x1f; the date is in RFC 2822 form
+const PATCH_HEADER_TEMPLATE: &str = r#"commit_id ++ "\x1f" ++ author.name() ++ "\x1f" ++ author.email() ++ "\x1f" ++ author.timestamp().format("%a, %d %b %Y %H:%M:%S %z")"#;
+
+/// Render a change as one mail-formatted patch, like `git format-patch` writes, so it can
+/// be applied with `git am`. `part` and `total` number it within a series
+/// If repo_path is provided, runs jj in that directory
+pub fn format_patch_in(
+    revset: &str,
+    part: usize,
+    total: usize,
+    repo_path: Option<&Path>,
+) -> Result<String> {
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+    let output = run_jj(cmd.args([
+        "log",
+        "-r",
+        revset,
+        "-T",
+        PATCH_HEADER_TEMPLATE,
+        "--no-graph",
+        "--ignore-working-copy",
+    ]))
+    .context("Failed to execute jj log")?;
+    if !output.status.success() {
+        anyhow::bail!("jj log failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+    let header = String::from_utf8_lossy(&output.stdout);
+    let fields: Vec<&str> = header.trim_end().split('\x1f').collect();
+    let [commit_id, name, email, date] = fields[..] else {
+        anyhow::bail!("Unexpected jj log output for '{}': {}", revset, header);
+    };
+
+    let description = get_commit_description_in(revset, repo_path)?;
+    let (subject, body) = description
+        .split_once('\n')
+        .unwrap_or((description.as_str(), ""));
+
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+    let output = run_jj(cmd.args([
+        "diff",
+        "--git",
+        "-r",
+        revset,
+        "--color",
+        "never",
+        "--ignore-working-copy",
+    ]))
+    .context("Failed to execute jj diff")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj diff failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let numbering = if total > 1 {
+        format!("PATCH {}/{}", part, total)
+    } else {
+        "PATCH".to_string()
+    };
+    let mut patch = format!(
+        "From {} Mon Sep 17 00:00:00 2001\nFrom: {} <{}>\nDate: {}\nSubject: [{}] {}\n",
+        commit_id, name, email, date, numbering, subject
+    );
+    let body = body.trim();
+    if !body.is_empty() {
+        patch.push('\n');
+        patch.push_str(body);
+        patch.push('\n');
+    }
+    patch.push_str("---\n");
+    patch.push_str(&String::from_utf8_lossy(&output.stdout));
+    Ok(patch)
+}
+
+/// Run `jj diff -r <revset>`, streaming its output straight to stdout
+/// If repo_path is provided, runs jj in that directory
+pub fn stream_diff_in(revset: &str, repo_path: Option<&Path>) -> Result<()> {
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+
+    let mut child = cmd
+        .args(["diff", "-r", revset])
+        .spawn()
+        .context("Failed to execute jj diff")?;
+    let status = wait_jj(&cmd, &mut child)?;
+
+    if !status.success() {
+        anyhow::bail!("jj diff failed for revision {}", revset);
+    }
+
     Ok(())
 }
 
-/// Move session tracking to an existing jj revision
-/// Verifies the reference is an ancestor of @ and updates its description with the session ID trailer
-pub fn move_session_into(
-    session_id: &str,
-    reference: &str,
-    repo_path: Option<&Path>,
-) -> Result<()> {
-    // Verify that reference is an ancestor of @ (working copy)
-    // Use ref..@ to check if there are descendants between ref and @
-    // If ref is @ itself, this will be empty, which means it's not a proper ancestor
-    let mut cmd = Command::new("jj");
+/// Compact template for `jjagent log`: change ID, conflict marker and the first line of the description
+const SESSION_LOG_TEMPLATE: &str = r#"separate(" ",
+  change_id.shortest(8),
+  if(conflict, label("conflict", "conflict")),
+  if(description, description.first_line(), label("empty", "(no description set)"))
+) ++ "\n""#;
+
+/// Revset covering a session's changes, the base they sit on and everything up to @
+pub fn session_log_revset(change_ids: &[String]) -> String {
+    let changes = change_ids.join(" | ");
+    format!("connected(roots({0})- | {0} | @)", changes)
+}
+
+/// Run `jj log` on a revset with the compact session template, streaming it to stdout
+/// `color` is passed through to jj's --color (auto, always or never)
+/// If repo_path is provided, runs jj in that directory
+pub fn stream_log_in(revset: &str, color: &str, repo_path: Option<&Path>) -> Result<()> {
+    let mut cmd = jj_command();
     if let Some(path) = repo_path {
         cmd.current_dir(path);
     }
-    let output = cmd
+
+    let mut child = cmd
         .args([
             "log",
             "-r",
-            &format!("{}..@", reference),
-            "--no-graph",
+            revset,
             "-T",
-            "change_id.short()",
+            SESSION_LOG_TEMPLATE,
+            "--color",
+            color,
         ])
-        .output()
-        .context("Failed to verify ancestry")?;
+        .spawn()
+        .context("Failed to execute jj log")?;
+    let status = wait_jj(&cmd, &mut child)?;
+
+    if !status.success() {
+        anyhow::bail!("jj log failed for revset {}", revset);
+    }
+
+    Ok(())
+}
+
+/// List the full change IDs of every commit in a revset
+/// If repo_path is provided, runs jj in that directory
+fn list_change_ids_in(revset: &str, repo_path: Option<&Path>) -> Result<Vec<String>> {
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+
+    let output = run_jj(cmd.args([
+        "log",
+        "-r",
+        revset,
+        "-T",
+        r#"change_id ++ "\n""#,
+        "--no-graph",
+        "--ignore-working-copy",
+    ]))
+    .context("Failed to execute jj log")?;
 
     if !output.status.success() {
-        anyhow::bail!(
-            "Error: '{}' is not an ancestor of the working copy",
-            reference
-        );
+        anyhow::bail!("jj log failed: {}", String::from_utf8_lossy(&output.stderr));
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    // If the output is empty, then reference is @ or is not an ancestor
-    if stdout.trim().is_empty() {
+    Ok(parse_change_ids(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// One conflicted region of a file, split into the contents of each side
+/// For the usual two-sided conflict there is one base and two sides: side #1 is the
+/// content the change was rebased onto (the uwc side) and side #2 is the change's own
+/// content (the session side)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ConflictHunk {
+    pub bases: Vec<String>,
+    pub sides: Vec<String>,
+}
+
+/// A conflicted file in a change, with each conflict laid out side by side
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictedFile {
+    pub change_id: String,
+    pub path: String,
+    pub hunks: Vec<ConflictHunk>,
+}
+
+/// List the paths of the conflicted files in a single revision
+/// If repo_path is provided, runs jj in that directory
+pub fn list_conflicted_files_in(revset: &str, repo_path: Option<&Path>) -> Result<Vec<String>> {
+    let mut cmd = jj_command();
+    if let Some(path) = repo_path {
+        cmd.current_dir(path);
+    }
+    let output = run_jj(cmd.args([
+        "file",
+        "list",
+        "-r",
+        revset,
+        "-T",
+        r#"if(self.conflict(), self.path().display() ++ "\n", "")"#,
+        "--ignore-working-copy",
+    ]))
+    .context("Failed to list conflicted files")?;
+
+    if !output.status.success() {
         anyhow::bail!(
-            "Error: '{}' is not an ancestor of the working copy",
-            reference
+            "jj file list failed: {}",
+            String::from_utf8_lossy(&output.stderr)
         );
     }
 
-    // Get the current description of the target revision
-    let current_description = get_commit_description_in(reference, repo_path)?;
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(String::from)
+        .collect())
+}
 
-    // Parse the description to extract title and existing trailers
-    let (title, existing_trailers) = parse_description_and_trailers(&current_description);
+/// Get the base and both sides of every conflict in the given revision
+/// Returns an empty list if the revision has no conflicts
+/// If repo_path is provided, runs jj in that directory
+pub fn get_conflict_sides_in(
+    revset: &str,
+    repo_path: Option<&Path>,
+) -> Result<Vec<ConflictedFile>> {
+    let change_id = get_change_id_in(revset, repo_path)?;
+    let paths = list_conflicted_files_in(&change_id, repo_path)?;
+
+    let mut files = Vec::new();
+    for path in &paths {
+        // Snapshot style prints every side in full rather than as a diff
+        let mut cmd = jj_command();
+        if let Some(repo) = repo_path {
+            cmd.current_dir(repo);
+        }
+        let output = run_jj(cmd.args([
+            "file",
+            "show",
+            "-r",
+            &change_id,
+            "--config",
+            "ui.conflict-marker-style=snapshot",
+            "--ignore-working-copy",
+            "--",
+            path,
+        ]))
+        .context("Failed to execute jj file show")?;
 
-    // Remove any existing Claude-session-id trailers
-    let filtered_trailers: Vec<String> = existing_trailers
-        .into_iter()
-        .filter(|t| !t.starts_with("Claude-session-id:"))
-        .collect();
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj file show failed for '{}': {}",
+                path,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
 
-    // Add the new session ID trailer
-    let mut new_trailers = filtered_trailers;
-    new_trailers.push(format!("Claude-session-id: {}", session_id));
+        files.push(ConflictedFile {
+            change_id: change_id.clone(),
+            path: path.clone(),
+            hunks: parse_conflict_hunks(&String::from_utf8_lossy(&output.stdout)),
+        });
+    }
 
-    // Build the complete message
-    let complete_message = if new_trailers.is_empty() {
-        title
-    } else {
-        format!("{}\n\n{}", title.trim(), new_trailers.join("\n"))
-    };
+    Ok(files)
+}
 
-    // Update the commit description
-    let mut cmd = Command::new("jj");
+/// Get the base and both sides of every conflict in the given revision in the current directory
+pub fn get_conflict_sides(revset: &str) -> Result<Vec<ConflictedFile>> {
+    get_conflict_sides_in(revset, None)
+}
+
+/// Parse snapshot-style conflict markers into hunks
+/// Markers are runs of at least 7 identical characters at the start of a line:
+/// `<<<<<<<` opens a conflict, `+++++++` starts a side, `-------` starts a base
+/// and `>>>>>>>` closes the conflict
+fn parse_conflict_hunks(content: &str) -> Vec<ConflictHunk> {
+    enum Section {
+        Outside,
+        Preamble,
+        Side,
+        Base,
+    }
+
+    fn marker(line: &str) -> Option<char> {
+        let first = line.chars().next()?;
+        if !matches!(first, '<' | '>' | '+' | '-') {
+            return None;
+        }
+        let run = line.chars().take_while(|&c| c == first).count();
+        let rest = &line[run..];
+        (run >= 7 && (rest.is_empty() || rest.starts_with(' '))).then_some(first)
+    }
+
+    let mut hunks = Vec::new();
+    let mut current = ConflictHunk::default();
+    let mut section = Section::Outside;
+
+    for line in content.split_inclusive('\n') {
+        match (marker(line.trim_end_matches('\n')), &section) {
+            (Some('<'), Section::Outside) => {
+                current = ConflictHunk::default();
+                section = Section::Preamble;
+            }
+            (Some('>'), Section::Outside) => {}
+            (Some('>'), _) => {
+                hunks.push(std::mem::take(&mut current));
+                section = Section::Outside;
+            }
+            (Some('+'), Section::Preamble | Section::Side | Section::Base) => {
+                current.sides.push(String::new());
+                section = Section::Side;
+            }
+            (Some('-'), Section::Preamble | Section::Side | Section::Base) => {
+                current.bases.push(String::new());
+                section = Section::Base;
+            }
+            (_, Section::Side) => current.sides.last_mut().unwrap().push_str(line),
+            (_, Section::Base) => current.bases.last_mut().unwrap().push_str(line),
+            _ => {}
+        }
+    }
+
+    hunks
+}
+
+/// Summary of a Claude session, aggregated over every change carrying its trailer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SessionSummary {
+    pub session_id: String,
+    /// Short change IDs, newest first
+    pub change_ids: Vec<String>,
+    /// First line of the newest change's description
+    pub title: String,
+    /// First line of each change's description, in the same order as change_ids
+    pub titles: Vec<String>,
+    /// Tools recorded in Claude-tools trailers across all of the session's changes
+    pub tools: Vec<String>,
+    /// Most recent committer timestamp across the session's changes
+    pub committed_at: Option<DateTime<Utc>>,
+    /// Whether any of the session's changes is conflicted
+    pub has_conflict: bool,
+    /// Files touched by any of the session's changes, sorted
+    pub files: Vec<String>,
+}
+
+/// List every Claude session in the repo, newest first
+/// If repo_path is provided, runs jj in that directory
+pub fn list_sessions_in(repo_path: Option<&Path>) -> Result<Vec<SessionSummary>> {
+    let revset = format!(
+        r#"all() & (description(substring:"Claude-session-id: ") | description(substring:"{}: "))"#,
+        LEGACY_SESSION_TRAILER_KEY
+    );
+    // One line per change: change id, session id, tools, commit time, conflict, files, title
+    // Files are separated by \x1f since paths may contain commas or spaces
+    let template = format!(
+        concat!(
+            r#"change_id.short() ++ "\t" ++ "#,
+            r#"trailers.map(|t| if({}, t.value())).join("") ++ "\t" ++ "#,
+            r#"trailers.map(|t| if(t.key() == "Claude-tools", t.value() ++ ",")).join("") ++ "\t" ++ "#,
+            r#"committer.timestamp().format("%s") ++ "\t" ++ "#,
+            r#"if(conflict, "conflict", "ok") ++ "\t" ++ "#,
+            r#"self.diff().files().map(|f| f.path().display()).join("\x1f") ++ "\t" ++ "#,
+            r#"description.first_line() ++ "\n""#
+        ),
+        SESSION_TRAILER_KEY_CONDITION
+    );
+
+    let mut cmd = jj_command();
     if let Some(path) = repo_path {
         cmd.current_dir(path);
     }
 
-    let output = cmd
-        .args(["describe", "-r", reference, "-m", &complete_message])
-        .output()
-        .context("Failed to execute jj describe")?;
+    let output = run_jj(cmd.args([
+        "log",
+        "-r",
+        &revset,
+        "-T",
+        &template,
+        "--no-graph",
+        "--ignore-working-copy",
+    ]))
+    .context("Failed to execute jj log")?;
 
     if !output.status.success() {
-        anyhow::bail!(
-            "jj describe failed: {}",
-            String::from_utf8_lossy(&output.stderr)
-        );
+        anyhow::bail!("jj log failed: {}", String::from_utf8_lossy(&output.stderr));
     }
 
-    Ok(())
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(parse_session_summaries(&stdout))
+}
+
+/// Record that a session used a tool by adding it to the Claude-tools trailer
+/// of the session's newest change. Does nothing if the tool is already recorded
+/// If repo_path is provided, runs jj in that directory
+pub fn record_session_tool_in(
+    session_id: &str,
+    tool_name: &str,
+    repo_path: Option<&Path>,
+) -> Result<()> {
+    let change_id = find_session_change_anywhere_in(session_id, repo_path)?
+        .context("Session change should exist")?
+        .change_id;
+
+    let description = get_commit_description_bytes_in(&change_id, repo_path)?;
+    let mut already_recorded = false;
+    let complete_message = rewrite_trailers(&description, |trailers| {
+        let mut tools: Vec<String> = trailers
+            .iter()
+            .filter_map(|t| t.strip_prefix("Claude-tools:"))
+            .flat_map(parse_tool_list)
+            .collect();
+        already_recorded = tools.iter().any(|t| t == tool_name);
+        tools.push(tool_name.to_string());
+
+        let mut new_trailers: Vec<String> = trailers
+            .into_iter()
+            .filter(|t| !t.starts_with("Claude-tools:"))
+            .collect();
+        new_trailers.push(format!("Claude-tools: {}", tools.join(", ")));
+        new_trailers
+    });
+
+    if already_recorded {
+        return Ok(());
+    }
+
+    describe_with_bytes_in(&change_id, &complete_message, repo_path)
+}
+
+/// Record that a session used a tool in the current directory
+pub fn record_session_tool(session_id: &str, tool_name: &str) -> Result<()> {
+    record_session_tool_in(session_id, tool_name, None)
+}
+
+/// Split a Claude-tools trailer value ("Edit, Bash") into tool names
+fn parse_tool_list(value: &str) -> impl Iterator<Item = String> + '_ {
+    value
+        .split(',')
+        .map(|t| t.trim())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_string())
+}
+
+/// Parse the output of the list_sessions_in template into per-session summaries
+/// Changes are grouped by session ID, keeping the order in which sessions first appear
+fn parse_session_summaries(output: &str) -> Vec<SessionSummary> {
+    let mut sessions: Vec<SessionSummary> = Vec::new();
+
+    for line in output.lines() {
+        let fields: Vec<&str> = line.splitn(7, '\t').collect();
+        let [
+            change_id,
+            session_id,
+            tools,
+            timestamp,
+            conflict,
+            files,
+            title,
+        ] = fields[..]
+        else {
+            continue;
+        };
+        if session_id.is_empty() {
+            continue;
+        }
+
+        let index = match sessions.iter().position(|s| s.session_id == session_id) {
+            Some(index) => index,
+            None => {
+                sessions.push(SessionSummary {
+                    session_id: session_id.to_string(),
+                    change_ids: Vec::new(),
+                    title: title.to_string(),
+                    titles: Vec::new(),
+                    tools: Vec::new(),
+                    committed_at: None,
+                    has_conflict: false,
+                    files: Vec::new(),
+                });
+                sessions.len() - 1
+            }
+        };
+
+        let session = &mut sessions[index];
+        session.change_ids.push(change_id.to_string());
+        session.titles.push(title.to_string());
+        for tool in parse_tool_list(tools) {
+            if !session.tools.contains(&tool) {
+                session.tools.push(tool);
+            }
+        }
+        let committed_at = timestamp
+            .parse::<i64>()
+            .ok()
+            .and_then(|secs| DateTime::from_timestamp(secs, 0));
+        session.committed_at = session.committed_at.max(committed_at);
+        session.has_conflict |= conflict == "conflict";
+        for file in files.split('\x1f').filter(|f| !f.is_empty()) {
+            if !session.files.iter().any(|f| f == file) {
+                session.files.push(file.to_string());
+            }
+        }
+    }
+
+    for session in &mut sessions {
+        session.files.sort();
+    }
+
+    sessions
 }
 
-/// Parse a commit description into title and trailers
+/// Split a raw commit description into title bytes and trailers
 /// Returns (title, trailers) where trailers is a Vec of "Key: Value" strings
-fn parse_description_and_trailers(description: &str) -> (String, Vec<String>) {
-    let lines: Vec<&str> = description.lines().collect();
+/// The trailer block is the paragraph after the last blank line, and only counts
+/// as trailers if it is valid UTF-8 and every line contains ':'. The title is
+/// returned untouched, so it may contain arbitrary bytes
+fn split_description_and_trailers(description: &[u8]) -> (&[u8], Vec<String>) {
+    let lines: Vec<&[u8]> = description.split(|&b| b == b'\n').collect();
 
     // Find where trailers start (after the last blank line)
-    let mut trailer_start = None;
-    for (i, line) in lines.iter().enumerate().rev() {
-        if line.trim().is_empty() {
-            trailer_start = Some(i + 1);
-            break;
+    let trailer_start = lines
+        .iter()
+        .rposition(|line| line.trim_ascii().is_empty())
+        .map(|i| i + 1);
+
+    let start = match trailer_start {
+        Some(start) if start < lines.len() => start,
+        // No blank line found, entire description is title
+        _ => return (description, Vec::new()),
+    };
+
+    let mut trailers = Vec::new();
+    for line in &lines[start..] {
+        match std::str::from_utf8(line) {
+            Ok(line) if line.contains(':') => trailers.push(line.trim_end().to_string()),
+            Ok(line) if line.trim().is_empty() => {}
+            // Not trailers, entire description is title
+            _ => return (description, Vec::new()),
         }
     }
 
-    match trailer_start {
-        Some(start) if start < lines.len() => {
-            // Check if lines after the blank line are actually trailers
-            let potential_trailers: Vec<&str> = lines[start..].to_vec();
-            let are_trailers = potential_trailers
-                .iter()
-                .all(|line| line.contains(':') || line.trim().is_empty());
-
-            if are_trailers {
-                let title = lines[..start - 1].join("\n");
-                let trailers: Vec<String> = potential_trailers
-                    .iter()
-                    .filter(|line| !line.trim().is_empty())
-                    .map(|s| s.to_string())
-                    .collect();
-                (title, trailers)
-            } else {
-                // Not trailers, entire description is title
-                (description.to_string(), Vec::new())
-            }
+    // Title ends just before the blank line that precedes the trailers
+    let title_len = lines[..start - 1]
+        .iter()
+        .map(|line| line.len() + 1)
+        .sum::<usize>()
+        .saturating_sub(1);
+    (&description[..title_len], trailers)
+}
+
+/// Rewrite the trailers of a raw commit description, leaving the title bytes intact
+/// The closure receives the existing trailers and returns the ones to keep
+fn rewrite_trailers(
+    description: &[u8],
+    update: impl FnOnce(Vec<String>) -> Vec<String>,
+) -> Vec<u8> {
+    let (title, trailers) = split_description_and_trailers(description);
+    let new_trailers = update(trailers);
+
+    if new_trailers.is_empty() {
+        return title.to_vec();
+    }
+
+    let mut message = title.trim_ascii().to_vec();
+    message.extend_from_slice(b"\n\n");
+    message.extend_from_slice(new_trailers.join("\n").as_bytes());
+    message
+}
+
+/// Count the lines containing anything other than whitespace, reading one line at a time
+fn count_nonempty_lines(reader: impl BufRead) -> Result<usize> {
+    let mut count = 0;
+    for line in reader.split(b'\n') {
+        if !line?.trim_ascii().is_empty() {
+            count += 1;
         }
-        _ => {
-            // No blank line found, entire description is title
-            (description.to_string(), Vec::new())
+    }
+    Ok(count)
+}
+
+/// Escape a value for use inside a double-quoted revset or template string literal
+fn escape_string_literal(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\0' => escaped.push_str("\\0"),
+            c if c.is_ascii_control() => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+            c => escaped.push(c),
         }
     }
+    escaped
 }
 
 /// Parse change IDs from jj log output
@@ -1155,6 +4124,358 @@ fn parse_change_ids(output: &str) -> Vec<String> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_diff_stat_totals() {
+        let output =
+            "a.txt | 3 ++-\nb.txt | 1 +\n2 files changed, 3 insertions(+), 1 deletion(-)\n";
+        assert_eq!(parse_diff_stat_totals(output), (3, 1));
+        assert_eq!(
+            parse_diff_stat_totals("1 file changed, 1 insertion(+), 0 deletions(-)\n"),
+            (1, 0)
+        );
+        assert_eq!(
+            parse_diff_stat_totals("0 files changed, 0 insertions(+), 0 deletions(-)\n"),
+            (0, 0)
+        );
+        assert_eq!(parse_diff_stat_totals(""), (0, 0));
+    }
+
+    #[test]
+    fn test_next_part_number() {
+        let change = |title: &str| SessionChange {
+            change_id: String::new(),
+            commit_id: String::new(),
+            description: format!("{}\n\nClaude-session-id: abc\n", title),
+            part: parse_part_number(title),
+        };
+
+        assert_eq!(next_part_number(&[]), 1);
+        assert_eq!(next_part_number(&[change("jjagent: session abc")]), 2);
+        // pt. 2 was abandoned: pt. 3 is still the highest, so the next part is 4, not 3
+        assert_eq!(
+            next_part_number(&[
+                change("jjagent: session abc pt. 3"),
+                change("jjagent: session abc"),
+            ]),
+            4
+        );
+        // Titles without part numbers fall back to counting
+        assert_eq!(
+            next_part_number(&[change("claude abc (second)"), change("claude abc (first)"),]),
+            3
+        );
+    }
+
+    #[test]
+    fn test_is_retryable_jj_error() {
+        assert!(is_retryable_jj_error(
+            "Error: The working copy is stale (not updated since operation 1a2b3c).\nHint: Run `jj workspace update-stale` to update it.\n"
+        ));
+        assert!(is_retryable_jj_error(
+            "Error: Concurrent operation detected while snapshotting"
+        ));
+        assert!(is_retryable_jj_error(
+            r#"Error: The "@" expression resolved to more than one operation"#
+        ));
+
+        assert!(!is_retryable_jj_error(
+            "Error: Revision `abc` doesn't exist\n"
+        ));
+        assert!(!is_retryable_jj_error("Error: Commit 1234 is immutable\n"));
+        assert!(!is_retryable_jj_error(""));
+    }
+
+    #[test]
+    fn test_parse_precommits() {
+        let output = "aaaa\tsess-1\tempty\t1000\nbbbb\tsess-2\t\t400\n\tsess-3\t\t400\n";
+        let precommits = parse_precommits(output, 1600);
+        assert_eq!(
+            precommits,
+            vec![
+                OrphanedPrecommit {
+                    change_id: "aaaa".to_string(),
+                    session_id: "sess-1".to_string(),
+                    empty: true,
+                    age_secs: 600,
+                },
+                OrphanedPrecommit {
+                    change_id: "bbbb".to_string(),
+                    session_id: "sess-2".to_string(),
+                    empty: false,
+                    age_secs: 1200,
+                },
+            ]
+        );
+        // A commit dated in the future counts as brand new
+        assert_eq!(parse_precommits("cccc\ts\t\t2000\n", 1600)[0].age_secs, 0);
+    }
+
+    #[test]
+    fn test_parse_pretool_invariants() {
+        assert_eq!(
+            parse_pretool_invariants("\x1f1\x1f0\x1f\n").unwrap(),
+            PretoolInvariants {
+                session_id: None,
+                at_head: true,
+                has_conflicts: false,
+                precommit_session_id: None,
+            }
+        );
+        assert_eq!(
+            parse_pretool_invariants("abcd-1234\x1f0\x1f1\x1f").unwrap(),
+            PretoolInvariants {
+                session_id: Some("abcd-1234".to_string()),
+                at_head: false,
+                has_conflicts: true,
+                precommit_session_id: None,
+            }
+        );
+        assert_eq!(
+            parse_pretool_invariants("\x1f1\x1f0\x1fabcd-1234").unwrap(),
+            PretoolInvariants {
+                session_id: None,
+                at_head: true,
+                has_conflicts: false,
+                precommit_session_id: Some("abcd-1234".to_string()),
+            }
+        );
+        // Output from before the precommit field was added
+        assert!(parse_pretool_invariants("\x1f1\x1f0").is_err());
+        assert!(parse_pretool_invariants("").is_err());
+    }
+
+    #[test]
+    fn test_parse_session_changes() {
+        let output = "aaaa\x1fc0ffee\x1fjjagent: session abcd1234 pt. 2\n\nClaude-session-id: abcd\n\x1e\
+                      bbbb\x1fdecaf0\x1fjjagent: session abcd1234\n\nClaude-session-id: abcd\n\x1e";
+
+        let changes = parse_session_changes(output);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].change_id, "aaaa");
+        assert_eq!(changes[0].commit_id, "c0ffee");
+        assert_eq!(changes[0].part, Some(2));
+        assert_eq!(
+            changes[1].description,
+            "jjagent: session abcd1234\n\nClaude-session-id: abcd\n"
+        );
+        assert_eq!(changes[1].part, None);
+        assert!(parse_session_changes("").is_empty());
+    }
+
+    #[test]
+    fn test_escape_string_literal() {
+        assert_eq!(escape_string_literal("abcd-1234"), "abcd-1234");
+        assert_eq!(escape_string_literal(r#"a"b\c"#), r#"a\"b\\c"#);
+        assert_eq!(escape_string_literal("a\nb\tc\r"), r"a\nb\tc\r");
+        // Parentheses and revset operators are harmless inside a string literal
+        assert_eq!(escape_string_literal("x) | all() | (y"), "x) | all() | (y");
+        assert_eq!(escape_string_literal("\u{1b}"), r"\x1b");
+    }
+
+    #[test]
+    fn test_toml_escape() {
+        assert_eq!(toml_escape("/tmp/plain.txt"), "/tmp/plain.txt");
+        assert_eq!(toml_escape(r#"C:\tmp\"q".txt"#), r#"C:\\tmp\\\"q\".txt"#);
+    }
+
+    #[test]
+    fn test_parse_conflicts_with_files() {
+        let output = "aaaa\x1fsrc/a.rs\x1fsrc/b.rs\nbbbb\x1fsrc/a.rs\ncccc\x1f\n";
+        assert_eq!(
+            parse_conflicts_with_files(output),
+            (3, vec!["src/a.rs".to_string(), "src/b.rs".to_string()])
+        );
+        assert_eq!(parse_conflicts_with_files(""), (0, Vec::new()));
+    }
+
+    #[test]
+    fn test_is_session_trailer_accepts_legacy_key() {
+        assert!(is_session_trailer("Claude-session-id: abcd"));
+        assert!(is_session_trailer("Jjagent-claude-session-id: abcd"));
+        assert!(!is_session_trailer("Jjagent-claude-session-idx: abcd"));
+        assert!(!is_session_trailer("Claude-precommit-session-id: abcd"));
+    }
+
+    #[test]
+    fn test_migrate_trailer_keys() {
+        let trailers = vec![
+            "Jjagent-claude-session-id: abcd".to_string(),
+            "Ticket: ABC-1".to_string(),
+            "Claude-session-id: abcd".to_string(),
+            "Jjagent-claude-temp-change: abcd".to_string(),
+        ];
+
+        let keys = legacy_trailer_keys(&crate::session::PrecommitConfig::default());
+        assert_eq!(
+            migrate_trailer_keys(trailers.clone(), &keys),
+            vec![
+                "Claude-session-id: abcd",
+                "Ticket: ABC-1",
+                "Claude-precommit-session-id: abcd",
+            ]
+        );
+
+        let namespaced = crate::session::PrecommitConfig {
+            namespace: Some("Acme".to_string()),
+        };
+        assert_eq!(
+            migrate_trailer_keys(trailers, &legacy_trailer_keys(&namespaced)),
+            vec![
+                "Claude-session-id: abcd",
+                "Ticket: ABC-1",
+                "Acme-Claude-precommit-session-id: abcd",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_combine_part_descriptions() {
+        let primary = b"jjagent: session abcd1234\n\nClaude-session-id: abcd".to_vec();
+        let parts = vec![
+            b"jjagent: session abcd1234 pt. 2\n\nClaude-session-id: abcd".to_vec(),
+            b"Follow-up refactor\n\nClaude-session-id: abcd\nTicket: ABC-1".to_vec(),
+        ];
+
+        assert_eq!(
+            String::from_utf8(combine_part_descriptions(&primary, &parts)).unwrap(),
+            "jjagent: session abcd1234\n\nFollow-up refactor\n\nClaude-session-id: abcd\nTicket: ABC-1"
+        );
+    }
+
+    #[test]
+    fn test_rewrite_trailers_preserves_non_utf8_title_bytes() {
+        let description: &[u8] =
+            b"caf\xc3\xa9 \xe2\x98\x95 title\n\nbody with bad \xff byte\n\nClaude-session-id: old";
+
+        let rewritten = rewrite_trailers(description, |trailers| {
+            assert_eq!(trailers, vec!["Claude-session-id: old".to_string()]);
+            vec!["Claude-session-id: new".to_string()]
+        });
+
+        assert_eq!(
+            rewritten,
+            b"caf\xc3\xa9 \xe2\x98\x95 title\n\nbody with bad \xff byte\n\nClaude-session-id: new"
+        );
+    }
+
+    #[test]
+    fn test_split_description_without_trailers() {
+        let description: &[u8] = b"caf\xc3\xa9\n\njust a \xff body";
+        let (title, trailers) = split_description_and_trailers(description);
+        assert_eq!(title, description);
+        assert!(trailers.is_empty());
+    }
+
+    #[test]
+    fn test_decode_utf8_strict_rejects_invalid_bytes() {
+        assert_eq!(
+            decode_utf8_strict(b"caf\xc3\xa9".to_vec(), "Trailers").unwrap(),
+            "café"
+        );
+        let err = decode_utf8_strict(b"ok\xff".to_vec(), "Trailers").unwrap_err();
+        assert!(err.to_string().contains("offset 2"), "got: {}", err);
+    }
+
+    #[test]
+    fn test_parse_session_summaries_groups_parts() {
+        let output = "aaaa\tsession-1\tEdit,\t1700000200\tconflict\tb.txt\tjjagent: session session-1 pt. 2\n\
+                      bbbb\tsession-2\t\t1700000100\tok\t\tjjagent: session session-2\n\
+                      cccc\tsession-1\tBash, Edit,\t1700000000\tok\ta.txt\x1fb.txt\tjjagent: session session-1\n";
+        let sessions = parse_session_summaries(output);
+
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].session_id, "session-1");
+        assert_eq!(sessions[0].change_ids, vec!["aaaa", "cccc"]);
+        assert_eq!(sessions[0].title, "jjagent: session session-1 pt. 2");
+        assert_eq!(
+            sessions[0].titles,
+            vec![
+                "jjagent: session session-1 pt. 2",
+                "jjagent: session session-1"
+            ]
+        );
+        assert_eq!(sessions[0].tools, vec!["Edit", "Bash"]);
+        assert_eq!(
+            sessions[0].committed_at,
+            DateTime::from_timestamp(1700000200, 0)
+        );
+        assert!(sessions[0].has_conflict);
+        assert_eq!(sessions[0].files, vec!["a.txt", "b.txt"]);
+        assert_eq!(sessions[1].session_id, "session-2");
+        assert!(sessions[1].tools.is_empty());
+        assert!(!sessions[1].has_conflict);
+        assert!(sessions[1].files.is_empty());
+    }
+
+    #[test]
+    fn test_parse_conflict_hunks_snapshot_style() {
+        let content = "before\n\
+                       <<<<<<< Conflict 1 of 1\n\
+                       +++++++ Contents of side #1\n\
+                       uwc\n\
+                       ------- Contents of base\n\
+                       base\n\
+                       +++++++ Contents of side #2\n\
+                       session\n\
+                       more session\n\
+                       >>>>>>> Conflict 1 of 1 ends\n\
+                       after\n";
+        let hunks = parse_conflict_hunks(content);
+
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].bases, vec!["base\n"]);
+        assert_eq!(hunks[0].sides, vec!["uwc\n", "session\nmore session\n"]);
+    }
+
+    #[test]
+    fn test_parse_conflict_hunks_ignores_short_markers() {
+        let content = "------ not a marker\n+++ nor this\n";
+        assert!(parse_conflict_hunks(content).is_empty());
+    }
+
+    #[test]
+    fn test_count_nonempty_lines() {
+        let output: &[u8] = b"abcd1234\n\n  \nefgh5678\nijkl9012";
+        assert_eq!(count_nonempty_lines(output).unwrap(), 3);
+        assert_eq!(count_nonempty_lines(&b""[..]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_parse_session_ancestry() {
+        let output = "OTHER:BLANK:aaaa\nSESSION:FULL:bbbb\nOTHER:FULL:cccc\n\n";
+        let entries = parse_session_ancestry(output);
+
+        assert_eq!(entries.len(), 3);
+        assert!(!entries[0].is_session && entries[0].is_blank);
+        assert!(entries[1].is_session && !entries[1].is_blank);
+        assert_eq!(entries[2].change_id, "cccc");
+        assert!(!entries[2].is_session);
+    }
+
+    #[test]
+    fn test_squash_strategy_parse() {
+        assert_eq!(SquashStrategy::parse("").unwrap(), SquashStrategy::Squash);
+        assert_eq!(
+            SquashStrategy::parse("squash").unwrap(),
+            SquashStrategy::Squash
+        );
+        assert_eq!(
+            SquashStrategy::parse(" Absorb ").unwrap(),
+            SquashStrategy::Absorb
+        );
+        assert!(SquashStrategy::parse("rebase").is_err());
+    }
+
+    #[test]
+    fn test_exclude_patterns_to_fileset() {
+        let patterns = parse_exclude_patterns(" .env, secrets/**,, ");
+        assert_eq!(patterns, vec![".env", "secrets/**"]);
+        assert_eq!(
+            exclude_fileset(&patterns),
+            r#"root-glob:".env" | root-glob:"secrets/**""#
+        );
+    }
+
     #[test]
     fn test_parse_change_ids_single() {
         let output = "abcd1234\n";
@@ -1188,4 +4509,66 @@ mod tests {
         assert_eq!(change_ids[0], "abcd1234");
         assert_eq!(change_ids[1], "efgh5678");
     }
+
+    #[test]
+    fn test_parse_descriptions() {
+        let output = b"abcd1234\x1ftitle\n\nClaude-session-id: s1\n\x1eefgh5678\x1f\x1e";
+        let descriptions = parse_descriptions(output);
+        assert_eq!(descriptions.len(), 2);
+        assert_eq!(
+            descriptions["abcd1234"],
+            b"title\n\nClaude-session-id: s1".to_vec()
+        );
+        assert_eq!(descriptions["efgh5678"], b"".to_vec());
+    }
+
+    #[test]
+    fn test_abandon_session_revsets() {
+        let parts = vec!["aaaa".to_string(), "bbbb".to_string()];
+        assert_eq!(session_changes_revset(&parts), "aaaa | bbbb");
+        assert_eq!(
+            foreign_descendants_revset(&parts),
+            "descendants(aaaa | bbbb) ~ (aaaa | bbbb) ~ ::@ ~ @::"
+        );
+    }
+
+    #[test]
+    fn test_ensure_no_foreign_descendants() {
+        assert!(ensure_no_foreign_descendants("s1", &[]).is_ok());
+
+        let foreign = vec!["cccc".to_string(), "dddd".to_string()];
+        let error = ensure_no_foreign_descendants("s1", &foreign)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("Session s1 has descendants"),
+            "got: {}",
+            error
+        );
+        assert!(error.contains("(cccc, dddd)"), "got: {}", error);
+    }
+
+    #[test]
+    fn test_precommit_notes() {
+        // A plain precommit has nothing to keep
+        assert_eq!(
+            precommit_notes(b"jjagent: precommit abcd1234\n\nClaude-precommit-session-id: abcd"),
+            None
+        );
+        assert_eq!(precommit_notes(b""), None);
+
+        // Notes added under the generated title
+        assert_eq!(
+            precommit_notes(
+                b"jjagent: precommit abcd1234\n\nKeep the parser change small\n\nClaude-precommit-session-id: abcd"
+            ),
+            Some(b"Keep the parser change small".to_vec())
+        );
+
+        // A title the user wrote is kept too
+        assert_eq!(
+            precommit_notes(b"Fix the parser\n\nSee #12\n\nClaude-precommit-session-id: abcd"),
+            Some(b"Fix the parser\n\nSee #12".to_vec())
+        );
+    }
 }
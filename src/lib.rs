@@ -19,11 +19,13 @@
 //! - [`logger`]: Optional logging for debugging
 
 use anyhow::{Context, Result};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::io::{self, Read};
-use std::path::Path;
-use std::process::Command;
+use std::ffi::OsStr;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::jj::{jj_command, run_jj};
 
 pub mod hooks;
 pub mod jj;
@@ -31,29 +33,376 @@ pub mod lock;
 pub mod logger;
 pub mod session;
 
+/// Path hooks should use to run this binary
+/// JJAGENT_EXECUTABLE_PATH overrides it. Otherwise, the path jjagent was invoked as
+/// (argv[0], looked up on PATH) is preferred over `current_exe()` when both are the same
+/// binary, since `current_exe()` resolves symlinks and may point into a cache directory
+/// that a later install removes
 pub fn get_executable_path() -> Result<std::path::PathBuf> {
-    std::env::current_exe().context("Failed to get current executable path")
+    if let Some(path) = executable_path_override() {
+        return Ok(path);
+    }
+    let current_exe = std::env::current_exe().context("Failed to get current executable path")?;
+    let invoked = std::env::args_os().next().and_then(|argv0| {
+        let cwd = std::env::current_dir().ok()?;
+        invoked_executable_path(
+            &argv0,
+            &current_exe,
+            std::env::var_os("PATH").as_deref(),
+            &cwd,
+        )
+    });
+    Ok(invoked.unwrap_or(current_exe))
+}
+
+/// The executable path set with JJAGENT_EXECUTABLE_PATH, if any
+fn executable_path_override() -> Option<PathBuf> {
+    std::env::var_os("JJAGENT_EXECUTABLE_PATH")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Resolve how the binary was invoked (`argv0`) to a path: relative to `cwd` if it names a
+/// path, otherwise the first match on `path_var` (PATH). Returns it only if it resolves
+/// through symlinks to the same file as `current_exe`
+pub fn invoked_executable_path(
+    argv0: &OsStr,
+    current_exe: &Path,
+    path_var: Option<&OsStr>,
+    cwd: &Path,
+) -> Option<PathBuf> {
+    let argv0 = Path::new(argv0);
+    let invoked = if argv0.components().count() > 1 {
+        cwd.join(argv0)
+    } else {
+        std::env::split_paths(path_var?)
+            .map(|dir| dir.join(argv0))
+            .find(|candidate| candidate.is_file())?
+    };
+    let target = std::fs::canonicalize(current_exe).ok()?;
+    std::fs::canonicalize(&invoked)
+        .is_ok_and(|resolved| resolved == target)
+        .then_some(invoked)
+}
+
+/// Find a stable path for the running executable
+/// `current_exe()` usually returns the fully resolved binary, which for homebrew or
+/// versioned installs changes on every upgrade. If an entry on `path_var` (PATH)
+/// resolves through symlinks to the same binary, that entry is returned instead so
+/// hooks keep working after an upgrade. Falls back to `current_exe` otherwise.
+pub fn stable_executable_path(current_exe: &Path, path_var: Option<&OsStr>) -> PathBuf {
+    let (Some(file_name), Some(path_var)) = (current_exe.file_name(), path_var) else {
+        return current_exe.to_path_buf();
+    };
+    let Ok(target) = std::fs::canonicalize(current_exe) else {
+        return current_exe.to_path_buf();
+    };
+
+    std::env::split_paths(path_var)
+        .map(|dir| dir.join(file_name))
+        .find(|candidate| std::fs::canonicalize(candidate).is_ok_and(|c| c == target))
+        .unwrap_or_else(|| current_exe.to_path_buf())
+}
+
+/// Hook commands in a Claude settings JSON value that run a different jjagent binary
+/// than `current_exe`. Binaries are compared after resolving symlinks, so a stable
+/// shim pointing at the current binary is not reported. Bare names like `jjagent` are
+/// looked up on PATH, as the shell running the hook would
+pub fn stale_hook_commands(settings: &serde_json::Value, current_exe: &Path) -> Vec<String> {
+    let resolve = |p: &Path| {
+        let on_path = (p.components().count() == 1)
+            .then(|| std::env::var_os("PATH"))
+            .flatten()
+            .and_then(|path_var| {
+                std::env::split_paths(&path_var)
+                    .map(|dir| dir.join(p))
+                    .find(|candidate| candidate.is_file())
+            });
+        let p = on_path.as_deref().unwrap_or(p);
+        std::fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf())
+    };
+    let current = resolve(current_exe);
+
+    let Some(events) = settings.get("hooks").and_then(|h| h.as_object()) else {
+        return Vec::new();
+    };
+
+    events
+        .values()
+        .filter_map(|matchers| matchers.as_array())
+        .flatten()
+        .filter_map(|matcher| matcher.get("hooks").and_then(|h| h.as_array()))
+        .flatten()
+        .filter_map(|hook| hook.get("command").and_then(|c| c.as_str()))
+        .filter_map(|command| {
+            let binary = command.split(" claude hooks ").next()?;
+            (binary != command && resolve(Path::new(binary)) != current)
+                .then(|| command.to_string())
+        })
+        .collect()
+}
+
+/// Subcommands from older jjagent layouts and their current replacements
+const LEGACY_SUBCOMMANDS: &[(&str, &str)] = &[("claude session split", "split")];
+
+/// Rewrite jjagent hook commands that use older subcommand layouts to the current ones.
+/// Hooks that don't run a jjagent binary are left untouched.
+/// Returns each (old, new) command that was rewritten
+pub fn migrate_claude_settings(settings: &mut serde_json::Value) -> Vec<(String, String)> {
+    let mut rewritten = Vec::new();
+    let Some(events) = settings.get_mut("hooks").and_then(|h| h.as_object_mut()) else {
+        return rewritten;
+    };
+
+    let commands = events
+        .values_mut()
+        .filter_map(|matchers| matchers.as_array_mut())
+        .flatten()
+        .filter_map(|matcher| matcher.get_mut("hooks").and_then(|h| h.as_array_mut()))
+        .flatten()
+        .filter_map(|hook| hook.get_mut("command"));
+
+    for command in commands {
+        let Some(old) = command.as_str() else {
+            continue;
+        };
+        if let Some(new) = migrate_hook_command(old) {
+            rewritten.push((old.to_string(), new.clone()));
+            *command = serde_json::Value::String(new);
+        }
+    }
+
+    rewritten
+}
+
+/// The current form of a single hook command, or None if it's already current
+/// or doesn't run jjagent
+fn migrate_hook_command(command: &str) -> Option<String> {
+    let (binary, rest) = command.split_once(' ')?;
+    if Path::new(binary).file_stem()? != "jjagent" {
+        return None;
+    }
+    LEGACY_SUBCOMMANDS.iter().find_map(|(old, new)| {
+        let args = rest.strip_prefix(old)?;
+        (args.is_empty() || args.starts_with(' ')).then(|| format!("{} {}{}", binary, new, args))
+    })
+}
+
+/// Migrate the hook commands in a Claude settings file in place.
+/// The file is only written if something changed
+pub fn migrate_claude_settings_file(path: &Path) -> Result<Vec<(String, String)>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read settings file {}", path.display()))?;
+    let mut settings: serde_json::Value = serde_json::from_str(&contents)
+        .with_context(|| format!("{} is not valid JSON", path.display()))?;
+
+    let rewritten = migrate_claude_settings(&mut settings);
+    if !rewritten.is_empty() {
+        let json =
+            serde_json::to_string_pretty(&settings).expect("settings JSON is always serializable");
+        std::fs::write(path, json + "\n")
+            .with_context(|| format!("Failed to write settings file {}", path.display()))?;
+    }
+
+    Ok(rewritten)
+}
+
+/// Whether a hook command is one of jjagent's own `claude hooks` commands
+fn is_jjagent_hook_command(command: &str) -> bool {
+    command
+        .split_once(" claude hooks ")
+        .is_some_and(|(binary, _)| Path::new(binary).file_stem() == Some(OsStr::new("jjagent")))
+}
+
+/// Remove jjagent's hook commands from one event's matcher entries, dropping entries
+/// that are left without hooks. Returns how many hook commands were removed
+fn remove_jjagent_hooks(matchers: &mut Vec<serde_json::Value>) -> usize {
+    let mut removed = 0;
+    matchers.retain_mut(|matcher| {
+        let Some(commands) = matcher.get_mut("hooks").and_then(|h| h.as_array_mut()) else {
+            return true;
+        };
+        let before = commands.len();
+        commands.retain(|hook| {
+            !hook
+                .get("command")
+                .and_then(|c| c.as_str())
+                .is_some_and(is_jjagent_hook_command)
+        });
+        removed += before - commands.len();
+        commands.len() == before || !commands.is_empty()
+    });
+    removed
+}
+
+/// Merge jjagent's hooks from `generated` into existing Claude settings.
+/// jjagent's previous hook commands are removed first (dropping matcher entries left
+/// without hooks), so merging again is idempotent. Other keys and hooks are untouched
+pub fn merge_claude_settings(
+    existing: &mut serde_json::Value,
+    generated: &serde_json::Value,
+) -> Result<()> {
+    let Some(root) = existing.as_object_mut() else {
+        anyhow::bail!("Claude settings must be a JSON object");
+    };
+    let hooks = root.entry("hooks").or_insert_with(|| json!({}));
+    let Some(hooks) = hooks.as_object_mut() else {
+        anyhow::bail!("Claude settings \"hooks\" must be a JSON object");
+    };
+
+    let generated_hooks = generated
+        .get("hooks")
+        .and_then(|h| h.as_object())
+        .context("Generated settings have no hooks")?;
+
+    for (event, entries) in generated_hooks {
+        let matchers = hooks.entry(event.as_str()).or_insert_with(|| json!([]));
+        let Some(matchers) = matchers.as_array_mut() else {
+            anyhow::bail!("Claude settings hooks.{} must be an array", event);
+        };
+
+        remove_jjagent_hooks(matchers);
+
+        if let Some(entries) = entries.as_array() {
+            matchers.extend(entries.iter().cloned());
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove jjagent's hooks from Claude settings, matching on the binary name so hooks
+/// from any install location are found. Events left without hooks are dropped.
+/// Other keys and hooks are untouched. Returns how many hook commands were removed
+pub fn uninstall_claude_settings(settings: &mut serde_json::Value) -> usize {
+    let Some(events) = settings.get_mut("hooks").and_then(|h| h.as_object_mut()) else {
+        return 0;
+    };
+
+    let mut removed = 0;
+    events.retain(|_, matchers| {
+        let Some(matchers) = matchers.as_array_mut() else {
+            return true;
+        };
+        let event_removed = remove_jjagent_hooks(matchers);
+        removed += event_removed;
+        event_removed == 0 || !matchers.is_empty()
+    });
+    removed
+}
+
+/// Remove jjagent's hooks from a Claude settings file in place.
+/// The file is only written if something was removed
+pub fn uninstall_claude_settings_file(path: &Path) -> Result<usize> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read settings file {}", path.display()))?;
+    let mut settings: serde_json::Value = serde_json::from_str(&contents)
+        .with_context(|| format!("{} is not valid JSON", path.display()))?;
+
+    let removed = uninstall_claude_settings(&mut settings);
+    if removed > 0 {
+        let json =
+            serde_json::to_string_pretty(&settings).expect("settings JSON is always serializable");
+        std::fs::write(path, json + "\n")
+            .with_context(|| format!("Failed to write settings file {}", path.display()))?;
+    }
+
+    Ok(removed)
+}
+
+/// Merge jjagent's hooks into a Claude settings file, creating it if it doesn't exist
+pub fn merge_claude_settings_file(path: &Path, generated: &str) -> Result<()> {
+    let mut settings = match std::fs::read_to_string(path) {
+        Ok(contents) if contents.trim().is_empty() => json!({}),
+        Ok(contents) => serde_json::from_str(&contents)
+            .with_context(|| format!("{} is not valid JSON", path.display()))?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => json!({}),
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("Failed to read settings file {}", path.display()));
+        }
+    };
+    let generated: serde_json::Value =
+        serde_json::from_str(generated).context("Generated settings are not valid JSON")?;
+
+    merge_claude_settings(&mut settings, &generated)?;
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let json =
+        serde_json::to_string_pretty(&settings).expect("settings JSON is always serializable");
+    std::fs::write(path, json + "\n")
+        .with_context(|| format!("Failed to write settings file {}", path.display()))
+}
+
+/// The user-level Claude settings file, ~/.claude/settings.json
+pub fn claude_user_settings_path() -> Result<PathBuf> {
+    let home = std::env::var_os("HOME").context("HOME is not set")?;
+    Ok(PathBuf::from(home).join(".claude").join("settings.json"))
+}
+
+/// The project-level Claude settings file, .claude/settings.json at the root of the jj or
+/// git repo containing `start`
+pub fn claude_project_settings_path(start: &Path) -> Result<PathBuf> {
+    let root = start
+        .ancestors()
+        .find(|dir| dir.join(".jj").is_dir() || dir.join(".git").exists())
+        .with_context(|| {
+            format!(
+                "{} is not in a jj or git repository; project settings live at its root",
+                start.display()
+            )
+        })?;
+    Ok(root.join(".claude").join("settings.json"))
+}
+
+/// Tools whose edits jjagent tracks unless `--matcher` says otherwise
+pub const DEFAULT_TOOL_MATCHER: &str = "Edit|MultiEdit|Write|NotebookEdit";
+
+/// Format Claude Code settings JSON for this binary
+/// `matcher` selects the tools the PreToolUse/PostToolUse hooks run for
+/// (defaults to DEFAULT_TOOL_MATCHER)
+pub fn format_claude_settings(resolve_symlinks: bool, matcher: Option<&str>) -> Result<String> {
+    let matcher = matcher.unwrap_or(DEFAULT_TOOL_MATCHER);
+    if matcher.trim().is_empty() {
+        anyhow::bail!(
+            "Tool matcher must not be empty (e.g. \"{}\")",
+            DEFAULT_TOOL_MATCHER
+        );
+    }
+
+    let mut exe_path = get_executable_path()?;
+    if resolve_symlinks && executable_path_override().is_none() {
+        exe_path = stable_executable_path(&exe_path, std::env::var_os("PATH").as_deref());
+    }
+    Ok(format_claude_settings_for(&exe_path, matcher))
 }
 
-pub fn format_claude_settings() -> Result<String> {
-    let exe_path = get_executable_path()?;
+/// Format Claude Code settings JSON with hooks that run the given executable
+/// for the tools in `matcher`
+pub fn format_claude_settings_for(exe_path: &Path, matcher: &str) -> String {
     let exe_str = exe_path.to_string_lossy();
 
     let pre_tool_use_cmd = format!("{} claude hooks PreToolUse", exe_str);
     let post_tool_use_cmd = format!("{} claude hooks PostToolUse", exe_str);
     let stop_cmd = format!("{} claude hooks Stop", exe_str);
+    let session_start_cmd = format!("{} claude hooks SessionStart", exe_str);
+    let pre_compact_cmd = format!("{} claude hooks PreCompact", exe_str);
+    let notification_cmd = format!("{} claude hooks Notification", exe_str);
 
     let config = json!({
         "hooks": {
             "PreToolUse": [{
-                "matcher": "Edit|MultiEdit|Write",
+                "matcher": matcher,
                 "hooks": [{
                     "type": "command",
                     "command": pre_tool_use_cmd
                 }]
             }],
             "PostToolUse": [{
-                "matcher": "Edit|MultiEdit|Write",
+                "matcher": matcher,
                 "hooks": [{
                     "type": "command",
                     "command": post_tool_use_cmd
@@ -64,31 +413,951 @@ pub fn format_claude_settings() -> Result<String> {
                     "type": "command",
                     "command": stop_cmd
                 }]
+            }],
+            "SessionStart": [{
+                "hooks": [{
+                    "type": "command",
+                    "command": session_start_cmd
+                }]
+            }],
+            "PreCompact": [{
+                "hooks": [{
+                    "type": "command",
+                    "command": pre_compact_cmd
+                }]
+            }],
+            "Notification": [{
+                "hooks": [{
+                    "type": "command",
+                    "command": notification_cmd
+                }]
             }]
         }
     });
 
-    Ok(serde_json::to_string_pretty(&config)?)
+    serde_json::to_string_pretty(&config).expect("settings JSON is always serializable")
+}
+
+/// Directory at a repo's root for per-repo jjagent settings
+pub const REPO_CONFIG_DIR: &str = ".jjagent";
+
+/// Find the jj repo containing `start` by walking up to the nearest directory with a .jj
+/// Doesn't spawn jj, so hooks can run it before doing anything else
+pub fn find_repo_root_from(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| dir.join(".jj").is_dir())
+        .map(Path::to_path_buf)
+}
+
+/// Why jjagent is turned off for the repo rooted at `repo_root`, if it is: either a
+/// `.jjagent/disabled` marker file or `disabled = true` in `.jjagent/config.toml`
+pub fn repo_disabled_reason(repo_root: &Path) -> Option<String> {
+    let config_dir = repo_root.join(REPO_CONFIG_DIR);
+    let marker = config_dir.join("disabled");
+    if marker.exists() {
+        return Some(marker.display().to_string());
+    }
+    let config = config_dir.join("config.toml");
+    let contents = std::fs::read_to_string(&config).ok()?;
+    config_disables(&contents).then(|| config.display().to_string())
+}
+
+/// Whether a `.jjagent/config.toml` sets `disabled = true` at the top level
+/// Only this one key is read, so this is a line scan rather than a full TOML parse
+fn config_disables(contents: &str) -> bool {
+    contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .take_while(|line| !line.starts_with('['))
+        .filter_map(|line| line.split_once('='))
+        .any(|(key, value)| key.trim() == "disabled" && value.trim() == "true")
+}
+
+/// Environment variables that configure jjagent, reported by `jjagent info`
+pub const CONFIG_ENV_VARS: &[&str] = &[
+    "JJAGENT_DETERMINISTIC",
+    "JJAGENT_DISABLE",
+    "JJAGENT_DRY_RUN",
+    "JJAGENT_EXCLUDE_PATHS",
+    "JJAGENT_EXECUTABLE_PATH",
+    "JJAGENT_GIT_EXPORT",
+    "JJAGENT_JJ_BIN",
+    "JJAGENT_JJ_TIMEOUT_SECS",
+    "JJAGENT_KEEP_PRECOMMIT_NOTES",
+    "JJAGENT_LOCK_TIMEOUT_SECS",
+    "JJAGENT_LOG",
+    "JJAGENT_LOG_FILE",
+    "JJAGENT_LOG_LEVEL",
+    "JJAGENT_LOG_MAX_BYTES",
+    "JJAGENT_NO_LOCK",
+    "JJAGENT_POSTTOOL_DELAY_MS",
+    "JJAGENT_PRECOMMIT_TRAILER_NAMESPACE",
+    "JJAGENT_PRESERVE_TIMESTAMP",
+    "JJAGENT_QUIET",
+    "JJAGENT_RECORD_TOOLS",
+    "JJAGENT_SESSION_BASE",
+    "JJAGENT_SESSION_MESSAGE_TEMPLATE",
+    "JJAGENT_SIGN",
+    "JJAGENT_SQUASH_STRATEGY",
+    "JJAGENT_STATUSLINE_TEMPLATE",
+    "JJAGENT_TRACK_TOOLS",
+];
+
+/// Get the output of `jj --version`, or None if jj can't be run
+pub fn jj_version() -> Option<String> {
+    let output = run_jj(jj_command().arg("--version")).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Format version and environment details for bug reports
+pub fn format_info() -> String {
+    let exe_path = get_executable_path()
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|e| format!("unknown ({})", e));
+
+    let mut lines = vec![
+        format!("jjagent {}", env!("CARGO_PKG_VERSION")),
+        format!(
+            "jj: {}",
+            jj_version().unwrap_or_else(|| "not found on PATH".to_string())
+        ),
+        format!("executable: {}", exe_path),
+        format!("os: {} {}", std::env::consts::OS, std::env::consts::ARCH),
+    ];
+
+    let config: Vec<String> = CONFIG_ENV_VARS
+        .iter()
+        .filter_map(|var| std::env::var(var).ok().map(|v| format!("  {}={}", var, v)))
+        .collect();
+    if config.is_empty() {
+        lines.push("config: defaults (no JJAGENT_* environment variables set)".to_string());
+    } else {
+        lines.push("config:".to_string());
+        lines.extend(config);
+    }
+
+    lines.join("\n")
+}
+
+/// Result of one `jjagent doctor` check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    /// Worth knowing about, but jjagent can still work
+    Warn,
+    /// jjagent's hooks will fail until this is fixed
+    Fail,
+}
+
+/// One line of `jjagent doctor` output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DoctorCheck {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl DoctorCheck {
+    fn new(name: &'static str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Check that jj and the repo are in a state the hooks can work with
+/// Checks that depend on an earlier failed one are skipped
+/// If repo_path is provided, runs jj in that directory
+pub fn run_doctor_checks_in(repo_path: Option<&Path>) -> Vec<DoctorCheck> {
+    let mut checks = Vec::new();
+
+    match jj_version() {
+        Some(version) => checks.push(DoctorCheck::new("jj", CheckStatus::Pass, version)),
+        None => {
+            checks.push(DoctorCheck::new(
+                "jj",
+                CheckStatus::Fail,
+                "not found on PATH (or set JJAGENT_JJ_BIN)",
+            ));
+            return checks;
+        }
+    }
+
+    let repo_root = match jj::get_repo_root_in(repo_path) {
+        Ok(root) => {
+            checks.push(DoctorCheck::new(
+                "repo",
+                CheckStatus::Pass,
+                root.display().to_string(),
+            ));
+            root
+        }
+        Err(_) => {
+            checks.push(DoctorCheck::new(
+                "repo",
+                CheckStatus::Fail,
+                "not in a jj repository",
+            ));
+            return checks;
+        }
+    };
+
+    match jj::check_pretool_invariants_in(repo_path) {
+        Ok(invariants) => {
+            checks.push(if invariants.at_head {
+                DoctorCheck::new("@ at head", CheckStatus::Pass, "no descendants")
+            } else {
+                DoctorCheck::new(
+                    "@ at head",
+                    CheckStatus::Fail,
+                    "@ has descendants; run `jj new` on a head",
+                )
+            });
+            checks.push(if invariants.has_conflicts {
+                DoctorCheck::new(
+                    "@ conflicts",
+                    CheckStatus::Fail,
+                    "@ has conflicts; resolve them before editing",
+                )
+            } else {
+                DoctorCheck::new("@ conflicts", CheckStatus::Pass, "none")
+            });
+        }
+        Err(e) => checks.push(DoctorCheck::new("@", CheckStatus::Fail, e.to_string())),
+    }
+
+    checks.push(match lock::current_holder(&repo_root) {
+        Ok(None) => DoctorCheck::new("lock", CheckStatus::Pass, "free"),
+        Ok(Some(holder)) if lock::is_stale(holder.age_seconds) => DoctorCheck::new(
+            "lock",
+            CheckStatus::Warn,
+            format!(
+                "stale lock held by session {} (pid {}, {}s); the next hook will take it over",
+                holder.session_id, holder.pid, holder.age_seconds
+            ),
+        ),
+        Ok(Some(holder)) => DoctorCheck::new(
+            "lock",
+            CheckStatus::Pass,
+            format!(
+                "held by session {} (pid {}, {}s)",
+                holder.session_id, holder.pid, holder.age_seconds
+            ),
+        ),
+        Err(_) => DoctorCheck::new(
+            "lock",
+            CheckStatus::Warn,
+            "lock file is present but unreadable",
+        ),
+    });
+
+    checks.push(
+        match jj::get_config_value_in("fsmonitor.backend", repo_path) {
+            Ok(Some(backend)) if backend == "watchman" => DoctorCheck::new(
+                "fsmonitor",
+                CheckStatus::Warn,
+                "watchman; its auto-snapshots can race the hooks, raise \
+                 JJAGENT_POSTTOOL_DELAY_MS if edits land in the wrong change",
+            ),
+            Ok(Some(backend)) => DoctorCheck::new("fsmonitor", CheckStatus::Pass, backend),
+            Ok(None) => DoctorCheck::new("fsmonitor", CheckStatus::Pass, "not set"),
+            Err(e) => DoctorCheck::new("fsmonitor", CheckStatus::Warn, e.to_string()),
+        },
+    );
+
+    checks.push(hooks_binary_check(&repo_root));
+
+    checks
+}
+
+/// Check that the hooks in the user and project Claude settings run this jjagent binary
+/// Settings generated by another install keep running that install's hooks after an upgrade
+fn hooks_binary_check(repo_root: &Path) -> DoctorCheck {
+    let current_exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(e) => return DoctorCheck::new("hooks", CheckStatus::Warn, e.to_string()),
+    };
+
+    let settings_paths = [
+        claude_user_settings_path().ok(),
+        Some(repo_root.join(".claude").join("settings.json")),
+    ];
+    let stale: Vec<String> = settings_paths
+        .into_iter()
+        .flatten()
+        .filter_map(|path| {
+            let contents = std::fs::read_to_string(&path).ok()?;
+            let settings: serde_json::Value = serde_json::from_str(&contents).ok()?;
+            let command = stale_hook_commands(&settings, &current_exe)
+                .into_iter()
+                .next()?;
+            let binary = command.split(" claude hooks ").next().unwrap_or(&command);
+            Some(format!("{} runs {}", path.display(), binary))
+        })
+        .collect();
+
+    if stale.is_empty() {
+        DoctorCheck::new(
+            "hooks",
+            CheckStatus::Pass,
+            "none run another jjagent binary",
+        )
+    } else {
+        DoctorCheck::new(
+            "hooks",
+            CheckStatus::Warn,
+            format!(
+                "{}, not {}; regenerate hooks with `jjagent claude settings --merge`",
+                stale.join("; "),
+                current_exe.display()
+            ),
+        )
+    }
+}
+
+/// Format `jjagent doctor` checks, one per line with a pass/warn/fail marker
+pub fn format_doctor_checks(checks: &[DoctorCheck]) -> String {
+    checks
+        .iter()
+        .map(|check| {
+            let marker = match check.status {
+                CheckStatus::Pass => "[pass]",
+                CheckStatus::Warn => "[warn]",
+                CheckStatus::Fail => "[fail]",
+            };
+            format!("{} {}: {}", marker, check.name, check.detail)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Format one line of `jjagent list`: short session ID, part count, change IDs, the
+/// first line of each part's description and any tools recorded for the session
+pub fn format_session_parts_line(session: &jj::SessionSummary) -> String {
+    let sid = session::SessionId::from_full(&session.session_id);
+    let parts = session.change_ids.len();
+    let tools = if session.tools.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", session.tools.join(", "))
+    };
+    format!(
+        "{} {} part{} {} {}{}\n",
+        sid.short(),
+        parts,
+        if parts == 1 { "" } else { "s" },
+        session.change_ids.join(","),
+        session.titles.join(" | "),
+        tools
+    )
+}
+
+/// Summarize what jjagent thinks the current state is, for `jjagent status`
+/// Without a session ID, uses the Claude-session-id on @ (if any)
+/// Outside a jj repo this is a single line rather than an error, like the hooks
+/// If repo_path is provided, runs jj in that directory
+pub fn format_status_in(session_id: Option<&str>, repo_path: Option<&Path>) -> Result<String> {
+    let Ok(repo_root) = jj::get_repo_root_in(repo_path) else {
+        return Ok("jjagent: Not in a jj repository".to_string());
+    };
+
+    let on_at = jj::get_current_commit_session_id_in(repo_path)?;
+    let session_id = session_id.map(str::to_string).or_else(|| on_at.clone());
+    let yes_no = |b: bool| if b { "yes" } else { "no" };
+
+    let mut lines = vec![format!(
+        "session: {}",
+        session_id.as_deref().unwrap_or("none (pass --session-id)")
+    )];
+
+    if let Some(session_id) = &session_id {
+        let is_precommit = jj::is_current_commit_precommit_for_session_in(session_id, repo_path)?;
+        lines.push(format!("@ is precommit: {}", yes_no(is_precommit)));
+    }
+    lines.push(match &on_at {
+        Some(id) => format!("@ is session change: yes ({})", id),
+        None => "@ is session change: no".to_string(),
+    });
+
+    lines.push(match lock::current_holder(&repo_root) {
+        Ok(None) => "lock: free".to_string(),
+        Ok(Some(holder)) => format!(
+            "lock: held by session {} (pid {}, {}s)",
+            holder.session_id, holder.pid, holder.age_seconds
+        ),
+        Err(_) => "lock: present but unreadable".to_string(),
+    });
+
+    if let Some(session_id) = &session_id {
+        lines.push(
+            match jj::find_session_change_anywhere_in(session_id, repo_path)? {
+                Some(change) => format!("session change: {}", change.change_id),
+                None => "session change: not found".to_string(),
+            },
+        );
+    }
+
+    Ok(lines.join("\n"))
+}
+
+/// Summarize the current jjagent state in the current directory
+pub fn format_status(session_id: Option<&str>) -> Result<String> {
+    format_status_in(session_id, None)
+}
+
+/// The state `jjagent watch` follows: who holds the working copy lock and the session on @
+/// The lock's age is left out, so states only differ when something actually changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchState {
+    /// Session and pid holding the lock, None when it's free
+    pub lock_holder: Option<(String, u32)>,
+    /// The lock file exists but its metadata can't be read (e.g. mid-write)
+    pub lock_unreadable: bool,
+    pub session_on_at: Option<String>,
+}
+
+/// Read the lock and the session on @ for the repo rooted at `repo_root`
+/// @ is read without snapshotting the working copy, so watching doesn't race the hooks
+pub fn read_watch_state_in(repo_root: &Path) -> Result<WatchState> {
+    let (lock_holder, lock_unreadable) = match lock::current_holder(repo_root) {
+        Ok(None) => (None, false),
+        Ok(Some(holder)) => (Some((holder.session_id, holder.pid)), false),
+        Err(_) => (None, true),
+    };
+    Ok(WatchState {
+        lock_holder,
+        lock_unreadable,
+        session_on_at: jj::get_current_commit_session_id_in(Some(repo_root))?,
+    })
+}
+
+/// Format a watch state as one line, e.g. "lock: held by abc (pid 42) | @: session abc"
+pub fn format_watch_line(state: &WatchState) -> String {
+    let lock = match (&state.lock_holder, state.lock_unreadable) {
+        (Some((session_id, pid)), _) => format!("held by {} (pid {})", session_id, pid),
+        (None, true) => "present but unreadable".to_string(),
+        (None, false) => "free".to_string(),
+    };
+    let at = match &state.session_on_at {
+        Some(session_id) => format!("session {}", session_id),
+        None => "no session".to_string(),
+    };
+    format!("lock: {} | @: {}", lock, at)
 }
 
 /// Split a change by inserting a new change before @ (working copy)
-pub fn split_change(reference: &str) -> Result<()> {
-    jj::split_change(reference, None)
+/// A message replaces the default "<first line> (split <timestamp>)" title of the new part
+/// Returns the full change ID of the new part
+pub fn split_change(reference: &str, message: Option<&str>) -> Result<String> {
+    jj::split_change(reference, message, None)
+}
+
+/// A session part as reported by `jjagent split --json`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SplitPart {
+    pub session_id: String,
+    /// 1-based position of the part within its session, oldest first
+    pub part: usize,
+    pub change_id: String,
+}
+
+/// Describe the session part a split created
+/// If repo_path is provided, runs jj in that directory
+pub fn split_part_in(change_id: &str, repo_path: Option<&Path>) -> Result<SplitPart> {
+    let session_id = jj::get_session_id_in(change_id, repo_path)?
+        .with_context(|| format!("Change {} has no Claude-session-id trailer", change_id))?;
+    // Session changes come back newest first
+    let changes = jj::find_all_session_changes_in(&session_id, repo_path)?;
+    let index = changes
+        .iter()
+        .position(|id| id == change_id)
+        .with_context(|| format!("Change {} is not part of session {}", change_id, session_id))?;
+    Ok(SplitPart {
+        session_id,
+        part: changes.len() - index,
+        change_id: change_id.to_string(),
+    })
+}
+
+/// Describe the session part a split created in the current directory
+pub fn split_part(change_id: &str) -> Result<SplitPart> {
+    split_part_in(change_id, None)
 }
 
 /// Move session tracking to an existing jj revision
 /// The reference must be an ancestor of @ (working copy)
-pub fn move_session_into(session_id: &str, reference: &str) -> Result<()> {
+/// Returns the full change ID that received the trailer
+pub fn move_session_into(session_id: &str, reference: &str) -> Result<String> {
     jj::move_session_into(session_id, reference, None)
 }
 
+/// Duplicate a session's changes onto another revision without moving the originals
+/// Returns the new change IDs and whether each copy is conflicted
+pub fn cherry_session(
+    session_id: &str,
+    onto: &str,
+    strip_trailer: bool,
+) -> Result<Vec<jj::DuplicatedChange>> {
+    jj::duplicate_session_in(session_id, onto, strip_trailer, None)
+}
+
+/// Abandon every change belonging to a session, leaving @ in place
+/// With dry_run set, only reports what would be abandoned
+/// Returns the change IDs that were (or would be) abandoned
+pub fn abandon_session(session_id: &str, dry_run: bool) -> Result<Vec<String>> {
+    jj::abandon_session_in(session_id, true, dry_run, None)
+}
+
+/// Abandon precommits left behind by interrupted tool calls in the current directory
+/// Precommits younger than `min_age_secs` are kept unless empty
+/// With dry_run set, only reports what would be abandoned
+pub fn gc_precommits(min_age_secs: u64, dry_run: bool) -> Result<Vec<jj::OrphanedPrecommit>> {
+    jj::gc_precommits_in(min_age_secs, dry_run, None)
+}
+
+/// Print a graph of a session's changes, their base and the working copy
+/// Without a session ID, uses the Claude-session-id on @
+pub fn show_session_log(session_id: Option<&str>, color: &str) -> Result<()> {
+    let session_id = match session_id {
+        Some(session_id) => session_id.to_string(),
+        None => jj::get_current_commit_session_id()?
+            .context("@ has no Claude-session-id trailer; pass --session-id to pick a session")?,
+    };
+
+    let changes = jj::find_all_session_changes(&session_id)?;
+    if changes.is_empty() {
+        anyhow::bail!("No change found for session ID: {}", session_id);
+    }
+
+    jj::stream_log_in(&jj::session_log_revset(&changes), color, None)
+}
+
+/// List the files a session touched across all of its parts, sorted
+pub fn session_files(session_id: &str) -> Result<Vec<String>> {
+    if jj::find_all_session_changes(session_id)?.is_empty() {
+        anyhow::bail!("No change found for session ID: {}", session_id);
+    }
+    jj::get_session_file_list(session_id)
+}
+
+/// Rebuild a session's change from the file edits recorded in its transcript, for when the
+/// change was abandoned or mangled but the transcript survives
+/// The final state of every edited file is written on top of @ and finalized like a
+/// live edit, so it lands in a new session change carrying the session's trailer.
+/// Returns the repo-relative paths written
+pub fn replay_session(
+    session_id: &str,
+    transcript_path: &Path,
+    verbosity: hooks::Verbosity,
+) -> Result<Vec<String>> {
+    if jj::session_exists(session_id)? {
+        anyhow::bail!(
+            "Session {} already has a change; abandon it before replaying",
+            session_id
+        );
+    }
+
+    let edits = session::parse_transcript_edits(transcript_path)?;
+    if edits.is_empty() {
+        anyhow::bail!(
+            "No Write, Edit or MultiEdit tool uses found in transcript: {}",
+            transcript_path.display()
+        );
+    }
+
+    // Work out every file's final content before touching the repo, so an edit that
+    // can't be replayed leaves everything as it was
+    let root = jj::get_repo_root_in(None)?;
+    let files = replay_file_states(&edits, &root)?;
+
+    let input = || hooks::HookInput {
+        session_id: session_id.to_string(),
+        tool_name: None,
+        hook_event_name: None,
+        transcript_path: Some(transcript_path.display().to_string()),
+        cwd: None,
+        verbosity,
+    };
+    hooks::handle_pretool_hook(input())?;
+
+    let written = files.iter().try_for_each(|(path, content)| {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))
+    });
+    // Finalize even if a write failed, so the precommit and lock aren't left behind
+    let finalized = hooks::handle_posttool_hook(input());
+    written?;
+    finalized?;
+
+    Ok(files
+        .keys()
+        .map(|path| {
+            path.strip_prefix(&root)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        })
+        .collect())
+}
+
+/// Apply transcript edits in order, starting from each file's current content on disk
+/// Fails if an edit's file is outside the repo or its text to replace can't be found
+fn replay_file_states(
+    edits: &[session::TranscriptEdit],
+    root: &Path,
+) -> Result<std::collections::BTreeMap<PathBuf, String>> {
+    let mut files: std::collections::BTreeMap<PathBuf, String> = std::collections::BTreeMap::new();
+    for edit in edits {
+        let file_path = match edit {
+            session::TranscriptEdit::Write { file_path, .. }
+            | session::TranscriptEdit::Edit { file_path, .. } => root.join(file_path),
+        };
+        let escapes = file_path
+            .components()
+            .any(|c| c == std::path::Component::ParentDir);
+        if escapes || !file_path.starts_with(root) {
+            anyhow::bail!(
+                "Can't replay an edit to {}: it's outside the repo at {}",
+                file_path.display(),
+                root.display()
+            );
+        }
+
+        let content = match edit {
+            session::TranscriptEdit::Write { content, .. } => content.clone(),
+            session::TranscriptEdit::Edit {
+                old_string,
+                new_string,
+                replace_all,
+                ..
+            } => {
+                let current = match files.get(&file_path) {
+                    Some(content) => content.clone(),
+                    None => std::fs::read_to_string(&file_path).with_context(|| {
+                        format!("Failed to read {} to replay an edit", file_path.display())
+                    })?,
+                };
+                if !current.contains(old_string.as_str()) {
+                    anyhow::bail!(
+                        "Can't replay an edit to {}: the text it replaces isn't there",
+                        file_path.display()
+                    );
+                }
+                if *replace_all {
+                    current.replace(old_string.as_str(), new_string)
+                } else {
+                    current.replacen(old_string.as_str(), new_string, 1)
+                }
+            }
+        };
+        files.insert(file_path, content);
+    }
+    Ok(files)
+}
+
+/// Print the diff of a session's change
+/// With parts set and a session split into several parts, prints each part's diff
+/// separately (oldest first) under a header line naming the part
+pub fn show_session_diff(session_id: &str, parts: bool) -> Result<()> {
+    let change_id = jj::find_session_change_anywhere(session_id)?
+        .ok_or_else(|| anyhow::anyhow!("No change found for session ID: {}", session_id))?
+        .change_id;
+
+    let mut all_parts = if parts {
+        jj::find_all_session_changes(session_id)?
+    } else {
+        Vec::new()
+    };
+    if all_parts.len() <= 1 {
+        return jj::stream_diff_in(&change_id, None);
+    }
+
+    // jj log lists newest first, but parts read best in the order they were made
+    all_parts.reverse();
+    let total = all_parts.len();
+    for (i, part) in all_parts.iter().enumerate() {
+        let title = jj::get_commit_description(part)?
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        if i > 0 {
+            println!();
+        }
+        println!("=== part {} of {}: {} {} ===", i + 1, total, part, title);
+        // jj writes to the same stdout, so the header must be out before it starts
+        io::stdout().flush()?;
+        jj::stream_diff_in(part, None)?;
+    }
+
+    Ok(())
+}
+
+/// Render a session as a series of mail-formatted patches (one per part, oldest first)
+/// that `git am` can apply. Each patch's message carries the change's description,
+/// including its Claude-session-id trailer
+pub fn export_session_patch(session_id: &str) -> Result<String> {
+    let mut parts = jj::find_all_session_changes(session_id)?;
+    if parts.is_empty() {
+        anyhow::bail!("No change found for session ID: {}", session_id);
+    }
+
+    // jj log lists newest first, but the patches have to apply in the order they were made
+    parts.reverse();
+    let total = parts.len();
+    let mut patches = Vec::with_capacity(total);
+    for (i, part) in parts.iter().enumerate() {
+        patches.push(jj::format_patch_in(part, i + 1, total, None)?);
+    }
+    Ok(patches.join("\n"))
+}
+
+/// Squash a session's later parts back into its first change
+/// Returns the merged change ID and the parts that were folded into it
+pub fn merge_session_parts(session_id: &str) -> Result<(String, Vec<String>)> {
+    jj::merge_session_parts_in(session_id, None)
+}
+
+/// Fold a session's changes into an existing ancestor of the working copy
+/// Returns the target change ID and the session changes folded into it
+pub fn squash_session_into(session_id: &str, reference: &str) -> Result<(String, Vec<String>)> {
+    jj::squash_session_into_in(session_id, reference, None)
+}
+
+/// List conflicted changes left behind by a session (its first change and everything
+/// after it), or anywhere in the repo if no session is given
+pub fn find_conflicts(session_id: Option<&str>) -> Result<Vec<String>> {
+    let from = match session_id {
+        Some(session_id) => jj::find_all_session_changes(session_id)?
+            .pop()
+            .ok_or_else(|| anyhow::anyhow!("No change found for session ID: {}", session_id))?,
+        None => "root()".to_string(),
+    };
+    jj::list_conflicts_in(&from, None)
+}
+
+/// Rewrite trailers left by older jjagent versions to the current keys
+/// Returns the change IDs of the rewritten commits
+pub fn migrate_trailers() -> Result<Vec<String>> {
+    jj::migrate_trailers_in(None)
+}
+
+/// Attribute all of a session's changes to a different session ID
+/// Returns the change IDs that were updated
+pub fn rename_session(old_id: &str, new_id: &str) -> Result<Vec<String>> {
+    jj::rename_session_in(old_id, new_id, None)
+}
+
+/// Filters applied by `jjagent list`
+/// Every filter that is set must match for a session to be listed
+#[derive(Debug, Default, Clone)]
+pub struct SessionListFilter {
+    /// Only list sessions that used this tool (matched case-insensitively)
+    pub tool: Option<String>,
+}
+
+/// List the Claude sessions in the repo that match the filter, newest first
+pub fn list_sessions(filter: &SessionListFilter) -> Result<Vec<jj::SessionSummary>> {
+    let sessions = jj::list_sessions_in(None)?;
+
+    if let Some(tool) = &filter.tool
+        && !sessions.is_empty()
+        && sessions.iter().all(|s| s.tools.is_empty())
+    {
+        anyhow::bail!(
+            "Cannot filter by tool '{}': no session has a Claude-tools trailer. \
+             Tool tracking is disabled - set JJAGENT_RECORD_TOOLS=1 in Claude Code's \
+             environment to capture tool names",
+            tool
+        );
+    }
+
+    Ok(sessions
+        .into_iter()
+        .filter(|s| match &filter.tool {
+            Some(tool) => s.tools.iter().any(|t| t.eq_ignore_ascii_case(tool)),
+            None => true,
+        })
+        .collect())
+}
+
+/// Collect the three-way view of every conflict in a session
+/// The reference can be a Claude session ID (all of its parts are checked) or a jj reference
+pub fn session_conflict_sides(reference: &str) -> Result<Vec<jj::ConflictedFile>> {
+    let session_changes = jj::find_all_session_changes(reference)?;
+    let revsets = if session_changes.is_empty() {
+        vec![reference.to_string()]
+    } else {
+        session_changes
+    };
+
+    let mut files = Vec::new();
+    for revset in &revsets {
+        files.extend(jj::get_conflict_sides(revset)?);
+    }
+    Ok(files)
+}
+
+/// Format conflicted files as labeled base / uwc side / session side sections
+/// Conflicts with more than two sides fall back to numbered labels
+pub fn format_conflict_sides(files: &[jj::ConflictedFile]) -> String {
+    let mut out = String::new();
+
+    for file in files {
+        for (i, hunk) in file.hunks.iter().enumerate() {
+            out.push_str(&format!(
+                "{} {} (conflict {} of {})\n",
+                file.change_id,
+                file.path,
+                i + 1,
+                file.hunks.len()
+            ));
+
+            let two_sided = hunk.sides.len() == 2 && hunk.bases.len() == 1;
+            for (j, base) in hunk.bases.iter().enumerate() {
+                let label = if two_sided {
+                    "base".to_string()
+                } else {
+                    format!("base #{}", j + 1)
+                };
+                push_section(&mut out, &label, base);
+            }
+            for (j, side) in hunk.sides.iter().enumerate() {
+                let label = match (two_sided, j) {
+                    (true, 0) => "uwc side".to_string(),
+                    (true, _) => "session side".to_string(),
+                    (false, _) => format!("side #{}", j + 1),
+                };
+                push_section(&mut out, &label, side);
+            }
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+fn push_section(out: &mut String, label: &str, content: &str) {
+    out.push_str(&format!("=== {} ===\n", label));
+    out.push_str(content);
+    if !content.is_empty() && !content.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// Set (or with value None, remove) a custom trailer on a session's change
+/// Refuses to touch the trailers jjagent uses to track sessions
+pub fn set_session_trailer(session_id: &str, key: &str, value: Option<&str>) -> Result<()> {
+    if session::is_reserved_trailer_key(key) {
+        anyhow::bail!(
+            "'{}' is reserved by jjagent for session tracking and can't be edited",
+            key
+        );
+    }
+    if key.is_empty() || key.contains(|c: char| c == ':' || c.is_whitespace()) {
+        anyhow::bail!(
+            "Invalid trailer key '{}': keys can't contain ':' or whitespace",
+            key
+        );
+    }
+    if value.is_some_and(|v| v.contains('\n')) {
+        anyhow::bail!("Trailer values can't span multiple lines");
+    }
+
+    let change_id = jj::find_session_change_anywhere(session_id)?
+        .context("No change found for session ID")?
+        .change_id;
+
+    match value {
+        Some(value) => jj::set_trailer_in(&change_id, key, value, None),
+        None => jj::remove_trailer_in(&change_id, key, None),
+    }
+}
+
+/// Move session changes that ended up above uwc back below it
+/// Returns the change IDs that were moved
+pub fn reflow_sessions() -> Result<Vec<String>> {
+    jj::reflow_uwc_above_sessions()
+}
+
+/// Order sessions chronologically (oldest first) by their most recent commit
+pub fn build_timeline(mut sessions: Vec<jj::SessionSummary>) -> Vec<jj::SessionSummary> {
+    sessions.sort_by_key(|s| s.committed_at);
+    sessions
+}
+
+/// Format a session timeline for reading, one line per session
+/// With by_day, sessions are grouped under a heading per (local) day
+pub fn format_timeline(sessions: &[jj::SessionSummary], by_day: bool) -> String {
+    let mut out = String::new();
+    let mut current_day = None;
+
+    for session in sessions {
+        let local = session
+            .committed_at
+            .map(|t| t.with_timezone(&chrono::Local));
+        let when = match (local, by_day) {
+            (Some(t), true) => {
+                let day = t.date_naive();
+                if current_day != Some(day) {
+                    if current_day.is_some() {
+                        out.push('\n');
+                    }
+                    out.push_str(&format!("{}\n", day.format("%Y-%m-%d")));
+                    current_day = Some(day);
+                }
+                format!("  {}", t.format("%H:%M"))
+            }
+            (Some(t), false) => t.format("%Y-%m-%d %H:%M").to_string(),
+            (None, _) => "unknown".to_string(),
+        };
+
+        let sid = session::SessionId::from_full(&session.session_id);
+        let parts = session.change_ids.len();
+        out.push_str(&format!(
+            "{}  {}  {} part{}  {} file{}{}  {}\n",
+            when,
+            sid.short(),
+            parts,
+            if parts == 1 { "" } else { "s" },
+            session.files.len(),
+            if session.files.len() == 1 { "" } else { "s" },
+            if session.has_conflict {
+                "  conflict"
+            } else {
+                ""
+            },
+            session.title
+        ));
+    }
+
+    out
+}
+
+/// Resolve the change the describe commands rewrite, refusing immutable ones
+/// Immutable changes are still looked up so the error says why they can't be described
+fn describable_session_change(session_id: &str) -> Result<String> {
+    let change_id = jj::find_session_change_including_immutable_in(session_id, None)?
+        .context("No change found for session ID")?
+        .change_id;
+    jj::ensure_mutable_in(&change_id, None)?;
+    Ok(change_id)
+}
+
 /// Update a session change's description while preserving trailers
 /// Looks up the change by session ID and updates its description with the new message
 /// while automatically preserving all existing trailers
 pub fn describe_session_change(session_id: &str, new_message: &str) -> Result<()> {
     // Find the change by session ID
-    let change_id =
-        jj::find_session_change_anywhere(session_id)?.context("No change found for session ID")?;
+    let change_id = describable_session_change(session_id)?;
 
     // Update the description while preserving trailers
     jj::update_description_preserving_trailers(&change_id, new_message)?;
@@ -96,6 +1365,72 @@ pub fn describe_session_change(session_id: &str, new_message: &str) -> Result<()
     Ok(())
 }
 
+/// Append a line to the description of a session's change, above its trailers
+pub fn append_session_description(session_id: &str, text: &str) -> Result<()> {
+    let change_id = describable_session_change(session_id)?;
+    jj::append_description_line_in(&change_id, text, None)
+}
+
+/// Edit the description of a session's change in $EDITOR (or $VISUAL)
+/// The editor starts with the current description minus trailers; the trailers are
+/// re-appended afterwards, so the session ID survives whatever is deleted
+pub fn edit_session_description(session_id: &str) -> Result<()> {
+    let change_id = describable_session_change(session_id)?;
+
+    let editor = ["EDITOR", "VISUAL"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.trim().is_empty())
+        .context("No editor found: set $EDITOR or $VISUAL, or pass the message with -m")?;
+
+    // The editor works on text, so a body with invalid UTF-8 would come back altered
+    let description = jj::get_commit_description_bytes_in(&change_id, None)?;
+    if std::str::from_utf8(&description).is_err() {
+        anyhow::bail!(
+            "The description of change {} is not valid UTF-8; use -m or --append instead of an editor",
+            change_id
+        );
+    }
+
+    let current = jj::get_description_without_trailers_in(&change_id, None)?;
+    let path = std::env::temp_dir().join(format!("jjagent-describe-{}.txt", std::process::id()));
+    std::fs::write(
+        &path,
+        format!(
+            "{}\n\nJJ: Describe session {}. Lines starting with \"JJ:\" are removed.\n\
+             JJ: Trailers (including Claude-session-id) are kept automatically.\n",
+            current, session_id
+        ),
+    )
+    .context("Failed to write description file")?;
+
+    // Run through the shell so editors with arguments (e.g. "code --wait") work
+    let status = std::process::Command::new("sh")
+        .args(["-c", &format!("{} \"$1\"", editor), "sh"])
+        .arg(&path)
+        .status();
+    let edited = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+
+    let status = status.with_context(|| format!("Failed to run editor '{}'", editor))?;
+    if !status.success() {
+        anyhow::bail!("Editor '{}' exited with {}", editor, status);
+    }
+
+    let message = edited
+        .context("Failed to read description file")?
+        .lines()
+        .filter(|line| !line.starts_with("JJ:"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let message = message.trim();
+    if message.is_empty() {
+        anyhow::bail!("Empty description, leaving the session change unchanged");
+    }
+
+    jj::update_description_preserving_trailers(&change_id, message)
+}
+
 /// Format a commit message for a session change
 /// If no custom message is provided, uses the default session message format
 /// If a custom message is provided, appends the Claude-session-id trailer
@@ -129,6 +1464,9 @@ struct WorkspaceInfo {
     current_dir: String,
 }
 
+/// jj template for the session change in the status line, unless JJAGENT_STATUSLINE_TEMPLATE is set
+pub const DEFAULT_STATUSLINE_TEMPLATE: &str = "format_commit_summary_with_refs(self, bookmarks)";
+
 /// Format jj session change info for status line
 /// Reads JSON input from stdin with session_id and workspace.current_dir
 /// Outputs the jj session change info part only (if in jj repo and session has a change)
@@ -143,40 +1481,39 @@ pub fn format_jj_statusline_info() -> Result<String> {
     let data: StatuslineInput = serde_json::from_str(&input)?;
 
     // Check if we're in a jj repo
-    let is_jj_repo = Command::new("jj")
-        .arg("--ignore-working-copy")
-        .arg("root")
-        .current_dir(&data.workspace.current_dir)
-        .output()
-        .map(|o| o.status.success())
-        .unwrap_or(false);
-
-    if !is_jj_repo {
+    let repo_path = Path::new(&data.workspace.current_dir);
+    if !jj::is_jj_repo_in(Some(repo_path)) {
         return Ok(String::new());
     }
 
     // Try to get the session change
-    let repo_path = Path::new(&data.workspace.current_dir);
     let change_id = match jj::find_session_change_anywhere_in(&data.session_id, Some(repo_path))
         .ok()
         .flatten()
     {
-        Some(id) => id,
+        Some(change) => change.change_id,
         None => return Ok(String::new()),
     };
 
     // Get formatted commit info with jj log
-    let jj_output = Command::new("jj")
-        .arg("log")
-        .arg("--ignore-working-copy")
-        .arg("--color=always")
-        .arg("--no-graph")
-        .arg("-r")
-        .arg(&change_id)
-        .arg("-T")
-        .arg("format_commit_summary_with_refs(self, bookmarks)")
-        .current_dir(&data.workspace.current_dir)
-        .output();
+    let custom_template = std::env::var("JJAGENT_STATUSLINE_TEMPLATE")
+        .ok()
+        .filter(|t| !t.trim().is_empty());
+    let template = custom_template
+        .clone()
+        .unwrap_or_else(|| DEFAULT_STATUSLINE_TEMPLATE.to_string());
+    let jj_output = run_jj(
+        jj_command()
+            .arg("log")
+            .arg("--ignore-working-copy")
+            .arg("--color=always")
+            .arg("--no-graph")
+            .arg("-r")
+            .arg(&change_id)
+            .arg("-T")
+            .arg(&template)
+            .current_dir(&data.workspace.current_dir),
+    );
 
     if let Ok(jj_output) = jj_output
         && jj_output.status.success()
@@ -185,9 +1522,37 @@ pub fn format_jj_statusline_info() -> Result<String> {
             .trim()
             .to_string();
         if !change_info.is_empty() {
-            return Ok(change_info);
+            // A custom template decides for itself what to show
+            if custom_template.is_some() {
+                return Ok(change_info);
+            }
+            let parts = jj::count_session_parts_in(&data.session_id, Some(repo_path)).unwrap_or(1);
+            let conflicts =
+                jj::count_conflicts_without_snapshot_in(&change_id, Some(repo_path)).unwrap_or(0);
+            return Ok(format!(
+                "{}{}",
+                change_info,
+                format_statusline_suffix(parts, conflicts)
+            ));
         }
     }
 
     Ok(String::new())
 }
+
+/// Suffix for the default statusline, e.g. " (pt.3 ⚠)" for a session with three parts and
+/// unresolved conflicts. Empty when there is one part and nothing is conflicted
+pub fn format_statusline_suffix(parts: usize, conflicts: usize) -> String {
+    let mut markers = Vec::new();
+    if parts > 1 {
+        markers.push(format!("pt.{}", parts));
+    }
+    if conflicts > 0 {
+        markers.push("⚠".to_string());
+    }
+    if markers.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", markers.join(" "))
+    }
+}
@@ -9,15 +9,20 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::fs::{File, OpenOptions};
-use std::io::{Read, Write};
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
+use crate::hooks::Verbosity;
+
 const LOCK_FILENAME: &str = "jjagent-wc.lock";
-const LOCK_TIMEOUT_SECS: u64 = 300; // 5 minutes
+const DEFAULT_LOCK_TIMEOUT_SECS: u64 = 300; // 5 minutes
 const INITIAL_RETRY_MS: u64 = 100;
 const MAX_RETRY_MS: u64 = 5000; // 5 seconds
 const PROGRESS_INTERVAL_SECS: u64 = 10;
+// A holder creates the lock file before writing its metadata, so a briefly
+// unparseable lock is normal. Only treat it as corrupt once it's this old
+const CORRUPT_LOCK_GRACE_SECS: u64 = 5;
 
 #[derive(Serialize, Deserialize, Debug)]
 struct LockMetadata {
@@ -47,8 +52,8 @@ impl LockMetadata {
     }
 }
 
-fn get_lock_path() -> PathBuf {
-    Path::new(".jj").join(LOCK_FILENAME)
+fn get_lock_path_in(repo_root: &Path) -> PathBuf {
+    repo_root.join(".jj").join(LOCK_FILENAME)
 }
 
 fn read_lock_holder(lock_path: &Path) -> Option<LockMetadata> {
@@ -58,13 +63,111 @@ fn read_lock_holder(lock_path: &Path) -> Option<LockMetadata> {
     serde_json::from_str(&contents).ok()
 }
 
+/// If the lock file exists but can't be parsed, return how long ago it was last written
+fn corrupt_lock_age(lock_path: &Path) -> Option<Duration> {
+    if read_lock_holder(lock_path).is_some() {
+        return None;
+    }
+    let modified = std::fs::metadata(lock_path).ok()?.modified().ok()?;
+    Some(modified.elapsed().unwrap_or_default())
+}
+
+/// Who holds the working copy lock, as reported by `jjagent status` and `doctor`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockInfo {
+    pub pid: u32,
+    pub session_id: String,
+    /// Unix timestamp
+    pub acquired_at: u64,
+    pub age_seconds: u64,
+}
+
+/// Read the holder of the working copy lock for the repo rooted at `repo_root`
+/// Returns None when the lock is free, and errors if the lock file can't be parsed
+pub fn current_holder(repo_root: &Path) -> Result<Option<LockInfo>> {
+    current_holder_at(&get_lock_path_in(repo_root))
+}
+
+fn current_holder_at(lock_path: &Path) -> Result<Option<LockInfo>> {
+    if !lock_path.exists() {
+        return Ok(None);
+    }
+    let holder = read_lock_holder(lock_path)
+        .with_context(|| format!("Lock file {} exists but can't be read", lock_path.display()))?;
+    Ok(Some(LockInfo {
+        age_seconds: holder.age_seconds(),
+        pid: holder.pid,
+        session_id: holder.session_id,
+        acquired_at: holder.acquired_at,
+    }))
+}
+
+/// How long to wait for the lock, and how old a lock must be to count as stale
+/// Configurable via JJAGENT_LOCK_TIMEOUT_SECS (default: 300s)
+fn lock_timeout_secs() -> u64 {
+    lock_timeout_from(std::env::var("JJAGENT_LOCK_TIMEOUT_SECS").ok().as_deref())
+}
+
+/// Parse a JJAGENT_LOCK_TIMEOUT_SECS value, falling back to the default when it's unset
+/// or not a number
+fn lock_timeout_from(value: Option<&str>) -> u64 {
+    value
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_LOCK_TIMEOUT_SECS)
+}
+
+/// Whether a lock held for `age_secs` is older than the lock timeout
+pub fn is_stale(age_secs: u64) -> bool {
+    age_secs > lock_timeout_secs()
+}
+
+/// Whether locking is turned off with JJAGENT_NO_LOCK=1, for setups that never run
+/// sessions concurrently. Every hook reads the same variable, so acquire and release
+/// are skipped together
+pub fn lock_disabled() -> bool {
+    std::env::var("JJAGENT_NO_LOCK").unwrap_or_default() == "1"
+}
+
+/// Whether an IO error means .jj can't be written at all (e.g. a read-only checkout)
+/// Such a checkout can't have concurrent writers, so the lock is skipped instead of failing
+fn is_unwritable(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem
+    )
+}
+
+fn warn_lock_skipped(error: &io::Error) {
+    eprintln!(
+        "jjagent: Warning - .jj is not writable ({}), proceeding without the working copy lock",
+        error
+    );
+}
+
 /// Acquire the working copy lock in PreToolUse hook
+/// Does nothing when locking is disabled, and only warns when .jj isn't writable
 pub fn acquire_lock(session_id: &str) -> Result<()> {
-    let lock_path = get_lock_path();
+    acquire_lock_in(session_id, Path::new(""), Verbosity::from_env())
+}
 
-    std::fs::create_dir_all(".jj").context("Failed to create .jj directory")?;
+/// Acquire the working copy lock for the repo rooted at `repo_root`
+pub fn acquire_lock_in(session_id: &str, repo_root: &Path, verbosity: Verbosity) -> Result<()> {
+    if lock_disabled() {
+        return Ok(());
+    }
+    if let Err(e) = std::fs::create_dir_all(repo_root.join(".jj")) {
+        if is_unwritable(&e) {
+            warn_lock_skipped(&e);
+            return Ok(());
+        }
+        return Err(e).context("Failed to create .jj directory");
+    }
+    acquire_lock_at(&get_lock_path_in(repo_root), session_id, verbosity)
+}
 
-    let timeout = Duration::from_secs(LOCK_TIMEOUT_SECS);
+fn acquire_lock_at(lock_path: &Path, session_id: &str, verbosity: Verbosity) -> Result<()> {
+    let timeout_secs = lock_timeout_secs();
+    let timeout = Duration::from_secs(timeout_secs);
     let start = Instant::now();
     let mut retry_delay = Duration::from_millis(INITIAL_RETRY_MS);
     let mut last_progress = Instant::now();
@@ -74,7 +177,7 @@ pub fn acquire_lock(session_id: &str) -> Result<()> {
         match OpenOptions::new()
             .create_new(true) // Fails if file exists (atomic operation)
             .write(true)
-            .open(&lock_path)
+            .open(lock_path)
         {
             Ok(mut file) => {
                 // Write lock metadata
@@ -82,29 +185,55 @@ pub fn acquire_lock(session_id: &str) -> Result<()> {
                 file.write_all(serde_json::to_string(&metadata)?.as_bytes())?;
                 file.sync_all()?;
 
-                eprintln!(
-                    "jjagent: Acquired working copy lock (session {})",
+                verbosity.info(format_args!(
+                    "Acquired working copy lock (session {})",
                     &session_id[..8.min(session_id.len())]
-                );
+                ));
+                return Ok(());
+            }
+            Err(e) if is_unwritable(&e) => {
+                warn_lock_skipped(&e);
+                return Ok(());
+            }
+            // A session re-entering PreToolUse (e.g. after an interrupted tool call) already
+            // holds the lock, and would otherwise wait on itself until the timeout
+            Err(_) if read_lock_holder(lock_path).is_some_and(|m| m.session_id == session_id) => {
+                verbosity.info(format_args!(
+                    "Working copy lock already held (session {})",
+                    &session_id[..8.min(session_id.len())]
+                ));
                 return Ok(());
             }
             Err(_) if start.elapsed() < timeout => {
                 // Check if lock is stale and can be stolen
-                if let Some(metadata) = read_lock_holder(&lock_path)
-                    && metadata.age_seconds() > LOCK_TIMEOUT_SECS
+                if let Some(metadata) = read_lock_holder(lock_path)
+                    && metadata.age_seconds() > timeout_secs
                 {
                     eprintln!(
                         "jjagent: Lock is stale ({:.0}s old), attempting to steal it",
                         metadata.age_seconds()
                     );
                     // Try to remove stale lock
-                    if std::fs::remove_file(&lock_path).is_ok() {
+                    if std::fs::remove_file(lock_path).is_ok() {
+                        continue; // Try to acquire again immediately
+                    }
+                }
+
+                // A corrupt lock has no holder to wait for, so replace it after the grace period
+                if let Some(age) = corrupt_lock_age(lock_path)
+                    && age > Duration::from_secs(CORRUPT_LOCK_GRACE_SECS)
+                {
+                    eprintln!(
+                        "jjagent: Warning - lock file is corrupt ({:.0}s old), replacing it",
+                        age.as_secs_f64()
+                    );
+                    if std::fs::remove_file(lock_path).is_ok() {
                         continue; // Try to acquire again immediately
                     }
                 }
 
                 if last_progress.elapsed() >= Duration::from_secs(PROGRESS_INTERVAL_SECS) {
-                    let holder = read_lock_holder(&lock_path);
+                    let holder = read_lock_holder(lock_path);
                     eprintln!(
                         "jjagent: Waiting for working copy lock... ({:.0}s elapsed){}",
                         start.elapsed().as_secs_f64(),
@@ -124,7 +253,7 @@ pub fn acquire_lock(session_id: &str) -> Result<()> {
                 retry_delay = std::cmp::min(retry_delay * 2, Duration::from_millis(MAX_RETRY_MS));
             }
             Err(e) => {
-                let holder = read_lock_holder(&lock_path);
+                let holder = read_lock_holder(lock_path);
                 let holder_info = holder
                     .as_ref()
                     .map(|m| {
@@ -152,20 +281,32 @@ pub fn acquire_lock(session_id: &str) -> Result<()> {
 }
 
 /// Release the working copy lock in PostToolUse/Stop hook
+/// Does nothing when locking is disabled, since no lock was taken, and tolerates a lock
+/// that was never acquired because .jj isn't writable
 pub fn release_lock(session_id: &str) -> Result<()> {
-    let lock_path = get_lock_path();
+    release_lock_in(session_id, Path::new(""), Verbosity::from_env())
+}
+
+/// Release the working copy lock for the repo rooted at `repo_root`
+pub fn release_lock_in(session_id: &str, repo_root: &Path, verbosity: Verbosity) -> Result<()> {
+    if lock_disabled() {
+        return Ok(());
+    }
+    release_lock_at(&get_lock_path_in(repo_root), session_id, verbosity)
+}
 
+fn release_lock_at(lock_path: &Path, session_id: &str, verbosity: Verbosity) -> Result<()> {
     if !lock_path.exists() {
         // Lock already released or never acquired - not an error
-        eprintln!(
-            "jjagent: Lock already released or not held (session {})",
+        verbosity.info(format_args!(
+            "Lock already released or not held (session {})",
             &session_id[..8.min(session_id.len())]
-        );
+        ));
         return Ok(());
     }
 
     // Read and verify ownership
-    if let Some(metadata) = read_lock_holder(&lock_path) {
+    if let Some(metadata) = read_lock_holder(lock_path) {
         if metadata.session_id != session_id {
             anyhow::bail!(
                 "Lock ownership mismatch!\n\
@@ -178,21 +319,34 @@ pub fn release_lock(session_id: &str) -> Result<()> {
         }
 
         let age = metadata.age_seconds();
-        if age > LOCK_TIMEOUT_SECS {
+        if is_stale(age) {
             eprintln!(
                 "jjagent: Warning - lock is stale ({:.1}m old)",
                 age as f64 / 60.0
             );
         }
+    } else {
+        eprintln!(
+            "jjagent: Warning - lock file is corrupt, removing it without an ownership check"
+        );
     }
 
     // Delete lock file to release
-    std::fs::remove_file(&lock_path).context("Failed to remove lock file")?;
+    if let Err(e) = std::fs::remove_file(lock_path) {
+        if is_unwritable(&e) {
+            eprintln!(
+                "jjagent: Warning - .jj is not writable ({}), leaving the lock file in place",
+                e
+            );
+            return Ok(());
+        }
+        return Err(e).context("Failed to remove lock file");
+    }
 
-    eprintln!(
-        "jjagent: Released working copy lock (session {})",
+    verbosity.info(format_args!(
+        "Released working copy lock (session {})",
         &session_id[..8.min(session_id.len())]
-    );
+    ));
     Ok(())
 }
 
@@ -218,13 +372,118 @@ mod tests {
         assert!(age < 2, "Age should be less than 2 seconds, got {}", age);
     }
 
+    #[test]
+    fn test_lock_timeout_from() {
+        assert_eq!(lock_timeout_from(Some("900")), 900);
+        assert_eq!(lock_timeout_from(Some(" 60\n")), 60);
+
+        // Unset or unparseable values fall back to the default
+        assert_eq!(lock_timeout_from(Some("soon")), DEFAULT_LOCK_TIMEOUT_SECS);
+        assert_eq!(lock_timeout_from(None), DEFAULT_LOCK_TIMEOUT_SECS);
+    }
+
     #[test]
     fn test_lock_path() {
-        let path = get_lock_path();
+        let path = get_lock_path_in(Path::new(""));
         assert!(path.to_str().unwrap().ends_with("jjagent-wc.lock"));
         assert!(path.to_str().unwrap().contains(".jj"));
     }
 
+    #[test]
+    fn test_current_holder() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_path = temp_dir.path().join(LOCK_FILENAME);
+        assert_eq!(current_holder_at(&lock_path).unwrap(), None);
+
+        acquire_lock_at(&lock_path, "holder-session", Verbosity::Normal).unwrap();
+        let info = current_holder_at(&lock_path).unwrap().unwrap();
+        assert_eq!(info.session_id, "holder-session");
+        assert_eq!(info.pid, std::process::id());
+        assert!(info.acquired_at > 0);
+        assert!(info.age_seconds < 2);
+
+        std::fs::write(&lock_path, "not json").unwrap();
+        assert!(current_holder_at(&lock_path).is_err());
+    }
+
+    #[test]
+    fn test_same_session_reacquires_its_own_lock() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_path = temp_dir.path().join(LOCK_FILENAME);
+
+        acquire_lock_at(&lock_path, "own-session", Verbosity::Normal).unwrap();
+        let start = Instant::now();
+        acquire_lock_at(&lock_path, "own-session", Verbosity::Normal).unwrap();
+        assert!(start.elapsed() < Duration::from_millis(INITIAL_RETRY_MS));
+
+        // Still a single holder, released in one go
+        release_lock_at(&lock_path, "own-session", Verbosity::Normal).unwrap();
+        assert_eq!(current_holder_at(&lock_path).unwrap(), None);
+    }
+
+    #[test]
+    fn test_corrupt_lock_is_replaced_after_grace_period() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_path = temp_dir.path().join(LOCK_FILENAME);
+
+        // A truncated write left garbage behind a while ago
+        std::fs::write(&lock_path, "{\"pid\": 12").unwrap();
+        let old = std::time::SystemTime::now() - Duration::from_secs(60);
+        File::options()
+            .write(true)
+            .open(&lock_path)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        assert!(corrupt_lock_age(&lock_path).unwrap() >= Duration::from_secs(60));
+
+        let start = Instant::now();
+        acquire_lock_at(&lock_path, "test-session-corrupt", Verbosity::Normal).unwrap();
+        assert!(
+            start.elapsed() < Duration::from_secs(DEFAULT_LOCK_TIMEOUT_SECS),
+            "Corrupt lock should be replaced, not waited out"
+        );
+
+        let metadata = read_lock_holder(&lock_path).unwrap();
+        assert_eq!(metadata.session_id, "test-session-corrupt");
+
+        release_lock_at(&lock_path, "test-session-corrupt", Verbosity::Normal).unwrap();
+        assert!(!lock_path.exists());
+    }
+
+    #[test]
+    fn test_fresh_lock_is_not_corrupt() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_path = temp_dir.path().join(LOCK_FILENAME);
+
+        assert!(corrupt_lock_age(&lock_path).is_none());
+
+        let metadata = LockMetadata::new("test-session-valid".to_string());
+        std::fs::write(&lock_path, serde_json::to_string(&metadata).unwrap()).unwrap();
+        assert!(corrupt_lock_age(&lock_path).is_none());
+    }
+
+    #[test]
+    fn test_unwritable_errors_skip_the_lock() {
+        assert!(is_unwritable(&io::Error::from(
+            io::ErrorKind::PermissionDenied
+        )));
+        assert!(is_unwritable(&io::Error::from(
+            io::ErrorKind::ReadOnlyFilesystem
+        )));
+        assert!(!is_unwritable(&io::Error::from(
+            io::ErrorKind::AlreadyExists
+        )));
+        assert!(!is_unwritable(&io::Error::from(io::ErrorKind::NotFound)));
+    }
+
+    #[test]
+    fn test_release_of_never_acquired_lock_is_ok() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_path = temp_dir.path().join(LOCK_FILENAME);
+        assert!(release_lock_at(&lock_path, "never-acquired", Verbosity::Normal).is_ok());
+    }
+
     #[test]
     fn test_lock_persistence_between_acquire_and_release() {
         // Create a temporary directory for testing
@@ -238,7 +497,7 @@ mod tests {
         acquire_lock(session_id).unwrap();
 
         // Verify the lock file exists
-        let lock_path = get_lock_path();
+        let lock_path = get_lock_path_in(Path::new(""));
         assert!(lock_path.exists(), "Lock file should exist after acquire");
 
         // Verify lock metadata is correct
@@ -252,7 +511,7 @@ mod tests {
             OpenOptions::new()
                 .create_new(true)
                 .write(true)
-                .open(get_lock_path())
+                .open(get_lock_path_in(Path::new("")))
                 .is_ok()
         })
         .join()
@@ -3,11 +3,16 @@ use chrono::Utc;
 use serde::Serialize;
 use std::env;
 use std::fs::{self, OpenOptions};
-use std::io::Write;
-use std::path::PathBuf;
-use std::process::Command;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::sync::{Mutex, OnceLock};
 
+use crate::jj::{jj_command, run_jj};
+
+const DEFAULT_LOG_MAX_BYTES: u64 = 10 * 1024 * 1024; // 10MB
+/// Rotated logs kept next to the current one: jjagent.jsonl.1 (newest) to jjagent.jsonl.3
+const ROTATED_LOG_FILES: usize = 3;
+
 /// Global logger instance
 static LOGGER: OnceLock<Logger> = OnceLock::new();
 
@@ -16,10 +21,40 @@ pub fn logger() -> &'static Logger {
     LOGGER.get_or_init(Logger::new)
 }
 
+/// How important a log entry is. Entries below JJAGENT_LOG_LEVEL (default: info) are skipped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    /// Routine events, e.g. a hook starting
+    Debug,
+    /// Outcomes, e.g. a hook's result
+    Info,
+    Warn,
+    /// Failures and conflicts
+    Error,
+}
+
+impl LogLevel {
+    /// Parse a JJAGENT_LOG_LEVEL value
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "debug" => Ok(Self::Debug),
+            "info" => Ok(Self::Info),
+            "warn" => Ok(Self::Warn),
+            "error" => Ok(Self::Error),
+            other => anyhow::bail!(
+                "Unknown JJAGENT_LOG_LEVEL '{}' (expected error, warn, info or debug)",
+                other
+            ),
+        }
+    }
+}
+
 /// Log entry structure for JSONL output
 #[derive(Debug, Serialize)]
 pub struct LogEntry {
     timestamp: String,
+    level: LogLevel,
     event: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     session_id: Option<String>,
@@ -44,6 +79,10 @@ pub struct LogEntry {
 /// Logger instance that writes to a JSONL file
 pub struct Logger {
     file_path: Option<PathBuf>,
+    /// Size past which the log is rotated before the next write
+    max_bytes: u64,
+    /// Entries below this level are skipped
+    level: LogLevel,
     mutex: Mutex<()>,
 }
 
@@ -64,8 +103,24 @@ impl Logger {
             None
         };
 
+        // Configurable via JJAGENT_LOG_MAX_BYTES (default: 10MB)
+        let max_bytes = env::var("JJAGENT_LOG_MAX_BYTES")
+            .ok()
+            .and_then(|s| s.trim().parse::<u64>().ok())
+            .unwrap_or(DEFAULT_LOG_MAX_BYTES);
+
+        let level = match env::var("JJAGENT_LOG_LEVEL") {
+            Ok(value) if file_path.is_some() => LogLevel::parse(&value).unwrap_or_else(|e| {
+                eprintln!("jjagent: Warning - {}, using info", e);
+                LogLevel::Info
+            }),
+            _ => LogLevel::Info,
+        };
+
         Logger {
             file_path,
+            max_bytes,
+            level,
             mutex: Mutex::new(()),
         }
     }
@@ -99,6 +154,9 @@ impl Logger {
         let Some(ref path) = self.file_path else {
             return Ok(());
         };
+        if entry.level < self.level {
+            return Ok(());
+        }
 
         // Ensure the directory exists
         if let Some(parent) = path.parent() {
@@ -128,6 +186,9 @@ impl Logger {
         // Lock to ensure thread-safe writes
         let _guard = self.mutex.lock().unwrap();
 
+        // A failed rotation shouldn't cost the entry, so keep appending to the full log
+        let _ = rotate_if_full(path, self.max_bytes);
+
         let mut file = OpenOptions::new().create(true).append(true).open(path)?;
 
         writeln!(file, "{}", json)?;
@@ -158,6 +219,7 @@ impl Logger {
 
         let entry = LogEntry {
             timestamp: Utc::now().to_rfc3339(),
+            level: LogLevel::Debug,
             event: format!("hook:{}", hook_name),
             session_id: session_id.map(String::from),
             cwd: None,
@@ -184,13 +246,14 @@ impl Logger {
             return;
         }
 
-        let (result_str, error_msg) = match result {
-            Ok(_) => ("success".to_string(), None),
-            Err(e) => ("error".to_string(), Some(e.to_string())),
+        let (level, result_str, error_msg) = match result {
+            Ok(_) => (LogLevel::Info, "success".to_string(), None),
+            Err(e) => (LogLevel::Error, "error".to_string(), Some(e.to_string())),
         };
 
         let entry = LogEntry {
             timestamp: Utc::now().to_rfc3339(),
+            level,
             event: format!("hook:{}:result", hook_name),
             session_id: session_id.map(String::from),
             cwd: None,
@@ -219,6 +282,7 @@ impl Logger {
 
         let entry = LogEntry {
             timestamp: Utc::now().to_rfc3339(),
+            level: LogLevel::Debug,
             event: format!("session:{}", command),
             session_id: session_id.map(String::from),
             cwd: None,
@@ -245,13 +309,14 @@ impl Logger {
             return;
         }
 
-        let (result_str, error_msg) = match result {
-            Ok(_) => ("success".to_string(), None),
-            Err(e) => ("error".to_string(), Some(e.to_string())),
+        let (level, result_str, error_msg) = match result {
+            Ok(_) => (LogLevel::Info, "success".to_string(), None),
+            Err(e) => (LogLevel::Error, "error".to_string(), Some(e.to_string())),
         };
 
         let entry = LogEntry {
             timestamp: Utc::now().to_rfc3339(),
+            level,
             event: format!("session:{}:result", command),
             session_id: session_id.map(String::from),
             cwd: None,
@@ -267,6 +332,66 @@ impl Logger {
         let _ = self.log(entry);
     }
 
+    /// Log a squash that introduced conflicts and was split into a new session part
+    pub fn log_conflict(
+        &self,
+        session_id: &str,
+        part: usize,
+        conflicts_before: usize,
+        conflicts_after: usize,
+        conflicted_files: &[String],
+    ) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let entry = LogEntry {
+            timestamp: Utc::now().to_rfc3339(),
+            level: LogLevel::Error,
+            event: "conflict".to_string(),
+            session_id: Some(session_id.to_string()),
+            cwd: None,
+            jj_change_id: None,
+            commit_id: None,
+            tool_name: None,
+            prompt_preview: None,
+            result: Some(format!("pt. {}", part)),
+            error_message: None,
+            details: Some(serde_json::json!({
+                "part": part,
+                "conflicts_before": conflicts_before,
+                "conflicts_after": conflicts_after,
+                "conflicted_files": conflicted_files,
+            })),
+        };
+
+        let _ = self.log(entry);
+    }
+
+    /// Log a jj command a hook skipped because JJAGENT_DRY_RUN=1
+    pub fn log_dry_run(&self, hook: &str, session_id: &str, command: &[String]) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let entry = LogEntry {
+            timestamp: Utc::now().to_rfc3339(),
+            level: LogLevel::Info,
+            event: format!("hook:{}:dry_run", hook),
+            session_id: Some(session_id.to_string()),
+            cwd: None,
+            jj_change_id: None,
+            commit_id: None,
+            tool_name: None,
+            prompt_preview: None,
+            result: Some("skipped".to_string()),
+            error_message: None,
+            details: Some(serde_json::json!({ "command": command })),
+        };
+
+        let _ = self.log(entry);
+    }
+
     /// Log an error with context
     pub fn log_error(&self, error: &anyhow::Error, context: &str) {
         if !self.is_enabled() {
@@ -275,6 +400,7 @@ impl Logger {
 
         let entry = LogEntry {
             timestamp: Utc::now().to_rfc3339(),
+            level: LogLevel::Error,
             event: "error".to_string(),
             session_id: None,
             cwd: None,
@@ -291,11 +417,34 @@ impl Logger {
     }
 }
 
+/// Move a log that has grown past max_bytes to `<path>.1`, shifting older rotations up
+/// and dropping the oldest, so the next write starts a fresh file
+fn rotate_if_full(path: &Path, max_bytes: u64) -> io::Result<()> {
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.len() > max_bytes => {}
+        _ => return Ok(()),
+    }
+
+    let _ = fs::remove_file(rotated_log_path(path, ROTATED_LOG_FILES));
+    for n in (1..ROTATED_LOG_FILES).rev() {
+        let from = rotated_log_path(path, n);
+        if from.exists() {
+            fs::rename(&from, rotated_log_path(path, n + 1))?;
+        }
+    }
+    fs::rename(path, rotated_log_path(path, 1))
+}
+
+/// The path of the nth rotated log, e.g. jjagent.jsonl.1
+fn rotated_log_path(path: &Path, n: usize) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(format!(".{}", n));
+    PathBuf::from(rotated)
+}
+
 /// Get the current jj change ID
 fn get_jj_change_id() -> Result<String> {
-    let output = Command::new("jj")
-        .args(["log", "-r", "@", "--no-graph", "-T", "change_id"])
-        .output()?;
+    let output = run_jj(jj_command().args(["log", "-r", "@", "--no-graph", "-T", "change_id"]))?;
 
     if !output.status.success() {
         anyhow::bail!("Failed to get jj change_id");
@@ -306,9 +455,7 @@ fn get_jj_change_id() -> Result<String> {
 
 /// Get the current commit ID (git SHA equivalent)
 fn get_commit_id() -> Result<String> {
-    let output = Command::new("jj")
-        .args(["log", "-r", "@", "--no-graph", "-T", "commit_id"])
-        .output()?;
+    let output = run_jj(jj_command().args(["log", "-r", "@", "--no-graph", "-T", "commit_id"]))?;
 
     if !output.status.success() {
         anyhow::bail!("Failed to get commit_id");
@@ -357,6 +504,7 @@ mod tests {
 
         let entry = LogEntry {
             timestamp: Utc::now().to_rfc3339(),
+            level: LogLevel::Info,
             event: "test".to_string(),
             session_id: Some("test-session".to_string()),
             cwd: Some("/test/cwd".to_string()),
@@ -384,8 +532,10 @@ mod tests {
     fn test_log_hook() {
         let temp_dir = TempDir::new().unwrap();
         let log_path = temp_dir.path().join("hooks.jsonl");
+        // Hook starts are debug entries, skipped at the default level
         unsafe {
             env::set_var("JJAGENT_LOG_FILE", log_path.to_str().unwrap());
+            env::set_var("JJAGENT_LOG_LEVEL", "debug");
         }
 
         let logger = Logger::new();
@@ -403,6 +553,155 @@ mod tests {
 
         unsafe {
             env::remove_var("JJAGENT_LOG_FILE");
+            env::remove_var("JJAGENT_LOG_LEVEL");
         }
     }
+
+    #[test]
+    fn test_log_conflict() {
+        let temp_dir = TempDir::new().unwrap();
+        let log_path = temp_dir.path().join("conflicts.jsonl");
+        let logger = Logger {
+            file_path: Some(log_path.clone()),
+            max_bytes: DEFAULT_LOG_MAX_BYTES,
+            level: LogLevel::Info,
+            mutex: Mutex::new(()),
+        };
+
+        logger.log_conflict("session-123", 2, 0, 1, &["src/main.rs".to_string()]);
+
+        let content = fs::read_to_string(&log_path).unwrap();
+        let entry: serde_json::Value = serde_json::from_str(content.trim()).unwrap();
+        assert_eq!(entry["event"], "conflict");
+        assert_eq!(entry["session_id"], "session-123");
+        assert_eq!(
+            entry["details"],
+            serde_json::json!({
+                "part": 2,
+                "conflicts_before": 0,
+                "conflicts_after": 1,
+                "conflicted_files": ["src/main.rs"],
+            })
+        );
+    }
+
+    #[test]
+    fn test_log_dry_run() {
+        let temp_dir = TempDir::new().unwrap();
+        let log_path = temp_dir.path().join("dry_run.jsonl");
+        let logger = Logger {
+            file_path: Some(log_path.clone()),
+            max_bytes: DEFAULT_LOG_MAX_BYTES,
+            level: LogLevel::Info,
+            mutex: Mutex::new(()),
+        };
+
+        logger.log_dry_run(
+            "PreToolUse",
+            "session-123",
+            &["new".to_string(), "-m".to_string(), "precommit".to_string()],
+        );
+
+        let content = fs::read_to_string(&log_path).unwrap();
+        let entry: serde_json::Value = serde_json::from_str(content.trim()).unwrap();
+        assert_eq!(entry["event"], "hook:PreToolUse:dry_run");
+        assert_eq!(entry["result"], "skipped");
+        assert_eq!(
+            entry["details"]["command"],
+            serde_json::json!(["new", "-m", "precommit"])
+        );
+    }
+
+    #[test]
+    fn test_log_rotates_past_max_bytes() {
+        let temp_dir = TempDir::new().unwrap();
+        let log_path = temp_dir.path().join("rotate.jsonl");
+        let logger = Logger {
+            file_path: Some(log_path.clone()),
+            max_bytes: 300,
+            level: LogLevel::Info,
+            mutex: Mutex::new(()),
+        };
+        let entry = |n: usize| LogEntry {
+            timestamp: Utc::now().to_rfc3339(),
+            level: LogLevel::Info,
+            event: format!("test:{}", n),
+            session_id: Some("rotate-session".to_string()),
+            cwd: Some("/test/cwd".to_string()),
+            jj_change_id: Some("abc123".to_string()),
+            commit_id: Some("def456".to_string()),
+            tool_name: None,
+            prompt_preview: None,
+            result: None,
+            error_message: None,
+            details: None,
+        };
+
+        // Each entry is ~170 bytes, so the file passes 300 bytes every two writes
+        for n in 0..3 {
+            logger.log(entry(n)).unwrap();
+        }
+        let rotated = rotated_log_path(&log_path, 1);
+        assert!(rotated.exists(), "expected {} to exist", rotated.display());
+        let current = fs::read_to_string(&log_path).unwrap();
+        assert_eq!(current.lines().count(), 1);
+        assert!(current.contains("test:2"));
+        assert!(fs::read_to_string(&rotated).unwrap().contains("test:0"));
+
+        // Only the three newest rotations are kept
+        for n in 3..20 {
+            logger.log(entry(n)).unwrap();
+        }
+        assert!(rotated_log_path(&log_path, 3).exists());
+        assert!(!rotated_log_path(&log_path, 4).exists());
+        assert!(
+            fs::read_to_string(rotated_log_path(&log_path, 1))
+                .unwrap()
+                .contains("test:17")
+        );
+    }
+
+    #[test]
+    fn test_log_level_skips_routine_events() {
+        let temp_dir = TempDir::new().unwrap();
+        let log_path = temp_dir.path().join("levels.jsonl");
+        let logger_at = |level| Logger {
+            file_path: Some(log_path.clone()),
+            max_bytes: DEFAULT_LOG_MAX_BYTES,
+            level,
+            mutex: Mutex::new(()),
+        };
+
+        // At the default level, hook starts are skipped but results are kept
+        let logger = logger_at(LogLevel::Info);
+        logger.log_hook("PreToolUse", Some("session-123"), None, None);
+        logger.log_hook_result("PreToolUse", Some("session-123"), Ok(()));
+        let content = fs::read_to_string(&log_path).unwrap();
+        assert_eq!(content.lines().count(), 1, "got: {}", content);
+        assert!(content.contains(r#""level":"info""#), "got: {}", content);
+        assert!(content.contains("hook:PreToolUse:result"));
+
+        // At error, only failures are written
+        let logger = logger_at(LogLevel::Error);
+        logger.log_hook_result("PostToolUse", Some("session-123"), Ok(()));
+        logger.log_hook_result("PostToolUse", Some("session-123"), Err("boom"));
+        let content = fs::read_to_string(&log_path).unwrap();
+        assert_eq!(content.lines().count(), 2, "got: {}", content);
+        assert!(content.contains("boom"));
+
+        // At debug, everything is written
+        let logger = logger_at(LogLevel::Debug);
+        logger.log_hook("Stop", Some("session-123"), None, None);
+        let content = fs::read_to_string(&log_path).unwrap();
+        assert_eq!(content.lines().count(), 3, "got: {}", content);
+        assert!(content.contains(r#""level":"debug""#));
+    }
+
+    #[test]
+    fn test_log_level_parse() {
+        assert_eq!(LogLevel::parse("debug").unwrap(), LogLevel::Debug);
+        assert_eq!(LogLevel::parse(" WARN ").unwrap(), LogLevel::Warn);
+        assert!(LogLevel::parse("verbose").is_err());
+        assert!(LogLevel::Debug < LogLevel::Info && LogLevel::Warn < LogLevel::Error);
+    }
 }
@@ -1,11 +1,16 @@
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+use anyhow::{Context, Result};
+use clap::{CommandFactory, Parser, Subcommand};
 use std::env;
 
 #[derive(Parser)]
 #[command(name = "jjagent")]
+#[command(version)]
 #[command(about = "JJ Claude Code - Manage jj changesets for Claude sessions")]
 struct Cli {
+    /// Don't print informational messages (e.g. "hook called") to stderr; warnings and
+    /// errors still show. Same as JJAGENT_QUIET=1
+    #[arg(short, long, global = true)]
+    quiet: bool,
     #[command(subcommand)]
     command: Commands,
 }
@@ -15,13 +20,65 @@ enum Commands {
     /// Claude Code integration
     #[command(subcommand, alias = "c")]
     Claude(ClaudeCommands),
+    /// Work with the changes belonging to a Claude session
+    #[command(subcommand, alias = "s")]
+    Session(SessionCommands),
+    /// List every Claude session in the repo with its parts
+    List(ListArgs),
+    /// Print jjagent and jj versions, executable path, OS and config for bug reports
+    Info,
+    /// Check that jj is installed and the repo is in a state the hooks can work with
+    Doctor,
+    /// Print a shell completion script to stdout
+    Completions {
+        /// The shell to generate completions for
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+    /// Show whether @ is a precommit or session change, who holds the lock, and the session's change
+    Status {
+        /// The Claude session ID (defaults to the one on @)
+        #[arg(long, value_name = "SESSION_ID")]
+        session_id: Option<String>,
+    },
+    /// Print the lock holder and the session on @ whenever either changes, until Ctrl-C
+    Watch {
+        /// How often to check, in milliseconds
+        #[arg(long, value_name = "MS", default_value_t = 500)]
+        interval_ms: u64,
+    },
+    /// Abandon every change belonging to a session, leaving @ in place
+    Abandon {
+        /// The Claude session ID
+        #[arg(value_name = "SESSION_ID")]
+        session_id: String,
+        /// Print the changes that would be abandoned without abandoning them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Abandon precommits left behind by interrupted tool calls
+    Gc {
+        /// Only abandon non-empty precommits at least this many seconds old
+        #[arg(long, value_name = "SECONDS", default_value_t = 3600)]
+        older_than: u64,
+        /// Print the precommits that would be abandoned without abandoning them
+        #[arg(long)]
+        dry_run: bool,
+    },
     /// Split a change into a new session part before @
     Split {
         /// The Claude session ID or jj reference to split (e.g., session ID, change ID, or revset)
         #[arg(value_name = "SESSION_ID_OR_REF")]
         reference: String,
+        /// Description for the new part (the Claude-session-id trailer is added automatically)
+        #[arg(short, long, value_name = "MESSAGE")]
+        message: Option<String>,
+        /// Print the created part as JSON
+        #[arg(long)]
+        json: bool,
     },
-    /// Choose the change where this session will be squashed into
+    /// Choose the change where this session will be squashed into and print its change ID
+    #[command(alias = "move")]
     Into {
         /// The Claude session ID
         #[arg(value_name = "SESSION_ID")]
@@ -30,12 +87,96 @@ enum Commands {
         #[arg(value_name = "REF")]
         reference: String,
     },
+    /// Remove jjagent's hooks from a Claude settings file
+    Uninstall {
+        /// The Claude settings file (e.g. .claude/settings.json)
+        #[arg(value_name = "FILE")]
+        path: std::path::PathBuf,
+    },
     /// Get the jj change ID for a Claude session
     #[command(name = "change-id")]
     ChangeId {
         /// The Claude session ID
         #[arg(value_name = "SESSION_ID")]
         session_id: String,
+        /// Print every change for the session (all parts), oldest first
+        #[arg(long)]
+        all: bool,
+    },
+    /// Rewrite trailers from older jjagent versions (e.g. Jjagent-claude-session-id) to the current keys
+    Migrate,
+    /// List conflicted changes, exiting non-zero if there are any
+    Conflicts {
+        /// Only check this Claude session's changes and their descendants
+        #[arg(long, value_name = "SESSION_ID")]
+        session_id: Option<String>,
+    },
+    /// Squash a session's later parts (pt. 2, pt. 3, ...) back into its first change
+    #[command(name = "merge-parts")]
+    MergeParts {
+        /// The Claude session ID
+        #[arg(value_name = "SESSION_ID")]
+        session_id: String,
+    },
+    /// Fold a session's changes into an existing ancestor of the working copy
+    Squash {
+        /// The Claude session ID
+        #[arg(value_name = "SESSION_ID")]
+        session_id: String,
+        /// The jj revision to squash into (must be an ancestor of @)
+        #[arg(long, value_name = "REF")]
+        into: String,
+    },
+    /// Show a graph of a session's changes, their base and the working copy
+    Log {
+        /// The Claude session ID (defaults to the one on @)
+        #[arg(long, value_name = "SESSION_ID")]
+        session_id: Option<String>,
+        /// When to colorize the output
+        #[arg(long, value_name = "WHEN", default_value = "auto", value_parser = ["auto", "always", "never"])]
+        color: String,
+    },
+    /// Show the diff of a session's change
+    Diff {
+        /// The Claude session ID
+        #[arg(value_name = "SESSION_ID")]
+        session_id: String,
+        /// Show each part of a split session separately
+        #[arg(long)]
+        parts: bool,
+    },
+    /// Write a session's changes as a git-format patch series (one patch per part)
+    ExportPatch {
+        /// The Claude session ID
+        #[arg(value_name = "SESSION_ID")]
+        session_id: String,
+        /// Write the patch to this file instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<std::path::PathBuf>,
+    },
+    /// List the files a session touched across all of its parts
+    Files {
+        /// The Claude session ID
+        #[arg(value_name = "SESSION_ID")]
+        session_id: String,
+    },
+    /// Rebuild an abandoned session change from the edits recorded in its transcript
+    Replay {
+        /// The Claude session ID
+        #[arg(value_name = "SESSION_ID")]
+        session_id: String,
+        /// The session's transcript (a JSONL file under ~/.claude/projects)
+        #[arg(long, value_name = "PATH")]
+        transcript: std::path::PathBuf,
+    },
+    /// Show how many files and lines a session changed across all of its parts
+    Stats {
+        /// The Claude session ID
+        #[arg(value_name = "SESSION_ID")]
+        session_id: String,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
     },
     /// Get the Claude session ID from a jj revision
     #[command(name = "session-id")]
@@ -49,9 +190,12 @@ enum Commands {
         /// The Claude session ID
         #[arg(value_name = "SESSION_ID")]
         session_id: String,
-        /// The new commit message (without trailers)
+        /// The new commit message (without trailers); opens $EDITOR if omitted
         #[arg(short, long, value_name = "MESSAGE")]
-        message: String,
+        message: Option<String>,
+        /// Add a line to the end of the current description instead of replacing it
+        #[arg(long, value_name = "TEXT", conflicts_with = "message")]
+        append: Option<String>,
     },
     /// Generate a session commit message with trailers
     #[command(name = "session-message")]
@@ -65,10 +209,130 @@ enum Commands {
     },
 }
 
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum SettingsScope {
+    Project,
+    User,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum ListFormat {
+    Text,
+    Json,
+}
+
+#[derive(clap::Args)]
+struct ListArgs {
+    /// Output format
+    #[arg(long, value_enum, default_value_t = ListFormat::Text)]
+    format: ListFormat,
+    /// Only list sessions that used this tool (requires JJAGENT_RECORD_TOOLS=1)
+    #[arg(long, value_name = "NAME")]
+    tool: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum SessionCommands {
+    /// Split a session into a new part before @ (same as the top-level split)
+    Split {
+        /// The Claude session ID or jj reference to split (e.g., session ID, change ID, or revset)
+        #[arg(value_name = "SESSION_ID_OR_REF")]
+        reference: String,
+        /// Description for the new part (the Claude-session-id trailer is added automatically)
+        #[arg(short, long, value_name = "MESSAGE")]
+        message: Option<String>,
+        /// Print the created part as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Duplicate a session's changes onto another revision, leaving the originals in place
+    Cherry {
+        /// The Claude session ID
+        #[arg(value_name = "SESSION_ID")]
+        session_id: String,
+        /// The jj reference to duplicate the session onto
+        #[arg(long, value_name = "REF")]
+        onto: String,
+        /// Remove the Claude-session-id trailer from the copies to mark them as copies
+        #[arg(long)]
+        strip_trailer: bool,
+    },
+    /// Attribute all of a session's changes to a different session ID (e.g. after resuming under a new one)
+    Rename {
+        /// The session ID the changes currently carry
+        #[arg(value_name = "OLD_ID")]
+        old_id: String,
+        /// The session ID to attribute them to
+        #[arg(value_name = "NEW_ID")]
+        new_id: String,
+    },
+    /// Set or replace a custom trailer (e.g. a ticket ID) on a session's change
+    #[command(name = "set-trailer")]
+    SetTrailer {
+        /// The Claude session ID
+        #[arg(value_name = "SESSION_ID")]
+        session_id: String,
+        /// The trailer key (Claude-session-id and Claude-precommit-session-id are reserved)
+        #[arg(value_name = "KEY")]
+        key: String,
+        /// The trailer value
+        #[arg(
+            value_name = "VALUE",
+            required_unless_present = "remove",
+            conflicts_with = "remove"
+        )]
+        value: Option<String>,
+        /// Remove the trailer instead of setting it
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Show the base, uwc side and session side of each conflict in a session
+    #[command(name = "diff-base")]
+    DiffBase {
+        /// The Claude session ID or jj reference to inspect
+        #[arg(value_name = "SESSION_ID_OR_REF")]
+        reference: String,
+    },
+    /// Print the session ID on @, failing with nothing on stdout if @ has none
+    Current,
+    /// Move session changes that ended up above your working copy back below it
+    Reflow,
+    /// Show every session in chronological order with parts, conflicts and files touched
+    Timeline {
+        /// Group sessions under a heading for each day
+        #[arg(long)]
+        by_day: bool,
+        /// Output as JSON
+        #[arg(long, conflicts_with = "by_day")]
+        json: bool,
+    },
+    /// List every Claude session in the repo with its parts (same as the top-level list)
+    List(ListArgs),
+}
+
 #[derive(Subcommand)]
 enum ClaudeCommands {
     /// Print Claude Code settings JSON
-    Settings,
+    Settings {
+        /// Use a stable PATH entry (e.g. ~/.cargo/bin/jjagent) that symlinks to this binary,
+        /// so the generated hooks survive upgrades that move the real executable
+        #[arg(long)]
+        resolve_symlinks: bool,
+        /// Tools to track, as a Claude Code hook matcher
+        #[arg(long, value_name = "MATCHER", default_value = jjagent::DEFAULT_TOOL_MATCHER)]
+        matcher: String,
+        /// Merge the hooks into this settings file (created if missing) instead of printing them
+        /// Without a file, merges into the default file for --scope
+        #[arg(long, value_name = "FILE", num_args = 0..=1, conflicts_with = "migrate")]
+        merge: Option<Option<std::path::PathBuf>>,
+        /// With --merge, use Claude Code's settings file for this scope: .claude/settings.json
+        /// at the repo root (project) or ~/.claude/settings.json (user)
+        #[arg(long, value_enum, requires = "merge")]
+        scope: Option<SettingsScope>,
+        /// Rewrite jjagent hook commands from older layouts in this settings file, in place
+        #[arg(long, value_name = "FILE", conflicts_with = "resolve_symlinks")]
+        migrate: Option<std::path::PathBuf>,
+    },
     /// Get jj session change info for Claude status line scripts (see docs.claude.com)
     ///
     /// Reads JSON from stdin with session_id and workspace.current_dir.
@@ -88,6 +352,9 @@ enum ClaudeCommands {
     /// Claude Code hooks for jj integration
     #[command(subcommand)]
     Hooks(HookCommands),
+    /// Work with the changes belonging to a Claude session (same as the top-level session)
+    #[command(subcommand)]
+    Session(SessionCommands),
 }
 
 #[derive(Subcommand)]
@@ -104,6 +371,15 @@ enum HookCommands {
     /// Handle UserPromptSubmit hook
     #[command(name = "UserPromptSubmit")]
     UserPromptSubmit,
+    /// Handle SessionStart hook
+    #[command(name = "SessionStart")]
+    SessionStart,
+    /// Handle PreCompact hook
+    #[command(name = "PreCompact")]
+    PreCompact,
+    /// Handle Notification hook
+    #[command(name = "Notification")]
+    Notification,
 }
 
 fn main() -> Result<()> {
@@ -120,11 +396,52 @@ fn main() -> Result<()> {
 }
 
 fn run_command(cli: Cli) -> Result<()> {
+    let verbosity = if cli.quiet {
+        jjagent::hooks::Verbosity::Quiet
+    } else {
+        jjagent::hooks::Verbosity::from_env()
+    };
+
     match cli.command {
         Commands::Claude(claude_cmd) => {
             // Handle Settings command outside of jj repo check
-            if let ClaudeCommands::Settings = claude_cmd {
-                let settings = jjagent::format_claude_settings()?;
+            if let ClaudeCommands::Settings {
+                resolve_symlinks,
+                matcher,
+                merge,
+                scope,
+                migrate,
+            } = claude_cmd
+            {
+                if let Some(path) = migrate {
+                    let rewritten = jjagent::migrate_claude_settings_file(&path)?;
+                    if rewritten.is_empty() {
+                        println!("No legacy jjagent hook commands in {}", path.display());
+                    }
+                    for (old, new) in rewritten {
+                        println!("{} -> {}", old, new);
+                    }
+                    return Ok(());
+                }
+                let settings = jjagent::format_claude_settings(resolve_symlinks, Some(&matcher))?;
+                if let Some(file) = merge {
+                    let path = match (file, scope) {
+                        (Some(_), Some(_)) => anyhow::bail!(
+                            "Pass --merge either a settings file or --scope, not both"
+                        ),
+                        (Some(path), None) => path,
+                        (None, Some(SettingsScope::Project)) => {
+                            jjagent::claude_project_settings_path(&env::current_dir()?)?
+                        }
+                        (None, Some(SettingsScope::User)) => jjagent::claude_user_settings_path()?,
+                        (None, None) => {
+                            anyhow::bail!("--merge needs a settings file or --scope project|user")
+                        }
+                    };
+                    jjagent::merge_claude_settings_file(&path, &settings)?;
+                    println!("Merged jjagent hooks into {}", path.display());
+                    return Ok(());
+                }
                 println!("{}", settings);
                 return Ok(());
             }
@@ -137,34 +454,74 @@ fn run_command(cli: Cli) -> Result<()> {
             }
 
             match claude_cmd {
-                ClaudeCommands::Settings => unreachable!(),
+                ClaudeCommands::Settings { .. } => unreachable!(),
                 ClaudeCommands::Statusline => unreachable!(),
+                ClaudeCommands::Session(session_cmd) => run_session_command(session_cmd)?,
                 ClaudeCommands::Hooks(hook_cmd) => {
                     // Check if hooks are disabled
-                    if env::var("JJAGENT_DISABLE").unwrap_or_default() == "1" {
-                        eprintln!("jjagent: Disabled via JJAGENT_DISABLE=1");
+                    // JJAGENT_DISABLE wins over the repo's config either way, so =0 re-enables
+                    let disable = env::var("JJAGENT_DISABLE");
+                    if disable.as_deref() == Ok("1") {
+                        verbosity.info("Disabled via JJAGENT_DISABLE=1");
                         return Ok(());
                     }
 
+                    let input = jjagent::hooks::HookInput::from_stdin();
+
+                    // Claude may start the hook outside the repo it's editing, so check
+                    // the repo it reports as its cwd
+                    if disable.as_deref() != Ok("0") {
+                        let hook_dir = match &input {
+                            Ok(input) => input.cwd.as_ref().map(std::path::PathBuf::from),
+                            Err(_) => None,
+                        };
+                        if let Some(reason) = hook_dir
+                            .or_else(|| env::current_dir().ok())
+                            .and_then(|dir| jjagent::find_repo_root_from(&dir))
+                            .and_then(|root| jjagent::repo_disabled_reason(&root))
+                        {
+                            verbosity.info(format_args!("Disabled for this repo by {}", reason));
+                            return Ok(());
+                        }
+                    }
+                    let mut input = input?;
+                    input.verbosity = verbosity;
+
                     let hook_name = match hook_cmd {
                         HookCommands::PreToolUse => "PreToolUse",
                         HookCommands::PostToolUse => "PostToolUse",
                         HookCommands::Stop => "Stop",
                         HookCommands::UserPromptSubmit => "UserPromptSubmit",
+                        HookCommands::SessionStart => "SessionStart",
+                        HookCommands::PreCompact => "PreCompact",
+                        HookCommands::Notification => "Notification",
                     };
-                    eprintln!("jjagent: {} hook called", hook_name);
+                    verbosity.info(format_args!("{} hook called", hook_name));
 
                     // Handle hooks that return HookResponse directly
                     match hook_cmd {
-                        HookCommands::UserPromptSubmit => {
-                            let input = jjagent::hooks::HookInput::from_stdin()?;
-                            match jjagent::hooks::handle_user_prompt_submit_hook(&input) {
+                        HookCommands::UserPromptSubmit
+                        | HookCommands::SessionStart
+                        | HookCommands::PreCompact
+                        | HookCommands::Notification => {
+                            let response = match hook_cmd {
+                                HookCommands::SessionStart => {
+                                    jjagent::hooks::handle_session_start_hook(&input)
+                                }
+                                HookCommands::PreCompact => {
+                                    jjagent::hooks::handle_precompact_hook(&input)
+                                }
+                                HookCommands::Notification => {
+                                    jjagent::hooks::handle_notification_hook(&input)
+                                }
+                                _ => jjagent::hooks::handle_user_prompt_submit_hook(&input),
+                            };
+                            match response {
                                 Ok(response) => {
                                     response.output();
                                 }
                                 Err(e) => {
-                                    let response =
-                                        jjagent::hooks::HookResponse::stop(e.to_string());
+                                    let response = jjagent::hooks::HookResponse::from_error(&e);
                                     response.output();
                                     return Err(e);
                                 }
@@ -174,17 +531,12 @@ fn run_command(cli: Cli) -> Result<()> {
                             // PreToolUse, PostToolUse, Stop return Result<()>
                             let result = match hook_cmd {
                                 HookCommands::PreToolUse => {
-                                    let input = jjagent::hooks::HookInput::from_stdin()?;
                                     jjagent::hooks::handle_pretool_hook(input)
                                 }
                                 HookCommands::PostToolUse => {
-                                    let input = jjagent::hooks::HookInput::from_stdin()?;
                                     jjagent::hooks::handle_posttool_hook(input)
                                 }
-                                HookCommands::Stop => {
-                                    let input = jjagent::hooks::HookInput::from_stdin()?;
-                                    jjagent::hooks::handle_stop_hook(input)
-                                }
+                                HookCommands::Stop => jjagent::hooks::handle_stop_hook(input),
                                 _ => unreachable!(),
                             };
 
@@ -196,8 +548,7 @@ fn run_command(cli: Cli) -> Result<()> {
                                     response.output();
                                 }
                                 Err(e) => {
-                                    let response =
-                                        jjagent::hooks::HookResponse::stop(e.to_string());
+                                    let response = jjagent::hooks::HookResponse::from_error(&e);
                                     response.output();
                                     return Err(e);
                                 }
@@ -207,23 +558,172 @@ fn run_command(cli: Cli) -> Result<()> {
                 }
             }
         }
-        Commands::Split { reference } => {
-            jjagent::split_change(&reference)?;
+        Commands::Session(session_cmd) => run_session_command(session_cmd)?,
+        Commands::List(args) => run_list(args)?,
+        Commands::Info => {
+            println!("{}", jjagent::format_info());
+        }
+        Commands::Completions { shell } => {
+            clap_complete::generate(
+                shell,
+                &mut Cli::command(),
+                "jjagent",
+                &mut std::io::stdout(),
+            );
+        }
+        Commands::Doctor => {
+            let checks = jjagent::run_doctor_checks_in(None);
+            println!("{}", jjagent::format_doctor_checks(&checks));
+            let failed = checks
+                .iter()
+                .filter(|c| c.status == jjagent::CheckStatus::Fail)
+                .count();
+            if failed > 0 {
+                anyhow::bail!("{} check(s) failed", failed);
+            }
+        }
+        Commands::Status { session_id } => {
+            println!("{}", jjagent::format_status(session_id.as_deref())?);
+        }
+        Commands::Watch { interval_ms } => run_watch(interval_ms)?,
+        Commands::Abandon {
+            session_id,
+            dry_run,
+        } => {
+            for change_id in jjagent::abandon_session(&session_id, dry_run)? {
+                println!("{}", change_id);
+            }
+            if dry_run {
+                eprintln!("jjagent: Dry run - nothing was abandoned");
+            }
+        }
+        Commands::Gc {
+            older_than,
+            dry_run,
+        } => {
+            for precommit in jjagent::gc_precommits(older_than, dry_run)? {
+                println!("{} {}", precommit.change_id, precommit.session_id);
+            }
+            if dry_run {
+                eprintln!("jjagent: Dry run - nothing was abandoned");
+            }
+        }
+        Commands::Split {
+            reference,
+            message,
+            json,
+        } => {
+            run_split(&reference, message.as_deref(), json)?;
         }
         Commands::Into {
             session_id,
             reference,
         } => {
-            jjagent::move_session_into(&session_id, &reference)?;
+            let change_id = jjagent::move_session_into(&session_id, &reference)?;
+            println!("{}", change_id);
         }
-        Commands::ChangeId { session_id } => {
-            match jjagent::jj::find_session_change_anywhere(&session_id)? {
-                Some(change_id) => {
-                    println!("{}", change_id);
-                }
-                None => {
-                    anyhow::bail!("No change found for session ID: {}", session_id);
-                }
+        Commands::Uninstall { path } => {
+            let removed = jjagent::uninstall_claude_settings_file(&path)?;
+            println!(
+                "Removed {} jjagent hook(s) from {}",
+                removed,
+                path.display()
+            );
+        }
+        Commands::ChangeId {
+            session_id,
+            all: true,
+        } => {
+            let mut change_ids = jjagent::jj::find_all_session_changes(&session_id)?;
+            if change_ids.is_empty() {
+                anyhow::bail!("No change found for session ID: {}", session_id);
+            }
+            // jj log lists newest first
+            change_ids.reverse();
+            for change_id in change_ids {
+                println!("{}", change_id);
+            }
+        }
+        Commands::ChangeId {
+            session_id,
+            all: false,
+        } => match jjagent::jj::find_session_change_anywhere(&session_id)? {
+            Some(change) => {
+                println!("{}", change.change_id);
+            }
+            None => {
+                anyhow::bail!("No change found for session ID: {}", session_id);
+            }
+        },
+        Commands::Migrate => {
+            let rewritten = jjagent::migrate_trailers()?;
+            for change_id in &rewritten {
+                println!("{}", change_id);
+            }
+            eprintln!(
+                "jjagent: Migrated trailers on {} change(s)",
+                rewritten.len()
+            );
+        }
+        Commands::Conflicts { session_id } => {
+            let conflicts = jjagent::find_conflicts(session_id.as_deref())?;
+            for change_id in &conflicts {
+                println!("{}", change_id);
+            }
+            if !conflicts.is_empty() {
+                anyhow::bail!("Found {} conflicted change(s)", conflicts.len());
+            }
+        }
+        Commands::MergeParts { session_id } => {
+            let (change_id, parts) = jjagent::merge_session_parts(&session_id)?;
+            eprintln!("jjagent: Merged {} part(s) into {}", parts.len(), change_id);
+            println!("{}", change_id);
+        }
+        Commands::Squash { session_id, into } => {
+            let (change_id, changes) = jjagent::squash_session_into(&session_id, &into)?;
+            eprintln!(
+                "jjagent: Squashed {} change(s) into {}",
+                changes.len(),
+                change_id
+            );
+            println!("{}", change_id);
+        }
+        Commands::Log { session_id, color } => {
+            jjagent::show_session_log(session_id.as_deref(), &color)?;
+        }
+        Commands::Diff { session_id, parts } => {
+            jjagent::show_session_diff(&session_id, parts)?;
+        }
+        Commands::ExportPatch { session_id, output } => {
+            let patch = jjagent::export_session_patch(&session_id)?;
+            match output {
+                Some(path) => std::fs::write(&path, patch)
+                    .with_context(|| format!("Failed to write {}", path.display()))?,
+                None => print!("{}", patch),
+            }
+        }
+        Commands::Files { session_id } => {
+            for file in jjagent::session_files(&session_id)? {
+                println!("{}", file);
+            }
+        }
+        Commands::Replay {
+            session_id,
+            transcript,
+        } => {
+            for file in jjagent::replay_session(&session_id, &transcript, verbosity)? {
+                println!("{}", file);
+            }
+        }
+        Commands::Stats { session_id, json } => {
+            let stats = jjagent::jj::get_session_stats(&session_id)?;
+            if json {
+                println!("{}", serde_json::to_string(&stats)?);
+            } else {
+                println!(
+                    "{} files changed, {} insertions(+), {} deletions(-)",
+                    stats.files_changed, stats.insertions, stats.deletions
+                );
             }
         }
         Commands::SessionId { rev } => match jjagent::jj::get_session_id(&rev)? {
@@ -237,9 +737,12 @@ fn run_command(cli: Cli) -> Result<()> {
         Commands::Describe {
             session_id,
             message,
-        } => {
-            jjagent::describe_session_change(&session_id, &message)?;
-        }
+            append,
+        } => match (message, append) {
+            (Some(message), _) => jjagent::describe_session_change(&session_id, &message)?,
+            (None, Some(text)) => jjagent::append_session_description(&session_id, &text)?,
+            (None, None) => jjagent::edit_session_description(&session_id)?,
+        },
         Commands::SessionMessage {
             session_id,
             message,
@@ -251,3 +754,131 @@ fn run_command(cli: Cli) -> Result<()> {
 
     Ok(())
 }
+
+fn run_list(args: ListArgs) -> Result<()> {
+    let filter = jjagent::SessionListFilter { tool: args.tool };
+    let sessions = jjagent::list_sessions(&filter)?;
+    match args.format {
+        ListFormat::Json => println!("{}", serde_json::to_string_pretty(&sessions)?),
+        ListFormat::Text => {
+            for session in &sessions {
+                print!("{}", jjagent::format_session_parts_line(session));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_split(reference: &str, message: Option<&str>, json: bool) -> Result<()> {
+    let change_id = jjagent::split_change(reference, message)?;
+    if json {
+        let part = jjagent::split_part(&change_id)?;
+        println!("{}", serde_json::to_string(&part)?);
+    }
+    Ok(())
+}
+
+/// Poll the lock and the session on @, printing a timestamped line when either changes
+/// Errors reading the state (e.g. jj busy with a concurrent operation) are reported and
+/// polling carries on
+fn run_watch(interval_ms: u64) -> Result<()> {
+    let root = jjagent::jj::get_repo_root_in(None)?;
+    let interval = std::time::Duration::from_millis(interval_ms);
+    let mut last = None;
+    loop {
+        match jjagent::read_watch_state_in(&root) {
+            Ok(state) => {
+                if last.as_ref() != Some(&state) {
+                    println!(
+                        "{} {}",
+                        chrono::Local::now().format("%H:%M:%S%.3f"),
+                        jjagent::format_watch_line(&state)
+                    );
+                    last = Some(state);
+                }
+            }
+            Err(e) => eprintln!("jjagent: Warning - failed to read state: {:#}", e),
+        }
+        std::thread::sleep(interval);
+    }
+}
+
+fn run_session_command(session_cmd: SessionCommands) -> Result<()> {
+    match session_cmd {
+        SessionCommands::Split {
+            reference,
+            message,
+            json,
+        } => {
+            run_split(&reference, message.as_deref(), json)?;
+        }
+        SessionCommands::Cherry {
+            session_id,
+            onto,
+            strip_trailer,
+        } => {
+            let changes = jjagent::cherry_session(&session_id, &onto, strip_trailer)?;
+            for change in &changes {
+                if change.has_conflict {
+                    println!("{} (conflict)", change.change_id);
+                } else {
+                    println!("{}", change.change_id);
+                }
+            }
+
+            let conflicted = changes.iter().filter(|c| c.has_conflict).count();
+            if conflicted > 0 {
+                eprintln!(
+                    "jjagent: Warning - {} duplicated change(s) have conflicts",
+                    conflicted
+                );
+            }
+        }
+        SessionCommands::Rename { old_id, new_id } => {
+            let changes = jjagent::rename_session(&old_id, &new_id)?;
+            println!("Updated {} change(s) to session {}", changes.len(), new_id);
+        }
+        SessionCommands::SetTrailer {
+            session_id,
+            key,
+            value,
+            remove: _,
+        } => {
+            jjagent::set_session_trailer(&session_id, &key, value.as_deref())?;
+        }
+        SessionCommands::DiffBase { reference } => {
+            let files = jjagent::session_conflict_sides(&reference)?;
+            if files.is_empty() {
+                eprintln!("jjagent: No conflicts found in {}", reference);
+            } else {
+                print!("{}", jjagent::format_conflict_sides(&files));
+            }
+        }
+        SessionCommands::Current => match jjagent::jj::get_current_commit_session_id()? {
+            Some(session_id) => println!("{}", session_id),
+            None => anyhow::bail!("No Claude-session-id trailer on @"),
+        },
+        SessionCommands::Reflow => {
+            let moved = jjagent::reflow_sessions()?;
+            if moved.is_empty() {
+                eprintln!("jjagent: Session changes are already below the working copy");
+            }
+            for change_id in moved {
+                println!("{}", change_id);
+            }
+        }
+        SessionCommands::Timeline { by_day, json } => {
+            let sessions = jjagent::build_timeline(jjagent::list_sessions(
+                &jjagent::SessionListFilter::default(),
+            )?);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&sessions)?);
+            } else {
+                print!("{}", jjagent::format_timeline(&sessions, by_day));
+            }
+        }
+        SessionCommands::List(args) => run_list(args)?,
+    }
+
+    Ok(())
+}
@@ -5,6 +5,10 @@
 //! - Commit message formatting for precommit and session changes
 //! - Trailer formatting for storing session metadata
 
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::path::Path;
+
 /// Represents a Claude Code session ID with both full and short forms
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SessionId {
@@ -34,6 +38,49 @@ impl SessionId {
     }
 }
 
+/// Trailer key marking an in-flight precommit, before any namespace is applied
+pub const PRECOMMIT_TRAILER_KEY: &str = "Claude-precommit-session-id";
+
+/// Trailer keys jjagent uses to track sessions; users can't edit these directly
+pub const RESERVED_TRAILER_KEYS: &[&str] = &["Claude-session-id", PRECOMMIT_TRAILER_KEY];
+
+/// Check whether a trailer key is reserved by jjagent (case-insensitive, like git trailers)
+pub fn is_reserved_trailer_key(key: &str) -> bool {
+    RESERVED_TRAILER_KEYS
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(key))
+        || PrecommitConfig::from_env()
+            .trailer_key()
+            .eq_ignore_ascii_case(key)
+}
+
+/// How precommits are marked, so downstream tools scanning commit messages can tell
+/// them apart
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrecommitConfig {
+    /// Prefix for the precommit trailer key, e.g. "Acme" gives "Acme-Claude-precommit-session-id"
+    pub namespace: Option<String>,
+}
+
+impl PrecommitConfig {
+    /// Read the namespace from JJAGENT_PRECOMMIT_TRAILER_NAMESPACE (unset or blank means none)
+    pub fn from_env() -> Self {
+        let namespace = std::env::var("JJAGENT_PRECOMMIT_TRAILER_NAMESPACE")
+            .ok()
+            .map(|ns| ns.trim().to_string())
+            .filter(|ns| !ns.is_empty());
+        Self { namespace }
+    }
+
+    /// The trailer key precommits are written and detected with
+    pub fn trailer_key(&self) -> String {
+        match &self.namespace {
+            Some(namespace) => format!("{}-{}", namespace, PRECOMMIT_TRAILER_KEY),
+            None => PRECOMMIT_TRAILER_KEY.to_string(),
+        }
+    }
+}
+
 /// Format a precommit message for the given session
 /// Example:
 /// ```text
@@ -42,14 +89,47 @@ impl SessionId {
 /// Claude-precommit-session-id: abcd1234-5678-90ab-cdef-1234567890ab
 /// ```
 pub fn format_precommit_message(session_id: &SessionId) -> String {
+    format_precommit_message_with(session_id, &PrecommitConfig::from_env())
+}
+
+/// Format a precommit message using the trailer key from the given config
+pub fn format_precommit_message_with(session_id: &SessionId, config: &PrecommitConfig) -> String {
     format!(
-        "jjagent: precommit {}\n\nClaude-precommit-session-id: {}",
+        "jjagent: precommit {}\n\n{}: {}",
         session_id.short(),
+        config.trailer_key(),
         session_id.full()
     )
 }
 
+/// Title template used when JJAGENT_SESSION_MESSAGE_TEMPLATE is unset
+pub const DEFAULT_SESSION_MESSAGE_TEMPLATE: &str = "jjagent: session {short}";
+
+/// Render a session title from a template
+/// Supports `{short}`, `{full}` and `{part}` placeholders. For a later part, a template
+/// without `{part}` gets " pt. N" appended; the first part renders `{part}` as 1
+pub fn render_session_title(template: &str, session_id: &SessionId, part: Option<usize>) -> String {
+    let title = template
+        .replace("{short}", session_id.short())
+        .replace("{full}", session_id.full())
+        .replace("{part}", &part.unwrap_or(1).to_string());
+    match part {
+        Some(part) if !template.contains("{part}") => format!("{} pt. {}", title, part),
+        _ => title,
+    }
+}
+
+/// Title of a session change, from JJAGENT_SESSION_MESSAGE_TEMPLATE if set
+pub fn format_session_title(session_id: &SessionId, part: Option<usize>) -> String {
+    let template = std::env::var("JJAGENT_SESSION_MESSAGE_TEMPLATE")
+        .ok()
+        .filter(|t| !t.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_SESSION_MESSAGE_TEMPLATE.to_string());
+    render_session_title(&template, session_id, part)
+}
+
 /// Format a session message with trailer for the given session
+/// The Claude-session-id trailer is always appended, whatever the title template
 /// Example:
 /// ```text
 /// jjagent: session abcd1234
@@ -57,11 +137,159 @@ pub fn format_precommit_message(session_id: &SessionId) -> String {
 /// Claude-session-id: abcd1234-5678-90ab-cdef-1234567890ab
 /// ```
 pub fn format_session_message(session_id: &SessionId) -> String {
-    format!(
-        "jjagent: session {}\n\nClaude-session-id: {}",
-        session_id.short(),
-        session_id.full()
-    )
+    format_session_message_with_title(session_id, &format_session_title(session_id, None))
+}
+
+/// Format a session message with the given title and the Claude-session-id trailer
+pub fn format_session_message_with_title(session_id: &SessionId, title: &str) -> String {
+    format!("{}\n\nClaude-session-id: {}", title, session_id.full())
+}
+
+/// Longest title taken from a prompt, in characters
+const PROMPT_SUMMARY_MAX_CHARS: usize = 72;
+
+/// One-line summary of the most recent user prompt in a Claude transcript (JSONL), for
+/// titling a new session change. Tool results, meta entries and slash command wrappers are
+/// skipped. Returns None if the transcript can't be read or has no prompt
+pub fn summarize_prompt(transcript_path: &Path) -> Option<String> {
+    let transcript = std::fs::read_to_string(transcript_path).ok()?;
+    let prompt = transcript.lines().rev().find_map(prompt_text)?;
+
+    let first_line = prompt.lines().find(|line| !line.trim().is_empty())?;
+    let summary = first_line.split_whitespace().collect::<Vec<_>>().join(" ");
+    if summary.chars().count() <= PROMPT_SUMMARY_MAX_CHARS {
+        return Some(summary);
+    }
+
+    // Cut at the last word boundary that leaves room for the ellipsis
+    let cut: String = summary.chars().take(PROMPT_SUMMARY_MAX_CHARS - 3).collect();
+    let cut = match cut.rfind(' ') {
+        Some(i) if i > 0 => &cut[..i],
+        _ => &cut,
+    };
+    Some(format!("{}...", cut.trim_end()))
+}
+
+/// Text of a transcript line if it's a prompt the user typed
+fn prompt_text(line: &str) -> Option<String> {
+    let entry: serde_json::Value = serde_json::from_str(line).ok()?;
+    if entry.get("type")?.as_str()? != "user" || entry.get("isMeta") == Some(&true.into()) {
+        return None;
+    }
+
+    let text = match entry.get("message")?.get("content")? {
+        serde_json::Value::String(text) => text.clone(),
+        serde_json::Value::Array(blocks) => blocks
+            .iter()
+            .filter(|block| block.get("type").and_then(|t| t.as_str()) == Some("text"))
+            .filter_map(|block| block.get("text")?.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => return None,
+    };
+
+    let text = text.trim();
+    (!text.is_empty() && !text.starts_with('<')).then(|| text.to_string())
+}
+
+/// A file edit recorded in a Claude transcript
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TranscriptEdit {
+    /// The Write tool: the file's full new content
+    Write { file_path: String, content: String },
+    /// The Edit tool (or one step of a MultiEdit): replace old_string with new_string,
+    /// at its only occurrence or, with replace_all, everywhere
+    Edit {
+        file_path: String,
+        old_string: String,
+        new_string: String,
+        replace_all: bool,
+    },
+}
+
+/// Tools that change files. Other tool uses (Read, Bash, ...) are skipped
+const FILE_EDIT_TOOLS: &[&str] = &["Write", "Edit", "MultiEdit", "NotebookEdit"];
+
+/// File edits recorded in a Claude transcript (JSONL), oldest first
+/// Tool uses whose result was an error are skipped, since they didn't change anything.
+/// Fails on a file-editing tool that can't be replayed (e.g. NotebookEdit)
+pub fn parse_transcript_edits(transcript_path: &Path) -> Result<Vec<TranscriptEdit>> {
+    let transcript = std::fs::read_to_string(transcript_path)
+        .with_context(|| format!("Failed to read transcript: {}", transcript_path.display()))?;
+    let blocks: Vec<serde_json::Value> = transcript
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter_map(|entry| match entry.get("message")?.get("content")? {
+            serde_json::Value::Array(blocks) => Some(blocks.clone()),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+
+    let failed: HashSet<&str> = blocks
+        .iter()
+        .filter(|block| block.get("type").and_then(|t| t.as_str()) == Some("tool_result"))
+        .filter(|block| block.get("is_error") == Some(&true.into()))
+        .filter_map(|block| block.get("tool_use_id")?.as_str())
+        .collect();
+
+    let mut edits = Vec::new();
+    for block in &blocks {
+        if block.get("type").and_then(|t| t.as_str()) != Some("tool_use") {
+            continue;
+        }
+        let name = block.get("name").and_then(|n| n.as_str()).unwrap_or("");
+        let id = block.get("id").and_then(|id| id.as_str()).unwrap_or("");
+        if !FILE_EDIT_TOOLS.contains(&name) || failed.contains(id) {
+            continue;
+        }
+        edits.extend(tool_use_edits(
+            name,
+            block.get("input").unwrap_or(&serde_json::Value::Null),
+        )?);
+    }
+    Ok(edits)
+}
+
+/// The edits made by one file-editing tool use
+fn tool_use_edits(name: &str, input: &serde_json::Value) -> Result<Vec<TranscriptEdit>> {
+    let field = |value: &serde_json::Value, key: &str| -> Result<String> {
+        value
+            .get(key)
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .with_context(|| format!("{} tool use in transcript is missing '{}'", name, key))
+    };
+    let edit = |file_path: &str, step: &serde_json::Value| -> Result<TranscriptEdit> {
+        Ok(TranscriptEdit::Edit {
+            file_path: file_path.to_string(),
+            old_string: field(step, "old_string")?,
+            new_string: field(step, "new_string")?,
+            replace_all: step.get("replace_all") == Some(&true.into()),
+        })
+    };
+
+    match name {
+        "Write" => Ok(vec![TranscriptEdit::Write {
+            file_path: field(input, "file_path")?,
+            content: field(input, "content")?,
+        }]),
+        "Edit" => Ok(vec![edit(&field(input, "file_path")?, input)?]),
+        "MultiEdit" => {
+            let file_path = field(input, "file_path")?;
+            input
+                .get("edits")
+                .and_then(|edits| edits.as_array())
+                .context("MultiEdit tool use in transcript is missing 'edits'")?
+                .iter()
+                .map(|step| edit(&file_path, step))
+                .collect()
+        }
+        _ => anyhow::bail!(
+            "Can't replay {} tool uses; only Write, Edit and MultiEdit are supported",
+            name
+        ),
+    }
 }
 
 /// Format a session part message (for conflict scenarios)
@@ -73,13 +301,37 @@ pub fn format_session_message(session_id: &SessionId) -> String {
 /// ```
 pub fn format_session_part_message(session_id: &SessionId, part: usize) -> String {
     format!(
-        "jjagent: session {} pt. {}\n\nClaude-session-id: {}",
-        session_id.short(),
-        part,
+        "{}\n\nClaude-session-id: {}",
+        format_session_title(session_id, Some(part)),
         session_id.full()
     )
 }
 
+/// Format the title of a part created by `jjagent split`: the first line of the
+/// split change plus when it was split. An earlier split suffix is replaced, not stacked
+/// Example:
+/// ```text
+/// jjagent: session abcd1234 (split 2025-11-02T14:03:12Z)
+/// ```
+pub fn format_split_message(first_line: &str, timestamp: &str) -> String {
+    let first_line = match first_line.rfind(" (split ") {
+        Some(pos) if first_line.ends_with(')') => &first_line[..pos],
+        _ => first_line,
+    };
+    format!("{} (split {})", first_line, timestamp)
+}
+
+/// Timestamp used in split messages: the current UTC time, or the Unix epoch when
+/// JJAGENT_DETERMINISTIC=1 so output can be compared across runs
+pub fn split_timestamp() -> String {
+    let now = if std::env::var("JJAGENT_DETERMINISTIC").unwrap_or_default() == "1" {
+        chrono::DateTime::UNIX_EPOCH
+    } else {
+        chrono::Utc::now()
+    };
+    now.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -100,4 +352,44 @@ mod tests {
         assert!(format_session_message(&sid).contains("Claude-session-id:"));
         assert!(format_session_part_message(&sid, 2).contains("pt. 2"));
     }
+
+    #[test]
+    fn test_split_message() {
+        assert_eq!(
+            format_split_message("jjagent: session abcd1234", "2025-11-02T14:03:12Z"),
+            "jjagent: session abcd1234 (split 2025-11-02T14:03:12Z)"
+        );
+        // Splitting a split part doesn't stack suffixes
+        assert_eq!(
+            format_split_message(
+                "jjagent: session abcd1234 (split 2025-11-02T14:03:12Z)",
+                "2025-11-03T09:00:00Z"
+            ),
+            "jjagent: session abcd1234 (split 2025-11-03T09:00:00Z)"
+        );
+    }
+
+    #[test]
+    fn test_precommit_trailer_key_namespace() {
+        let sid = SessionId::from_full("abcd1234");
+        let config = PrecommitConfig {
+            namespace: Some("Acme".to_string()),
+        };
+        assert_eq!(config.trailer_key(), "Acme-Claude-precommit-session-id");
+        assert_eq!(
+            format_precommit_message_with(&sid, &config),
+            "jjagent: precommit abcd1234\n\nAcme-Claude-precommit-session-id: abcd1234"
+        );
+        assert_eq!(
+            PrecommitConfig::default().trailer_key(),
+            PRECOMMIT_TRAILER_KEY
+        );
+    }
+
+    #[test]
+    fn test_reserved_trailer_keys() {
+        assert!(is_reserved_trailer_key("Claude-session-id"));
+        assert!(is_reserved_trailer_key("claude-precommit-session-id"));
+        assert!(!is_reserved_trailer_key("Ticket"));
+    }
 }
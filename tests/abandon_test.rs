@@ -0,0 +1,202 @@
+use anyhow::Result;
+use std::process::Command;
+use tempfile::TempDir;
+
+const SESSION_ID: &str = "abandon-test-1111-2222-3333-44444444444";
+
+struct TestRepo {
+    dir: TempDir,
+}
+
+impl TestRepo {
+    fn new() -> Result<Self> {
+        let dir = TempDir::new()?;
+
+        // Initialize jj repo
+        let init_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["git", "init"])
+            .output()?;
+
+        if !init_output.status.success() {
+            anyhow::bail!(
+                "Failed to init jj repo: {}",
+                String::from_utf8_lossy(&init_output.stderr)
+            );
+        }
+
+        // Disable watchman for tests
+        let config_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["config", "set", "--repo", "fsmonitor.backend", "none"])
+            .output()?;
+
+        if !config_output.status.success() {
+            anyhow::bail!(
+                "Failed to disable watchman: {}",
+                String::from_utf8_lossy(&config_output.stderr)
+            );
+        }
+
+        let repo = Self { dir };
+        // Build: base -> session -> uwc (@)
+        std::fs::write(repo.path().join("base.txt"), "base\n")?;
+        repo.jj(&["describe", "-m", "base"])?;
+        repo.jj(&[
+            "new",
+            "-m",
+            &format!(
+                "jjagent: session abandon-\n\nClaude-session-id: {}",
+                SESSION_ID
+            ),
+        ])?;
+        std::fs::write(repo.path().join("claude.txt"), "claude\n")?;
+        repo.jj(&["new", "-m", "uwc"])?;
+        std::fs::write(repo.path().join("user.txt"), "user\n")?;
+
+        Ok(repo)
+    }
+
+    fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+
+    /// Run a jj command in the repo and return its stdout
+    fn jj(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("jj")
+            .current_dir(self.path())
+            .args(args)
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj {:?} failed: {}",
+                args,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn jjagent(&self, args: &[&str]) -> Result<std::process::Output> {
+        Ok(Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .current_dir(self.path())
+            .args(args)
+            .output()?)
+    }
+
+    fn change_id(&self, rev: &str) -> Result<String> {
+        Ok(self
+            .jj(&["log", "-r", rev, "--no-graph", "-T", "change_id"])?
+            .trim()
+            .to_string())
+    }
+}
+
+#[test]
+fn test_abandon_dry_run_lists_changes_without_abandoning() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let session = repo.change_id("@-")?;
+
+    let output = repo.jjagent(&["abandon", SESSION_ID, "--dry-run"])?;
+    assert!(
+        output.status.success(),
+        "abandon failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), session);
+
+    // The session change is still there
+    assert_eq!(
+        jjagent::jj::find_all_session_changes_in(SESSION_ID, Some(repo.path()))?,
+        vec![session]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_abandon_removes_all_parts_and_keeps_working_copy() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let uwc = repo.change_id("@")?;
+
+    // A second part of the same session between the first part and uwc
+    repo.jj(&[
+        "new",
+        "--insert-before",
+        "@",
+        "--no-edit",
+        "-m",
+        &format!("Second part\n\nClaude-session-id: {}", SESSION_ID),
+    ])?;
+
+    let output = repo.jjagent(&["abandon", SESSION_ID])?;
+    assert!(
+        output.status.success(),
+        "abandon failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 2);
+
+    assert!(jjagent::jj::find_all_session_changes_in(SESSION_ID, Some(repo.path()))?.is_empty());
+
+    // uwc is still @, now directly on base, with the user's edit and without Claude's
+    assert_eq!(repo.change_id("@")?, uwc);
+    assert_eq!(
+        repo.jj(&["log", "-r", "@-", "--no-graph", "-T", "description"])?,
+        "base\n"
+    );
+    assert!(repo.path().join("user.txt").exists());
+    assert!(!repo.path().join("claude.txt").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_abandon_refuses_with_unrelated_descendants() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    // A side branch built on top of the session change
+    repo.jj(&["new", "@-", "--no-edit", "-m", "side branch"])?;
+
+    let output = repo.jjagent(&["abandon", SESSION_ID])?;
+    assert!(!output.status.success(), "abandon should refuse to run");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("descendants that are not part of the session"),
+        "got: {}",
+        stderr
+    );
+
+    // Nothing was abandoned
+    assert_eq!(
+        jjagent::jj::find_all_session_changes_in(SESSION_ID, Some(repo.path()))?.len(),
+        1
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_abandon_without_keep_descendants_rebases_them() -> Result<()> {
+    let repo = TestRepo::new()?;
+    repo.jj(&["new", "@-", "--no-edit", "-m", "side branch"])?;
+
+    let abandoned = jjagent::jj::abandon_session_in(SESSION_ID, false, false, Some(repo.path()))?;
+    assert_eq!(abandoned.len(), 1);
+    assert!(jjagent::jj::find_all_session_changes_in(SESSION_ID, Some(repo.path()))?.is_empty());
+
+    // The side branch survives, moved onto the session's parent
+    assert_eq!(
+        repo.jj(&[
+            "log",
+            "-r",
+            r#"description(exact:"side branch\n")-"#,
+            "--no-graph",
+            "-T",
+            "description",
+        ])?,
+        "base\n"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,206 @@
+//! Regression guard for hook latency and the number of jj processes each hook spawns.
+//! Thresholds are deliberately generous: they exist to catch accidental N² blowups,
+//! not to measure small wins.
+#![cfg(unix)]
+
+use anyhow::Result;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+/// Number of PreToolUse -> write -> PostToolUse cycles to run
+const CYCLES: usize = 5;
+/// Median wall time allowed for one full cycle
+const MAX_MEDIAN_CYCLE: Duration = Duration::from_secs(5);
+/// jj processes allowed per cycle (currently around two dozen)
+const MAX_SPAWNS_PER_CYCLE: usize = 60;
+
+struct BenchRepo {
+    dir: TempDir,
+    tools: TempDir,
+}
+
+impl BenchRepo {
+    fn new() -> Result<Self> {
+        let dir = TempDir::new()?;
+        let tools = TempDir::new()?;
+
+        let output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["git", "init"])
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to init jj repo: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        let output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["config", "set", "--repo", "fsmonitor.backend", "none"])
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to disable watchman: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        // Wrapper that counts every jj invocation (and every `jj root` among them) before
+        // running the real binary
+        let wrapper = tools.path().join("jj-counter");
+        std::fs::write(
+            &wrapper,
+            format!(
+                "#!/bin/sh\necho >> '{}'\nfor arg in \"$@\"; do [ \"$arg\" = root ] && echo >> '{}'; done\nexec jj \"$@\"\n",
+                tools.path().join("count").display(),
+                tools.path().join("roots").display()
+            ),
+        )?;
+        std::fs::set_permissions(&wrapper, std::fs::Permissions::from_mode(0o755))?;
+
+        Ok(Self { dir, tools })
+    }
+
+    fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    fn wrapper(&self) -> PathBuf {
+        self.tools.path().join("jj-counter")
+    }
+
+    fn spawn_count(&self) -> Result<usize> {
+        self.count_lines("count")
+    }
+
+    /// Number of `jj root` invocations, i.e. repo lookups
+    fn root_count(&self) -> Result<usize> {
+        self.count_lines("roots")
+    }
+
+    fn count_lines(&self, name: &str) -> Result<usize> {
+        match std::fs::read_to_string(self.tools.path().join(name)) {
+            Ok(count) => Ok(count.lines().count()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn run_hook(&self, hook_name: &str, session_id: &str) -> Result<()> {
+        self.run_hook_in(self.path(), hook_name, session_id)
+    }
+
+    fn run_hook_in(&self, cwd: &Path, hook_name: &str, session_id: &str) -> Result<()> {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .current_dir(cwd)
+            .env_remove("JJAGENT_DISABLE")
+            .env_remove("JJAGENT_LOG")
+            .env_remove("JJAGENT_LOG_FILE")
+            .env("JJAGENT_JJ_BIN", self.wrapper())
+            .env("JJAGENT_POSTTOOL_DELAY_MS", "0")
+            .args(["claude", "hooks", hook_name])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            write!(
+                stdin,
+                r#"{{"session_id":"{}","tool_name":"Write"}}"#,
+                session_id
+            )?;
+        }
+
+        let output = child.wait_with_output()?;
+        assert!(
+            output.status.success(),
+            "{} hook failed: {}",
+            hook_name,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        Ok(())
+    }
+}
+
+#[test]
+fn bench_hook_cycle_latency_and_spawns() -> Result<()> {
+    let repo = BenchRepo::new()?;
+    let session_id = "bench-12345678-1234-5678-90ab-cdef12345678";
+
+    let mut durations = Vec::with_capacity(CYCLES);
+    for i in 0..CYCLES {
+        let start = Instant::now();
+        repo.run_hook("PreToolUse", session_id)?;
+        std::fs::write(repo.path().join(format!("file{}.txt", i)), "content")?;
+        repo.run_hook("PostToolUse", session_id)?;
+        durations.push(start.elapsed());
+    }
+
+    durations.sort();
+    let median = durations[CYCLES / 2];
+    let spawns = repo.spawn_count()?;
+    eprintln!(
+        "hook cycle median: {:?}, jj spawns: {} ({} per cycle)",
+        median,
+        spawns,
+        spawns / CYCLES
+    );
+
+    assert!(
+        median < MAX_MEDIAN_CYCLE,
+        "Median hook cycle took {:?}, expected under {:?}",
+        median,
+        MAX_MEDIAN_CYCLE
+    );
+    assert!(spawns > 0, "JJAGENT_JJ_BIN wrapper was never invoked");
+    assert!(
+        spawns <= MAX_SPAWNS_PER_CYCLE * CYCLES,
+        "Spawned jj {} times over {} cycles, expected at most {} per cycle",
+        spawns,
+        CYCLES,
+        MAX_SPAWNS_PER_CYCLE
+    );
+
+    Ok(())
+}
+
+#[test]
+fn bench_hooks_resolve_repo_once() -> Result<()> {
+    let repo = BenchRepo::new()?;
+    let session_id = "bench-root-5678-1234-5678-90ab-cdef12345678";
+
+    // Claude often works from a subdirectory; the hooks still resolve the repo root once
+    let subdir = repo.path().join("src");
+    std::fs::create_dir(&subdir)?;
+
+    for i in 0..CYCLES {
+        repo.run_hook_in(&subdir, "PreToolUse", session_id)?;
+        std::fs::write(subdir.join(format!("file{}.txt", i)), "content")?;
+        repo.run_hook_in(&subdir, "PostToolUse", session_id)?;
+    }
+
+    let spawns = repo.spawn_count()?;
+    let roots = repo.root_count()?;
+    eprintln!(
+        "multi-edit session: {} jj spawns ({} per cycle), {} repo lookups",
+        spawns,
+        spawns / CYCLES,
+        roots
+    );
+
+    assert_eq!(
+        roots,
+        2 * CYCLES,
+        "each hook should look up the repo root exactly once"
+    );
+    // The lock lives in the repo's .jj, not next to the subdirectory
+    assert!(!subdir.join(".jj").exists());
+
+    Ok(())
+}
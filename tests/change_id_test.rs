@@ -304,3 +304,116 @@ fn test_change_id_command_multiple_trailers_same_commit() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_change_id_command_all_parts() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let session_id = "all-parts-12345678-1234-5678-90ab-cdef12345678";
+
+    let mut expected = Vec::new();
+    for title in [
+        "jjagent: session all-part",
+        "jjagent: session all-part pt. 2",
+    ] {
+        Command::new("jj")
+            .current_dir(repo.path())
+            .args([
+                "new",
+                "-m",
+                &format!("{}\n\nClaude-session-id: {}", title, session_id),
+            ])
+            .output()?;
+        let change_id = Command::new("jj")
+            .current_dir(repo.path())
+            .args(["log", "-r", "@", "--no-graph", "-T", "change_id"])
+            .output()?;
+        expected.push(
+            String::from_utf8_lossy(&change_id.stdout)
+                .trim()
+                .to_string(),
+        );
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(repo.path())
+        .args(["change-id", session_id, "--all"])
+        .output()?;
+    assert!(
+        output.status.success(),
+        "change-id --all failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Oldest part first
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().collect::<Vec<_>>(), expected);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(repo.path())
+        .args(["change-id", "nonexistent-session-12345678", "--all"])
+        .output()?;
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("No change found for session ID: nonexistent-session-12345678")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_lookups_escape_session_ids() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let quoted_id = r#"quoted"session\id-1234"#;
+
+    Command::new("jj")
+        .current_dir(repo.path())
+        .args([
+            "new",
+            "-m",
+            &format!(
+                "jjagent: session quoted\n\nClaude-session-id: {}",
+                quoted_id
+            ),
+        ])
+        .output()?;
+    Command::new("jj")
+        .current_dir(repo.path())
+        .args(["new", "-m", "uwc"])
+        .output()?;
+
+    let path = Some(repo.path());
+    assert!(jjagent::jj::find_session_change_anywhere_in(quoted_id, path)?.is_some());
+    assert!(jjagent::jj::find_session_change_in(quoted_id, path)?.is_none());
+    assert_eq!(jjagent::jj::count_session_parts_in(quoted_id, path)?, 1);
+
+    // Hostile IDs produce well-formed queries that match nothing
+    for session_id in [
+        r#"x") | all() | description("x"#,
+        "quoted\"session",
+        "line\nbreak",
+        "(parens)",
+        r"back\slash",
+    ] {
+        assert_eq!(
+            jjagent::jj::find_session_change_anywhere_in(session_id, path)?,
+            None,
+            "{:?}",
+            session_id
+        );
+        assert_eq!(
+            jjagent::jj::find_session_change_in(session_id, path)?,
+            None,
+            "{:?}",
+            session_id
+        );
+        assert_eq!(
+            jjagent::jj::count_session_parts_in(session_id, path)?,
+            0,
+            "{:?}",
+            session_id
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,190 @@
+use anyhow::Result;
+use std::process::Command;
+use tempfile::TempDir;
+
+struct TestRepo {
+    dir: TempDir,
+}
+
+impl TestRepo {
+    fn new() -> Result<Self> {
+        let dir = TempDir::new()?;
+
+        // Initialize jj repo
+        let init_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["git", "init"])
+            .output()?;
+
+        if !init_output.status.success() {
+            anyhow::bail!(
+                "Failed to init jj repo: {}",
+                String::from_utf8_lossy(&init_output.stderr)
+            );
+        }
+
+        // Disable watchman for tests
+        let config_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["config", "set", "--repo", "fsmonitor.backend", "none"])
+            .output()?;
+
+        if !config_output.status.success() {
+            anyhow::bail!(
+                "Failed to disable watchman: {}",
+                String::from_utf8_lossy(&config_output.stderr)
+            );
+        }
+
+        Ok(Self { dir })
+    }
+
+    fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+
+    /// Run a jj command in the repo and return its trimmed stdout
+    fn jj(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("jj")
+            .current_dir(self.path())
+            .args(args)
+            .output()?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj {:?} failed: {}",
+                args,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Build: sibling -> base <- session, with @ on the sibling
+    /// Returns the sibling's change ID
+    fn with_session_and_sibling(&self, session_id: &str) -> Result<String> {
+        self.jj(&["describe", "-m", "base"])?;
+        std::fs::write(self.path().join("base.txt"), "base")?;
+
+        let session_message = format!(
+            "jjagent: session cherry-t\n\nClaude-session-id: {}",
+            session_id
+        );
+        self.jj(&["new", "-m", &session_message])?;
+        std::fs::write(self.path().join("session.txt"), "session content")?;
+
+        self.jj(&["new", "-m", "sibling", "@-"])?;
+        self.jj(&["log", "-r", "@", "--no-graph", "-T", "change_id"])
+    }
+}
+
+#[test]
+fn test_cherry_duplicates_session_onto_sibling() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let session_id = "cherry-test-12345678-1234-5678-90ab-cdef12345678";
+    let sibling = repo.with_session_and_sibling(session_id)?;
+
+    let original = jjagent::jj::find_session_change_anywhere_in(session_id, Some(repo.path()))?
+        .expect("session change should exist")
+        .change_id;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(repo.path())
+        .env_remove("JJAGENT_DISABLE")
+        .args(["session", "cherry", session_id, "--onto", &sibling])
+        .output()?;
+
+    assert!(
+        output.status.success(),
+        "session cherry should succeed, stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let new_ids: Vec<&str> = stdout.lines().filter(|l| !l.trim().is_empty()).collect();
+    assert_eq!(
+        new_ids.len(),
+        1,
+        "Should report one new change, got: {}",
+        stdout
+    );
+    assert_ne!(new_ids[0], original, "Copy should have a new change ID");
+
+    // The copy sits on top of the sibling and carries the session's content
+    let children = repo.jj(&[
+        "log",
+        "-r",
+        &format!("{}+", sibling),
+        "--no-graph",
+        "-T",
+        "change_id",
+    ])?;
+    assert_eq!(children, new_ids[0]);
+    let files = repo.jj(&["file", "list", "-r", new_ids[0]])?;
+    assert!(
+        files.contains("session.txt"),
+        "Copy should contain session.txt, got: {}",
+        files
+    );
+
+    // The original is untouched and both now carry the trailer
+    let all = jjagent::jj::find_all_session_changes_in(session_id, Some(repo.path()))?;
+    assert_eq!(
+        all.len(),
+        2,
+        "Original and copy should both have the trailer"
+    );
+    assert!(all.contains(&original));
+
+    Ok(())
+}
+
+#[test]
+fn test_cherry_strip_trailer() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let session_id = "cherry-strip-12345678-1234-5678-90ab-cdef12345678";
+    let sibling = repo.with_session_and_sibling(session_id)?;
+
+    let changes = jjagent::jj::duplicate_session_in(session_id, &sibling, true, Some(repo.path()))?;
+    assert_eq!(changes.len(), 1);
+    assert!(
+        !changes[0].has_conflict,
+        "Copy onto a sibling should not conflict"
+    );
+
+    let desc = jjagent::jj::get_commit_description_in(&changes[0].change_id, Some(repo.path()))?;
+    assert!(
+        !desc.contains("Claude-session-id"),
+        "Copy should not carry the session trailer, got: {}",
+        desc
+    );
+    assert!(desc.contains("jjagent: session cherry-t"));
+
+    // Session lookups still resolve to the single original change
+    let all = jjagent::jj::find_all_session_changes_in(session_id, Some(repo.path()))?;
+    assert_eq!(all.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_cherry_unknown_session() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(repo.path())
+        .env_remove("JJAGENT_DISABLE")
+        .args(["session", "cherry", "missing-session", "--onto", "@"])
+        .output()?;
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("No change found for session ID"),
+        "Error should mention missing session, got: {}",
+        stderr
+    );
+
+    Ok(())
+}
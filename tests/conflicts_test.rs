@@ -0,0 +1,155 @@
+use anyhow::Result;
+use std::process::Command;
+use tempfile::TempDir;
+
+const SESSION_ID: &str = "conflicts-test-1111-2222-3333-4444444444";
+
+struct TestRepo {
+    dir: TempDir,
+}
+
+impl TestRepo {
+    fn new() -> Result<Self> {
+        let dir = TempDir::new()?;
+
+        // Initialize jj repo
+        let init_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["git", "init"])
+            .output()?;
+
+        if !init_output.status.success() {
+            anyhow::bail!(
+                "Failed to init jj repo: {}",
+                String::from_utf8_lossy(&init_output.stderr)
+            );
+        }
+
+        // Disable watchman for tests
+        let config_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["config", "set", "--repo", "fsmonitor.backend", "none"])
+            .output()?;
+
+        if !config_output.status.success() {
+            anyhow::bail!(
+                "Failed to disable watchman: {}",
+                String::from_utf8_lossy(&config_output.stderr)
+            );
+        }
+
+        let repo = Self { dir };
+        // Build: base -> session -> uwc (@)
+        std::fs::write(repo.path().join("file.txt"), "base\n")?;
+        repo.jj(&["describe", "-m", "base"])?;
+        repo.jj(&[
+            "new",
+            "-m",
+            &format!(
+                "jjagent: session conflict\n\nClaude-session-id: {}",
+                SESSION_ID
+            ),
+        ])?;
+        std::fs::write(repo.path().join("claude.txt"), "claude\n")?;
+        repo.jj(&["new", "-m", "uwc"])?;
+
+        Ok(repo)
+    }
+
+    fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+
+    /// Run a jj command in the repo and return its stdout
+    fn jj(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("jj")
+            .current_dir(self.path())
+            .args(args)
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj {:?} failed: {}",
+                args,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn jjagent(&self, args: &[&str]) -> Result<std::process::Output> {
+        Ok(Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .current_dir(self.path())
+            .args(args)
+            .output()?)
+    }
+
+    /// Leave a conflicted change on a branch off base that the session never touched
+    /// Returns its change ID
+    fn create_unrelated_conflict(&self) -> Result<String> {
+        self.jj(&["new", "description(exact:\"base\n\")", "-m", "ours"])?;
+        std::fs::write(self.path().join("file.txt"), "ours\n")?;
+        let ours = self.change_id("@")?;
+        self.jj(&["new", "description(exact:\"base\n\")", "-m", "theirs"])?;
+        std::fs::write(self.path().join("file.txt"), "theirs\n")?;
+        let theirs = self.change_id("@")?;
+        self.jj(&["rebase", "-r", &theirs, "-d", &ours])?;
+        Ok(theirs)
+    }
+
+    fn change_id(&self, rev: &str) -> Result<String> {
+        Ok(self
+            .jj(&["log", "-r", rev, "--no-graph", "-T", "change_id"])?
+            .trim()
+            .to_string())
+    }
+}
+
+#[test]
+fn test_conflicts_none() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    let output = repo.jjagent(&["conflicts"])?;
+    assert!(
+        output.status.success(),
+        "conflicts failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(output.stdout.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_conflicts_reports_conflicted_changes() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let conflicted = repo.create_unrelated_conflict()?;
+
+    let output = repo.jjagent(&["conflicts"])?;
+    assert!(!output.status.success(), "conflicts should exit non-zero");
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), conflicted);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Found 1 conflicted change(s)"),
+        "got: {}",
+        stderr
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_conflicts_scoped_to_session() -> Result<()> {
+    let repo = TestRepo::new()?;
+    repo.create_unrelated_conflict()?;
+
+    // The conflict isn't on or after the session change
+    let output = repo.jjagent(&["conflicts", "--session-id", SESSION_ID])?;
+    assert!(
+        output.status.success(),
+        "conflicts failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(output.stdout.is_empty());
+
+    Ok(())
+}
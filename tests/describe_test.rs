@@ -332,3 +332,153 @@ fn test_describe_finds_session_in_history() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_describe_in_editor_keeps_trailers() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let session_id = "editor-12345678-1234-5678-90ab-cdef12345678";
+
+    let new_output = Command::new("jj")
+        .current_dir(repo.path())
+        .args([
+            "new",
+            "-m",
+            &format!("Original title\n\nClaude-session-id: {}", session_id),
+        ])
+        .output()?;
+    assert!(new_output.status.success());
+
+    // A fake editor that records what it was given and overwrites it without any trailers
+    let seen = repo.path().join("seen.txt");
+    let editor = repo.path().join("editor.sh");
+    std::fs::write(
+        &editor,
+        format!(
+            "#!/bin/sh\ncp \"$1\" {}\nprintf 'Edited title\\n\\nA longer body.\\n' > \"$1\"\n",
+            seen.display()
+        ),
+    )?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(repo.path())
+        .env("EDITOR", format!("sh {}", editor.display()))
+        .args(["describe", session_id])
+        .output()?;
+    assert!(
+        output.status.success(),
+        "describe failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // The editor started from the title without trailers
+    let seen = std::fs::read_to_string(&seen)?;
+    assert!(seen.starts_with("Original title\n"), "got: {}", seen);
+    assert!(!seen.contains("Claude-session-id: "), "got: {}", seen);
+
+    let desc_output = Command::new("jj")
+        .current_dir(repo.path())
+        .args(["log", "-r", "@", "-T", "description", "--no-graph"])
+        .output()?;
+    assert_eq!(
+        String::from_utf8_lossy(&desc_output.stdout),
+        format!(
+            "Edited title\n\nA longer body.\n\nClaude-session-id: {}\n",
+            session_id
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_describe_without_message_or_editor_fails() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let session_id = "no-editor-12345678-1234-5678-90ab-cdef12345678";
+
+    let new_output = Command::new("jj")
+        .current_dir(repo.path())
+        .args([
+            "new",
+            "-m",
+            &format!("Original title\n\nClaude-session-id: {}", session_id),
+        ])
+        .output()?;
+    assert!(new_output.status.success());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(repo.path())
+        .env_remove("EDITOR")
+        .env_remove("VISUAL")
+        .args(["describe", session_id])
+        .output()?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("set $EDITOR or $VISUAL"), "got: {}", stderr);
+
+    Ok(())
+}
+
+#[test]
+fn test_describe_append_adds_lines_above_trailers() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let session_id = "append-12345678-1234-5678-90ab-cdef12345678";
+
+    let new_output = Command::new("jj")
+        .current_dir(repo.path())
+        .args([
+            "new",
+            "-m",
+            &format!(
+                "Refactor lock handling\n\nSigned-off-by: Test User <test@example.com>\nClaude-session-id: {}",
+                session_id
+            ),
+        ])
+        .output()?;
+    assert!(new_output.status.success());
+
+    for note in ["- split acquire and release", "- add timeout env var"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .current_dir(repo.path())
+            .args(["describe", session_id, "--append", note])
+            .output()?;
+        assert!(
+            output.status.success(),
+            "describe --append failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let desc_output = Command::new("jj")
+        .current_dir(repo.path())
+        .args(["log", "-r", "@", "-T", "description", "--no-graph"])
+        .output()?;
+    assert_eq!(
+        String::from_utf8_lossy(&desc_output.stdout),
+        format!(
+            "Refactor lock handling\n- split acquire and release\n- add timeout env var\n\n\
+             Signed-off-by: Test User <test@example.com>\nClaude-session-id: {}\n",
+            session_id
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_describe_append_conflicts_with_message() -> Result<()> {
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .args([
+            "describe",
+            "some-session",
+            "-m",
+            "title",
+            "--append",
+            "note",
+        ])
+        .output()?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("cannot be used with"), "got: {}", stderr);
+
+    Ok(())
+}
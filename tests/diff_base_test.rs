@@ -0,0 +1,145 @@
+use anyhow::Result;
+use std::process::Command;
+use tempfile::TempDir;
+
+struct TestRepo {
+    dir: TempDir,
+}
+
+impl TestRepo {
+    fn new() -> Result<Self> {
+        let dir = TempDir::new()?;
+
+        // Initialize jj repo
+        let init_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["git", "init"])
+            .output()?;
+
+        if !init_output.status.success() {
+            anyhow::bail!(
+                "Failed to init jj repo: {}",
+                String::from_utf8_lossy(&init_output.stderr)
+            );
+        }
+
+        // Disable watchman for tests
+        let config_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["config", "set", "--repo", "fsmonitor.backend", "none"])
+            .output()?;
+
+        if !config_output.status.success() {
+            anyhow::bail!(
+                "Failed to disable watchman: {}",
+                String::from_utf8_lossy(&config_output.stderr)
+            );
+        }
+
+        Ok(Self { dir })
+    }
+
+    fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+
+    /// Run a jj command in the repo and return its trimmed stdout
+    fn jj(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("jj")
+            .current_dir(self.path())
+            .args(args)
+            .output()?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj {:?} failed: {}",
+                args,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Build a session change whose edit to file.txt conflicts with a sibling,
+    /// then rebase the session onto the sibling. Returns the session change ID
+    fn with_conflicted_session(&self, session_id: &str) -> Result<String> {
+        self.jj(&["describe", "-m", "base"])?;
+        std::fs::write(self.path().join("file.txt"), "base\n")?;
+
+        let session_message = format!(
+            "jjagent: session diffbase\n\nClaude-session-id: {}",
+            session_id
+        );
+        self.jj(&["new", "-m", &session_message])?;
+        std::fs::write(self.path().join("file.txt"), "session\n")?;
+        let session = self.jj(&["log", "-r", "@", "--no-graph", "-T", "change_id"])?;
+
+        self.jj(&["new", "-m", "uwc", "@-"])?;
+        std::fs::write(self.path().join("file.txt"), "uwc\n")?;
+        self.jj(&["rebase", "-r", &session, "-d", "@"])?;
+
+        Ok(session)
+    }
+}
+
+#[test]
+fn test_diff_base_shows_all_three_sides() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let session_id = "diffbase-12345678-1234-5678-90ab-cdef12345678";
+    let session = repo.with_conflicted_session(session_id)?;
+
+    let files = jjagent::jj::get_conflict_sides_in(&session, Some(repo.path()))?;
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].path, "file.txt");
+    assert_eq!(files[0].hunks.len(), 1);
+    assert_eq!(files[0].hunks[0].bases, vec!["base\n"]);
+    assert_eq!(files[0].hunks[0].sides, vec!["uwc\n", "session\n"]);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(repo.path())
+        .args(["session", "diff-base", session_id])
+        .output()?;
+    assert!(
+        output.status.success(),
+        "diff-base should succeed, stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("=== base ===\nbase\n"), "got: {}", stdout);
+    assert!(
+        stdout.contains("=== uwc side ===\nuwc\n"),
+        "got: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("=== session side ===\nsession\n"),
+        "got: {}",
+        stdout
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_base_reports_no_conflicts() -> Result<()> {
+    let repo = TestRepo::new()?;
+    repo.jj(&[
+        "describe",
+        "-m",
+        "jjagent: session clean\n\nClaude-session-id: clean-session",
+    ])?;
+    std::fs::write(repo.path().join("file.txt"), "clean\n")?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(repo.path())
+        .args(["session", "diff-base", "clean-session"])
+        .output()?;
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No conflicts found"));
+
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use anyhow::Result;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn doctor(dir: &std::path::Path) -> Result<std::process::Output> {
+    Ok(Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(dir)
+        .arg("doctor")
+        .output()?)
+}
+
+fn jj(dir: &std::path::Path, args: &[&str]) -> Result<()> {
+    let output = Command::new("jj").current_dir(dir).args(args).output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_doctor_fails_without_jj() -> Result<()> {
+    let dir = TempDir::new()?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(dir.path())
+        .env("JJAGENT_JJ_BIN", dir.path().join("no-such-jj"))
+        .arg("doctor")
+        .output()?;
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.starts_with("[fail] jj: not found"),
+        "got: {}",
+        stdout
+    );
+    // Nothing else can be checked without jj
+    assert_eq!(stdout.lines().count(), 1, "got: {}", stdout);
+
+    Ok(())
+}
+
+#[test]
+fn test_doctor_warns_about_stale_lock_and_watchman() -> Result<()> {
+    let dir = TempDir::new()?;
+    jj(dir.path(), &["git", "init"])?;
+    jj(
+        dir.path(),
+        &["config", "set", "--repo", "fsmonitor.backend", "watchman"],
+    )?;
+    std::fs::write(
+        dir.path().join(".jj").join("jjagent-wc.lock"),
+        r#"{"pid":1,"session_id":"stale-session","acquired_at":0}"#,
+    )?;
+
+    let output = doctor(dir.path())?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "warnings aren't failures: {}",
+        stdout
+    );
+    assert!(stdout.contains("[pass] jj: jj "), "got: {}", stdout);
+    assert!(stdout.contains("[pass] repo: "), "got: {}", stdout);
+    assert!(stdout.contains("[pass] @ at head"), "got: {}", stdout);
+    assert!(stdout.contains("[pass] @ conflicts"), "got: {}", stdout);
+    assert!(
+        stdout.contains("[warn] lock: stale lock held by session stale-session"),
+        "got: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("[warn] fsmonitor: watchman"),
+        "got: {}",
+        stdout
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_doctor_fails_when_at_has_descendants() -> Result<()> {
+    let dir = TempDir::new()?;
+    jj(dir.path(), &["git", "init"])?;
+    jj(
+        dir.path(),
+        &["config", "set", "--repo", "fsmonitor.backend", "none"],
+    )?;
+    jj(dir.path(), &["new"])?;
+    jj(dir.path(), &["prev", "--edit"])?;
+
+    let output = doctor(dir.path())?;
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[fail] @ at head"), "got: {}", stdout);
+    assert!(stdout.contains("[pass] fsmonitor: none"), "got: {}", stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("1 check(s) failed"), "got: {}", stderr);
+
+    Ok(())
+}
+
+#[test]
+fn test_doctor_warns_about_hooks_for_another_binary() -> Result<()> {
+    let dir = TempDir::new()?;
+    let home = TempDir::new()?;
+    jj(dir.path(), &["git", "init"])?;
+    let run_doctor = || {
+        Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .current_dir(dir.path())
+            .env("HOME", home.path())
+            .arg("doctor")
+            .output()
+    };
+
+    // Hooks written by this binary pass
+    let settings = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .args(["claude", "settings"])
+        .output()?;
+    std::fs::create_dir(home.path().join(".claude"))?;
+    std::fs::write(
+        home.path().join(".claude").join("settings.json"),
+        &settings.stdout,
+    )?;
+    let stdout = String::from_utf8_lossy(&run_doctor()?.stdout).to_string();
+    assert!(stdout.contains("[pass] hooks: "), "got: {}", stdout);
+
+    // Project hooks from an old install are reported with the binary they run
+    std::fs::create_dir(dir.path().join(".claude"))?;
+    std::fs::write(
+        dir.path().join(".claude").join("settings.json"),
+        r#"{"hooks":{"PreToolUse":[{"matcher":"Edit","hooks":[{"type":"command","command":"/opt/old/jjagent claude hooks PreToolUse"}]}]}}"#,
+    )?;
+    let output = run_doctor()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "warnings aren't failures: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("[warn] hooks: ") && stdout.contains("runs /opt/old/jjagent"),
+        "got: {}",
+        stdout
+    );
+    assert!(
+        !stdout.contains(&home.path().display().to_string()),
+        "user hooks run this binary: {}",
+        stdout
+    );
+
+    Ok(())
+}
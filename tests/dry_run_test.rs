@@ -0,0 +1,97 @@
+//! Integration test for JJAGENT_DRY_RUN leaving the repo untouched
+
+use anyhow::Result;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+const SESSION_ID: &str = "dryrun-1-2222-3333-4444-555555555555";
+
+fn jj(repo_path: &std::path::Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("jj")
+        .current_dir(repo_path)
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Run a hook in dry-run mode, returning its stderr
+fn run_dry_hook(repo_path: &std::path::Path, hook: &str) -> Result<String> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .args(["claude", "hooks", hook])
+        .current_dir(repo_path)
+        .env("JJAGENT_DRY_RUN", "1")
+        .env("JJAGENT_POSTTOOL_DELAY_MS", "0")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(format!(r#"{{"session_id":"{}"}}"#, SESSION_ID).as_bytes())?;
+    }
+
+    let output = child.wait_with_output()?;
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    if !output.status.success() {
+        anyhow::bail!("{} failed: {}", hook, stderr);
+    }
+    Ok(stderr)
+}
+
+#[test]
+fn test_dry_run_cycle_creates_no_commits() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let repo_path = temp_dir.path();
+    jj(repo_path, &["git", "init"])?;
+    jj(
+        repo_path,
+        &["config", "set", "--repo", "fsmonitor.backend", "none"],
+    )?;
+    jj(repo_path, &["describe", "-m", "uwc"])?;
+
+    let all_changes = || {
+        jj(
+            repo_path,
+            &[
+                "log",
+                "-r",
+                "all()",
+                "--no-graph",
+                "-T",
+                "change_id ++ \"\\n\"",
+            ],
+        )
+    };
+    let before = all_changes()?;
+
+    let pretool = run_dry_hook(repo_path, "PreToolUse")?;
+    assert!(
+        pretool.contains("Dry run - would run: jj new"),
+        "got: {}",
+        pretool
+    );
+    std::fs::write(repo_path.join("claude.txt"), "claude\n")?;
+    let posttool = run_dry_hook(repo_path, "PostToolUse")?;
+    assert!(
+        posttool.contains("Dry run - would run: jj squash"),
+        "got: {}",
+        posttool
+    );
+
+    assert_eq!(all_changes()?, before);
+    let wc = jj(
+        repo_path,
+        &["log", "-r", "@", "--no-graph", "-T", "description"],
+    )?;
+    assert_eq!(wc.trim(), "uwc");
+
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use anyhow::Result;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+struct TestRepo {
+    dir: TempDir,
+}
+
+impl TestRepo {
+    fn new() -> Result<Self> {
+        let dir = TempDir::new()?;
+
+        // Initialize jj repo
+        let init_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["git", "init"])
+            .output()?;
+
+        if !init_output.status.success() {
+            anyhow::bail!(
+                "Failed to init jj repo: {}",
+                String::from_utf8_lossy(&init_output.stderr)
+            );
+        }
+
+        // Disable watchman for tests
+        let config_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["config", "set", "--repo", "fsmonitor.backend", "none"])
+            .output()?;
+
+        if !config_output.status.success() {
+            anyhow::bail!(
+                "Failed to disable watchman: {}",
+                String::from_utf8_lossy(&config_output.stderr)
+            );
+        }
+
+        // Give the user a working copy change on top
+        let output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["describe", "-m", "uwc"])
+            .output()?;
+        assert!(output.status.success());
+
+        Ok(Self { dir })
+    }
+
+    fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+
+    /// Simulate one Claude tool call writing several files
+    fn tool_call(&self, session_id: &str, files: &[&str]) -> Result<()> {
+        self.run_hook("PreToolUse", session_id, "Write")?;
+        for file in files {
+            std::fs::write(self.path().join(file), "content")?;
+        }
+        self.run_hook("PostToolUse", session_id, "Write")
+    }
+
+    fn run_hook(&self, hook_name: &str, session_id: &str, tool_name: &str) -> Result<()> {
+        let hook_input = format!(
+            r#"{{"session_id":"{}","tool_name":"{}"}}"#,
+            session_id, tool_name
+        );
+
+        let mut child = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .current_dir(self.path())
+            .env_remove("JJAGENT_DISABLE")
+            .env_remove("JJAGENT_LOG")
+            .env_remove("JJAGENT_LOG_FILE")
+            .env("JJAGENT_EXCLUDE_PATHS", ".env, secrets/**")
+            .env("JJAGENT_POSTTOOL_DELAY_MS", "0")
+            .args(["claude", "hooks", hook_name])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(hook_input.as_bytes())?;
+        }
+
+        let output = child.wait_with_output()?;
+        assert!(
+            output.status.success(),
+            "{} hook failed: {}",
+            hook_name,
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_excluded_paths_stay_out_of_session_change() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let session_id = "eeeeeeee-1111-2222-3333-444444444444";
+
+    repo.tool_call(session_id, &[".env", "app.txt"])?;
+
+    let sessions = jjagent::jj::list_sessions_in(Some(repo.path()))?;
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].files, vec!["app.txt"]);
+
+    // The excluded file is kept in the user's working copy change instead
+    let output = Command::new("jj")
+        .current_dir(repo.path())
+        .args(["diff", "-r", "@", "--name-only"])
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), ".env");
+    assert!(repo.path().join(".env").exists());
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+#![cfg(unix)]
+
+use anyhow::Result;
+use std::os::unix::fs::PermissionsExt;
+use std::process::Command;
+use tempfile::TempDir;
+
+/// Install a fake jj that records its arguments and prints nothing
+fn recording_jj(dir: &TempDir) -> Result<std::path::PathBuf> {
+    let script = dir.path().join("jj-recorder");
+    std::fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\nprintf '%s\\n' \"$@\" >> '{}'\necho >> '{}'\n",
+            dir.path().join("args").display(),
+            dir.path().join("args").display()
+        ),
+    )?;
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+    Ok(script)
+}
+
+#[test]
+fn test_find_session_change_anywhere_passes_limit() -> Result<()> {
+    let dir = TempDir::new()?;
+    let jj = recording_jj(&dir)?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(dir.path())
+        .env("JJAGENT_JJ_BIN", &jj)
+        .args(["change-id", "limit-test-session"])
+        .output()?;
+
+    // The fake jj finds nothing, so the command reports a missing session
+    assert!(!output.status.success());
+
+    let recorded = std::fs::read_to_string(dir.path().join("args"))?;
+    let invocations: Vec<Vec<&str>> = recorded
+        .split("\n\n")
+        .map(|args| args.lines().collect())
+        .filter(|args: &Vec<&str>| !args.is_empty())
+        .collect();
+    assert_eq!(invocations.len(), 1, "got: {:?}", invocations);
+
+    let args = &invocations[0];
+    assert_eq!(args[..2], ["--no-pager", "log"]);
+    let limit = args
+        .iter()
+        .position(|a| *a == "--limit")
+        .expect("jj log should be called with --limit");
+    assert_eq!(args[limit + 1], "1");
+
+    Ok(())
+}
@@ -0,0 +1,171 @@
+//! Integration tests for `jjagent gc`, which abandons precommits left by interrupted tool calls
+
+use anyhow::Result;
+use std::process::Command;
+use tempfile::TempDir;
+
+struct TestRepo {
+    dir: TempDir,
+}
+
+impl TestRepo {
+    fn new() -> Result<Self> {
+        let dir = TempDir::new()?;
+        let repo = Self { dir };
+        repo.jj(&["git", "init"])?;
+        repo.jj(&["config", "set", "--repo", "fsmonitor.backend", "none"])?;
+        std::fs::write(repo.path().join("user.txt"), "user\n")?;
+        repo.jj(&["describe", "-m", "uwc"])?;
+        Ok(repo)
+    }
+
+    fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+
+    /// Run a jj command in the repo and return its stdout
+    fn jj(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("jj")
+            .current_dir(self.path())
+            .args(args)
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj {:?} failed: {}",
+                args,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn gc(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .current_dir(self.path())
+            .arg("gc")
+            .args(args)
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "jjagent gc failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn change_id(&self, rev: &str) -> Result<String> {
+        Ok(self
+            .jj(&["log", "-r", rev, "--no-graph", "-T", "change_id"])?
+            .trim()
+            .to_string())
+    }
+
+    /// Session IDs of the precommits still in the repo, sorted
+    fn precommit_sessions(&self) -> Result<Vec<String>> {
+        let output = self.jj(&[
+            "log",
+            "-r",
+            r#"description(substring:"Claude-precommit-session-id: ")"#,
+            "--no-graph",
+            "-T",
+            r#"trailers.map(|t| if(t.key() == "Claude-precommit-session-id", t.value())).join("") ++ "\n""#,
+        ])?;
+        let mut sessions: Vec<String> = output.lines().map(String::from).collect();
+        sessions.sort();
+        Ok(sessions)
+    }
+}
+
+fn precommit_message(session_id: &str) -> String {
+    format!(
+        "jjagent: precommit {}\n\nClaude-precommit-session-id: {}",
+        &session_id[..8],
+        session_id
+    )
+}
+
+#[test]
+fn test_gc_abandons_orphaned_precommits() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let uwc = repo.change_id("@")?;
+
+    // An empty precommit beside @, as an interrupted PreToolUse leaves behind
+    repo.jj(&[
+        "new",
+        "--no-edit",
+        "-m",
+        &precommit_message("orphan-empty-1"),
+    ])?;
+
+    // A recent precommit with edits, which might still be wanted
+    repo.jj(&["new", "-m", &precommit_message("orphan-edits-2")])?;
+    std::fs::write(repo.path().join("claude.txt"), "claude\n")?;
+    repo.jj(&["edit", &uwc])?;
+
+    // An empty precommit for a session that holds the lock, whose tool may still be running
+    repo.jj(&[
+        "new",
+        "--no-edit",
+        "-m",
+        &precommit_message("orphan-locked-3"),
+    ])?;
+    jjagent::lock::acquire_lock_in("orphan-locked-3", repo.path(), Default::default())?;
+
+    // An empty precommit at @
+    repo.jj(&["new", "-m", &precommit_message("orphan-at-wc-4")])?;
+
+    let stdout = repo.gc(&["--dry-run"])?;
+    assert!(stdout.contains("orphan-empty-1"), "got: {}", stdout);
+    assert!(stdout.contains("orphan-at-wc-4"), "got: {}", stdout);
+    assert!(!stdout.contains("orphan-edits-2"), "got: {}", stdout);
+    assert!(!stdout.contains("orphan-locked-3"), "got: {}", stdout);
+    assert_eq!(repo.precommit_sessions()?.len(), 4, "dry run abandoned");
+
+    repo.gc(&[])?;
+    assert_eq!(
+        repo.precommit_sessions()?,
+        vec!["orphan-edits-2", "orphan-locked-3"]
+    );
+    assert_eq!(repo.change_id("@")?, uwc, "@ should move back to uwc");
+
+    // Non-empty precommits go once they're older than the threshold
+    repo.gc(&["--older-than", "0"])?;
+    assert_eq!(repo.precommit_sessions()?, vec!["orphan-locked-3"]);
+
+    jjagent::lock::release_lock_in("orphan-locked-3", repo.path(), Default::default())?;
+    repo.gc(&[])?;
+    assert!(repo.precommit_sessions()?.is_empty());
+    assert_eq!(repo.change_id("@")?, uwc);
+    assert_eq!(
+        std::fs::read_to_string(repo.path().join("user.txt"))?,
+        "user\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_gc_keeps_working_copy_precommit_with_edits() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    repo.jj(&["new", "-m", &precommit_message("orphan-wc-edits")])?;
+    std::fs::write(repo.path().join("claude.txt"), "claude\n")?;
+    repo.jj(&["status"])?;
+    let precommit = repo.change_id("@")?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(repo.path())
+        .args(["gc", "--older-than", "0"])
+        .output()?;
+    assert!(output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("leaving the working copy precommit"),
+        "got: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(repo.change_id("@")?, precommit);
+    assert!(repo.path().join("claude.txt").exists());
+
+    Ok(())
+}
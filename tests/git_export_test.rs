@@ -0,0 +1,92 @@
+//! Integration test for JJAGENT_GIT_EXPORT in a colocated jj+git repo
+
+use anyhow::Result;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+const SESSION_ID: &str = "gitexport-2222-3333-4444-555555555555";
+
+fn run(program: &str, repo_path: &std::path::Path, args: &[&str]) -> Result<String> {
+    let output = Command::new(program)
+        .current_dir(repo_path)
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} {:?} failed: {}",
+            program,
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn run_hook(repo_path: &std::path::Path, hook: &str) -> Result<()> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .args(["claude", "hooks", hook])
+        .current_dir(repo_path)
+        .env("JJAGENT_GIT_EXPORT", "1")
+        .env("JJAGENT_POSTTOOL_DELAY_MS", "0")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(format!(r#"{{"session_id":"{}"}}"#, SESSION_ID).as_bytes())?;
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} failed: {}",
+            hook,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_git_head_follows_session_in_colocated_repo() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let repo_path = temp_dir.path();
+    run("jj", repo_path, &["git", "init", "--colocate"])?;
+    run(
+        "jj",
+        repo_path,
+        &["config", "set", "--repo", "fsmonitor.backend", "none"],
+    )?;
+    assert!(jjagent::jj::is_colocated_in(Some(repo_path))?);
+
+    std::fs::write(repo_path.join("base.txt"), "base\n")?;
+    run("jj", repo_path, &["commit", "-m", "base"])?;
+    run("jj", repo_path, &["describe", "-m", "uwc"])?;
+    let base_head = run("git", repo_path, &["rev-parse", "HEAD"])?;
+
+    run_hook(repo_path, "PreToolUse")?;
+    std::fs::write(repo_path.join("claude.txt"), "claude\n")?;
+    run_hook(repo_path, "PostToolUse")?;
+
+    // uwc sits on the session change now, and git HEAD follows it
+    let session_commit = run(
+        "jj",
+        repo_path,
+        &["log", "-r", "@-", "--no-graph", "-T", "commit_id"],
+    )?;
+    let head = run("git", repo_path, &["rev-parse", "HEAD"])?;
+    assert_ne!(head, base_head);
+    assert_eq!(head, session_commit);
+
+    Ok(())
+}
+
+#[test]
+fn test_plain_jj_repo_is_not_colocated() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    run("jj", temp_dir.path(), &["git", "init"])?;
+    assert!(!jjagent::jj::is_colocated_in(Some(temp_dir.path()))?);
+    Ok(())
+}
@@ -0,0 +1,104 @@
+//! Integration tests for hooks acting on the repo named by the input's cwd
+
+use anyhow::Result;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+const SESSION_ID: &str = "hook-cwd-1111-2222-3333-444444444444";
+
+/// Run jj in the repo and return its trimmed stdout
+fn jj(repo: &std::path::Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("jj").current_dir(repo).args(args).output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Run a hook from `run_in`, telling it Claude is working in `cwd`
+fn run_hook(hook: &str, run_in: &std::path::Path, cwd: &std::path::Path) -> Result<()> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(run_in)
+        .args(["claude", "hooks", hook])
+        .env_remove("JJAGENT_DISABLE")
+        .env("JJAGENT_POSTTOOL_DELAY_MS", "0")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let input = serde_json::json!({"session_id": SESSION_ID, "cwd": cwd});
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.to_string().as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} failed: {}",
+            hook,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_hooks_use_cwd_from_input() -> Result<()> {
+    let repo = TempDir::new()?;
+    jj(repo.path(), &["git", "init"])?;
+    jj(
+        repo.path(),
+        &["config", "set", "--repo", "fsmonitor.backend", "none"],
+    )?;
+    jj(repo.path(), &["describe", "-m", "uwc"])?;
+    let subdir = repo.path().join("src");
+    std::fs::create_dir(&subdir)?;
+
+    // The hook process starts outside any repo
+    let elsewhere = TempDir::new()?;
+
+    run_hook("PreToolUse", elsewhere.path(), &subdir)?;
+    let description = jj(
+        repo.path(),
+        &["log", "-r", "@", "--no-graph", "-T", "description"],
+    )?;
+    assert!(
+        description.contains(&format!("Claude-precommit-session-id: {}", SESSION_ID)),
+        "got: {}",
+        description
+    );
+
+    std::fs::write(subdir.join("edit.txt"), "claude\n")?;
+    run_hook("PostToolUse", elsewhere.path(), &subdir)?;
+
+    let session_files = jj(
+        repo.path(),
+        &[
+            "log",
+            "-r",
+            &format!(
+                r#"description(substring:"Claude-session-id: {}")"#,
+                SESSION_ID
+            ),
+            "--no-graph",
+            "-T",
+            r#"self.diff().files().map(|f| f.path().display()).join(",")"#,
+        ],
+    )?;
+    assert_eq!(session_files, "src/edit.txt");
+    assert_eq!(
+        jj(
+            repo.path(),
+            &["log", "-r", "@", "--no-graph", "-T", "description"]
+        )?,
+        "uwc"
+    );
+
+    Ok(())
+}
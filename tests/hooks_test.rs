@@ -1,4 +1,4 @@
-use jjagent::hooks::{HookInput, HookResponse};
+use jjagent::hooks::{HookInput, HookResponse, HookStop};
 use std::io::Write;
 
 #[test]
@@ -18,6 +18,32 @@ fn test_hook_response_stop() {
     );
 }
 
+#[test]
+fn test_hook_response_from_error_includes_stop_code() {
+    let error = anyhow::Error::from(HookStop::new(HookStop::NOT_AT_HEAD, "not at a head"));
+    let json = serde_json::to_string(&HookResponse::from_error(&error)).unwrap();
+    assert_eq!(
+        json,
+        r#"{"continue":false,"stopReason":"not at a head","stopReasonCode":"NOT_AT_HEAD"}"#
+    );
+
+    // Errors without a code keep the plain stop response
+    let error = anyhow::anyhow!("something else");
+    let json = serde_json::to_string(&HookResponse::from_error(&error)).unwrap();
+    assert_eq!(json, r#"{"continue":false,"stopReason":"something else"}"#);
+}
+
+#[test]
+fn test_hook_response_from_error_marks_jj_timeout() {
+    let error = anyhow::Error::from(jjagent::jj::JjTimeout {
+        subcommand: "squash".to_string(),
+        timeout: std::time::Duration::from_secs(30),
+    })
+    .context("Failed to execute jj squash");
+    let response = HookResponse::from_error(&error);
+    assert_eq!(response.reason_code.as_deref(), Some(HookStop::JJ_TIMEOUT));
+}
+
 #[test]
 fn test_hook_response_stop_does_not_include_null_reason() {
     let response = HookResponse::continue_execution();
@@ -43,6 +69,8 @@ fn test_user_prompt_submit_hook_without_transcript() {
         tool_name: None,
         hook_event_name: Some("UserPromptSubmit".to_string()),
         transcript_path: None,
+        cwd: None,
+        verbosity: Default::default(),
     };
 
     let response = jjagent::hooks::handle_user_prompt_submit_hook(&input).unwrap();
@@ -66,6 +94,8 @@ fn test_user_prompt_submit_hook_first_session() {
         tool_name: None,
         hook_event_name: Some("UserPromptSubmit".to_string()),
         transcript_path: Some(transcript_path.to_string_lossy().to_string()),
+        cwd: None,
+        verbosity: Default::default(),
     };
 
     let response = jjagent::hooks::handle_user_prompt_submit_hook(&input).unwrap();
@@ -92,6 +122,8 @@ fn test_user_prompt_submit_hook_same_session() {
         tool_name: None,
         hook_event_name: Some("UserPromptSubmit".to_string()),
         transcript_path: Some(transcript_path.to_string_lossy().to_string()),
+        cwd: None,
+        verbosity: Default::default(),
     };
 
     let response = jjagent::hooks::handle_user_prompt_submit_hook(&input).unwrap();
@@ -116,6 +148,8 @@ fn test_user_prompt_submit_hook_different_session() {
         tool_name: None,
         hook_event_name: Some("UserPromptSubmit".to_string()),
         transcript_path: Some(transcript_path.to_string_lossy().to_string()),
+        cwd: None,
+        verbosity: Default::default(),
     };
 
     let response = jjagent::hooks::handle_user_prompt_submit_hook(&input).unwrap();
@@ -126,3 +160,94 @@ fn test_user_prompt_submit_hook_different_session() {
     assert!(json.contains("UserPromptSubmit"));
     assert!(json.contains("hookSpecificOutput"));
 }
+
+#[test]
+fn test_hook_input_missing_session_id() {
+    let err = HookInput::from_json(r#"{"tool_name":"Edit"}"#).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Hook input is missing required string field 'session_id'"
+    );
+}
+
+#[test]
+fn test_hook_input_session_id_wrong_type() {
+    let err = HookInput::from_json(r#"{"session_id":42}"#).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Hook input field 'session_id' must be a string, got a number"
+    );
+
+    let err = HookInput::from_json(r#"{"session_id":"abc","tool_name":["Edit"]}"#).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Hook input field 'tool_name' must be a string, got an array"
+    );
+
+    let err = HookInput::from_json(r#"{"session_id":"abc","cwd":{"path":"/repo"}}"#).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Hook input field 'cwd' must be a string, got an object"
+    );
+}
+
+#[test]
+fn test_hook_input_not_json() {
+    let err = HookInput::from_json("session_id=abc").unwrap_err();
+    assert!(
+        err.to_string().starts_with("Hook input is not valid JSON"),
+        "got: {}",
+        err
+    );
+
+    let err = HookInput::from_json("  ").unwrap_err();
+    assert!(err.to_string().contains("empty"), "got: {}", err);
+
+    let err = HookInput::from_json(r#""abc""#).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Hook input must be a JSON object, got a string"
+    );
+}
+
+#[test]
+fn test_hook_input_valid() {
+    let input = HookInput::from_json(r#"{"session_id":"abc","tool_name":null}"#).unwrap();
+    assert_eq!(input.session_id, "abc");
+    assert_eq!(input.tool_name, None);
+}
+
+#[test]
+fn test_session_start_hook_outputs_session_id_context() -> anyhow::Result<()> {
+    use std::process::{Command, Stdio};
+
+    let dir = tempfile::TempDir::new()?;
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(dir.path())
+        .env_remove("JJAGENT_DISABLE")
+        .args(["claude", "hooks", "SessionStart"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(br#"{"session_id":"start-1234-5678","hook_event_name":"SessionStart"}"#)?;
+
+    let output = child.wait_with_output()?;
+    assert!(output.status.success());
+    let response: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(response["continue"], true);
+    assert_eq!(
+        response["hookSpecificOutput"]["hookEventName"],
+        "SessionStart"
+    );
+    let context = response["hookSpecificOutput"]["additionalContext"]
+        .as_str()
+        .unwrap();
+    assert!(context.contains("start-1234-5678"), "got: {}", context);
+
+    Ok(())
+}
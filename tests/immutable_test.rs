@@ -98,7 +98,7 @@ fn test_find_session_change_excludes_immutable() -> Result<()> {
     let found = jj::find_session_change_anywhere_in(session_id, Some(repo.path()))?;
     assert!(found.is_some(), "Should find session change");
     assert_eq!(
-        found.as_ref().unwrap(),
+        &found.as_ref().unwrap().change_id,
         &change_id,
         "Should return correct change ID"
     );
@@ -168,14 +168,14 @@ fn test_find_session_change_finds_mutable_when_immutable_exists() -> Result<()>
     let found = jj::find_session_change_anywhere_in(session_id, Some(repo.path()))?;
     assert!(found.is_some(), "Should find a session change");
     assert_eq!(
-        found.as_ref().unwrap(),
+        &found.as_ref().unwrap().change_id,
         &mutable_change_id,
         "Should return the mutable change ID, not the immutable one"
     );
 
     // Verify the immutable one is not returned
     assert_ne!(
-        found.as_ref().unwrap(),
+        &found.as_ref().unwrap().change_id,
         &immutable_change_id,
         "Should not return the immutable change ID"
     );
@@ -183,6 +183,32 @@ fn test_find_session_change_finds_mutable_when_immutable_exists() -> Result<()>
     Ok(())
 }
 
+#[test]
+fn test_session_exists_excludes_immutable() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let session_id = "exists-test-12345678-1234-5678-90ab-cdef12345678";
+
+    assert!(!jj::session_exists_in(session_id, Some(repo.path()))?);
+
+    let session_message = format!(
+        "jjagent: session exists-t\n\nClaude-session-id: {}",
+        session_id
+    );
+    Command::new("jj")
+        .current_dir(repo.path())
+        .args(["new", "-m", &session_message])
+        .output()?;
+    assert!(jj::session_exists_in(session_id, Some(repo.path()))?);
+
+    // A session ID that is only a prefix of the real one doesn't count
+    assert!(!jj::session_exists_in("exists-test", Some(repo.path()))?);
+
+    repo.set_immutable_heads("builtin_immutable_heads() | @")?;
+    assert!(!jj::session_exists_in(session_id, Some(repo.path()))?);
+
+    Ok(())
+}
+
 #[test]
 fn test_find_session_change_in_excludes_immutable() -> Result<()> {
     let repo = TestRepo::new()?;
@@ -270,3 +296,60 @@ fn test_creates_new_session_when_all_immutable() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_mutating_commands_refuse_immutable_commits() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let session_id = "immutable-edit-12345678-1234-5678-90ab-cdef12345678";
+
+    // base (made immutable) -> uwc (@)
+    let base_message = format!(
+        "jjagent: session immutab\n\nClaude-session-id: {}",
+        session_id
+    );
+    Command::new("jj")
+        .current_dir(repo.path())
+        .args(["describe", "-m", &base_message])
+        .output()?;
+    Command::new("jj")
+        .current_dir(repo.path())
+        .args(["new", "-m", "uwc"])
+        .output()?;
+    let base = jj::get_change_id_in("@-", Some(repo.path()))?;
+
+    assert!(!jj::is_immutable_in("@-", Some(repo.path()))?);
+    repo.set_immutable_heads(&format!("builtin_immutable_heads() | {}", base))?;
+    assert!(jj::is_immutable_in("@-", Some(repo.path()))?);
+    assert!(!jj::is_immutable_in("@", Some(repo.path()))?);
+
+    let expected = format!("Cannot modify immutable commit {}", base);
+
+    let err = jj::move_session_into("other-session-1234", "@-", Some(repo.path()))
+        .expect_err("moving a session into an immutable commit should fail");
+    assert!(err.to_string().contains(&expected), "got: {:#}", err);
+
+    let err = jj::split_change("@-", None, Some(repo.path()))
+        .expect_err("splitting an immutable commit should fail");
+    assert!(err.to_string().contains(&expected), "got: {:#}", err);
+
+    // Every form of describe: -m, --append and $EDITOR
+    for args in [
+        vec!["describe", session_id, "-m", "New title"],
+        vec!["describe", session_id, "--append", "More"],
+        vec!["describe", session_id],
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .current_dir(repo.path())
+            .env("EDITOR", "true")
+            .args(&args)
+            .output()?;
+        assert!(!output.status.success(), "{:?} should fail", args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(&expected), "{:?} got: {}", args, stderr);
+    }
+
+    // Nothing was rewritten
+    assert_eq!(jj::get_change_id_in("@-", Some(repo.path()))?, base);
+
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use anyhow::Result;
+use std::process::Command;
+
+#[test]
+fn test_info_reports_versions_and_config() -> Result<()> {
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .env("JJAGENT_POSTTOOL_DELAY_MS", "250")
+        .arg("info")
+        .output()?;
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.starts_with(&format!("jjagent {}\n", env!("CARGO_PKG_VERSION"))),
+        "got: {}",
+        stdout
+    );
+    assert!(stdout.contains("\njj: "), "got: {}", stdout);
+    assert!(stdout.contains("\nexecutable: "), "got: {}", stdout);
+    assert!(
+        stdout.contains("  JJAGENT_POSTTOOL_DELAY_MS=250"),
+        "got: {}",
+        stdout
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_version_flag() -> Result<()> {
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .arg("--version")
+        .output()?;
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        format!("jjagent {}", env!("CARGO_PKG_VERSION"))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_bash_completions() -> Result<()> {
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .args(["completions", "bash"])
+        .output()?;
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.trim().is_empty());
+    assert!(stdout.contains("claude"), "got: {}", stdout);
+
+    Ok(())
+}
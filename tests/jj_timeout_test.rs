@@ -0,0 +1,113 @@
+#![cfg(unix)]
+
+use anyhow::Result;
+use serial_test::serial;
+use std::os::unix::fs::PermissionsExt;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+/// Install a fake jj that hangs well past any timeout the tests set
+fn hanging_jj(dir: &TempDir) -> Result<std::path::PathBuf> {
+    let script = dir.path().join("jj-hang");
+    std::fs::write(&script, "#!/bin/sh\nexec sleep 10\n")?;
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+    Ok(script)
+}
+
+#[test]
+#[serial]
+fn test_hung_jj_is_killed_after_timeout() -> Result<()> {
+    let dir = TempDir::new()?;
+    let jj = hanging_jj(&dir)?;
+    unsafe {
+        std::env::set_var("JJAGENT_JJ_BIN", &jj);
+        std::env::set_var("JJAGENT_JJ_TIMEOUT_SECS", "1");
+    }
+
+    let started = Instant::now();
+    let result = jjagent::jj::is_at_head_in(Some(dir.path()));
+    let elapsed = started.elapsed();
+
+    unsafe {
+        std::env::remove_var("JJAGENT_JJ_BIN");
+        std::env::remove_var("JJAGENT_JJ_TIMEOUT_SECS");
+    }
+
+    let error = result.expect_err("a hung jj should time out");
+    let timeout = error
+        .downcast_ref::<jjagent::jj::JjTimeout>()
+        .expect("error should be a JjTimeout");
+    assert_eq!(timeout.subcommand, "log");
+    assert_eq!(timeout.timeout, Duration::from_secs(1));
+    assert!(
+        format!("{:#}", error).contains("jj log timed out after 1s"),
+        "got: {:#}",
+        error
+    );
+    assert!(elapsed < Duration::from_secs(5), "took {:?}", elapsed);
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_jj_is_run_without_pager() -> Result<()> {
+    let dir = TempDir::new()?;
+    let script = dir.path().join("jj-args");
+    std::fs::write(&script, "#!/bin/sh\nprintf '%s\\n' \"$@\"\n")?;
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+
+    unsafe {
+        std::env::set_var("JJAGENT_JJ_BIN", &script);
+    }
+    let output = jjagent::jj::run_jj(jjagent::jj::jj_command().arg("status"));
+    unsafe {
+        std::env::remove_var("JJAGENT_JJ_BIN");
+    }
+
+    let output = output?;
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "--no-pager\nstatus\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_pretool_releases_lock_when_jj_times_out() -> Result<()> {
+    let dir = TempDir::new()?;
+    std::fs::create_dir(dir.path().join(".jj"))?;
+    // Answer `jj root` so the hook finds the repo, then hang on everything else
+    let script = dir.path().join("jj-hang-after-root");
+    std::fs::write(
+        &script,
+        "#!/bin/sh\ncase \"$*\" in\n  *\" root \"*) pwd ;;\n  *) exec sleep 10 ;;\nesac\n",
+    )?;
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(dir.path())
+        .env("JJAGENT_JJ_BIN", &script)
+        .env("JJAGENT_JJ_TIMEOUT_SECS", "1")
+        .args(["claude", "hooks", "PreToolUse"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        use std::io::Write;
+        stdin.write_all(br#"{"session_id":"timeout-1-2222-3333-4444-555555555555"}"#)?;
+    }
+    let output = child.wait_with_output()?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("timed out"), "got: {}", stderr);
+    // The lock isn't left behind for the next tool call to wait on
+    assert!(
+        !dir.path().join(".jj").join("jjagent-wc.lock").exists(),
+        "lock was not released"
+    );
+
+    Ok(())
+}
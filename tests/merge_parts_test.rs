@@ -0,0 +1,213 @@
+use anyhow::Result;
+use std::process::Command;
+use tempfile::TempDir;
+
+const SESSION_ID: &str = "merge-parts-1111-2222-3333-444444444444";
+
+struct TestRepo {
+    dir: TempDir,
+}
+
+impl TestRepo {
+    fn new() -> Result<Self> {
+        let dir = TempDir::new()?;
+
+        // Initialize jj repo
+        let init_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["git", "init"])
+            .output()?;
+
+        if !init_output.status.success() {
+            anyhow::bail!(
+                "Failed to init jj repo: {}",
+                String::from_utf8_lossy(&init_output.stderr)
+            );
+        }
+
+        // Disable watchman for tests
+        let config_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["config", "set", "--repo", "fsmonitor.backend", "none"])
+            .output()?;
+
+        if !config_output.status.success() {
+            anyhow::bail!(
+                "Failed to disable watchman: {}",
+                String::from_utf8_lossy(&config_output.stderr)
+            );
+        }
+
+        let repo = Self { dir };
+        // Build: base -> session -> uwc (@)
+        std::fs::write(repo.path().join("base.txt"), "base\n")?;
+        repo.jj(&["describe", "-m", "base"])?;
+        repo.jj(&[
+            "new",
+            "-m",
+            &format!(
+                "jjagent: session merge-pa\n\nClaude-session-id: {}",
+                SESSION_ID
+            ),
+        ])?;
+        std::fs::write(repo.path().join("claude.txt"), "claude\n")?;
+        repo.jj(&["new", "-m", "uwc"])?;
+        std::fs::write(repo.path().join("user.txt"), "user\n")?;
+
+        Ok(repo)
+    }
+
+    fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+
+    /// Run a jj command in the repo and return its stdout
+    fn jj(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("jj")
+            .current_dir(self.path())
+            .args(args)
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj {:?} failed: {}",
+                args,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn jjagent(&self, args: &[&str]) -> Result<std::process::Output> {
+        Ok(Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .current_dir(self.path())
+            .args(args)
+            .output()?)
+    }
+
+    fn change_id(&self, rev: &str) -> Result<String> {
+        Ok(self
+            .jj(&["log", "-r", rev, "--no-graph", "-T", "change_id"])?
+            .trim()
+            .to_string())
+    }
+
+    fn description(&self, rev: &str) -> Result<String> {
+        self.jj(&["log", "-r", rev, "--no-graph", "-T", "description"])
+    }
+
+    /// Insert a new session part between the latest part and uwc, writing a file in it
+    fn add_part(&self, title: &str, file: &str, content: &str) -> Result<()> {
+        self.jj(&[
+            "new",
+            "--insert-before",
+            "@",
+            "-m",
+            &format!("{}\n\nClaude-session-id: {}", title, SESSION_ID),
+        ])?;
+        std::fs::write(self.path().join(file), content)?;
+        self.jj(&["next", "--edit"])?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_merge_parts_squashes_into_first_change() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let session = repo.change_id("@-")?;
+    repo.add_part("jjagent: session merge-pa pt. 2", "second.txt", "second\n")?;
+    repo.add_part("Follow-up refactor", "third.txt", "third\n")?;
+
+    let output = repo.jjagent(&["merge-parts", SESSION_ID])?;
+    assert!(
+        output.status.success(),
+        "merge-parts failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), session);
+
+    assert_eq!(
+        jjagent::jj::find_all_session_changes_in(SESSION_ID, Some(repo.path()))?,
+        vec![session]
+    );
+    assert_eq!(
+        repo.description("@-")?,
+        format!(
+            "jjagent: session merge-pa\n\nFollow-up refactor\n\nClaude-session-id: {}\n",
+            SESSION_ID
+        )
+    );
+    let files = repo.jj(&["diff", "-r", "@-", "--name-only"])?;
+    assert_eq!(files, "claude.txt\nsecond.txt\nthird.txt\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_merge_parts_rolls_back_on_conflict() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let session = repo.change_id("@-")?;
+    let uwc = repo.change_id("@")?;
+    repo.add_part(
+        "jjagent: session merge-pa pt. 2",
+        "claude.txt",
+        "claude again\n",
+    )?;
+    // A side branch on the first change also edits claude.txt, so folding part 2 into
+    // the first change would rebase the side branch into a conflict
+    repo.jj(&["new", &session, "-m", "side branch"])?;
+    std::fs::write(repo.path().join("claude.txt"), "side\n")?;
+    repo.jj(&["edit", &uwc])?;
+
+    let output = repo.jjagent(&["merge-parts", SESSION_ID])?;
+    assert!(!output.status.success(), "merge-parts should refuse");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("would introduce conflicts"),
+        "got: {}",
+        stderr
+    );
+
+    // Both parts are still there and nothing else changed
+    assert_eq!(
+        jjagent::jj::find_all_session_changes_in(SESSION_ID, Some(repo.path()))?.len(),
+        2
+    );
+    let conflicted = repo.jj(&["log", "-r", "conflicts()", "--no-graph", "-T", "change_id"])?;
+    assert!(conflicted.is_empty(), "conflicts left behind");
+
+    Ok(())
+}
+
+#[test]
+fn test_find_furthest_session_change_returns_top_part() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let session = repo.change_id("@-")?;
+
+    assert_eq!(
+        jjagent::jj::find_furthest_session_change_in(SESSION_ID, Some(repo.path()))?
+            .map(|change| change.change_id),
+        Some(session)
+    );
+
+    repo.add_part("jjagent: session merge-pa pt. 2", "second.txt", "second\n")?;
+    repo.add_part("jjagent: session merge-pa pt. 3", "third.txt", "third\n")?;
+    let third = repo.change_id("@-")?;
+    let latest = jjagent::jj::find_furthest_session_change_in(SESSION_ID, Some(repo.path()))?
+        .expect("session should have a latest part");
+    assert_eq!(latest.change_id, third);
+    assert_eq!(latest.part, Some(3));
+
+    // Parts are compared by position, so a part stacked above it is the latest one
+    repo.add_part(
+        "jjagent: session merge-pa (split)",
+        "fourth.txt",
+        "fourth\n",
+    )?;
+    assert_eq!(
+        jjagent::jj::find_furthest_session_change_in(SESSION_ID, Some(repo.path()))?
+            .map(|change| change.change_id),
+        Some(repo.change_id("@-")?)
+    );
+
+    Ok(())
+}
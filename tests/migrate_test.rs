@@ -0,0 +1,135 @@
+use anyhow::Result;
+use std::process::Command;
+use tempfile::TempDir;
+
+struct TestRepo {
+    dir: TempDir,
+}
+
+impl TestRepo {
+    fn new() -> Result<Self> {
+        let dir = TempDir::new()?;
+
+        // Initialize jj repo
+        let init_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["git", "init"])
+            .output()?;
+
+        if !init_output.status.success() {
+            anyhow::bail!(
+                "Failed to init jj repo: {}",
+                String::from_utf8_lossy(&init_output.stderr)
+            );
+        }
+
+        // Disable watchman for tests
+        let config_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["config", "set", "--repo", "fsmonitor.backend", "none"])
+            .output()?;
+
+        if !config_output.status.success() {
+            anyhow::bail!(
+                "Failed to disable watchman: {}",
+                String::from_utf8_lossy(&config_output.stderr)
+            );
+        }
+
+        Ok(Self { dir })
+    }
+
+    fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+}
+
+#[test]
+fn test_migrate_rewrites_legacy_session_trailer() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let session_id = "legacy-12345678-1234-5678-90ab-cdef12345678";
+
+    // A session change written with the old trailer key
+    let output = Command::new("jj")
+        .current_dir(repo.path())
+        .args([
+            "new",
+            "-m",
+            &format!(
+                "jjagent: session legacy-1\n\nJjagent-claude-session-id: {}",
+                session_id
+            ),
+        ])
+        .output()?;
+    assert!(output.status.success());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(repo.path())
+        .args(["migrate"])
+        .output()?;
+    assert!(
+        output.status.success(),
+        "migrate failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 1);
+
+    let description = Command::new("jj")
+        .current_dir(repo.path())
+        .args(["log", "-r", "@", "--no-graph", "-T", "description"])
+        .output()?;
+    assert_eq!(
+        String::from_utf8_lossy(&description.stdout),
+        format!(
+            "jjagent: session legacy-1\n\nClaude-session-id: {}\n",
+            session_id
+        )
+    );
+    assert!(jjagent::jj::find_session_change_anywhere_in(session_id, Some(repo.path()))?.is_some());
+
+    Ok(())
+}
+
+#[test]
+fn test_lookups_find_unmigrated_legacy_session() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let session_id = "legacy-87654321-1234-5678-90ab-cdef12345678";
+
+    let output = Command::new("jj")
+        .current_dir(repo.path())
+        .args([
+            "new",
+            "-m",
+            &format!(
+                "jjagent: session legacy-8\n\nJjagent-claude-session-id: {}",
+                session_id
+            ),
+        ])
+        .output()?;
+    assert!(output.status.success());
+
+    let change = jjagent::jj::find_session_change_anywhere_in(session_id, Some(repo.path()))?
+        .expect("legacy session change should be found before migrating");
+    assert_eq!(
+        jjagent::jj::find_all_session_changes_in(session_id, Some(repo.path()))?,
+        vec![change.change_id.clone()]
+    );
+    assert_eq!(
+        jjagent::jj::get_session_id_in("@", Some(repo.path()))?.as_deref(),
+        Some(session_id)
+    );
+    assert_eq!(
+        jjagent::jj::get_current_commit_session_id_in(Some(repo.path()))?.as_deref(),
+        Some(session_id)
+    );
+    assert_eq!(
+        jjagent::jj::count_session_parts_in(session_id, Some(repo.path()))?,
+        1
+    );
+
+    let sessions = jjagent::jj::list_sessions_in(Some(repo.path()))?;
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].session_id, session_id);
+
+    Ok(())
+}
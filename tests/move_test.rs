@@ -0,0 +1,123 @@
+use anyhow::Result;
+use std::process::Command;
+use tempfile::TempDir;
+
+const SESSION_ID: &str = "move-test-1111-2222-3333-444444444444";
+
+struct TestRepo {
+    dir: TempDir,
+}
+
+impl TestRepo {
+    fn new() -> Result<Self> {
+        let dir = TempDir::new()?;
+
+        // Initialize jj repo
+        let init_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["git", "init"])
+            .output()?;
+
+        if !init_output.status.success() {
+            anyhow::bail!(
+                "Failed to init jj repo: {}",
+                String::from_utf8_lossy(&init_output.stderr)
+            );
+        }
+
+        // Disable watchman for tests
+        let config_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["config", "set", "--repo", "fsmonitor.backend", "none"])
+            .output()?;
+
+        if !config_output.status.success() {
+            anyhow::bail!(
+                "Failed to disable watchman: {}",
+                String::from_utf8_lossy(&config_output.stderr)
+            );
+        }
+
+        let repo = Self { dir };
+        // Build: feature -> uwc (@)
+        std::fs::write(repo.path().join("feature.txt"), "feature\n")?;
+        repo.jj(&["describe", "-m", "Add feature"])?;
+        repo.jj(&["new", "-m", "uwc"])?;
+
+        Ok(repo)
+    }
+
+    fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+
+    /// Run a jj command in the repo and return its stdout
+    fn jj(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("jj")
+            .current_dir(self.path())
+            .args(args)
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj {:?} failed: {}",
+                args,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn jjagent(&self, args: &[&str]) -> Result<std::process::Output> {
+        Ok(Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .current_dir(self.path())
+            .args(args)
+            .output()?)
+    }
+}
+
+#[test]
+fn test_into_attributes_change_and_prints_its_id() -> Result<()> {
+    // `move` is an alias for `into`
+    for command in ["into", "move"] {
+        let repo = TestRepo::new()?;
+        let feature = repo.jj(&["log", "-r", "@-", "--no-graph", "-T", "change_id"])?;
+
+        let output = repo.jjagent(&[command, SESSION_ID, "@-"])?;
+        assert!(
+            output.status.success(),
+            "{} failed: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout).trim(),
+            feature.trim()
+        );
+
+        let description = repo.jj(&["log", "-r", "@-", "--no-graph", "-T", "description"])?;
+        assert_eq!(
+            description,
+            format!("Add feature\n\nClaude-session-id: {}\n", SESSION_ID)
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_move_refuses_working_copy() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    let output = repo.jjagent(&["move", SESSION_ID, "@"])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("'@' is not an ancestor of the working copy"),
+        "got: {}",
+        stderr
+    );
+    assert!(!stderr.contains("Error: Error:"), "got: {}", stderr);
+    assert!(String::from_utf8_lossy(&output.stdout).is_empty());
+
+    Ok(())
+}
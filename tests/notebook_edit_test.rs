@@ -0,0 +1,145 @@
+//! Integration test for tracking NotebookEdit tool calls on Jupyter notebooks
+
+use anyhow::Result;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+const SESSION_ID: &str = "notebook-1111-2222-3333-444444444444";
+
+fn jj(repo_path: &std::path::Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("jj")
+        .current_dir(repo_path)
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Run a hook the way Claude Code does for a NotebookEdit tool call
+fn run_hook(repo_path: &std::path::Path, hook: &str) -> Result<()> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .args(["claude", "hooks", hook])
+        .current_dir(repo_path)
+        .env_remove("JJAGENT_TRACK_TOOLS")
+        .env("JJAGENT_POSTTOOL_DELAY_MS", "0")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(
+            format!(
+                r#"{{"session_id":"{}","tool_name":"NotebookEdit"}}"#,
+                SESSION_ID
+            )
+            .as_bytes(),
+        )?;
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} failed: {}",
+            hook,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
+/// A notebook with `cells` code cells, each with a sizeable output, as one JSON document
+fn notebook(cells: usize, source: &str) -> String {
+    let cells: Vec<serde_json::Value> = (0..cells)
+        .map(|i| {
+            serde_json::json!({
+                "cell_type": "code",
+                "execution_count": i,
+                "metadata": {},
+                "source": [format!("{} # cell {}\n", source, i)],
+                "outputs": [{
+                    "output_type": "stream",
+                    "name": "stdout",
+                    "text": vec![format!("row {}\n", i); 50],
+                }],
+            })
+        })
+        .collect();
+    serde_json::to_string_pretty(&serde_json::json!({
+        "cells": cells,
+        "metadata": {},
+        "nbformat": 4,
+        "nbformat_minor": 5,
+    }))
+    .unwrap()
+}
+
+#[test]
+fn test_notebook_edits_land_in_session_change() -> Result<()> {
+    let dir = TempDir::new()?;
+    let repo_path = dir.path();
+    jj(repo_path, &["git", "init"])?;
+    jj(
+        repo_path,
+        &["config", "set", "--repo", "fsmonitor.backend", "none"],
+    )?;
+    jj(repo_path, &["describe", "-m", "uwc"])?;
+
+    // Creating the notebook, then rewriting every cell of it, like two NotebookEdit calls
+    let path = repo_path.join("analysis.ipynb");
+    let first = notebook(500, "print('hello')");
+    let second = notebook(500, "print('goodbye')");
+    assert!(second.len() > 500_000, "notebook should be large");
+
+    for contents in [&first, &second] {
+        run_hook(repo_path, "PreToolUse")?;
+        std::fs::write(&path, contents)?;
+        run_hook(repo_path, "PostToolUse")?;
+    }
+
+    let sessions = jjagent::jj::list_sessions_in(Some(repo_path))?;
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].session_id, SESSION_ID);
+    assert_eq!(
+        sessions[0].change_ids.len(),
+        1,
+        "both edits go in one change"
+    );
+    assert_eq!(sessions[0].files, vec!["analysis.ipynb"]);
+
+    // The session change holds the final notebook, and the user's change is untouched
+    let in_session = jj(
+        repo_path,
+        &[
+            "file",
+            "show",
+            "-r",
+            "@-",
+            "analysis.ipynb",
+            "--ignore-working-copy",
+        ],
+    )?;
+    assert_eq!(in_session, second);
+    assert_eq!(
+        jj(repo_path, &["diff", "-r", "@", "--name-only"])?.trim(),
+        ""
+    );
+    assert_eq!(
+        jj(
+            repo_path,
+            &["log", "-r", "@", "--no-graph", "-T", "description"]
+        )?
+        .trim(),
+        "uwc"
+    );
+    assert_eq!(std::fs::read_to_string(&path)?, second);
+
+    Ok(())
+}
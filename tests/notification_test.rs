@@ -0,0 +1,142 @@
+//! Integration test for the Notification hook reporting who holds the working copy lock
+
+use anyhow::Result;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+const SESSION_ID: &str = "notify-1-2222-3333-4444-555555555555";
+const OTHER_SESSION_ID: &str = "holder-1-2222-3333-4444-555555555555";
+
+fn jj(repo_path: &std::path::Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("jj")
+        .current_dir(repo_path)
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Run the Notification hook, returning its parsed JSON response
+fn run_notification_hook(repo_path: &std::path::Path) -> Result<serde_json::Value> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .args(["claude", "hooks", "Notification"])
+        .current_dir(repo_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(
+            format!(
+                r#"{{"session_id":"{}","message":"Claude is waiting for your input"}}"#,
+                SESSION_ID
+            )
+            .as_bytes(),
+        )?;
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Notification failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+#[test]
+fn test_notification_reports_lock_held_by_another_session() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let repo_path = temp_dir.path();
+    jj(repo_path, &["git", "init"])?;
+    jj(
+        repo_path,
+        &["config", "set", "--repo", "fsmonitor.backend", "none"],
+    )?;
+
+    // Nobody holds the lock: nothing to say
+    let response = run_notification_hook(repo_path)?;
+    assert_eq!(response["continue"], true);
+    assert!(response.get("systemMessage").is_none(), "got: {}", response);
+
+    jjagent::lock::acquire_lock_in(OTHER_SESSION_ID, repo_path, Default::default())?;
+    let response = run_notification_hook(repo_path)?;
+    jjagent::lock::release_lock_in(OTHER_SESSION_ID, repo_path, Default::default())?;
+
+    assert_eq!(response["continue"], true);
+    let message = response["systemMessage"].as_str().unwrap_or_default();
+    assert!(
+        message.contains(&format!(
+            "held by session holder-1 (pid {})",
+            std::process::id()
+        )),
+        "got: {}",
+        response
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_notification_shortens_non_ascii_holder_ids() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let repo_path = temp_dir.path();
+    jj(repo_path, &["git", "init"])?;
+    jj(
+        repo_path,
+        &["config", "set", "--repo", "fsmonitor.backend", "none"],
+    )?;
+
+    // The 8th byte falls inside a multi-byte character
+    std::fs::write(
+        repo_path.join(".jj").join("jjagent-wc.lock"),
+        r#"{"pid": 42, "session_id": "aééééé-session", "acquired_at": 0}"#,
+    )?;
+    let response = run_notification_hook(repo_path)?;
+    let message = response["systemMessage"].as_str().unwrap_or_default();
+    assert!(
+        message.contains("held by session aééééé-s (pid 42)"),
+        "got: {}",
+        response
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_notification_is_quiet_when_session_holds_its_own_lock() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let repo_path = temp_dir.path();
+    jj(repo_path, &["git", "init"])?;
+    jj(
+        repo_path,
+        &["config", "set", "--repo", "fsmonitor.backend", "none"],
+    )?;
+
+    jjagent::lock::acquire_lock_in(SESSION_ID, repo_path, Default::default())?;
+    let response = run_notification_hook(repo_path)?;
+    jjagent::lock::release_lock_in(SESSION_ID, repo_path, Default::default())?;
+
+    assert_eq!(response["continue"], true);
+    assert!(response.get("systemMessage").is_none(), "got: {}", response);
+
+    Ok(())
+}
+
+#[test]
+fn test_notification_outside_repo_continues() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let response = run_notification_hook(temp_dir.path())?;
+    assert_eq!(response["continue"], true);
+    assert!(response.get("systemMessage").is_none(), "got: {}", response);
+    Ok(())
+}
@@ -205,3 +205,196 @@ fn test_parallel_sessions_with_locking() {
         result_2
     );
 }
+
+/// Run jj in the repo and return its trimmed stdout
+fn jj_stdout(repo_path: &std::path::Path, args: &[&str]) -> String {
+    let output = Command::new("jj")
+        .args(args)
+        .current_dir(repo_path)
+        .output()
+        .expect("Failed to run jj");
+    assert!(
+        output.status.success(),
+        "jj {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+#[test]
+fn test_interleaved_conflicting_sessions_keep_uwc() {
+    let temp_dir = create_test_repo();
+    let repo_path = temp_dir.path();
+
+    let session_a = "session-a-12345678-1234-1234-1234-123456789012";
+    let session_b = "session-b-87654321-4321-4321-4321-210987654321";
+
+    // uwc edits the file both sessions write, so squashing their edits below it conflicts
+    // and splits the sessions into parts
+    std::fs::write(repo_path.join("README.md"), "# Test Repo\n\nuser's edit\n").unwrap();
+
+    let edits = [
+        (session_a, "claude a, first edit\n"),
+        (session_b, "claude b, first edit\n"),
+        (session_a, "claude a, second edit\n"),
+        (session_b, "claude b, second edit\n"),
+    ];
+    for (session_id, content) in edits {
+        run_pretool_hook(repo_path, session_id).unwrap();
+        std::fs::write(repo_path.join("README.md"), content).unwrap();
+
+        // The change the precommit sits on is this round's uwc
+        let uwc_id = jj_stdout(
+            repo_path,
+            &["log", "-r", "@-", "--no-graph", "-T", "change_id"],
+        );
+        let uwc_description = jj_stdout(
+            repo_path,
+            &["log", "-r", "@-", "--no-graph", "-T", "description"],
+        );
+
+        run_posttool_hook(repo_path, session_id).unwrap();
+
+        // uwc either stayed where it was or was moved into the new working copy,
+        // never swapped for one of the sessions' changes
+        let uwc_kept = !jj_stdout(
+            repo_path,
+            &[
+                "log",
+                "-r",
+                &format!("present({}) & ::@", uwc_id),
+                "--no-graph",
+                "-T",
+                "change_id",
+            ],
+        )
+        .is_empty();
+        let at_description = jj_stdout(
+            repo_path,
+            &["log", "-r", "@", "--no-graph", "-T", "description"],
+        );
+        assert!(
+            uwc_kept || at_description == uwc_description,
+            "uwc {} ({:?}) was lost after {}'s edit; @ is {:?}",
+            uwc_id,
+            uwc_description,
+            session_id,
+            at_description
+        );
+        assert!(
+            !at_description.contains("Claude-session-id"),
+            "@ should not be a session change after {}'s edit: {:?}",
+            session_id,
+            at_description
+        );
+    }
+
+    // The user's change is still a single non-session change below @
+    let uwc_changes = jj_stdout(
+        repo_path,
+        &[
+            "log",
+            "-r",
+            r#"::@ & description(exact:"uwc\n")"#,
+            "--no-graph",
+            "-T",
+            r#"change_id ++ "\n""#,
+        ],
+    );
+    assert_eq!(uwc_changes.lines().count(), 1, "got: {}", uwc_changes);
+
+    // Both sessions kept their edits in session changes
+    for session_id in [session_a, session_b] {
+        let parts = jj_stdout(
+            repo_path,
+            &[
+                "log",
+                "-r",
+                &format!(
+                    r#"description(substring:"Claude-session-id: {}")"#,
+                    session_id
+                ),
+                "--no-graph",
+                "-T",
+                r#"change_id ++ "\n""#,
+            ],
+        );
+        assert!(!parts.is_empty(), "no changes for {}", session_id);
+    }
+}
+
+/// Run the Stop hook for a session, returning its stderr
+fn run_stop_hook(repo_path: &std::path::Path, session_id: &str) -> String {
+    use std::io::Write;
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .args(["claude", "hooks", "Stop"])
+        .current_dir(repo_path)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn Stop");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(format!(r#"{{"session_id":"{}"}}"#, session_id).as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    assert!(output.status.success(), "Stop failed: {}", stderr);
+    stderr
+}
+
+#[test]
+fn test_stop_leaves_another_sessions_lock_alone() {
+    let temp_dir = create_test_repo();
+    let repo_path = temp_dir.path();
+    let session_a = "stop-aaaa-1111-2222-3333-444444444444";
+    let session_b = "stop-bbbb-1111-2222-3333-444444444444";
+
+    // Session A is mid-edit: it holds the lock and @ is its precommit
+    run_pretool_hook(repo_path, session_a).expect("PreToolUse for A failed");
+    std::fs::write(repo_path.join("a.txt"), "from A\n").unwrap();
+    let precommit = jj_stdout(
+        repo_path,
+        &["log", "-r", "@", "--no-graph", "-T", "change_id"],
+    );
+
+    let stderr = run_stop_hook(repo_path, session_b);
+    assert!(
+        stderr.contains("session stop-aaa holds the working copy lock"),
+        "got: {}",
+        stderr
+    );
+    assert!(
+        !stderr.contains("Lock ownership mismatch"),
+        "got: {}",
+        stderr
+    );
+    assert_eq!(
+        jjagent::lock::current_holder(repo_path)
+            .unwrap()
+            .map(|holder| holder.session_id),
+        Some(session_a.to_string()),
+        "A should still hold the lock"
+    );
+    assert_eq!(
+        jj_stdout(
+            repo_path,
+            &["log", "-r", "@", "--no-graph", "-T", "change_id"]
+        ),
+        precommit,
+        "B's Stop should not touch A's precommit"
+    );
+
+    // A finishes normally
+    run_posttool_hook(repo_path, session_a).expect("PostToolUse for A failed");
+    assert_eq!(jjagent::lock::current_holder(repo_path).unwrap(), None);
+
+    // With the lock free, B's Stop has nothing to release and says nothing about it
+    let stderr = run_stop_hook(repo_path, session_b);
+    assert!(!stderr.contains("Lock already released"), "got: {}", stderr);
+    assert!(!stderr.contains("Warning"), "got: {}", stderr);
+}
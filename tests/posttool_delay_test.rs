@@ -0,0 +1,81 @@
+//! Integration test for skipping the PostToolUse file-watcher delay without watchman
+
+use anyhow::Result;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+const SESSION_ID: &str = "delay-111-2222-3333-4444-555555555555";
+
+fn jj(repo_path: &std::path::Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("jj")
+        .current_dir(repo_path)
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn run_hook(repo_path: &std::path::Path, hook: &str) -> Result<()> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .args(["claude", "hooks", hook])
+        .current_dir(repo_path)
+        .env("JJAGENT_POSTTOOL_DELAY_MS", "10000")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(format!(r#"{{"session_id":"{}"}}"#, SESSION_ID).as_bytes())?;
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} failed: {}",
+            hook,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_posttool_skips_delay_when_fsmonitor_is_off() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let repo_path = temp_dir.path();
+    jj(repo_path, &["git", "init"])?;
+    jj(
+        repo_path,
+        &["config", "set", "--repo", "fsmonitor.backend", "none"],
+    )?;
+    jj(repo_path, &["describe", "-m", "uwc"])?;
+
+    run_hook(repo_path, "PreToolUse")?;
+    std::fs::write(repo_path.join("claude.txt"), "claude\n")?;
+
+    // The 10s delay is only an upper bound for waiting on watchman
+    let start = Instant::now();
+    run_hook(repo_path, "PostToolUse")?;
+    assert!(
+        start.elapsed() < Duration::from_secs(5),
+        "PostToolUse took {:?}",
+        start.elapsed()
+    );
+
+    let wc = jj(
+        repo_path,
+        &["log", "-r", "@", "--no-graph", "-T", "description"],
+    )?;
+    assert_eq!(wc.trim(), "uwc");
+
+    Ok(())
+}
@@ -0,0 +1,119 @@
+//! Integration test for keeping notes written on a precommit with JJAGENT_KEEP_PRECOMMIT_NOTES
+
+use anyhow::Result;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+const SESSION_ID: &str = "notes-11-2222-3333-4444-555555555555";
+
+fn jj(repo_path: &std::path::Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("jj")
+        .current_dir(repo_path)
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn run_hook(repo_path: &std::path::Path, hook: &str, keep_notes: bool) -> Result<()> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .args(["claude", "hooks", hook])
+        .current_dir(repo_path)
+        .env("JJAGENT_POSTTOOL_DELAY_MS", "0")
+        .env(
+            "JJAGENT_KEEP_PRECOMMIT_NOTES",
+            if keep_notes { "1" } else { "0" },
+        )
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(format!(r#"{{"session_id":"{}"}}"#, SESSION_ID).as_bytes())?;
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} failed: {}",
+            hook,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
+/// Run a tool call whose precommit the user described with notes, returning the session
+/// change's description afterwards
+fn edit_with_precommit_notes(keep_notes: bool) -> Result<String> {
+    let temp_dir = TempDir::new()?;
+    let repo_path = temp_dir.path();
+    jj(repo_path, &["git", "init"])?;
+    jj(
+        repo_path,
+        &["config", "set", "--repo", "fsmonitor.backend", "none"],
+    )?;
+    jj(repo_path, &["describe", "-m", "uwc"])?;
+
+    run_hook(repo_path, "PreToolUse", keep_notes)?;
+    std::fs::write(repo_path.join("claude.txt"), "claude\n")?;
+
+    // Add a paragraph between the generated title and the precommit trailer
+    let description = jj(
+        repo_path,
+        &["log", "-r", "@", "--no-graph", "-T", "description"],
+    )?;
+    let (title, trailers) = description
+        .trim_end()
+        .split_once("\n\n")
+        .expect("precommit has trailers");
+    jj(
+        repo_path,
+        &[
+            "describe",
+            "-m",
+            &format!("{}\n\nKeep the parser change small\n\n{}", title, trailers),
+        ],
+    )?;
+
+    run_hook(repo_path, "PostToolUse", keep_notes)?;
+
+    let changes = jjagent::jj::find_all_session_changes_in(SESSION_ID, Some(repo_path))?;
+    assert_eq!(changes.len(), 1);
+    jj(
+        repo_path,
+        &["log", "-r", &changes[0], "--no-graph", "-T", "description"],
+    )
+}
+
+#[test]
+fn test_precommit_notes_are_folded_into_session_change() -> Result<()> {
+    assert_eq!(
+        edit_with_precommit_notes(true)?,
+        format!(
+            "jjagent: session notes-11\n\nKeep the parser change small\n\nClaude-session-id: {}\n",
+            SESSION_ID
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn test_precommit_notes_are_dropped_by_default() -> Result<()> {
+    assert_eq!(
+        edit_with_precommit_notes(false)?,
+        format!(
+            "jjagent: session notes-11\n\nClaude-session-id: {}\n",
+            SESSION_ID
+        )
+    );
+    Ok(())
+}
@@ -0,0 +1,172 @@
+//! Integration test for the PreCompact hook finalizing an interrupted tool call
+
+use anyhow::Result;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+const SESSION_ID: &str = "compact-1-2222-3333-4444-555555555555";
+
+fn jj(repo_path: &std::path::Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("jj")
+        .current_dir(repo_path)
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Run a hook, returning its stdout
+fn run_hook(repo_path: &std::path::Path, hook: &str) -> Result<String> {
+    run_hook_as(repo_path, hook, SESSION_ID)
+}
+
+/// Run a hook for the given session, returning its stdout
+fn run_hook_as(repo_path: &std::path::Path, hook: &str, session_id: &str) -> Result<String> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .args(["claude", "hooks", hook])
+        .current_dir(repo_path)
+        .env("JJAGENT_POSTTOOL_DELAY_MS", "0")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(format!(r#"{{"session_id":"{}"}}"#, session_id).as_bytes())?;
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} failed: {}",
+            hook,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[test]
+fn test_precompact_finalizes_interrupted_tool_call() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let repo_path = temp_dir.path();
+    jj(repo_path, &["git", "init"])?;
+    jj(
+        repo_path,
+        &["config", "set", "--repo", "fsmonitor.backend", "none"],
+    )?;
+    jj(repo_path, &["describe", "-m", "uwc"])?;
+
+    // PreToolUse ran but the tool never reached PostToolUse
+    run_hook(repo_path, "PreToolUse")?;
+    std::fs::write(repo_path.join("claude.txt"), "claude\n")?;
+
+    let stdout = run_hook(repo_path, "PreCompact")?;
+    let response: serde_json::Value = serde_json::from_str(&stdout)?;
+    assert_eq!(response["continue"], true, "got: {}", stdout);
+
+    // The precommit was squashed into the session change and @ is back on the uwc
+    let wc = jj(
+        repo_path,
+        &["log", "-r", "@", "--no-graph", "-T", "description"],
+    )?;
+    assert_eq!(wc.trim(), "uwc");
+    let files = jj(
+        repo_path,
+        &[
+            "log",
+            "-r",
+            &format!(
+                r#"description(substring:"Claude-session-id: {}")"#,
+                SESSION_ID
+            ),
+            "--no-graph",
+            "-T",
+            r#"diff.files().map(|f| f.path()).join("\n")"#,
+        ],
+    )?;
+    assert_eq!(files.trim(), "claude.txt");
+
+    // Nothing in flight: a second PreCompact is a noop
+    run_hook(repo_path, "PreCompact")?;
+    assert_eq!(jjagent::lock::current_holder(repo_path)?, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_precompact_leaves_another_sessions_lock_alone() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let repo_path = temp_dir.path();
+    jj(repo_path, &["git", "init"])?;
+    jj(
+        repo_path,
+        &["config", "set", "--repo", "fsmonitor.backend", "none"],
+    )?;
+    jj(repo_path, &["describe", "-m", "uwc"])?;
+
+    // Another session is mid-edit: it holds the lock and @ is its precommit
+    let other_session = "compact-2-2222-3333-4444-555555555555";
+    run_hook_as(repo_path, "PreToolUse", other_session)?;
+    std::fs::write(repo_path.join("other.txt"), "other\n")?;
+    let precommit = jj(
+        repo_path,
+        &["log", "-r", "@", "--no-graph", "-T", "change_id"],
+    )?;
+    let operation = jj(
+        repo_path,
+        &[
+            "op",
+            "log",
+            "--limit",
+            "1",
+            "--no-graph",
+            "-T",
+            "id",
+            "--ignore-working-copy",
+        ],
+    )?;
+
+    let stdout = run_hook(repo_path, "PreCompact")?;
+    let response: serde_json::Value = serde_json::from_str(&stdout)?;
+    assert_eq!(response["continue"], true, "got: {}", stdout);
+
+    assert_eq!(
+        jjagent::lock::current_holder(repo_path)?.map(|holder| holder.session_id),
+        Some(other_session.to_string()),
+        "the other session should still hold the lock"
+    );
+    assert_eq!(
+        jj(
+            repo_path,
+            &[
+                "op",
+                "log",
+                "--limit",
+                "1",
+                "--no-graph",
+                "-T",
+                "id",
+                "--ignore-working-copy",
+            ],
+        )?,
+        operation,
+        "PreCompact should not have run any jj operation"
+    );
+    assert_eq!(
+        jj(
+            repo_path,
+            &["log", "-r", "@", "--no-graph", "-T", "change_id"],
+        )?,
+        precommit
+    );
+
+    Ok(())
+}
@@ -0,0 +1,120 @@
+//! Integration test for PreToolUse running twice in a session without a PostToolUse between
+
+use anyhow::Result;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+const SESSION_ID: &str = "reentry-1-2222-3333-4444-555555555555";
+
+fn jj(repo_path: &std::path::Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("jj")
+        .current_dir(repo_path)
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Run a hook, returning its stderr
+fn run_hook(repo_path: &std::path::Path, hook: &str) -> Result<String> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .args(["claude", "hooks", hook])
+        .current_dir(repo_path)
+        .env("JJAGENT_POSTTOOL_DELAY_MS", "0")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(format!(r#"{{"session_id":"{}"}}"#, SESSION_ID).as_bytes())?;
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} failed: {}",
+            hook,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stderr).to_string())
+}
+
+fn precommit_count(repo_path: &std::path::Path) -> Result<usize> {
+    Ok(jj(
+        repo_path,
+        &[
+            "log",
+            "-r",
+            r#"description(substring:"jjagent: precommit")"#,
+            "--no-graph",
+            "-T",
+            r#"change_id ++ "\n""#,
+        ],
+    )?
+    .lines()
+    .count())
+}
+
+#[test]
+fn test_repeated_pretool_reuses_the_precommit() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let repo_path = temp_dir.path();
+    jj(repo_path, &["git", "init"])?;
+    jj(
+        repo_path,
+        &["config", "set", "--repo", "fsmonitor.backend", "none"],
+    )?;
+    jj(repo_path, &["describe", "-m", "uwc"])?;
+
+    run_hook(repo_path, "PreToolUse")?;
+    std::fs::write(repo_path.join("first.txt"), "first\n")?;
+
+    // The first tool call never got its PostToolUse; the session holds the lock itself,
+    // so the second PreToolUse must not wait on it
+    let start = Instant::now();
+    let stderr = run_hook(repo_path, "PreToolUse")?;
+    assert!(
+        start.elapsed() < Duration::from_secs(10),
+        "waited on its own lock"
+    );
+    assert!(stderr.contains("reusing it"), "got: {}", stderr);
+    assert_eq!(precommit_count(repo_path)?, 1);
+    assert!(jjagent::jj::is_current_commit_precommit_for_session_in(
+        SESSION_ID,
+        Some(repo_path)
+    )?);
+
+    std::fs::write(repo_path.join("second.txt"), "second\n")?;
+    run_hook(repo_path, "PostToolUse")?;
+
+    // Both edits land in the one session change, and the lock is released
+    assert_eq!(precommit_count(repo_path)?, 0);
+    let files = jj(
+        repo_path,
+        &[
+            "log",
+            "-r",
+            &format!(
+                r#"description(substring:"Claude-session-id: {}")"#,
+                SESSION_ID
+            ),
+            "--no-graph",
+            "-T",
+            r#"diff.files().map(|f| f.path()).join("\n")"#,
+        ],
+    )?;
+    assert_eq!(files.trim(), "first.txt\nsecond.txt");
+    assert_eq!(jjagent::lock::current_holder(repo_path)?, None);
+
+    Ok(())
+}
@@ -0,0 +1,56 @@
+//! Tests for `--quiet` / JJAGENT_QUIET=1, which hide the hooks' informational stderr lines
+
+use anyhow::Result;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+/// Run PreToolUse outside any jj repo, returning its stderr
+fn pretool_stderr(dir: &TempDir, args: &[&str], envs: &[(&str, &str)]) -> Result<String> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .args(args)
+        .args(["claude", "hooks", "PreToolUse"])
+        .current_dir(dir.path())
+        .env_remove("JJAGENT_QUIET")
+        .env_remove("JJAGENT_LOG")
+        .envs(envs.iter().copied())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(br#"{"session_id":"quiet-test-1234","tool_name":"Edit"}"#)?;
+    }
+    let output = child.wait_with_output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        r#"{"continue":true}"#
+    );
+    Ok(String::from_utf8_lossy(&output.stderr).to_string())
+}
+
+#[test]
+fn test_hooks_report_progress_by_default() -> Result<()> {
+    let dir = TempDir::new()?;
+    let stderr = pretool_stderr(&dir, &[], &[])?;
+    assert!(
+        stderr.contains("jjagent: PreToolUse hook called"),
+        "got: {}",
+        stderr
+    );
+    assert!(stderr.contains("skipping hook"), "got: {}", stderr);
+    Ok(())
+}
+
+#[test]
+fn test_quiet_flag_and_env_silence_info_messages() -> Result<()> {
+    let dir = TempDir::new()?;
+    assert_eq!(pretool_stderr(&dir, &["--quiet"], &[])?, "");
+    assert_eq!(pretool_stderr(&dir, &["-q"], &[])?, "");
+    assert_eq!(pretool_stderr(&dir, &[], &[("JJAGENT_QUIET", "1")])?, "");
+
+    let stderr = pretool_stderr(&dir, &[], &[("JJAGENT_QUIET", "0")])?;
+    assert!(stderr.contains("hook called"), "got: {}", stderr);
+    Ok(())
+}
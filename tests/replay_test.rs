@@ -0,0 +1,173 @@
+use anyhow::Result;
+use std::process::Command;
+use tempfile::TempDir;
+
+const SESSION_ID: &str = "replay-test-1111-2222-3333-444444444444";
+
+struct TestRepo {
+    dir: TempDir,
+}
+
+impl TestRepo {
+    fn new() -> Result<Self> {
+        let dir = TempDir::new()?;
+        let repo = Self { dir };
+        repo.jj(&["git", "init"])?;
+        // Disable watchman for tests
+        repo.jj(&["config", "set", "--repo", "fsmonitor.backend", "none"])?;
+
+        std::fs::write(repo.path().join("existing.txt"), "hello world\n")?;
+        repo.jj(&["commit", "-m", "base"])?;
+        repo.jj(&["describe", "-m", "uwc"])?;
+        Ok(repo)
+    }
+
+    fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+
+    /// Run a jj command in the repo and return its stdout
+    fn jj(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("jj")
+            .current_dir(self.path())
+            .args(args)
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj {:?} failed: {}",
+                args,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn jjagent(&self, args: &[&str]) -> Result<std::process::Output> {
+        Ok(Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .current_dir(self.path())
+            .env_remove("JJAGENT_DISABLE")
+            .env("JJAGENT_POSTTOOL_DELAY_MS", "0")
+            .args(args)
+            .output()?)
+    }
+
+    /// Write a transcript whose assistant entries are the given tool uses
+    fn transcript(&self, tool_uses: &[serde_json::Value]) -> Result<std::path::PathBuf> {
+        // Inside .jj so the snapshot doesn't pick it up as an edit
+        let path = self.path().join(".jj").join("transcript.jsonl");
+        let lines: Vec<String> = tool_uses
+            .iter()
+            .map(|tool_use| {
+                serde_json::json!({
+                    "type": "assistant",
+                    "message": {"role": "assistant", "content": [tool_use]},
+                    "sessionId": SESSION_ID,
+                })
+                .to_string()
+            })
+            .collect();
+        std::fs::write(&path, lines.join("\n"))?;
+        Ok(path)
+    }
+}
+
+#[test]
+fn test_replay_rebuilds_session_change() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let new_file = repo.path().join("src").join("new.txt");
+    let existing = repo.path().join("existing.txt");
+    let transcript = repo.transcript(&[
+        serde_json::json!({"type": "tool_use", "id": "t1", "name": "Write", "input": {
+            "file_path": new_file, "content": "draft\n"
+        }}),
+        serde_json::json!({"type": "tool_use", "id": "t2", "name": "Edit", "input": {
+            "file_path": new_file, "old_string": "draft", "new_string": "final"
+        }}),
+        serde_json::json!({"type": "tool_use", "id": "t3", "name": "Edit", "input": {
+            "file_path": existing, "old_string": "world", "new_string": "claude"
+        }}),
+    ])?;
+
+    let output = repo.jjagent(&[
+        "replay",
+        SESSION_ID,
+        "--transcript",
+        transcript.to_str().unwrap(),
+    ])?;
+    assert!(
+        output.status.success(),
+        "replay failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "existing.txt\nsrc/new.txt\n"
+    );
+
+    // The edits live in a session change below the user's working copy
+    let session_rev = format!(
+        r#"description(substring:"Claude-session-id: {}")"#,
+        SESSION_ID
+    );
+    assert_eq!(
+        repo.jj(&["file", "show", "-r", &session_rev, "src/new.txt"])?,
+        "final\n"
+    );
+    assert_eq!(
+        repo.jj(&["file", "show", "-r", &session_rev, "existing.txt"])?,
+        "hello claude\n"
+    );
+    assert_eq!(
+        repo.jj(&["log", "-r", "@", "--no-graph", "-T", "description"])?
+            .trim(),
+        "uwc"
+    );
+
+    // A second replay would duplicate the session, so it's refused
+    let output = repo.jjagent(&[
+        "replay",
+        SESSION_ID,
+        "--transcript",
+        transcript.to_str().unwrap(),
+    ])?;
+    assert!(!output.status.success(), "second replay should fail");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("already has a change"),
+        "got: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_replay_leaves_repo_alone_when_an_edit_does_not_apply() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let transcript = repo.transcript(&[serde_json::json!({
+        "type": "tool_use", "id": "t1", "name": "Edit", "input": {
+            "file_path": repo.path().join("existing.txt"),
+            "old_string": "not in the file",
+            "new_string": "x"
+        }
+    })])?;
+    let op_before = repo.jj(&["op", "log", "--no-graph", "-n", "1", "-T", "id"])?;
+
+    let output = repo.jjagent(&[
+        "replay",
+        SESSION_ID,
+        "--transcript",
+        transcript.to_str().unwrap(),
+    ])?;
+    assert!(!output.status.success(), "replay should fail");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("the text it replaces isn't there"),
+        "got: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        repo.jj(&["op", "log", "--no-graph", "-n", "1", "-T", "id"])?,
+        op_before
+    );
+
+    Ok(())
+}
@@ -0,0 +1,147 @@
+//! Integration tests for disabling jjagent in a single repo via .jjagent/
+
+use anyhow::Result;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use tempfile::TempDir;
+
+/// A directory that looks like a jj repo root to jjagent, with a nested working directory
+fn fake_repo() -> Result<(TempDir, std::path::PathBuf)> {
+    let dir = TempDir::new()?;
+    std::fs::create_dir(dir.path().join(".jj"))?;
+    std::fs::create_dir(dir.path().join(".jjagent"))?;
+    let subdir = dir.path().join("vendor").join("dep");
+    std::fs::create_dir_all(&subdir)?;
+    Ok((dir, subdir))
+}
+
+fn run_pretool(cwd: &std::path::Path, disable: Option<&str>) -> Result<Output> {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_jjagent"));
+    cmd.current_dir(cwd)
+        .args(["claude", "hooks", "PreToolUse"])
+        .env_remove("JJAGENT_DISABLE")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(value) = disable {
+        cmd.env("JJAGENT_DISABLE", value);
+    }
+    let mut child = cmd.spawn()?;
+    // JJAGENT_DISABLE=1 exits without reading the input, so the pipe may already be closed
+    let _ = child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(br#"{"session_id":"disabled-1234-5678"}"#);
+    Ok(child.wait_with_output()?)
+}
+
+#[test]
+fn test_disabled_marker_makes_hooks_a_noop() -> Result<()> {
+    let (dir, subdir) = fake_repo()?;
+    std::fs::write(dir.path().join(".jjagent").join("disabled"), "")?;
+
+    let output = run_pretool(&subdir, None)?;
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Disabled for this repo by"),
+        "got: {}",
+        stderr
+    );
+    assert!(stderr.contains(".jjagent/disabled"), "got: {}", stderr);
+    assert_eq!(jjagent::lock::current_holder(dir.path())?, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_config_disabled_key_makes_hooks_a_noop() -> Result<()> {
+    let (dir, subdir) = fake_repo()?;
+    std::fs::write(
+        dir.path().join(".jjagent").join("config.toml"),
+        "# vendored, leave it alone\ndisabled = true\n",
+    )?;
+
+    let output = run_pretool(&subdir, None)?;
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("config.toml"), "got: {}", stderr);
+
+    Ok(())
+}
+
+#[test]
+fn test_config_without_disabled_key_keeps_hooks_on() -> Result<()> {
+    let (dir, subdir) = fake_repo()?;
+    std::fs::write(
+        dir.path().join(".jjagent").join("config.toml"),
+        "disabled = false\n\n[other]\ndisabled = true\n",
+    )?;
+
+    let output = run_pretool(&subdir, None)?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        !stderr.contains("Disabled for this repo"),
+        "got: {}",
+        stderr
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_env_var_takes_precedence_over_marker() -> Result<()> {
+    let (dir, subdir) = fake_repo()?;
+    std::fs::write(dir.path().join(".jjagent").join("disabled"), "")?;
+
+    // JJAGENT_DISABLE=0 re-enables the hooks despite the marker
+    let output = run_pretool(&subdir, Some("0"))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        !stderr.contains("Disabled for this repo"),
+        "got: {}",
+        stderr
+    );
+
+    let output = run_pretool(&subdir, Some("1"))?;
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("JJAGENT_DISABLE=1"), "got: {}", stderr);
+
+    Ok(())
+}
+
+#[test]
+fn test_disabled_marker_found_via_hook_cwd() -> Result<()> {
+    let (dir, subdir) = fake_repo()?;
+    std::fs::write(dir.path().join(".jjagent").join("disabled"), "")?;
+    let elsewhere = TempDir::new()?;
+
+    // The hook runs outside the repo, but the input says Claude is working in it
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(elsewhere.path())
+        .args(["claude", "hooks", "PreToolUse"])
+        .env_remove("JJAGENT_DISABLE")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let input = serde_json::json!({"session_id": "disabled-1234-5678", "cwd": subdir});
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.to_string().as_bytes())?;
+    let output = child.wait_with_output()?;
+
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Disabled for this repo by"),
+        "got: {}",
+        stderr
+    );
+
+    Ok(())
+}
@@ -0,0 +1,320 @@
+use anyhow::Result;
+use std::process::Command;
+use tempfile::TempDir;
+
+const SESSION_ID: &str = "diff-test-1111-2222-3333-444444444444";
+
+struct TestRepo {
+    dir: TempDir,
+}
+
+impl TestRepo {
+    fn new() -> Result<Self> {
+        let dir = TempDir::new()?;
+
+        // Initialize jj repo
+        let init_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["git", "init"])
+            .output()?;
+
+        if !init_output.status.success() {
+            anyhow::bail!(
+                "Failed to init jj repo: {}",
+                String::from_utf8_lossy(&init_output.stderr)
+            );
+        }
+
+        // Disable watchman for tests
+        let config_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["config", "set", "--repo", "fsmonitor.backend", "none"])
+            .output()?;
+
+        if !config_output.status.success() {
+            anyhow::bail!(
+                "Failed to disable watchman: {}",
+                String::from_utf8_lossy(&config_output.stderr)
+            );
+        }
+
+        let repo = Self { dir };
+        // Build: session (writes first.txt) -> uwc (@)
+        repo.jj(&[
+            "describe",
+            "-m",
+            &format!(
+                "jjagent: session diff-tes\n\nClaude-session-id: {}",
+                SESSION_ID
+            ),
+        ])?;
+        std::fs::write(repo.path().join("first.txt"), "first\n")?;
+        repo.jj(&["new", "-m", "uwc"])?;
+
+        Ok(repo)
+    }
+
+    fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+
+    /// Run a jj command in the repo and return its stdout
+    fn jj(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("jj")
+            .current_dir(self.path())
+            .args(args)
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj {:?} failed: {}",
+                args,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn jjagent(&self, args: &[&str]) -> Result<std::process::Output> {
+        Ok(Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .current_dir(self.path())
+            .args(args)
+            .output()?)
+    }
+}
+
+#[test]
+fn test_diff_shows_session_changes() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    let output = repo.jjagent(&["diff", SESSION_ID])?;
+    assert!(
+        output.status.success(),
+        "diff failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("first.txt"), "got: {}", stdout);
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_not_found() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    let output = repo.jjagent(&["diff", "nonexistent-session-12345678"])?;
+    assert!(!output.status.success(), "diff should fail");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("No change found for session ID: nonexistent-session-12345678"),
+        "got: {}",
+        stderr
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_parts_shows_each_part() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    // A second part between the first part and uwc, touching another file
+    repo.jj(&[
+        "new",
+        "--insert-before",
+        "@",
+        "-m",
+        &format!(
+            "jjagent: session diff-tes pt. 2\n\nClaude-session-id: {}",
+            SESSION_ID
+        ),
+    ])?;
+    std::fs::write(repo.path().join("second.txt"), "second\n")?;
+    repo.jj(&["next", "--edit"])?;
+
+    let output = repo.jjagent(&["diff", SESSION_ID, "--parts"])?;
+    assert!(
+        output.status.success(),
+        "diff failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    let first = stdout
+        .find("=== part 1 of 2:")
+        .expect("missing part 1 header");
+    let second = stdout
+        .find("=== part 2 of 2:")
+        .expect("missing part 2 header");
+    assert!(first < second, "parts out of order: {}", stdout);
+    assert!(
+        stdout[first..second].contains("first.txt"),
+        "got: {}",
+        stdout
+    );
+    assert!(stdout[second..].contains("second.txt"), "got: {}", stdout);
+    assert!(stdout[second..].contains("pt. 2"), "got: {}", stdout);
+
+    Ok(())
+}
+
+#[test]
+fn test_files_unions_all_parts() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    // A second part touching first.txt again plus a new file
+    repo.jj(&[
+        "new",
+        "--insert-before",
+        "@",
+        "-m",
+        &format!(
+            "jjagent: session diff-tes pt. 2\n\nClaude-session-id: {}",
+            SESSION_ID
+        ),
+    ])?;
+    std::fs::write(repo.path().join("first.txt"), "first, again\n")?;
+    std::fs::write(repo.path().join("a.txt"), "a\n")?;
+    repo.jj(&["next", "--edit"])?;
+
+    assert_eq!(
+        jjagent::jj::get_session_file_list_in(SESSION_ID, Some(repo.path()))?,
+        vec!["a.txt", "first.txt"]
+    );
+
+    let output = repo.jjagent(&["files", SESSION_ID])?;
+    assert!(
+        output.status.success(),
+        "files failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "a.txt\nfirst.txt\n"
+    );
+
+    let output = repo.jjagent(&["files", "nonexistent-session-12345678"])?;
+    assert!(!output.status.success(), "files should fail");
+
+    Ok(())
+}
+
+#[test]
+fn test_stats_sums_all_parts() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    // A second part rewriting first.txt and adding a two-line file
+    repo.jj(&[
+        "new",
+        "--insert-before",
+        "@",
+        "-m",
+        &format!(
+            "jjagent: session diff-tes pt. 2\n\nClaude-session-id: {}",
+            SESSION_ID
+        ),
+    ])?;
+    std::fs::write(repo.path().join("first.txt"), "first, again\n")?;
+    std::fs::write(repo.path().join("a.txt"), "a\nb\n")?;
+    repo.jj(&["next", "--edit"])?;
+
+    assert_eq!(
+        jjagent::jj::get_session_stats_in(SESSION_ID, Some(repo.path()))?,
+        jjagent::jj::SessionStats {
+            files_changed: 2,
+            insertions: 4,
+            deletions: 1,
+        }
+    );
+
+    let output = repo.jjagent(&["stats", SESSION_ID, "--json"])?;
+    assert!(
+        output.status.success(),
+        "stats failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(json["files_changed"], 2);
+    assert_eq!(json["insertions"], 4);
+    assert_eq!(json["deletions"], 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_stats_empty_session_is_zero() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    let output = repo.jjagent(&["stats", "nonexistent-session-12345678"])?;
+    assert!(
+        output.status.success(),
+        "stats failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "0 files changed, 0 insertions(+), 0 deletions(-)\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_export_patch_concatenates_parts_in_order() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    repo.jj(&[
+        "new",
+        "--insert-before",
+        "@",
+        "-m",
+        &format!(
+            "jjagent: session diff-tes pt. 2\n\nClaude-session-id: {}",
+            SESSION_ID
+        ),
+    ])?;
+    std::fs::write(repo.path().join("second.txt"), "second\n")?;
+    repo.jj(&["next", "--edit"])?;
+
+    let output = repo.jjagent(&["export-patch", SESSION_ID])?;
+    assert!(
+        output.status.success(),
+        "export-patch failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let patch = String::from_utf8_lossy(&output.stdout).to_string();
+
+    let first = patch
+        .find("Subject: [PATCH 1/2] jjagent: session diff-tes\n")
+        .expect("missing first patch");
+    let second = patch
+        .find("Subject: [PATCH 2/2] jjagent: session diff-tes pt. 2\n")
+        .expect("missing second patch");
+    assert!(first < second, "patches out of order: {}", patch);
+    assert!(patch.starts_with("From "), "got: {}", patch);
+    assert_eq!(
+        patch
+            .matches(&format!("Claude-session-id: {}", SESSION_ID))
+            .count(),
+        2
+    );
+    assert!(patch[first..second].contains("diff --git a/first.txt b/first.txt"));
+    assert!(patch[second..].contains("diff --git a/second.txt b/second.txt"));
+
+    // --output writes the same patch to a file
+    let out = repo.path().join(".jj").join("session.patch");
+    let output = repo.jjagent(&[
+        "export-patch",
+        SESSION_ID,
+        "--output",
+        out.to_str().unwrap(),
+    ])?;
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(std::fs::read_to_string(&out)?, patch);
+
+    let output = repo.jjagent(&["export-patch", "nonexistent-session-12345678"])?;
+    assert!(!output.status.success(), "export-patch should fail");
+
+    Ok(())
+}
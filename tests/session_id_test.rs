@@ -247,3 +247,44 @@ fn test_session_id_command_default_rev() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_session_current_prints_session_on_working_copy() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let session_id = "current-session-12345678-1234-5678-90ab-cdef1234";
+    let current = || {
+        Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .current_dir(repo.path())
+            .env_remove("JJAGENT_DISABLE")
+            .args(["session", "current"])
+            .output()
+    };
+
+    // No trailer on @: fails with nothing on stdout
+    let output = current()?;
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let message = format!(
+        "jjagent: session current\n\nClaude-session-id: {}",
+        session_id
+    );
+    let new_output = Command::new("jj")
+        .current_dir(repo.path())
+        .args(["new", "-m", &message])
+        .output()?;
+    assert!(new_output.status.success());
+
+    let output = current()?;
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("{}\n", session_id)
+    );
+
+    Ok(())
+}
@@ -0,0 +1,210 @@
+use anyhow::Result;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+struct TestRepo {
+    dir: TempDir,
+}
+
+impl TestRepo {
+    fn new() -> Result<Self> {
+        let dir = TempDir::new()?;
+
+        // Initialize jj repo
+        let init_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["git", "init"])
+            .output()?;
+
+        if !init_output.status.success() {
+            anyhow::bail!(
+                "Failed to init jj repo: {}",
+                String::from_utf8_lossy(&init_output.stderr)
+            );
+        }
+
+        // Disable watchman for tests
+        let config_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["config", "set", "--repo", "fsmonitor.backend", "none"])
+            .output()?;
+
+        if !config_output.status.success() {
+            anyhow::bail!(
+                "Failed to disable watchman: {}",
+                String::from_utf8_lossy(&config_output.stderr)
+            );
+        }
+
+        // Give the user a working copy change on top
+        let output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["describe", "-m", "uwc"])
+            .output()?;
+        assert!(output.status.success());
+
+        Ok(Self { dir })
+    }
+
+    fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+
+    /// Simulate one Claude tool call: PreToolUse, write a file, PostToolUse
+    fn tool_call(&self, session_id: &str, tool_name: &str, file: &str) -> Result<()> {
+        self.run_hook("PreToolUse", session_id, tool_name)?;
+        std::fs::write(self.path().join(file), tool_name)?;
+        self.run_hook("PostToolUse", session_id, tool_name)
+    }
+
+    fn run_hook(&self, hook_name: &str, session_id: &str, tool_name: &str) -> Result<()> {
+        let hook_input = format!(
+            r#"{{"session_id":"{}","tool_name":"{}"}}"#,
+            session_id, tool_name
+        );
+
+        let mut child = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .current_dir(self.path())
+            .env_remove("JJAGENT_DISABLE")
+            .env_remove("JJAGENT_LOG")
+            .env_remove("JJAGENT_LOG_FILE")
+            .env("JJAGENT_RECORD_TOOLS", "1")
+            .env("JJAGENT_POSTTOOL_DELAY_MS", "0")
+            .args(["claude", "hooks", hook_name])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(hook_input.as_bytes())?;
+        }
+
+        let output = child.wait_with_output()?;
+        assert!(
+            output.status.success(),
+            "{} hook failed: {}",
+            hook_name,
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        Ok(())
+    }
+
+    fn session_list(&self, args: &[&str]) -> Result<std::process::Output> {
+        Ok(Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .current_dir(self.path())
+            .args(["session", "list"])
+            .args(args)
+            .output()?)
+    }
+}
+
+#[test]
+fn test_session_list_filters_by_tool() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let edit_session = "aaaaaaaa-1111-2222-3333-444444444444";
+    let bash_session = "bbbbbbbb-1111-2222-3333-444444444444";
+
+    repo.tool_call(edit_session, "Edit", "edit.txt")?;
+    repo.tool_call(bash_session, "Bash", "bash.txt")?;
+    repo.tool_call(bash_session, "Write", "write.txt")?;
+
+    let sessions = jjagent::jj::list_sessions_in(Some(repo.path()))?;
+    assert_eq!(sessions.len(), 2);
+    let bash = sessions
+        .iter()
+        .find(|s| s.session_id == bash_session)
+        .expect("bash session should be listed");
+    assert_eq!(bash.tools, vec!["Bash", "Write"]);
+
+    let output = repo.session_list(&["--tool", "bash"])?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("bbbbbbbb"), "got: {}", stdout);
+    assert!(!stdout.contains("aaaaaaaa"), "got: {}", stdout);
+
+    let output = repo.session_list(&[])?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().count(), 2, "got: {}", stdout);
+
+    // The top-level list is the same command, so it takes both the filter and --format
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(repo.path())
+        .args(["list", "--tool", "bash", "--format", "json"])
+        .output()?;
+    assert!(output.status.success());
+    let sessions: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(sessions.as_array().map(|s| s.len()), Some(1));
+    assert_eq!(sessions[0]["session_id"], bash_session);
+
+    Ok(())
+}
+
+#[test]
+fn test_session_list_tool_filter_requires_tracking() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    // A session created without tool tracking
+    let output = Command::new("jj")
+        .current_dir(repo.path())
+        .args([
+            "new",
+            "-m",
+            "jjagent: session cccccccc\n\nClaude-session-id: cccccccc-1111",
+        ])
+        .output()?;
+    assert!(output.status.success());
+
+    let output = repo.session_list(&["--tool", "Bash"])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("JJAGENT_RECORD_TOOLS=1"),
+        "Error should hint at enabling tool tracking, got: {}",
+        stderr
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_list_groups_parts_by_session() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let session = "dddddddd-1111-2222-3333-444444444444";
+    repo.tool_call(session, "Write", "one.txt")?;
+
+    // A second part of the same session
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(repo.path())
+        .args(["split", session, "-m", "Second part"])
+        .output()?;
+    assert!(output.status.success());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(repo.path())
+        .args(["list"])
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().count(), 1, "got: {}", stdout);
+    assert!(stdout.starts_with("dddddddd 2 parts "), "got: {}", stdout);
+    assert!(
+        stdout.contains("Second part | jjagent: session dddddddd"),
+        "got: {}",
+        stdout
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(repo.path())
+        .args(["list", "--format", "json"])
+        .output()?;
+    assert!(output.status.success());
+    let sessions: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(sessions[0]["session_id"], session);
+    assert_eq!(sessions[0]["change_ids"].as_array().unwrap().len(), 2);
+    assert_eq!(sessions[0]["titles"][0], "Second part");
+
+    Ok(())
+}
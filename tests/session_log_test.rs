@@ -0,0 +1,102 @@
+use anyhow::Result;
+use std::process::Command;
+use tempfile::TempDir;
+
+const SESSION_ID: &str = "session-log-1111-2222-3333-444444444444";
+
+fn jj(dir: &std::path::Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("jj").current_dir(dir).args(args).output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn log(dir: &std::path::Path, args: &[&str]) -> Result<std::process::Output> {
+    Ok(Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(dir)
+        .args(["log", "--color", "never"])
+        .args(args)
+        .output()?)
+}
+
+/// base -> session -> uwc (@), plus an unrelated change off root
+fn setup() -> Result<TempDir> {
+    let dir = TempDir::new()?;
+    let path = dir.path();
+    jj(path, &["git", "init"])?;
+    jj(
+        path,
+        &["config", "set", "--repo", "fsmonitor.backend", "none"],
+    )?;
+    jj(path, &["describe", "-m", "base"])?;
+    jj(
+        path,
+        &[
+            "new",
+            "-m",
+            &format!(
+                "jjagent: session session-\n\nClaude-session-id: {}",
+                SESSION_ID
+            ),
+        ],
+    )?;
+    let session = jj(path, &["log", "-r", "@", "--no-graph", "-T", "change_id"])?;
+    jj(path, &["new", "root()", "-m", "unrelated"])?;
+    jj(path, &["new", &session])?;
+    Ok(dir)
+}
+
+#[test]
+fn test_log_shows_session_base_and_working_copy() -> Result<()> {
+    let dir = setup()?;
+    jj(dir.path(), &["describe", "-m", "uwc"])?;
+
+    let output = log(dir.path(), &["--session-id", SESSION_ID])?;
+    assert!(
+        output.status.success(),
+        "log failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("uwc"), "got: {}", stdout);
+    assert!(
+        stdout.contains("jjagent: session session-"),
+        "got: {}",
+        stdout
+    );
+    assert!(stdout.contains("base"), "got: {}", stdout);
+    assert!(!stdout.contains("unrelated"), "got: {}", stdout);
+    assert!(
+        !stdout.contains("\x1b["),
+        "--color never still colored: {}",
+        stdout
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_log_without_session_on_at_errors() -> Result<()> {
+    let dir = setup()?;
+
+    let output = log(dir.path(), &[])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("pass --session-id"), "got: {}", stderr);
+
+    // Defaults to the session on @ when there is one
+    jj(dir.path(), &["edit", "@-"])?;
+    let output = log(dir.path(), &[])?;
+    assert!(
+        output.status.success(),
+        "log failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use anyhow::Result;
+use std::process::Command;
+use tempfile::TempDir;
+
+const SESSION_ID: &str = "rename-old-1111-2222-3333-444444444444";
+const NEW_ID: &str = "rename-new-5555-6666-7777-888888888888";
+
+struct TestRepo {
+    dir: TempDir,
+}
+
+impl TestRepo {
+    fn new() -> Result<Self> {
+        let dir = TempDir::new()?;
+
+        // Initialize jj repo
+        let init_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["git", "init"])
+            .output()?;
+
+        if !init_output.status.success() {
+            anyhow::bail!(
+                "Failed to init jj repo: {}",
+                String::from_utf8_lossy(&init_output.stderr)
+            );
+        }
+
+        // Disable watchman for tests
+        let config_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["config", "set", "--repo", "fsmonitor.backend", "none"])
+            .output()?;
+
+        if !config_output.status.success() {
+            anyhow::bail!(
+                "Failed to disable watchman: {}",
+                String::from_utf8_lossy(&config_output.stderr)
+            );
+        }
+
+        let repo = Self { dir };
+        // Build: session -> uwc (@)
+        repo.jj(&[
+            "describe",
+            "-m",
+            &format!(
+                "jjagent: session rename-o\n\nClaude-session-id: {}\nTicket: ABC-1",
+                SESSION_ID
+            ),
+        ])?;
+        repo.jj(&["new", "-m", "uwc"])?;
+
+        Ok(repo)
+    }
+
+    fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+
+    /// Run a jj command in the repo and return its stdout
+    fn jj(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("jj")
+            .current_dir(self.path())
+            .args(args)
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj {:?} failed: {}",
+                args,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn jjagent(&self, args: &[&str]) -> Result<std::process::Output> {
+        Ok(Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .current_dir(self.path())
+            .args(args)
+            .output()?)
+    }
+
+    fn description(&self, rev: &str) -> Result<String> {
+        self.jj(&["log", "-r", rev, "--no-graph", "-T", "description"])
+    }
+
+    fn operation_count(&self) -> Result<usize> {
+        Ok(self
+            .jj(&["op", "log", "--no-graph", "-T", r#"id ++ "\n""#])?
+            .lines()
+            .count())
+    }
+}
+
+#[test]
+fn test_session_rename_updates_all_parts() -> Result<()> {
+    let repo = TestRepo::new()?;
+    // A second part between the first and uwc
+    repo.jj(&[
+        "new",
+        "--insert-before",
+        "@",
+        "--no-edit",
+        "-m",
+        &format!(
+            "jjagent: session rename-o pt. 2\n\nClaude-session-id: {}",
+            SESSION_ID
+        ),
+    ])?;
+
+    let output = repo.jjagent(&["session", "rename", SESSION_ID, NEW_ID])?;
+    assert!(
+        output.status.success(),
+        "rename failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Updated 2 change(s)"));
+
+    assert!(jjagent::jj::find_all_session_changes_in(SESSION_ID, Some(repo.path()))?.is_empty());
+    assert_eq!(
+        jjagent::jj::find_all_session_changes_in(NEW_ID, Some(repo.path()))?.len(),
+        2
+    );
+    // Titles and other trailers are kept
+    assert_eq!(
+        repo.description("@--")?,
+        format!(
+            "jjagent: session rename-o\n\nClaude-session-id: {}\nTicket: ABC-1\n",
+            NEW_ID
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_session_rename_refuses_immutable_changes() -> Result<()> {
+    let repo = TestRepo::new()?;
+    repo.jj(&[
+        "config",
+        "set",
+        "--repo",
+        "revset-aliases.\"immutable_heads()\"",
+        "@-",
+    ])?;
+
+    let output = repo.jjagent(&["session", "rename", SESSION_ID, NEW_ID])?;
+    assert!(!output.status.success(), "rename should refuse");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("immutable"), "got: {}", stderr);
+    assert_eq!(
+        jjagent::jj::find_all_session_changes_in(SESSION_ID, Some(repo.path()))?.len(),
+        1
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_describe_many_is_a_single_operation() -> Result<()> {
+    let repo = TestRepo::new()?;
+    repo.jj(&["new", "--insert-before", "@", "--no-edit", "-m", "second"])?;
+    let first = jjagent::jj::get_full_change_id_in("@--", Some(repo.path()))?;
+    let second = jjagent::jj::get_full_change_id_in("@-", Some(repo.path()))?;
+
+    let before = repo.operation_count()?;
+    jjagent::jj::describe_many_in(
+        &[
+            (first, b"first, described".to_vec()),
+            (second, b"second, described".to_vec()),
+        ],
+        Some(repo.path()),
+    )?;
+    assert_eq!(repo.operation_count()? - before, 1);
+
+    assert_eq!(repo.description("@--")?, "first, described\n");
+    assert_eq!(repo.description("@-")?, "second, described\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_describe_many_rolls_back_and_names_the_failing_change() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let first = jjagent::jj::get_full_change_id_in("@-", Some(repo.path()))?;
+    let original = repo.description("@-")?;
+
+    let result = jjagent::jj::describe_many_in(
+        &[
+            (first, b"rewritten".to_vec()),
+            ("zzzzzzzzzzzz".to_string(), b"missing".to_vec()),
+        ],
+        Some(repo.path()),
+    );
+    let error = format!("{:#}", result.unwrap_err());
+    assert!(
+        error.contains("Failed to describe zzzzzzzzzzzz"),
+        "got: {}",
+        error
+    );
+    assert_eq!(repo.description("@-")?, original);
+
+    Ok(())
+}
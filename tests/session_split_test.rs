@@ -0,0 +1,385 @@
+use anyhow::Result;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+const SESSION_ID: &str = "split-test-1111-2222-3333-444444444444";
+
+struct TestRepo {
+    dir: TempDir,
+}
+
+impl TestRepo {
+    fn new() -> Result<Self> {
+        let dir = TempDir::new()?;
+
+        // Initialize jj repo
+        let init_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["git", "init"])
+            .output()?;
+
+        if !init_output.status.success() {
+            anyhow::bail!(
+                "Failed to init jj repo: {}",
+                String::from_utf8_lossy(&init_output.stderr)
+            );
+        }
+
+        // Disable watchman for tests
+        let config_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["config", "set", "--repo", "fsmonitor.backend", "none"])
+            .output()?;
+
+        if !config_output.status.success() {
+            anyhow::bail!(
+                "Failed to disable watchman: {}",
+                String::from_utf8_lossy(&config_output.stderr)
+            );
+        }
+
+        let repo = Self { dir };
+        // Build: session -> uwc (@)
+        repo.jj(&[
+            "describe",
+            "-m",
+            &format!(
+                "jjagent: session split-te\n\nClaude-session-id: {}",
+                SESSION_ID
+            ),
+        ])?;
+        repo.jj(&["new", "-m", "uwc"])?;
+
+        Ok(repo)
+    }
+
+    fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+
+    /// Run a jj command in the repo and return its stdout
+    fn jj(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("jj")
+            .current_dir(self.path())
+            .args(args)
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj {:?} failed: {}",
+                args,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn jjagent(&self, args: &[&str]) -> Result<std::process::Output> {
+        Ok(Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .current_dir(self.path())
+            .args(args)
+            .output()?)
+    }
+
+    fn description(&self, rev: &str) -> Result<String> {
+        self.jj(&["log", "-r", rev, "--no-graph", "-T", "description"])
+    }
+
+    /// Run a Claude hook for the session
+    fn hook(&self, hook: &str) -> Result<()> {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .current_dir(self.path())
+            .env("JJAGENT_POSTTOOL_DELAY_MS", "0")
+            .args(["claude", "hooks", hook])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(format!(r#"{{"session_id":"{}"}}"#, SESSION_ID).as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "{} failed: {}",
+                hook,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_claude_session_split_with_message() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    let output = repo.jjagent(&[
+        "claude",
+        "session",
+        "split",
+        SESSION_ID,
+        "-m",
+        "Follow-up refactor",
+    ])?;
+    assert!(
+        output.status.success(),
+        "split failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // The new part sits between the session and uwc, with the custom description and trailer
+    assert_eq!(
+        repo.description("@-")?,
+        format!("Follow-up refactor\n\nClaude-session-id: {}\n", SESSION_ID)
+    );
+    assert_eq!(repo.description("@")?, "uwc\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_split_json_reports_created_part() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    let output = repo.jjagent(&["split", SESSION_ID, "--json"])?;
+    assert!(
+        output.status.success(),
+        "split failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let report: serde_json::Value = serde_json::from_str(&stdout)?;
+    assert_eq!(report["session_id"], SESSION_ID);
+    assert_eq!(report["part"], 2);
+    let change_id = repo.jj(&["log", "-r", "@-", "--no-graph", "-T", "change_id"])?;
+    assert_eq!(report["change_id"], change_id.trim());
+
+    Ok(())
+}
+
+#[test]
+fn test_session_split_without_message_uses_split_title() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(repo.path())
+        .env("JJAGENT_DETERMINISTIC", "1")
+        .args(["session", "split", SESSION_ID])
+        .output()?;
+    assert!(
+        output.status.success(),
+        "split failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    assert_eq!(
+        repo.description("@-")?,
+        format!(
+            "jjagent: session split-te (split 1970-01-01T00:00:00Z)\n\nClaude-session-id: {}\n",
+            SESSION_ID
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_session_split_diverged_working_copy() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    // Move @ onto an unrelated branch: the session is no longer an ancestor
+    repo.jj(&["new", "root()", "-m", "elsewhere"])?;
+
+    let output = repo.jjagent(&["session", "split", SESSION_ID])?;
+    assert!(!output.status.success(), "split should refuse to run");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("is not an ancestor of @"),
+        "got: {}",
+        stderr
+    );
+
+    // Nothing was inserted
+    assert_eq!(repo.description("@-")?, "");
+
+    Ok(())
+}
+
+#[test]
+fn test_session_split_multiple_sessions() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    // A second part of the same session between the first part and uwc
+    repo.jj(&[
+        "new",
+        "--insert-before",
+        "@",
+        "--no-edit",
+        "-m",
+        &format!("Second part\n\nClaude-session-id: {}", SESSION_ID),
+    ])?;
+    let second = repo.jj(&["log", "-r", "@-", "--no-graph", "-T", "change_id"])?;
+    assert_eq!(
+        jjagent::jj::find_furthest_session_change_in(SESSION_ID, Some(repo.path()))?
+            .map(|change| change.change_id),
+        Some(second.trim().to_string())
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(repo.path())
+        .env("JJAGENT_DETERMINISTIC", "1")
+        .args(["session", "split", SESSION_ID])
+        .output()?;
+    assert!(
+        output.status.success(),
+        "split failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // The new part is titled after the latest part, not the first one
+    assert_eq!(
+        repo.description("@-")?,
+        format!(
+            "Second part (split 1970-01-01T00:00:00Z)\n\nClaude-session-id: {}\n",
+            SESSION_ID
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_session_split_empty_commit() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    // Uncommitted edits in the working copy
+    std::fs::write(repo.path().join("wip.txt"), "work in progress")?;
+
+    let output = repo.jjagent(&["session", "split", SESSION_ID, "-m", "Next step"])?;
+    assert!(
+        output.status.success(),
+        "split failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // The inserted part is empty and the edits stay in @
+    let part_files = repo.jj(&["diff", "-r", "@-", "--name-only"])?;
+    assert_eq!(part_files, "", "new part should be empty");
+    let wc_files = repo.jj(&["diff", "-r", "@", "--name-only"])?;
+    assert_eq!(wc_files.trim(), "wip.txt");
+    assert_eq!(
+        std::fs::read_to_string(repo.path().join("wip.txt"))?,
+        "work in progress"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_session_split_working_copy_unchanged() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let change_id = |rev: &str| repo.jj(&["log", "-r", rev, "--no-graph", "-T", "change_id"]);
+    let working_copy = change_id("@")?;
+    let session = change_id("@-")?;
+
+    let output = repo.jjagent(&["claude", "session", "split", SESSION_ID, "-m", "Part two"])?;
+    assert!(
+        output.status.success(),
+        "split failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // @ is the same change, with the new part between it and the session
+    assert_eq!(change_id("@")?, working_copy);
+    assert_eq!(change_id("@--")?, session);
+    assert!(repo.description("@-")?.starts_with("Part two\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_session_split_working_copy_is_session() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    // Work directly on the session change
+    repo.jj(&["edit", "@-"])?;
+    std::fs::write(repo.path().join("session.txt"), "session work")?;
+
+    let output = repo.jjagent(&["session", "split", SESSION_ID, "-m", "Part two"])?;
+    assert!(
+        output.status.success(),
+        "split failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // @ moved onto a new child of the session; the session kept its edits
+    assert!(repo.description("@")?.starts_with("Part two\n"));
+    let session_files = repo.jj(&["diff", "-r", "@-", "--name-only"])?;
+    assert_eq!(session_files.trim(), "session.txt");
+
+    let parts = repo.jj(&[
+        "log",
+        "-r",
+        &format!(
+            "description(substring:\"Claude-session-id: {}\")",
+            SESSION_ID
+        ),
+        "--no-graph",
+        "-T",
+        "change_id ++ \"\\n\"",
+    ])?;
+    assert_eq!(parts.lines().count(), 2, "got: {}", parts);
+
+    Ok(())
+}
+
+#[test]
+fn test_edit_after_splitting_a_conflict_part_lands_in_split_part() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    // A "pt. 2" like the hooks create on conflicts, between the session and uwc
+    repo.jj(&[
+        "new",
+        "--insert-before",
+        "@",
+        "--no-edit",
+        "-m",
+        &format!(
+            "jjagent: session split-te pt. 2\n\nClaude-session-id: {}",
+            SESSION_ID
+        ),
+    ])?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(repo.path())
+        .env("JJAGENT_DETERMINISTIC", "1")
+        .args(["split", SESSION_ID])
+        .output()?;
+    assert!(
+        output.status.success(),
+        "split failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let split_part = repo.jj(&["log", "-r", "@-", "--no-graph", "-T", "change_id"])?;
+
+    // The split part has no " pt. N" number, but it's the furthest part, so edits go there
+    repo.hook("PreToolUse")?;
+    std::fs::write(repo.path().join("after-split.txt"), "edit\n")?;
+    repo.hook("PostToolUse")?;
+
+    assert_eq!(
+        repo.jj(&["log", "-r", "@-", "--no-graph", "-T", "change_id"])?,
+        split_part
+    );
+    let files = repo.jj(&["diff", "-r", "@-", "--name-only"])?;
+    assert_eq!(files.trim(), "after-split.txt");
+    assert_eq!(
+        jjagent::jj::find_all_session_changes_in(SESSION_ID, Some(repo.path()))?.len(),
+        3,
+        "no new part should be created"
+    );
+
+    Ok(())
+}
@@ -1,6 +1,9 @@
 use jjagent::session::{
-    SessionId, format_precommit_message, format_session_message, format_session_part_message,
+    DEFAULT_SESSION_MESSAGE_TEMPLATE, SessionId, TranscriptEdit, format_precommit_message,
+    format_session_message, format_session_part_message, parse_transcript_edits,
+    render_session_title, summarize_prompt,
 };
+use std::path::Path;
 
 #[test]
 fn test_session_id_from_full() {
@@ -71,6 +74,40 @@ fn test_format_session_part_message_higher_parts() {
     );
 }
 
+#[test]
+fn test_render_session_title_custom_template() {
+    let session_id = SessionId::from_full("abcd1234-5678-90ab-cdef-1234567890ab");
+    let template = "chore(claude): session {short}";
+
+    assert_eq!(
+        render_session_title(template, &session_id, None),
+        "chore(claude): session abcd1234"
+    );
+    // Templates without {part} get the usual suffix on later parts
+    assert_eq!(
+        render_session_title(template, &session_id, Some(2)),
+        "chore(claude): session abcd1234 pt. 2"
+    );
+    assert_eq!(
+        render_session_title("claude {full} [{part}]", &session_id, Some(3)),
+        "claude abcd1234-5678-90ab-cdef-1234567890ab [3]"
+    );
+    assert_eq!(
+        render_session_title("claude {short} [{part}]", &session_id, None),
+        "claude abcd1234 [1]"
+    );
+}
+
+#[test]
+fn test_render_session_title_default_template() {
+    let session_id = SessionId::from_full("abcd1234-5678-90ab-cdef-1234567890ab");
+
+    assert_eq!(
+        render_session_title(DEFAULT_SESSION_MESSAGE_TEMPLATE, &session_id, Some(2)),
+        "jjagent: session abcd1234 pt. 2"
+    );
+}
+
 #[test]
 fn test_commit_message_with_trailer_format() {
     // Ensure the trailer format follows RFC 2822-like convention
@@ -83,3 +120,146 @@ fn test_commit_message_with_trailer_format() {
     // Trailer should be at the end
     assert!(message.ends_with("abcd1234-5678-90ab-cdef-1234567890ab"));
 }
+
+#[test]
+fn test_summarize_prompt_uses_latest_typed_prompt() {
+    // Tool results, meta entries and slash command wrappers after the prompt are skipped
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/transcript.jsonl");
+    assert_eq!(
+        summarize_prompt(&fixture).as_deref(),
+        Some("Fix the login redirect loop")
+    );
+}
+
+#[test]
+fn test_summarize_prompt_truncates_long_prompts() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("transcript.jsonl");
+    let prompt = "Refactor the lock module so that acquiring and releasing share one code path and stale locks are reported";
+    std::fs::write(
+        &path,
+        serde_json::json!({"type": "user", "message": {"role": "user", "content": prompt}})
+            .to_string(),
+    )
+    .unwrap();
+
+    let summary = summarize_prompt(&path).unwrap();
+    assert!(summary.chars().count() <= 72, "got: {}", summary);
+    assert_eq!(
+        summary,
+        "Refactor the lock module so that acquiring and releasing share one..."
+    );
+}
+
+#[test]
+fn test_summarize_prompt_without_transcript() {
+    let dir = tempfile::TempDir::new().unwrap();
+    assert_eq!(summarize_prompt(&dir.path().join("missing.jsonl")), None);
+
+    let empty = dir.path().join("empty.jsonl");
+    std::fs::write(&empty, "not json\n").unwrap();
+    assert_eq!(summarize_prompt(&empty), None);
+}
+
+/// Write a transcript with one assistant tool use per entry
+fn transcript_with(dir: &tempfile::TempDir, lines: &[serde_json::Value]) -> std::path::PathBuf {
+    let path = dir.path().join("transcript.jsonl");
+    let text: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    std::fs::write(&path, text.join("\n")).unwrap();
+    path
+}
+
+fn tool_use(id: &str, name: &str, input: serde_json::Value) -> serde_json::Value {
+    serde_json::json!({
+        "type": "assistant",
+        "message": {"role": "assistant", "content": [
+            {"type": "tool_use", "id": id, "name": name, "input": input}
+        ]}
+    })
+}
+
+#[test]
+fn test_parse_transcript_edits() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = transcript_with(
+        &dir,
+        &[
+            tool_use(
+                "t1",
+                "Write",
+                serde_json::json!({"file_path": "/repo/a.txt", "content": "one\n"}),
+            ),
+            tool_use(
+                "t2",
+                "Read",
+                serde_json::json!({"file_path": "/repo/a.txt"}),
+            ),
+            tool_use(
+                "t3",
+                "Edit",
+                serde_json::json!({"file_path": "/repo/a.txt", "old_string": "one", "new_string": "two"}),
+            ),
+            // A failed edit didn't change anything, so it isn't replayed
+            tool_use(
+                "t4",
+                "Edit",
+                serde_json::json!({"file_path": "/repo/a.txt", "old_string": "missing", "new_string": "x"}),
+            ),
+            serde_json::json!({
+                "type": "user",
+                "message": {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "t4", "is_error": true, "content": "not found"}
+                ]}
+            }),
+            tool_use(
+                "t5",
+                "MultiEdit",
+                serde_json::json!({"file_path": "/repo/a.txt", "edits": [
+                    {"old_string": "t", "new_string": "T", "replace_all": true}
+                ]}),
+            ),
+        ],
+    );
+
+    assert_eq!(
+        parse_transcript_edits(&path).unwrap(),
+        vec![
+            TranscriptEdit::Write {
+                file_path: "/repo/a.txt".to_string(),
+                content: "one\n".to_string(),
+            },
+            TranscriptEdit::Edit {
+                file_path: "/repo/a.txt".to_string(),
+                old_string: "one".to_string(),
+                new_string: "two".to_string(),
+                replace_all: false,
+            },
+            TranscriptEdit::Edit {
+                file_path: "/repo/a.txt".to_string(),
+                old_string: "t".to_string(),
+                new_string: "T".to_string(),
+                replace_all: true,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_parse_transcript_edits_rejects_unsupported_tools() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = transcript_with(
+        &dir,
+        &[tool_use(
+            "t1",
+            "NotebookEdit",
+            serde_json::json!({"notebook_path": "/repo/a.ipynb", "new_source": "x"}),
+        )],
+    );
+
+    let error = parse_transcript_edits(&path).unwrap_err().to_string();
+    assert!(
+        error.contains("Can't replay NotebookEdit"),
+        "got: {}",
+        error
+    );
+}
@@ -0,0 +1,159 @@
+use anyhow::Result;
+use std::process::Command;
+use tempfile::TempDir;
+
+struct TestRepo {
+    dir: TempDir,
+}
+
+impl TestRepo {
+    fn new() -> Result<Self> {
+        let dir = TempDir::new()?;
+
+        // Initialize jj repo
+        let init_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["git", "init"])
+            .output()?;
+
+        if !init_output.status.success() {
+            anyhow::bail!(
+                "Failed to init jj repo: {}",
+                String::from_utf8_lossy(&init_output.stderr)
+            );
+        }
+
+        // Disable watchman for tests
+        let config_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["config", "set", "--repo", "fsmonitor.backend", "none"])
+            .output()?;
+
+        if !config_output.status.success() {
+            anyhow::bail!(
+                "Failed to disable watchman: {}",
+                String::from_utf8_lossy(&config_output.stderr)
+            );
+        }
+
+        Ok(Self { dir })
+    }
+
+    fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+
+    /// Run a jj command in the repo and return its trimmed stdout
+    fn jj(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("jj")
+            .current_dir(self.path())
+            .args(args)
+            .output()?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj {:?} failed: {}",
+                args,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Run `jjagent session set-trailer` with the given arguments
+    fn set_trailer(&self, args: &[&str]) -> Result<std::process::Output> {
+        Ok(Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .current_dir(self.path())
+            .args(["session", "set-trailer"])
+            .args(args)
+            .output()?)
+    }
+
+    fn description(&self, change_id: &str) -> Result<String> {
+        jjagent::jj::get_commit_description_in(change_id, Some(self.path()))
+    }
+}
+
+const SESSION_ID: &str = "trailer-12345678-1234-5678-90ab-cdef12345678";
+
+fn repo_with_session() -> Result<(TestRepo, String)> {
+    let repo = TestRepo::new()?;
+    let message = format!(
+        "jjagent: session trailer\n\nClaude-session-id: {}",
+        SESSION_ID
+    );
+    repo.jj(&["describe", "-m", &message])?;
+    repo.jj(&["new", "-m", "uwc"])?;
+    let session = repo.jj(&["log", "-r", "@-", "--no-graph", "-T", "change_id"])?;
+    Ok((repo, session))
+}
+
+#[test]
+fn test_set_replace_and_remove_trailer() -> Result<()> {
+    let (repo, session) = repo_with_session()?;
+
+    let output = repo.set_trailer(&[SESSION_ID, "Ticket", "ABC-1"])?;
+    assert!(
+        output.status.success(),
+        "set-trailer failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        repo.description(&session)?,
+        format!(
+            "jjagent: session trailer\n\nClaude-session-id: {}\nTicket: ABC-1",
+            SESSION_ID
+        )
+    );
+
+    // Setting again replaces rather than appends
+    assert!(
+        repo.set_trailer(&[SESSION_ID, "Ticket", "ABC-2"])?
+            .status
+            .success()
+    );
+    let description = repo.description(&session)?;
+    assert!(description.contains("Ticket: ABC-2"));
+    assert!(!description.contains("ABC-1"));
+
+    assert!(
+        repo.set_trailer(&[SESSION_ID, "Ticket", "--remove"])?
+            .status
+            .success()
+    );
+    assert_eq!(
+        repo.description(&session)?,
+        format!(
+            "jjagent: session trailer\n\nClaude-session-id: {}",
+            SESSION_ID
+        )
+    );
+
+    // Session detection is unaffected
+    assert_eq!(
+        jjagent::jj::find_session_change_anywhere_in(SESSION_ID, Some(repo.path()))?
+            .map(|change| change.change_id),
+        Some(session)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_set_trailer_refuses_reserved_keys() -> Result<()> {
+    let (repo, session) = repo_with_session()?;
+
+    for key in ["Claude-session-id", "claude-precommit-session-id"] {
+        let output = repo.set_trailer(&[SESSION_ID, key, "other"])?;
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("reserved"));
+    }
+
+    let output = repo.set_trailer(&[SESSION_ID, "Claude-session-id", "--remove"])?;
+    assert!(!output.status.success());
+
+    assert!(repo.description(&session)?.contains(SESSION_ID));
+
+    Ok(())
+}
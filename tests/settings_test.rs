@@ -0,0 +1,467 @@
+#![cfg(unix)]
+
+use anyhow::Result;
+use std::os::unix::fs::symlink;
+use tempfile::TempDir;
+
+/// Lay out a versioned install with a stable shim symlinked to it:
+/// cellar/0.5.0/jjagent (real binary) <- bin/jjagent (symlink)
+fn versioned_install(dir: &TempDir) -> Result<(std::path::PathBuf, std::path::PathBuf)> {
+    let cellar = dir.path().join("cellar").join("0.5.0");
+    let bin = dir.path().join("bin");
+    std::fs::create_dir_all(&cellar)?;
+    std::fs::create_dir_all(&bin)?;
+
+    let real = cellar.join("jjagent");
+    std::fs::write(&real, "")?;
+    let shim = bin.join("jjagent");
+    symlink(&real, &shim)?;
+
+    Ok((real, shim))
+}
+
+#[test]
+fn test_stable_executable_path_prefers_symlink_on_path() -> Result<()> {
+    let dir = TempDir::new()?;
+    let (real, shim) = versioned_install(&dir)?;
+    let path_var = std::env::join_paths([dir.path().join("elsewhere"), dir.path().join("bin")])?;
+
+    let stable = jjagent::stable_executable_path(&real, Some(&path_var));
+    assert_eq!(stable, shim);
+
+    // Settings generated from the shim are identical no matter which real binary runs
+    let settings = jjagent::format_claude_settings_for(&stable, jjagent::DEFAULT_TOOL_MATCHER);
+    assert!(settings.contains(&format!("{} claude hooks PreToolUse", shim.display())));
+    assert_eq!(
+        settings,
+        jjagent::format_claude_settings_for(&stable, jjagent::DEFAULT_TOOL_MATCHER)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_stable_executable_path_falls_back_to_current_exe() -> Result<()> {
+    let dir = TempDir::new()?;
+    let (real, _shim) = versioned_install(&dir)?;
+
+    // Nothing on PATH points at the binary
+    let path_var = std::env::join_paths([dir.path().join("elsewhere")])?;
+    assert_eq!(
+        jjagent::stable_executable_path(&real, Some(&path_var)),
+        real
+    );
+    assert_eq!(jjagent::stable_executable_path(&real, None), real);
+
+    Ok(())
+}
+
+#[test]
+fn test_invoked_executable_path_keeps_symlink() -> Result<()> {
+    let dir = TempDir::new()?;
+    let (real, shim) = versioned_install(&dir)?;
+    let path_var = std::env::join_paths([dir.path().join("elsewhere"), dir.path().join("bin")])?;
+    let invoked = |argv0: &str, path_var: Option<&std::ffi::OsStr>| {
+        jjagent::invoked_executable_path(argv0.as_ref(), &real, path_var, dir.path())
+    };
+
+    // Invoked by name, found on PATH
+    assert_eq!(invoked("jjagent", Some(&path_var)), Some(shim.clone()));
+    assert_eq!(invoked("jjagent", None), None);
+    // Invoked by an absolute or relative path
+    assert_eq!(invoked(shim.to_str().unwrap(), None), Some(shim.clone()));
+    assert_eq!(
+        invoked("bin/jjagent", None),
+        Some(dir.path().join("bin/jjagent"))
+    );
+
+    // A path to some other binary is ignored
+    let other = dir.path().join("bin").join("other");
+    std::fs::write(&other, "")?;
+    assert_eq!(invoked(other.to_str().unwrap(), None), None);
+
+    Ok(())
+}
+
+#[test]
+fn test_settings_use_invoked_symlink() -> Result<()> {
+    let dir = TempDir::new()?;
+    let shim = dir.path().join("jjagent");
+    symlink(env!("CARGO_BIN_EXE_jjagent"), &shim)?;
+
+    let output = std::process::Command::new(&shim)
+        .args(["claude", "settings"])
+        .env_remove("JJAGENT_EXECUTABLE_PATH")
+        .output()?;
+    assert!(output.status.success());
+    let settings: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(
+        settings["hooks"]["PreToolUse"][0]["hooks"][0]["command"],
+        format!("{} claude hooks PreToolUse", shim.display())
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_settings_use_executable_path_override() -> Result<()> {
+    for extra_args in [&[][..], &["--resolve-symlinks"][..]] {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .args(["claude", "settings"])
+            .args(extra_args)
+            .env("JJAGENT_EXECUTABLE_PATH", "/opt/tools/jjagent")
+            .output()?;
+        assert!(output.status.success());
+        let settings: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+        for event in ["PreToolUse", "PostToolUse", "Stop", "Notification"] {
+            assert_eq!(
+                settings["hooks"][event][0]["hooks"][0]["command"],
+                format!("/opt/tools/jjagent claude hooks {}", event),
+                "args: {:?}",
+                extra_args
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_stale_hook_commands_compares_resolved_binaries() -> Result<()> {
+    let dir = TempDir::new()?;
+    let (real, shim) = versioned_install(&dir)?;
+
+    // Hooks installed via the shim still point at the current binary
+    let settings: serde_json::Value = serde_json::from_str(&jjagent::format_claude_settings_for(
+        &shim,
+        jjagent::DEFAULT_TOOL_MATCHER,
+    ))?;
+    assert!(jjagent::stale_hook_commands(&settings, &real).is_empty());
+
+    // Hooks installed from an old versioned path are reported
+    let old = dir.path().join("cellar").join("0.4.2").join("jjagent");
+    let settings: serde_json::Value = serde_json::from_str(&jjagent::format_claude_settings_for(
+        &old,
+        jjagent::DEFAULT_TOOL_MATCHER,
+    ))?;
+    let stale = jjagent::stale_hook_commands(&settings, &real);
+    assert_eq!(stale.len(), 6, "got: {:?}", stale);
+    assert!(stale[0].starts_with(&old.display().to_string()));
+
+    Ok(())
+}
+
+#[test]
+fn test_migrate_rewrites_legacy_commands_and_keeps_other_hooks() -> Result<()> {
+    let dir = TempDir::new()?;
+    let path = dir.path().join("settings.json");
+    std::fs::write(
+        &path,
+        r#"{
+  "model": "opus",
+  "hooks": {
+    "PostToolUse": [
+      {
+        "matcher": "Edit|MultiEdit|Write",
+        "hooks": [
+          { "type": "command", "command": "cargo fmt" },
+          { "type": "command", "command": "/usr/local/bin/jjagent claude hooks PostToolUse" }
+        ]
+      }
+    ],
+    "Stop": [
+      {
+        "hooks": [
+          { "type": "command", "command": "/usr/local/bin/jjagent claude session split $SESSION" }
+        ]
+      }
+    ]
+  }
+}"#,
+    )?;
+
+    let rewritten = jjagent::migrate_claude_settings_file(&path)?;
+    assert_eq!(
+        rewritten,
+        vec![(
+            "/usr/local/bin/jjagent claude session split $SESSION".to_string(),
+            "/usr/local/bin/jjagent split $SESSION".to_string()
+        )]
+    );
+
+    let settings: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+    assert_eq!(settings["model"], "opus");
+    let post = &settings["hooks"]["PostToolUse"][0]["hooks"];
+    assert_eq!(post[0]["command"], "cargo fmt");
+    assert_eq!(
+        post[1]["command"],
+        "/usr/local/bin/jjagent claude hooks PostToolUse"
+    );
+    assert_eq!(
+        settings["hooks"]["Stop"][0]["hooks"][0]["command"],
+        "/usr/local/bin/jjagent split $SESSION"
+    );
+
+    // Already migrated: nothing to do
+    assert!(jjagent::migrate_claude_settings_file(&path)?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_settings_matcher_applies_to_tool_hooks_only() -> Result<()> {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .args(["claude", "settings", "--matcher", "Edit|Write|Bash"])
+        .output()?;
+    assert!(output.status.success());
+    let settings: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(
+        settings["hooks"]["PreToolUse"][0]["matcher"],
+        "Edit|Write|Bash"
+    );
+    assert_eq!(
+        settings["hooks"]["PostToolUse"][0]["matcher"],
+        "Edit|Write|Bash"
+    );
+    assert!(settings["hooks"]["Stop"][0].get("matcher").is_none());
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .args(["claude", "settings", "--matcher", " "])
+        .output()?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("must not be empty"), "got: {}", stderr);
+
+    Ok(())
+}
+
+#[test]
+fn test_merge_replaces_jjagent_hooks_and_keeps_the_rest() -> Result<()> {
+    let dir = TempDir::new()?;
+    let path = dir.path().join(".claude").join("settings.json");
+    let merge = || {
+        std::process::Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .args(["claude", "settings", "--merge"])
+            .arg(&path)
+            .output()
+    };
+
+    // Creates the file when it doesn't exist
+    assert!(merge()?.status.success());
+    let created: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+    assert_eq!(created["hooks"]["PreToolUse"].as_array().unwrap().len(), 1);
+
+    std::fs::write(
+        &path,
+        r#"{
+  "model": "opus",
+  "permissions": { "allow": ["Bash(cargo test:*)"] },
+  "hooks": {
+    "PostToolUse": [
+      {
+        "matcher": "Edit|MultiEdit|Write",
+        "hooks": [
+          { "type": "command", "command": "cargo fmt" },
+          { "type": "command", "command": "/old/bin/jjagent claude hooks PostToolUse" }
+        ]
+      }
+    ],
+    "PreToolUse": [
+      {
+        "matcher": "Edit|MultiEdit|Write",
+        "hooks": [
+          { "type": "command", "command": "/old/bin/jjagent claude hooks PreToolUse" }
+        ]
+      }
+    ]
+  }
+}"#,
+    )?;
+
+    // Merging twice gives the same result
+    assert!(merge()?.status.success());
+    let once = std::fs::read_to_string(&path)?;
+    assert!(merge()?.status.success());
+    assert_eq!(std::fs::read_to_string(&path)?, once);
+
+    let settings: serde_json::Value = serde_json::from_str(&once)?;
+    assert_eq!(settings["model"], "opus");
+    assert_eq!(settings["permissions"]["allow"][0], "Bash(cargo test:*)");
+    // User keys keep their order
+    let keys: Vec<&String> = settings.as_object().unwrap().keys().collect();
+    assert_eq!(keys, ["model", "permissions", "hooks"]);
+
+    let commands = |event: &str| -> Vec<String> {
+        settings["hooks"][event]
+            .as_array()
+            .unwrap()
+            .iter()
+            .flat_map(|m| m["hooks"].as_array().unwrap().iter())
+            .map(|h| h["command"].as_str().unwrap().to_string())
+            .collect()
+    };
+    let post = commands("PostToolUse");
+    assert_eq!(post.len(), 2, "got: {:?}", post);
+    assert_eq!(post[0], "cargo fmt");
+    assert!(post[1].ends_with(" claude hooks PostToolUse"));
+    assert!(!post[1].starts_with("/old/bin/"));
+    let pre = commands("PreToolUse");
+    assert_eq!(pre.len(), 1, "got: {:?}", pre);
+    assert!(!pre[0].starts_with("/old/bin/"));
+    assert_eq!(commands("Stop").len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_uninstall_removes_only_jjagent_hooks() -> Result<()> {
+    let dir = TempDir::new()?;
+    let path = dir.path().join("settings.json");
+    std::fs::write(
+        &path,
+        r#"{
+  "model": "opus",
+  "hooks": {
+    "PostToolUse": [
+      {
+        "matcher": "Edit|MultiEdit|Write",
+        "hooks": [
+          { "type": "command", "command": "cargo fmt" },
+          { "type": "command", "command": "/old/bin/jjagent claude hooks PostToolUse" }
+        ]
+      }
+    ],
+    "PreToolUse": [
+      {
+        "matcher": "Edit|MultiEdit|Write",
+        "hooks": [
+          { "type": "command", "command": "/Users/me/.cargo/bin/jjagent claude hooks PreToolUse" }
+        ]
+      }
+    ],
+    "Stop": [
+      { "hooks": [ { "type": "command", "command": "notify-done" } ] }
+    ]
+  }
+}"#,
+    )?;
+    let uninstall = || {
+        std::process::Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .arg("uninstall")
+            .arg(&path)
+            .output()
+    };
+
+    let output = uninstall()?;
+    assert!(output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("Removed 2 jjagent hook(s)"),
+        "got: {}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    let settings: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+    assert_eq!(settings["model"], "opus");
+    assert_eq!(
+        settings["hooks"]["PostToolUse"][0]["hooks"],
+        serde_json::json!([{ "type": "command", "command": "cargo fmt" }])
+    );
+    // PreToolUse only had jjagent's hook, so the event is gone entirely
+    assert!(settings["hooks"].get("PreToolUse").is_none());
+    assert_eq!(
+        settings["hooks"]["Stop"][0]["hooks"][0]["command"],
+        "notify-done"
+    );
+
+    // Nothing left to remove the second time
+    let output = uninstall()?;
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Removed 0 jjagent hook(s)"));
+
+    Ok(())
+}
+
+#[test]
+fn test_merge_scope_project_targets_repo_root() -> Result<()> {
+    let dir = TempDir::new()?;
+    std::fs::create_dir(dir.path().join(".git"))?;
+    let subdir = dir.path().join("src").join("nested");
+    std::fs::create_dir_all(&subdir)?;
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .args(["claude", "settings", "--merge", "--scope", "project"])
+        .current_dir(&subdir)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let path = dir.path().join(".claude").join("settings.json");
+    let settings: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+    assert_eq!(settings["hooks"]["PreToolUse"].as_array().unwrap().len(), 1);
+    assert!(!subdir.join(".claude").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_merge_scope_project_outside_repo_fails() -> Result<()> {
+    let dir = TempDir::new()?;
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .args(["claude", "settings", "--merge", "--scope", "project"])
+        .current_dir(dir.path())
+        .output()?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("not in a jj or git repository"),
+        "got: {}",
+        stderr
+    );
+    assert!(!dir.path().join(".claude").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_merge_scope_user_targets_home() -> Result<()> {
+    let home = TempDir::new()?;
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .args(["claude", "settings", "--merge", "--scope", "user"])
+        .env("HOME", home.path())
+        .output()?;
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(home.path().join(".claude").join("settings.json").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_merge_needs_file_or_scope() -> Result<()> {
+    let dir = TempDir::new()?;
+    let run = |args: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .args(["claude", "settings"])
+            .args(args)
+            .current_dir(dir.path())
+            .output()
+    };
+
+    assert!(!run(&["--merge"])?.status.success());
+    assert!(
+        !run(&["--merge", "settings.json", "--scope", "user"])?
+            .status
+            .success()
+    );
+    // --scope only picks the file for --merge
+    assert!(!run(&["--scope", "user"])?.status.success());
+    assert!(!dir.path().join("settings.json").exists());
+
+    Ok(())
+}
@@ -0,0 +1,120 @@
+#![cfg(unix)]
+
+use anyhow::Result;
+use serial_test::serial;
+use std::os::unix::fs::PermissionsExt;
+use tempfile::TempDir;
+
+/// Install a fake jj that records its arguments and prints nothing
+fn recording_jj(dir: &TempDir) -> Result<std::path::PathBuf> {
+    let script = dir.path().join("jj-recorder");
+    std::fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\nprintf '%s\\n' \"$@\" >> '{}'\necho >> '{}'\n",
+            dir.path().join("args").display(),
+            dir.path().join("args").display()
+        ),
+    )?;
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+    Ok(script)
+}
+
+fn recorded_invocations(dir: &TempDir) -> Result<Vec<Vec<String>>> {
+    let recorded = std::fs::read_to_string(dir.path().join("args"))?;
+    Ok(recorded
+        .split("\n\n")
+        .map(|args| args.lines().map(String::from).collect::<Vec<_>>())
+        .filter(|args| !args.is_empty())
+        .collect())
+}
+
+fn passes_sign_flag(args: &[String]) -> bool {
+    args.windows(2)
+        .any(|w| w[0] == "--config" && w[1] == "signing.behavior=own")
+}
+
+fn create_session_change_with_sign(sign: Option<&str>) -> Result<Vec<Vec<String>>> {
+    let dir = TempDir::new()?;
+    let jj = recording_jj(&dir)?;
+    unsafe {
+        std::env::set_var("JJAGENT_JJ_BIN", &jj);
+        match sign {
+            Some(value) => std::env::set_var("JJAGENT_SIGN", value),
+            None => std::env::remove_var("JJAGENT_SIGN"),
+        }
+    }
+
+    let session_id = jjagent::session::SessionId::from_full("sign-test-12345678");
+    let result = jjagent::jj::create_session_change_titled_in(
+        &session_id,
+        None,
+        None,
+        None,
+        Some(dir.path()),
+    );
+
+    unsafe {
+        std::env::remove_var("JJAGENT_JJ_BIN");
+        std::env::remove_var("JJAGENT_SIGN");
+    }
+    result?;
+    recorded_invocations(&dir)
+}
+
+#[test]
+#[serial]
+fn test_sign_passes_signing_config_to_new() -> Result<()> {
+    // The session message's blank line splits it across entries, so only the first is the command
+    let invocations = create_session_change_with_sign(Some("1"))?;
+    assert!(passes_sign_flag(&invocations[0]), "got: {:?}", invocations);
+    assert!(invocations[0].contains(&"new".to_string()));
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_no_signing_config_without_sign() -> Result<()> {
+    let invocations = create_session_change_with_sign(None)?;
+    assert!(!passes_sign_flag(&invocations[0]), "got: {:?}", invocations);
+
+    let invocations = create_session_change_with_sign(Some("0"))?;
+    assert!(!passes_sign_flag(&invocations[0]), "got: {:?}", invocations);
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_sign_passes_signing_config_to_squashes() -> Result<()> {
+    let dir = TempDir::new()?;
+    let jj = recording_jj(&dir)?;
+    unsafe {
+        std::env::set_var("JJAGENT_JJ_BIN", &jj);
+        std::env::set_var("JJAGENT_SIGN", "1");
+    }
+
+    // The fake jj prints nothing, so conflict counts and the uwc description come back empty
+    let result =
+        jjagent::jj::squash_precommit_into_session_in("pre", "session", "uwc", Some(dir.path()));
+
+    unsafe {
+        std::env::remove_var("JJAGENT_JJ_BIN");
+        std::env::remove_var("JJAGENT_SIGN");
+    }
+    result?;
+
+    let squashes: Vec<_> = recorded_invocations(&dir)?
+        .into_iter()
+        .filter(|args| args.contains(&"squash".to_string()))
+        .collect();
+    assert_eq!(squashes.len(), 2, "got: {:?}", squashes);
+    assert!(squashes.iter().all(|args| passes_sign_flag(args)));
+
+    // Read-only queries are left alone
+    let others: Vec<_> = recorded_invocations(&dir)?
+        .into_iter()
+        .filter(|args| !args.contains(&"squash".to_string()))
+        .collect();
+    assert!(!others.iter().any(|args| passes_sign_flag(args)));
+    Ok(())
+}
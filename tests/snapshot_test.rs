@@ -205,6 +205,40 @@ impl TestRepo {
         self.dir.path()
     }
 
+    /// Run a jj command in the repo, failing the test if it fails
+    fn jj(&self, args: &[&str]) -> Result<()> {
+        let output = Command::new("jj")
+            .current_dir(self.path())
+            .args(args)
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj {:?} failed: {}",
+                args,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(())
+    }
+
+    /// Compact snapshot: one line per commit followed by its changed paths
+    fn summary_snapshot(&self) -> Result<String> {
+        let template = r#"if(root, "root", description.first_line()) ++ "\n""#;
+
+        let output = Command::new("jj")
+            .current_dir(self.path())
+            .env("JJ_CONFIG", "/dev/null")
+            .args(["log", "--no-graph", "-T", template, "--summary"])
+            .output()
+            .context("Failed to run jj log")?;
+
+        if !output.status.success() {
+            anyhow::bail!("jj log failed: {}", String::from_utf8_lossy(&output.stderr));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
     /// Get a deterministic snapshot of the repo state (log + all changes)
     fn snapshot(&self) -> Result<String> {
         let template =
@@ -452,7 +486,7 @@ fn test_create_session_change() -> Result<()> {
 
     // Now @ is at precommit, create session change
     // Should insert between uwc and base
-    jjagent::jj::create_session_change_in(&session_id, Some(repo.path()))?;
+    jjagent::jj::create_session_change_in(&session_id, None, Some(repo.path()))?;
 
     // Verify the structure: @ precommit -> uwc -> session -> base -> root
     let snapshot = repo.snapshot()?;
@@ -461,6 +495,151 @@ fn test_create_session_change() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_create_session_change_below_base() -> Result<()> {
+    let repo = TestRepo::new_with_uwc()?;
+    let session_id = jjagent::session::SessionId::from_full("create-base-12345678");
+
+    // A two-deep user stack: base -> uwc -> uwc 2, then the precommit on top
+    for args in [
+        ["new", "-m", "uwc 2"],
+        ["new", "-m", "jjagent: precommit create-b"],
+    ] {
+        let output = Command::new("jj")
+            .current_dir(repo.path())
+            .args(args)
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to run jj {:?}: {}",
+                args,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+    }
+
+    // The session goes below both user changes instead of right under @-
+    jjagent::jj::create_session_change_in(&session_id, Some("@--"), Some(repo.path()))?;
+
+    let snapshot = repo.snapshot()?;
+    insta::assert_snapshot!("create_session_change_below_base", snapshot);
+
+    Ok(())
+}
+
+#[test]
+fn test_create_session_change_rejects_base_outside_ancestors() -> Result<()> {
+    let repo = TestRepo::new_with_uwc()?;
+    let session_id = jjagent::session::SessionId::from_full("create-base-12345678");
+
+    let result = jjagent::jj::create_session_change_in(&session_id, Some("@"), Some(repo.path()));
+    let error = format!("{:#}", result.unwrap_err());
+    assert!(
+        error.contains("not an ancestor of the working copy"),
+        "got: {}",
+        error
+    );
+    assert!(
+        jjagent::jj::find_all_session_changes_in(session_id.full(), Some(repo.path()))?.is_empty()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_create_session_change_preserves_precommit_timestamp() -> Result<()> {
+    let repo = TestRepo::new_with_uwc()?;
+    let session_id = jjagent::session::SessionId::from_full("timestamp-12345678");
+
+    // Date uwc and the precommit deterministically, an hour apart
+    let jj_at = |timestamp: &str, args: &[&str]| -> Result<()> {
+        let output = Command::new("jj")
+            .current_dir(repo.path())
+            .env("JJ_TIMESTAMP", timestamp)
+            .args(args)
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj {:?} failed: {}",
+                args,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(())
+    };
+    jj_at(
+        "2024-01-01T09:00:00+00:00",
+        &["describe", "-m", "uwc", "--reset-author"],
+    )?;
+    jj_at(
+        "2024-01-01T10:00:00+00:00",
+        &["new", "-m", "jjagent: precommit timestam"],
+    )?;
+
+    let timestamp = jjagent::jj::get_author_timestamp_in("@", Some(repo.path()))?;
+    assert_eq!(timestamp, "2024-01-01T10:00:00+00:00");
+    jjagent::jj::create_session_change_titled_in(
+        &session_id,
+        None,
+        Some(&timestamp),
+        None,
+        Some(repo.path()),
+    )?;
+
+    // The session change is dated with the precommit, not when it was created
+    let output = Command::new("jj")
+        .current_dir(repo.path())
+        .args([
+            "log",
+            "-r",
+            "@ | @- | @--",
+            "--no-graph",
+            "-T",
+            r#"description.first_line() ++ "  " ++ author.timestamp().utc().format("%Y-%m-%d %H:%M:%S") ++ "\n""#,
+        ])
+        .output()?;
+    assert!(output.status.success());
+    insta::assert_snapshot!(
+        "create_session_change_preserves_timestamp",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    // Only the author date is moved: the session change was still committed, and its
+    // operations recorded, at the current time
+    let output = Command::new("jj")
+        .current_dir(repo.path())
+        .args([
+            "log",
+            "-r",
+            "@--",
+            "--no-graph",
+            "-T",
+            r#"committer.timestamp().utc().format("%Y")"#,
+        ])
+        .output()?;
+    assert!(output.status.success());
+    assert_ne!(String::from_utf8_lossy(&output.stdout), "2024");
+    let output = Command::new("jj")
+        .current_dir(repo.path())
+        .args([
+            "op",
+            "log",
+            "--limit",
+            "2",
+            "--no-graph",
+            "-T",
+            r#"self.time().start().utc().format("%Y") ++ "\n""#,
+            "--ignore-working-copy",
+        ])
+        .output()?;
+    assert!(output.status.success());
+    let years = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(years.lines().count(), 2, "got: {}", years);
+    assert!(years.lines().all(|year| year != "2024"), "got: {}", years);
+
+    Ok(())
+}
+
 #[test]
 fn test_create_session_change_verifies_position() -> Result<()> {
     let repo = TestRepo::new_with_uwc()?;
@@ -483,7 +662,7 @@ fn test_create_session_change_verifies_position() -> Result<()> {
     }
 
     // Now @ is at precommit, create session change
-    jjagent::jj::create_session_change_in(&session_id, Some(repo.path()))?;
+    jjagent::jj::create_session_change_in(&session_id, None, Some(repo.path()))?;
 
     // Verify that:
     // 1. Session change is between uwc and base
@@ -744,13 +923,14 @@ fn test_squash_happy_path() -> Result<()> {
     let precommit_id = jjagent::jj::get_change_id_in("@", Some(repo.path()))?;
 
     // Create session change
-    jjagent::jj::create_session_change_in(&session_id, Some(repo.path()))?;
+    jjagent::jj::create_session_change_in(&session_id, None, Some(repo.path()))?;
 
     // Get uwc and session change IDs
     let uwc_id = jjagent::jj::get_change_id_in("@-", Some(repo.path()))?;
     let session_change_id =
         jjagent::jj::find_session_change_anywhere_in("squash-test-12345678", Some(repo.path()))?
-            .expect("Session change should exist");
+            .expect("Session change should exist")
+            .change_id;
 
     // Attempt squash (should succeed without introducing conflicts)
     let new_conflicts = jjagent::jj::squash_precommit_into_session_in(
@@ -797,13 +977,14 @@ fn test_squash_with_changes() -> Result<()> {
     let precommit_id = jjagent::jj::get_change_id_in("@", Some(repo.path()))?;
 
     // Create session change
-    jjagent::jj::create_session_change_in(&session_id, Some(repo.path()))?;
+    jjagent::jj::create_session_change_in(&session_id, None, Some(repo.path()))?;
 
     // Get uwc and session change IDs
     let uwc_id = jjagent::jj::get_change_id_in("@-", Some(repo.path()))?;
     let session_change_id =
         jjagent::jj::find_session_change_anywhere_in("squash-changes-12345678", Some(repo.path()))?
-            .expect("Session change should exist");
+            .expect("Session change should exist")
+            .change_id;
 
     // Attempt squash
     let new_conflicts = jjagent::jj::squash_precommit_into_session_in(
@@ -850,15 +1031,17 @@ fn test_handle_squash_conflicts() -> Result<()> {
     let precommit_id = jjagent::jj::get_change_id_in("@", Some(repo.path()))?;
 
     // Create session change
-    jjagent::jj::create_session_change_in(&session_id, Some(repo.path()))?;
+    jjagent::jj::create_session_change_in(&session_id, None, Some(repo.path()))?;
 
     // Get uwc and session change IDs
     let uwc_id = jjagent::jj::get_change_id_in("@-", Some(repo.path()))?;
     let session_change_id =
         jjagent::jj::find_session_change_anywhere_in("conflict-test-12345678", Some(repo.path()))?
-            .expect("Session change should exist");
+            .expect("Session change should exist")
+            .change_id;
 
     // Attempt squash (should introduce conflicts due to same file modification)
+    let operation = jjagent::jj::current_operation_id_in(Some(repo.path()))?;
     let _new_conflicts = jjagent::jj::squash_precommit_into_session_in(
         &precommit_id,
         &session_change_id,
@@ -868,7 +1051,13 @@ fn test_handle_squash_conflicts() -> Result<()> {
 
     // For this test, we'll handle conflicts regardless of whether they were introduced
     // (simulating the conflict path from the workflow)
-    jjagent::jj::handle_squash_conflicts_in(&session_id, 2, Some(repo.path()))?;
+    jjagent::jj::handle_squash_conflicts_in(
+        &session_id,
+        2,
+        &uwc_id,
+        &operation,
+        Some(repo.path()),
+    )?;
 
     // Verify final state: @ new wc -> pt. 2 -> uwc -> session -> base -> root
     let snapshot = repo.snapshot()?;
@@ -907,15 +1096,17 @@ fn test_conflict_path_multiple_parts() -> Result<()> {
     let precommit_id = jjagent::jj::get_change_id_in("@", Some(repo.path()))?;
 
     // Create session change
-    jjagent::jj::create_session_change_in(&session_id, Some(repo.path()))?;
+    jjagent::jj::create_session_change_in(&session_id, None, Some(repo.path()))?;
 
     // Get uwc and session change IDs
     let uwc_id = jjagent::jj::get_change_id_in("@-", Some(repo.path()))?;
     let session_change_id =
         jjagent::jj::find_session_change_anywhere_in("multipart-test-12345678", Some(repo.path()))?
-            .expect("Session change should exist");
+            .expect("Session change should exist")
+            .change_id;
 
     // Attempt squash
+    let operation = jjagent::jj::current_operation_id_in(Some(repo.path()))?;
     jjagent::jj::squash_precommit_into_session_in(
         &precommit_id,
         &session_change_id,
@@ -924,12 +1115,21 @@ fn test_conflict_path_multiple_parts() -> Result<()> {
     )?;
 
     // Simulate conflict path for part 2
-    jjagent::jj::handle_squash_conflicts_in(&session_id, 2, Some(repo.path()))?;
+    jjagent::jj::handle_squash_conflicts_in(
+        &session_id,
+        2,
+        &uwc_id,
+        &operation,
+        Some(repo.path()),
+    )?;
 
     // Verify we can create part 3 as well
     // Add more changes
     std::fs::write(repo.path().join("part2.txt"), "second part")?;
 
+    // The new working copy now holds uwc, and the next precommit goes on top of it
+    let uwc_id = jjagent::jj::get_change_id_in("@", Some(repo.path()))?;
+
     // Simulate another pretool -> posttool cycle
     let precommit2_output = Command::new("jj")
         .current_dir(repo.path())
@@ -950,8 +1150,29 @@ fn test_conflict_path_multiple_parts() -> Result<()> {
 
     std::fs::write(repo.path().join("part3.txt"), "third part")?;
 
-    // Handle conflicts again for part 3
-    jjagent::jj::handle_squash_conflicts_in(&session_id, 3, Some(repo.path()))?;
+    // Snapshot the edit into the precommit, as PostToolUse's first jj commands do
+    repo.jj(&["status"])?;
+    let precommit_id = jjagent::jj::get_change_id_in("@", Some(repo.path()))?;
+    let session_change_id =
+        jjagent::jj::find_furthest_session_change_in("multipart-test-12345678", Some(repo.path()))?
+            .expect("Session change should exist")
+            .change_id;
+
+    // Squash again, then simulate the conflict path for part 3
+    let operation = jjagent::jj::current_operation_id_in(Some(repo.path()))?;
+    jjagent::jj::squash_precommit_into_session_in(
+        &precommit_id,
+        &session_change_id,
+        &uwc_id,
+        Some(repo.path()),
+    )?;
+    jjagent::jj::handle_squash_conflicts_in(
+        &session_id,
+        3,
+        &uwc_id,
+        &operation,
+        Some(repo.path()),
+    )?;
 
     // Verify final state shows multiple parts
     let snapshot = repo.snapshot()?;
@@ -1046,6 +1267,67 @@ fn test_integration_multiple_conflicts() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_conflict_part_numbers_skip_abandoned_parts() -> Result<()> {
+    let repo = TestRepo::new_with_uwc()?;
+    let session_id = "integration-renumber-12345678";
+    let simulator = ClaudeSimulator::new(repo.path(), session_id);
+
+    // base -> session -> pt. 2 -> pt. 3 -> uwc (@)
+    for title in [
+        "jjagent: session integrat",
+        "jjagent: session integrat pt. 2",
+        "jjagent: session integrat pt. 3",
+    ] {
+        repo.jj(&[
+            "new",
+            "--insert-before",
+            "@",
+            "--no-edit",
+            "-m",
+            &format!("{}\n\nClaude-session-id: {}", title, session_id),
+        ])?;
+    }
+
+    // Drop pt. 2, leaving two parts whose highest number is 3
+    repo.jj(&["abandon", r#"description(substring:"integrat pt. 2")"#])?;
+
+    // The user's file conflicts with Claude's edit when squashed into pt. 3
+    std::fs::write(repo.path().join("shared.txt"), "user's version")?;
+    simulator.write_file("shared.txt", "claude's version")?;
+
+    let output = Command::new("jj")
+        .current_dir(repo.path())
+        .args([
+            "log",
+            "--no-graph",
+            "-r",
+            &format!(
+                r#"description(substring:"Claude-session-id: {}")"#,
+                session_id
+            ),
+            "-T",
+            r#"description.first_line() ++ "\n""#,
+        ])
+        .output()?;
+    assert!(output.status.success());
+    let mut titles: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(String::from)
+        .collect();
+    titles.sort();
+    assert_eq!(
+        titles,
+        [
+            "jjagent: session integrat",
+            "jjagent: session integrat pt. 3",
+            "jjagent: session integrat pt. 4",
+        ]
+    );
+
+    Ok(())
+}
+
 #[test]
 fn test_empty_changes() -> Result<()> {
     let repo = TestRepo::new_with_uwc()?;
@@ -1495,6 +1777,10 @@ fn test_pretool_hook_fails_when_not_at_head() -> Result<()> {
         "stopReason should mention 'not at a head', got: {}",
         stop_reason
     );
+    assert_eq!(
+        json.get("stopReasonCode").and_then(|v| v.as_str()),
+        Some("NOT_AT_HEAD")
+    );
 
     Ok(())
 }
@@ -1646,6 +1932,10 @@ fn test_pretool_hook_fails_with_conflicts() -> Result<()> {
         "stopReason should mention 'conflicts', got: {}",
         stop_reason
     );
+    assert_eq!(
+        json.get("stopReasonCode").and_then(|v| v.as_str()),
+        Some("HAS_CONFLICTS")
+    );
 
     Ok(())
 }
@@ -1782,12 +2072,13 @@ fn test_pretool_hook_fails_on_session_change() -> Result<()> {
 
     // Create a session change
     let session_id_struct = jjagent::session::SessionId::from_full(session_id);
-    jjagent::jj::create_session_change_in(&session_id_struct, Some(repo.path()))?;
+    jjagent::jj::create_session_change_in(&session_id_struct, None, Some(repo.path()))?;
 
     // Find the session change and edit to it
     let session_change_id =
         jjagent::jj::find_session_change_anywhere_in(session_id, Some(repo.path()))?
-            .context("Session change should exist")?;
+            .context("Session change should exist")?
+            .change_id;
 
     let edit_output = Command::new("jj")
         .current_dir(repo.path())
@@ -1843,17 +2134,27 @@ fn test_pretool_hook_fails_on_session_change() -> Result<()> {
         "stopReason should mention 'session change' and 'Claude-session-id', got: {}",
         stop_reason
     );
+    assert_eq!(
+        json.get("stopReasonCode").and_then(|v| v.as_str()),
+        Some("ON_SESSION_CHANGE")
+    );
 
     Ok(())
 }
 
+/// Replace the wall-clock timestamp in default split titles so snapshots are stable
+fn redact_split_timestamps(snapshot: &str) -> String {
+    let re = regex::Regex::new(r"\(split \d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}Z\)").unwrap();
+    re.replace_all(snapshot, "(split [timestamp])").to_string()
+}
+
 #[test]
 fn test_split_change_basic() -> Result<()> {
     let repo = TestRepo::new_with_uwc()?;
     let session_id = jjagent::session::SessionId::from_full("split-basic-12345678");
 
     // Create a session change
-    jjagent::jj::create_session_change_in(&session_id, Some(repo.path()))?;
+    jjagent::jj::create_session_change_in(&session_id, None, Some(repo.path()))?;
 
     // Get the session change ID
     let log_output = Command::new("jj")
@@ -1881,10 +2182,10 @@ fn test_split_change_basic() -> Result<()> {
     std::fs::write(repo.path().join("file1.txt"), "content1")?;
 
     // Split at session, inserting a new change before @ (which is currently at commit1)
-    jjagent::jj::split_change(&session_change_id, Some(repo.path()))?;
+    jjagent::jj::split_change(&session_change_id, None, Some(repo.path()))?;
 
     // Verify: @ should have a new session part inserted between session and commit1
-    let snapshot = repo.snapshot()?;
+    let snapshot = redact_split_timestamps(&repo.snapshot()?);
     insta::assert_snapshot!("split_change_basic", snapshot);
 
     Ok(())
@@ -1895,7 +2196,7 @@ fn test_split_change_not_ancestor() -> Result<()> {
     let repo = TestRepo::new_with_uwc()?;
 
     // Try to split on a non-existent/non-ancestor change
-    let result = jjagent::jj::split_change("nonexistent", Some(repo.path()));
+    let result = jjagent::jj::split_change("nonexistent", None, Some(repo.path()));
 
     // Should fail
     assert!(
@@ -1920,7 +2221,7 @@ fn test_split_change_with_session_id() -> Result<()> {
     let session_id = jjagent::session::SessionId::from_full("split-sid-test-12345678");
 
     // Create a session change
-    jjagent::jj::create_session_change_in(&session_id, Some(repo.path()))?;
+    jjagent::jj::create_session_change_in(&session_id, None, Some(repo.path()))?;
 
     // Create a commit on the session (will become the parent of @)
     let log_output = Command::new("jj")
@@ -1948,22 +2249,49 @@ fn test_split_change_with_session_id() -> Result<()> {
 
     // Split using the FULL SESSION ID instead of change ID
     // This tests that session ID lookup works
-    jjagent::jj::split_change(session_id.full(), Some(repo.path()))?;
+    jjagent::jj::split_change(session_id.full(), None, Some(repo.path()))?;
 
     // Verify: @ should have a new session part inserted between session and commit1
-    let snapshot = repo.snapshot()?;
+    let snapshot = redact_split_timestamps(&repo.snapshot()?);
     insta::assert_snapshot!("split_change_with_session_id", snapshot);
 
     Ok(())
 }
 
+#[test]
+fn test_split_change_custom_description() -> Result<()> {
+    let repo = TestRepo::new_with_uwc()?;
+    let session_id = jjagent::session::SessionId::from_full("split-msg-test-12345678");
+
+    jjagent::jj::create_session_change_in(&session_id, None, Some(repo.path()))?;
+    let session_change_id =
+        jjagent::jj::find_session_change_anywhere_in(session_id.full(), Some(repo.path()))?
+            .expect("Session change should exist")
+            .change_id;
+
+    Command::new("jj")
+        .current_dir(repo.path())
+        .args(["new", "-m", "commit1", &session_change_id])
+        .output()?;
+
+    std::fs::write(repo.path().join("file1.txt"), "content1")?;
+
+    // The message replaces the "pt. N" title; the trailer is still added
+    jjagent::jj::split_change(session_id.full(), Some("Extract parser"), Some(repo.path()))?;
+
+    let snapshot = repo.snapshot()?;
+    insta::assert_snapshot!("split_change_custom_description", snapshot);
+
+    Ok(())
+}
+
 #[test]
 fn test_split_change_with_session() -> Result<()> {
     let repo = TestRepo::new_with_uwc()?;
     let session_id = jjagent::session::SessionId::from_full("split-test-12345678");
 
     // Create a session change
-    jjagent::jj::create_session_change_in(&session_id, Some(repo.path()))?;
+    jjagent::jj::create_session_change_in(&session_id, None, Some(repo.path()))?;
 
     // Get the session change ID
     let log_output = Command::new("jj")
@@ -1991,10 +2319,10 @@ fn test_split_change_with_session() -> Result<()> {
     std::fs::write(repo.path().join("session_file.txt"), "session content")?;
 
     // Split at the session change
-    jjagent::jj::split_change(&session_change_id, Some(repo.path()))?;
+    jjagent::jj::split_change(&session_change_id, None, Some(repo.path()))?;
 
     // Verify the new structure
-    let snapshot = repo.snapshot()?;
+    let snapshot = redact_split_timestamps(&repo.snapshot()?);
     insta::assert_snapshot!("split_change_with_session", snapshot);
 
     Ok(())
@@ -2344,3 +2672,128 @@ fn test_move_session_into_integration() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_reflow_moves_session_below_uwc() -> Result<()> {
+    let repo = TestRepo::new_with_uwc()?;
+    let session_id = "reflow-test-12345678";
+
+    // Broken order: base -> uwc -> session -> @ (as if the user rebased the session on top)
+    std::fs::write(repo.path().join("user.txt"), "user content")?;
+    let session_message = format!(
+        "jjagent: session reflow-t\n\nClaude-session-id: {}",
+        session_id
+    );
+    let output = Command::new("jj")
+        .current_dir(repo.path())
+        .args(["new", "-m", &session_message])
+        .output()?;
+    assert!(output.status.success());
+    std::fs::write(repo.path().join("claude.txt"), "claude content")?;
+    let output = Command::new("jj")
+        .current_dir(repo.path())
+        .args(["new"])
+        .output()?;
+    assert!(output.status.success());
+
+    let moved = jjagent::jj::reflow_uwc_above_sessions_in(Some(repo.path()))?;
+    assert_eq!(moved.len(), 1);
+
+    // Repaired: base -> session -> uwc -> @, with content unchanged
+    let snapshot = repo.snapshot()?;
+    insta::assert_snapshot!("reflow_moves_session_below_uwc", snapshot);
+
+    // Running again is a noop
+    assert!(jjagent::jj::reflow_uwc_above_sessions_in(Some(repo.path()))?.is_empty());
+
+    Ok(())
+}
+
+/// Build: base -> session (a.txt) -> session pt. 2 (b.txt) -> uwc -> precommit
+/// where the precommit edits both a.txt and b.txt
+fn split_session_with_precommit(session_id: &str) -> Result<TestRepo> {
+    let repo = TestRepo::new()?;
+    repo.jj(&["describe", "-m", "base"])?;
+
+    let trailer = format!("Claude-session-id: {}", session_id);
+    repo.jj(&[
+        "new",
+        "-m",
+        &format!("jjagent: session absorb-t\n\n{}", trailer),
+    ])?;
+    std::fs::write(repo.path().join("a.txt"), "one\n")?;
+    repo.jj(&[
+        "new",
+        "-m",
+        &format!("jjagent: session absorb-t pt. 2\n\n{}", trailer),
+    ])?;
+    std::fs::write(repo.path().join("b.txt"), "two\n")?;
+    repo.jj(&["new", "-m", "uwc"])?;
+    repo.jj(&["new", "-m", "jjagent: precommit absorb-t"])?;
+    std::fs::write(repo.path().join("a.txt"), "one edited\n")?;
+    std::fs::write(repo.path().join("b.txt"), "two edited\n")?;
+
+    Ok(repo)
+}
+
+/// Fold the precommit into the session the way finalize_precommit does for a strategy
+fn fold_precommit(
+    repo: &TestRepo,
+    session_id: &str,
+    strategy: jjagent::jj::SquashStrategy,
+) -> Result<()> {
+    if strategy == jjagent::jj::SquashStrategy::Absorb {
+        assert!(jjagent::jj::absorb_precommit_into_session_in(
+            session_id,
+            Some(repo.path())
+        )?);
+    }
+
+    let precommit_id = jjagent::jj::get_full_change_id_in("@", Some(repo.path()))?;
+    let uwc_id = jjagent::jj::get_full_change_id_in("@-", Some(repo.path()))?;
+    let session_change_id =
+        jjagent::jj::find_session_change_anywhere_in(session_id, Some(repo.path()))?
+            .expect("Session change should exist")
+            .change_id;
+    let new_conflicts = jjagent::jj::squash_precommit_into_session_in(
+        &precommit_id,
+        &session_change_id,
+        &uwc_id,
+        Some(repo.path()),
+    )?;
+    assert!(!new_conflicts, "Should not introduce new conflicts");
+
+    Ok(())
+}
+
+#[test]
+fn test_squash_strategy_squash_folds_into_newest_part() -> Result<()> {
+    let session_id = "absorb-test-12345678";
+    let repo = split_session_with_precommit(session_id)?;
+
+    fold_precommit(&repo, session_id, jjagent::jj::SquashStrategy::Squash)?;
+
+    // Both edits land in pt. 2
+    let snapshot = repo.summary_snapshot()?;
+    insta::assert_snapshot!("squash_strategy_squash", snapshot);
+
+    Ok(())
+}
+
+#[test]
+fn test_squash_strategy_absorb_attributes_hunks_per_part() -> Result<()> {
+    let session_id = "absorb-test-12345678";
+    let repo = split_session_with_precommit(session_id)?;
+
+    fold_precommit(&repo, session_id, jjagent::jj::SquashStrategy::Absorb)?;
+
+    // Each edit lands in the part that created the file
+    let snapshot = repo.summary_snapshot()?;
+    insta::assert_snapshot!("squash_strategy_absorb", snapshot);
+    assert_eq!(
+        std::fs::read_to_string(repo.path().join("a.txt"))?,
+        "one edited\n"
+    );
+
+    Ok(())
+}
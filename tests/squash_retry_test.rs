@@ -0,0 +1,110 @@
+#![cfg(unix)]
+
+//! Tests for retrying the precommit squash when jj reports a stale working copy
+
+use anyhow::Result;
+use serial_test::serial;
+use std::os::unix::fs::PermissionsExt;
+use tempfile::TempDir;
+
+/// Install a fake jj that records each invocation's arguments on one line and fails
+/// `jj squash` with `squash_error` the first `failures` times
+fn flaky_jj(dir: &TempDir, failures: usize, squash_error: &str) -> Result<std::path::PathBuf> {
+    let script = dir.path().join("jj-flaky");
+    let args = dir.path().join("args");
+    let failed = dir.path().join("failed");
+    std::fs::write(
+        &script,
+        format!(
+            r#"#!/bin/sh
+echo "$*" >> '{args}'
+if [ "$1" = "--no-pager" ] && [ "$2" = "squash" ]; then
+    count=$(cat '{failed}' 2>/dev/null || echo 0)
+    if [ "$count" -lt {failures} ]; then
+        echo $((count + 1)) > '{failed}'
+        echo '{squash_error}' >&2
+        exit 1
+    fi
+fi
+"#,
+            args = args.display(),
+            failed = failed.display(),
+        ),
+    )?;
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+    Ok(script)
+}
+
+/// Run the precommit squash against a flaky jj, returning its result and the jj subcommands run
+fn squash_with_flaky_jj(
+    failures: usize,
+    squash_error: &str,
+) -> Result<(Result<bool>, Vec<String>)> {
+    let dir = TempDir::new()?;
+    let jj = flaky_jj(&dir, failures, squash_error)?;
+    unsafe {
+        std::env::set_var("JJAGENT_JJ_BIN", &jj);
+    }
+    let result = jjagent::jj::squash_precommit_into_session_in(
+        "precommit",
+        "session",
+        "uwc",
+        Some(dir.path()),
+    );
+    unsafe {
+        std::env::remove_var("JJAGENT_JJ_BIN");
+    }
+
+    let subcommands = std::fs::read_to_string(dir.path().join("args"))?
+        .lines()
+        .filter_map(|line| {
+            let mut args = line.split(' ').skip_while(|a| a.starts_with('-'));
+            match args.next()? {
+                "workspace" => Some("update-stale".to_string()),
+                "squash" => Some("squash".to_string()),
+                _ => None,
+            }
+        })
+        .collect();
+    Ok((result, subcommands))
+}
+
+const STALE: &str = "Error: The working copy is stale (not updated since operation 1a2b3c).";
+
+#[test]
+#[serial]
+fn test_squash_retries_after_stale_working_copy() -> Result<()> {
+    let (result, subcommands) = squash_with_flaky_jj(2, STALE)?;
+    assert!(result.is_ok(), "got: {:?}", result);
+    assert_eq!(
+        subcommands,
+        [
+            "squash",
+            "update-stale",
+            "squash",
+            "update-stale",
+            "squash", // precommit into the session
+            "squash", // uwc restored
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_squash_gives_up_after_three_attempts() -> Result<()> {
+    let (result, subcommands) = squash_with_flaky_jj(3, STALE)?;
+    let error = format!("{:#}", result.expect_err("squash should fail"));
+    assert!(error.contains("working copy is stale"), "got: {}", error);
+    assert_eq!(subcommands.iter().filter(|s| *s == "squash").count(), 3);
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_squash_does_not_retry_other_errors() -> Result<()> {
+    let (result, subcommands) = squash_with_flaky_jj(1, "Error: Commit abc is immutable")?;
+    assert!(result.is_err());
+    assert_eq!(subcommands, ["squash"]);
+    Ok(())
+}
@@ -0,0 +1,185 @@
+use anyhow::Result;
+use std::process::Command;
+use tempfile::TempDir;
+
+const SESSION_ID: &str = "squash-into-1111-2222-3333-444444444444";
+
+struct TestRepo {
+    dir: TempDir,
+}
+
+impl TestRepo {
+    fn new() -> Result<Self> {
+        let dir = TempDir::new()?;
+
+        // Initialize jj repo
+        let init_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["git", "init"])
+            .output()?;
+
+        if !init_output.status.success() {
+            anyhow::bail!(
+                "Failed to init jj repo: {}",
+                String::from_utf8_lossy(&init_output.stderr)
+            );
+        }
+
+        // Disable watchman for tests
+        let config_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["config", "set", "--repo", "fsmonitor.backend", "none"])
+            .output()?;
+
+        if !config_output.status.success() {
+            anyhow::bail!(
+                "Failed to disable watchman: {}",
+                String::from_utf8_lossy(&config_output.stderr)
+            );
+        }
+
+        let repo = Self { dir };
+        // Build: feature -> session -> uwc (@)
+        std::fs::write(repo.path().join("feature.txt"), "feature\n")?;
+        repo.jj(&["describe", "-m", "Add feature\n\nReviewed-by: someone"])?;
+        repo.jj(&[
+            "new",
+            "-m",
+            &format!(
+                "jjagent: session squash-i\n\nClaude-session-id: {}",
+                SESSION_ID
+            ),
+        ])?;
+        std::fs::write(repo.path().join("claude.txt"), "claude\n")?;
+        repo.jj(&["new", "-m", "uwc"])?;
+        std::fs::write(repo.path().join("user.txt"), "user\n")?;
+
+        Ok(repo)
+    }
+
+    fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+
+    /// Run a jj command in the repo and return its stdout
+    fn jj(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("jj")
+            .current_dir(self.path())
+            .args(args)
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj {:?} failed: {}",
+                args,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn jjagent(&self, args: &[&str]) -> Result<std::process::Output> {
+        Ok(Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .current_dir(self.path())
+            .args(args)
+            .output()?)
+    }
+
+    fn change_id(&self, rev: &str) -> Result<String> {
+        Ok(self
+            .jj(&["log", "-r", rev, "--no-graph", "-T", "change_id"])?
+            .trim()
+            .to_string())
+    }
+
+    fn description(&self, rev: &str) -> Result<String> {
+        self.jj(&["log", "-r", rev, "--no-graph", "-T", "description"])
+    }
+}
+
+#[test]
+fn test_squash_folds_session_into_target() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let feature = repo.change_id("@--")?;
+    let session = repo.change_id("@-")?;
+
+    let output = repo.jjagent(&["squash", SESSION_ID, "--into", &feature])?;
+    assert!(
+        output.status.success(),
+        "squash failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), feature);
+
+    // The target keeps its description and now holds the session's file
+    assert_eq!(
+        repo.description(&feature)?,
+        "Add feature\n\nReviewed-by: someone\n"
+    );
+    let files = repo.jj(&["diff", "-r", &feature, "--name-only"])?;
+    assert!(files.contains("claude.txt"), "got: {}", files);
+
+    // The session change is gone and @ is still the uwc on top of the target
+    assert!(repo.jj(&["log", "-r", &session, "--no-graph"]).is_err());
+    assert!(jjagent::jj::find_all_session_changes_in(SESSION_ID, Some(repo.path()))?.is_empty());
+    assert_eq!(repo.change_id("@-")?, feature);
+    assert_eq!(repo.description("@")?, "uwc\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_squash_refuses_target_that_is_not_an_ancestor() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let before = repo.jj(&["op", "log", "--no-graph", "-T", "id", "--limit", "1"])?;
+
+    let output = repo.jjagent(&["squash", SESSION_ID, "--into", "@"])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("not an ancestor"), "got: {}", stderr);
+
+    let after = repo.jj(&["op", "log", "--no-graph", "-T", "id", "--limit", "1"])?;
+    assert_eq!(before, after);
+
+    Ok(())
+}
+
+#[test]
+fn test_squash_rolls_back_on_conflicts() -> Result<()> {
+    let repo = TestRepo::new()?;
+    // Edit feature.txt in a change between the target and the session, and again in the
+    // session, so moving the session's edit onto the target conflicts
+    repo.jj(&["new", "--insert-before", "@-", "-m", "middle"])?;
+    std::fs::write(repo.path().join("feature.txt"), "middle\n")?;
+    repo.jj(&["next", "--edit"])?;
+    std::fs::write(repo.path().join("feature.txt"), "claude\n")?;
+    repo.jj(&["next", "--edit"])?;
+    let feature = repo.change_id("@---")?;
+    let before = repo.jj(&["op", "log", "--no-graph", "-T", "id", "--limit", "1"])?;
+
+    let output = repo.jjagent(&["squash", SESSION_ID, "--into", &feature])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("would introduce conflicts"),
+        "got: {}",
+        stderr
+    );
+
+    // The repo is back where it started
+    assert_eq!(
+        repo.jj(&["log", "-r", "conflicts()", "--no-graph", "-T", "change_id"])?,
+        ""
+    );
+    assert_eq!(
+        jjagent::jj::find_all_session_changes_in(SESSION_ID, Some(repo.path()))?.len(),
+        1
+    );
+    let restored = repo.jj(&["op", "log", "--no-graph", "-T", "id", "--limit", "1"])?;
+    assert_ne!(before, restored, "expected a restore operation");
+    assert_eq!(
+        repo.description(&feature)?,
+        "Add feature\n\nReviewed-by: someone\n"
+    );
+
+    Ok(())
+}
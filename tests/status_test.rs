@@ -0,0 +1,177 @@
+use anyhow::Result;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn status(dir: &std::path::Path, args: &[&str]) -> Result<std::process::Output> {
+    Ok(Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(dir)
+        .arg("status")
+        .args(args)
+        .output()?)
+}
+
+fn jj(dir: &std::path::Path, args: &[&str]) -> Result<()> {
+    let output = Command::new("jj").current_dir(dir).args(args).output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "jj {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_status_outside_jj_repo_is_one_line() -> Result<()> {
+    let dir = TempDir::new()?;
+
+    let output = status(dir.path(), &[])?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout, "jjagent: Not in a jj repository\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_status_reports_session_on_at_and_lock() -> Result<()> {
+    let dir = TempDir::new()?;
+    let session_id = "status-1111-2222-3333-444444444444";
+    jj(dir.path(), &["git", "init"])?;
+    jj(
+        dir.path(),
+        &["config", "set", "--repo", "fsmonitor.backend", "none"],
+    )?;
+    jj(
+        dir.path(),
+        &[
+            "describe",
+            "-m",
+            &format!(
+                "jjagent: session status-1\n\nClaude-session-id: {}",
+                session_id
+            ),
+        ],
+    )?;
+    std::fs::write(
+        dir.path().join(".jj").join("jjagent-wc.lock"),
+        format!(
+            r#"{{"pid":4242,"session_id":"{}","acquired_at":0}}"#,
+            session_id
+        ),
+    )?;
+
+    // No --session-id: falls back to the session on @
+    let output = status(dir.path(), &[])?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains(&format!("session: {}\n", session_id)),
+        "got: {}",
+        stdout
+    );
+    assert!(stdout.contains("@ is precommit: no\n"), "got: {}", stdout);
+    assert!(
+        stdout.contains(&format!("@ is session change: yes ({})", session_id)),
+        "got: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains(&format!("lock: held by session {} (pid 4242", session_id)),
+        "got: {}",
+        stdout
+    );
+    assert!(stdout.contains("session change: "), "got: {}", stdout);
+    assert!(!stdout.contains("not found"), "got: {}", stdout);
+
+    // An unknown session has no change
+    let output = status(dir.path(), &["--session-id", "other-session"])?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("session change: not found"),
+        "got: {}",
+        stdout
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_is_jj_repo_in_checks_the_given_directory() -> Result<()> {
+    let repo = TempDir::new()?;
+    jj(repo.path(), &["git", "init"])?;
+    let plain = TempDir::new()?;
+
+    assert!(jjagent::jj::is_jj_repo_in(Some(repo.path())));
+    assert!(!jjagent::jj::is_jj_repo_in(Some(plain.path())));
+
+    Ok(())
+}
+
+#[test]
+fn test_format_watch_line() {
+    let mut state = jjagent::WatchState {
+        lock_holder: None,
+        lock_unreadable: false,
+        session_on_at: None,
+    };
+    assert_eq!(
+        jjagent::format_watch_line(&state),
+        "lock: free | @: no session"
+    );
+
+    state.lock_holder = Some(("watch-1234".to_string(), 42));
+    state.session_on_at = Some("watch-1234".to_string());
+    assert_eq!(
+        jjagent::format_watch_line(&state),
+        "lock: held by watch-1234 (pid 42) | @: session watch-1234"
+    );
+
+    state.lock_holder = None;
+    state.lock_unreadable = true;
+    assert!(jjagent::format_watch_line(&state).starts_with("lock: present but unreadable"));
+}
+
+#[test]
+fn test_watch_state_follows_lock_and_session() -> Result<()> {
+    let dir = TempDir::new()?;
+    let session_id = "watch-1111-2222-3333-444444444444";
+    jj(dir.path(), &["git", "init"])?;
+    jj(
+        dir.path(),
+        &["config", "set", "--repo", "fsmonitor.backend", "none"],
+    )?;
+
+    let before = jjagent::read_watch_state_in(dir.path())?;
+    assert_eq!(before.lock_holder, None);
+    assert_eq!(before.session_on_at, None);
+
+    jj(
+        dir.path(),
+        &[
+            "describe",
+            "-m",
+            &format!(
+                "jjagent: session watch-11\n\nClaude-session-id: {}",
+                session_id
+            ),
+        ],
+    )?;
+    jjagent::lock::acquire_lock_in(session_id, dir.path(), Default::default())?;
+    let during = jjagent::read_watch_state_in(dir.path())?;
+    jjagent::lock::release_lock_in(session_id, dir.path(), Default::default())?;
+
+    assert_eq!(
+        during.lock_holder,
+        Some((session_id.to_string(), std::process::id()))
+    );
+    assert_eq!(during.session_on_at.as_deref(), Some(session_id));
+    assert_ne!(before, during);
+
+    let after = jjagent::read_watch_state_in(dir.path())?;
+    assert_eq!(after.lock_holder, None);
+    assert_eq!(after.session_on_at, during.session_on_at);
+
+    Ok(())
+}
@@ -126,8 +126,15 @@ fn create_test_git_repo() -> TempDir {
 
 /// Run the statusline command with given input
 fn run_statusline(input: &str) -> String {
+    run_statusline_with_env(input, &[])
+}
+
+/// Run the statusline command with given input and extra environment variables
+fn run_statusline_with_env(input: &str, envs: &[(&str, &str)]) -> String {
     let mut child = Command::new(env!("CARGO_BIN_EXE_jjagent"))
         .args(["claude", "statusline"])
+        .env_remove("JJAGENT_STATUSLINE_TEMPLATE")
+        .envs(envs.iter().copied())
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
@@ -265,3 +272,41 @@ fn test_statusline_with_git_repo() {
 
     insta::assert_snapshot!(stripped);
 }
+
+#[test]
+fn test_statusline_with_custom_template() {
+    let repo = create_test_jj_repo();
+    let repo_path = repo.path();
+    let session_id = "template-session-123";
+
+    create_session_change(repo_path, session_id, "Custom template");
+
+    let input = format!(
+        r#"{{"session_id": "{}", "workspace": {{"current_dir": "{}"}}}}"#,
+        session_id,
+        repo_path.display()
+    );
+
+    let output = run_statusline_with_env(
+        &input,
+        &[(
+            "JJAGENT_STATUSLINE_TEMPLATE",
+            r#""● claude " ++ description.first_line()"#,
+        )],
+    );
+    assert_eq!(strip_ansi_codes(&output).trim(), "● claude Custom template");
+
+    // A blank template falls back to the default
+    let output = run_statusline_with_env(&input, &[("JJAGENT_STATUSLINE_TEMPLATE", "  ")]);
+    assert!(strip_ansi_codes(&output).contains("Custom template"));
+    assert!(!strip_ansi_codes(&output).contains("● claude"));
+}
+
+#[test]
+fn test_statusline_suffix() {
+    assert_eq!(jjagent::format_statusline_suffix(1, 0), "");
+    assert_eq!(jjagent::format_statusline_suffix(0, 0), "");
+    assert_eq!(jjagent::format_statusline_suffix(3, 0), " (pt.3)");
+    assert_eq!(jjagent::format_statusline_suffix(1, 2), " (⚠)");
+    assert_eq!(jjagent::format_statusline_suffix(3, 1), " (pt.3 ⚠)");
+}
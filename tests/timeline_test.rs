@@ -0,0 +1,136 @@
+use anyhow::Result;
+use std::process::Command;
+use tempfile::TempDir;
+
+struct TestRepo {
+    dir: TempDir,
+}
+
+impl TestRepo {
+    fn new() -> Result<Self> {
+        let dir = TempDir::new()?;
+
+        // Initialize jj repo
+        let init_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["git", "init"])
+            .output()?;
+
+        if !init_output.status.success() {
+            anyhow::bail!(
+                "Failed to init jj repo: {}",
+                String::from_utf8_lossy(&init_output.stderr)
+            );
+        }
+
+        // Disable watchman for tests
+        let config_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["config", "set", "--repo", "fsmonitor.backend", "none"])
+            .output()?;
+
+        if !config_output.status.success() {
+            anyhow::bail!(
+                "Failed to disable watchman: {}",
+                String::from_utf8_lossy(&config_output.stderr)
+            );
+        }
+
+        Ok(Self { dir })
+    }
+
+    fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+
+    /// Run a jj command in the repo and return its trimmed stdout
+    fn jj(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("jj")
+            .current_dir(self.path())
+            .args(args)
+            .output()?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj {:?} failed: {}",
+                args,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Add a session change touching one file, committed at a fabricated time
+    fn add_session(&self, session_id: &str, file: &str, timestamp: &str) -> Result<()> {
+        std::fs::write(self.path().join(file), session_id)?;
+
+        // Snapshot and describe in one command so the commit gets this timestamp
+        let message = format!(
+            "jjagent: session {}\n\nClaude-session-id: {}",
+            file, session_id
+        );
+        let output = Command::new("jj")
+            .current_dir(self.path())
+            .env("JJ_TIMESTAMP", timestamp)
+            .args(["describe", "-m", &message])
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj describe failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        self.jj(&["new"])?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_timeline_orders_sessions_chronologically() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    // Created out of chronological order
+    repo.add_session("session-b", "b.txt", "2024-03-02T09:00:00+00:00")?;
+    repo.add_session("session-c", "c.txt", "2024-03-03T09:00:00+00:00")?;
+    repo.add_session("session-a", "a.txt", "2024-03-01T09:00:00+00:00")?;
+
+    let timeline = jjagent::build_timeline(jjagent::jj::list_sessions_in(Some(repo.path()))?);
+    let order: Vec<&str> = timeline.iter().map(|s| s.session_id.as_str()).collect();
+    assert_eq!(order, vec!["session-a", "session-b", "session-c"]);
+
+    assert_eq!(timeline[0].files, vec!["a.txt"]);
+    assert_eq!(timeline[0].change_ids.len(), 1);
+    assert!(!timeline[0].has_conflict);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(repo.path())
+        .args(["session", "timeline", "--json"])
+        .output()?;
+    assert!(
+        output.status.success(),
+        "timeline failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let ids: Vec<&str> = json
+        .as_array()
+        .expect("timeline JSON should be an array")
+        .iter()
+        .map(|s| s["session_id"].as_str().unwrap())
+        .collect();
+    assert_eq!(ids, vec!["session-a", "session-b", "session-c"]);
+    assert_eq!(json[0]["committed_at"], "2024-03-01T09:00:00Z");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+        .current_dir(repo.path())
+        .env("TZ", "UTC")
+        .args(["session", "timeline", "--by-day"])
+        .output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let days: Vec<&str> = stdout.lines().filter(|l| l.starts_with("2024")).collect();
+    assert_eq!(days, vec!["2024-03-01", "2024-03-02", "2024-03-03"]);
+
+    Ok(())
+}
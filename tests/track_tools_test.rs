@@ -0,0 +1,132 @@
+//! Tests for JJAGENT_TRACK_TOOLS, which limits the tools the hooks act on
+
+use anyhow::Result;
+use jjagent::hooks::{DEFAULT_TRACK_TOOLS, tool_is_tracked};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+const SESSION_ID: &str = "track-11-2222-3333-4444-555555555555";
+
+#[test]
+fn test_default_tracks_edit_tools_only() {
+    for tool in ["Edit", "MultiEdit", "Write", "NotebookEdit", "Bash"] {
+        assert!(tool_is_tracked(DEFAULT_TRACK_TOOLS, tool), "{}", tool);
+    }
+    for tool in ["Read", "Grep", "Glob", "WebFetch"] {
+        assert!(!tool_is_tracked(DEFAULT_TRACK_TOOLS, tool), "{}", tool);
+    }
+}
+
+#[test]
+fn test_allow_and_deny_entries() {
+    assert!(tool_is_tracked(" Edit , Write ", "Write"));
+    assert!(!tool_is_tracked("Edit,Write", "Bash"));
+    assert!(tool_is_tracked("*", "NotebookEdit"));
+    assert!(!tool_is_tracked("*,!Read", "Read"));
+    assert!(tool_is_tracked("*,!Read", "Grep"));
+    assert!(!tool_is_tracked("!Bash,Bash", "Bash"));
+    assert!(!tool_is_tracked("", "Edit"));
+}
+
+#[test]
+fn test_deny_only_list_applies_to_defaults() {
+    assert!(!tool_is_tracked("!Bash", "Bash"));
+    assert!(tool_is_tracked("!Bash", "Edit"));
+    assert!(tool_is_tracked("!Bash", "NotebookEdit"));
+    assert!(!tool_is_tracked("!Bash", "Read"));
+    assert!(!tool_is_tracked("!Bash,!Write", "Write"));
+}
+
+#[test]
+fn test_pipe_separated_list() {
+    assert!(tool_is_tracked("Edit|Write", "Write"));
+    assert!(!tool_is_tracked("Edit|Write", "Bash"));
+    assert!(tool_is_tracked("Edit | Read, Grep", "Grep"));
+    assert!(!tool_is_tracked("*|!Read", "Read"));
+    assert!(tool_is_tracked("*|!Read", "Glob"));
+}
+
+/// Run a hook for `tool_name` with jj replaced by a script that records its arguments and fails,
+/// so a tracked tool finds no repo. Returns the recorded arguments
+fn run_hook_with_recording_jj(
+    hook: &str,
+    tool_name: &str,
+    track_tools: Option<&str>,
+) -> Result<String> {
+    let dir = TempDir::new()?;
+    let args_file = dir.path().join("args");
+    let script = dir.path().join("jj-recorder");
+    std::fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\nprintf '%s\\n' \"$@\" >> '{}'\nexit 1\n",
+            args_file.display()
+        ),
+    )?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+    }
+
+    let mut command = Command::new(env!("CARGO_BIN_EXE_jjagent"));
+    command
+        .args(["claude", "hooks", hook])
+        .current_dir(dir.path())
+        .env("JJAGENT_JJ_BIN", &script)
+        .env("JJAGENT_POSTTOOL_DELAY_MS", "0")
+        .env_remove("JJAGENT_TRACK_TOOLS")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(track_tools) = track_tools {
+        command.env("JJAGENT_TRACK_TOOLS", track_tools);
+    }
+    let mut child = command.spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(
+            format!(
+                r#"{{"session_id":"{}","tool_name":"{}"}}"#,
+                SESSION_ID, tool_name
+            )
+            .as_bytes(),
+        )?;
+    }
+    let output = child.wait_with_output()?;
+    assert!(
+        output.status.success(),
+        "{} failed: {}",
+        hook,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        !dir.path().join(".jj").exists(),
+        "hook should not have touched the directory"
+    );
+    Ok(std::fs::read_to_string(&args_file).unwrap_or_default())
+}
+
+#[test]
+#[cfg(unix)]
+fn test_untracked_tool_skips_jj_entirely() -> Result<()> {
+    for hook in ["PreToolUse", "PostToolUse"] {
+        let args = run_hook_with_recording_jj(hook, "Read", None)?;
+        assert!(args.is_empty(), "{} ran jj for Read: {}", hook, args);
+
+        let args = run_hook_with_recording_jj(hook, "Bash", Some("*,!Bash"))?;
+        assert!(args.is_empty(), "{} ran jj for denied Bash: {}", hook, args);
+    }
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn test_tracked_tool_runs_jj() -> Result<()> {
+    let args = run_hook_with_recording_jj("PreToolUse", "Edit", None)?;
+    assert!(args.contains("root"), "got: {}", args);
+
+    let args = run_hook_with_recording_jj("PreToolUse", "Read", Some("Read"))?;
+    assert!(args.contains("root"), "got: {}", args);
+    Ok(())
+}
@@ -0,0 +1,224 @@
+use anyhow::Result;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+struct TestRepo {
+    dir: TempDir,
+}
+
+impl TestRepo {
+    fn new() -> Result<Self> {
+        let dir = TempDir::new()?;
+
+        // Initialize jj repo
+        let init_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["git", "init"])
+            .output()?;
+
+        if !init_output.status.success() {
+            anyhow::bail!(
+                "Failed to init jj repo: {}",
+                String::from_utf8_lossy(&init_output.stderr)
+            );
+        }
+
+        // Disable watchman for tests
+        let config_output = Command::new("jj")
+            .current_dir(dir.path())
+            .args(["config", "set", "--repo", "fsmonitor.backend", "none"])
+            .output()?;
+
+        if !config_output.status.success() {
+            anyhow::bail!(
+                "Failed to disable watchman: {}",
+                String::from_utf8_lossy(&config_output.stderr)
+            );
+        }
+
+        Ok(Self { dir })
+    }
+
+    fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+
+    /// Run a jj command in the repo and return its trimmed stdout
+    fn jj(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("jj")
+            .current_dir(self.path())
+            .args(args)
+            .output()?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj {:?} failed: {}",
+                args,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn run_hook(&self, hook_name: &str, session_id: &str) -> Result<std::process::Output> {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_jjagent"))
+            .current_dir(self.path())
+            .env_remove("JJAGENT_DISABLE")
+            .env("JJAGENT_POSTTOOL_DELAY_MS", "0")
+            .args(["claude", "hooks", hook_name])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            write!(
+                stdin,
+                r#"{{"session_id":"{}","tool_name":"Write"}}"#,
+                session_id
+            )?;
+        }
+
+        Ok(child.wait_with_output()?)
+    }
+}
+
+#[test]
+fn test_get_change_id_rejects_multiple_commits() -> Result<()> {
+    let repo = TestRepo::new()?;
+    repo.jj(&["new", "-m", "second"])?;
+
+    let err = jjagent::jj::get_change_id_in("all() ~ root()", Some(repo.path()))
+        .expect_err("two commits should not resolve to one change ID");
+    assert!(
+        err.to_string().contains("resolved to 2 commits"),
+        "got: {}",
+        err
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_short_and_full_change_ids_agree() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let session_id = "idform-1111-2222-3333-444444444444";
+    repo.jj(&[
+        "new",
+        "-m",
+        &format!(
+            "jjagent: session idform\n\nClaude-session-id: {}",
+            session_id
+        ),
+    ])?;
+
+    let short = jjagent::jj::get_change_id_in("@", Some(repo.path()))?;
+    let full = jjagent::jj::get_full_change_id_in("@", Some(repo.path()))?;
+    assert!(
+        full.starts_with(&short) && full.len() > short.len(),
+        "short id {} should be a prefix of full id {}",
+        short,
+        full
+    );
+
+    // The session lookups return the full form, so they compare equal to it
+    let found = jjagent::jj::find_session_change_anywhere_in(session_id, Some(repo.path()))?;
+    assert_eq!(found.map(|c| c.change_id).as_deref(), Some(full.as_str()));
+    repo.jj(&["new", "root()"])?;
+    let found = jjagent::jj::find_session_change_in(session_id, Some(repo.path()))?;
+    assert_eq!(found.map(|c| c.change_id).as_deref(), Some(full.as_str()));
+
+    // Both forms resolve to the same commit
+    let commit_of = |rev: &str| repo.jj(&["log", "-r", rev, "--no-graph", "-T", "commit_id"]);
+    assert_eq!(commit_of(&short)?, commit_of(&full)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_posttool_hook_fails_clearly_when_uwc_is_a_merge() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let session_id = "merge-uwc-12345678-1234-5678-90ab-cdef12345678";
+
+    // Two parents for the precommit, as if @- resolved to more than one commit
+    repo.jj(&["describe", "-m", "left"])?;
+    let left = repo.jj(&["log", "-r", "@", "--no-graph", "-T", "change_id"])?;
+    repo.jj(&["new", "root()", "-m", "right"])?;
+    let right = repo.jj(&["log", "-r", "@", "--no-graph", "-T", "change_id"])?;
+
+    let precommit = jjagent::session::format_precommit_message(
+        &jjagent::session::SessionId::from_full(session_id),
+    );
+    repo.jj(&["new", &left, &right, "-m", &precommit])?;
+    std::fs::write(repo.path().join("file.txt"), "claude")?;
+
+    let output = repo.run_hook("PostToolUse", session_id)?;
+    assert!(!output.status.success());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("does not resolve to exactly one commit"),
+        "Hook should stop with a friendly error, got: {}",
+        stdout
+    );
+
+    // Nothing was squashed or created
+    assert!(jjagent::jj::find_all_session_changes_in(session_id, Some(repo.path()))?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_precommit_detection_with_namespaced_trailer_key() -> Result<()> {
+    let repo = TestRepo::new()?;
+    let session_id = "namespace-12345678-1234-5678-90ab-cdef12345678";
+    let sid = jjagent::session::SessionId::from_full(session_id);
+    let namespaced = jjagent::session::PrecommitConfig {
+        namespace: Some("Acme".to_string()),
+    };
+
+    let precommit = jjagent::session::format_precommit_message_with(&sid, &namespaced);
+    repo.jj(&["new", "-m", &precommit])?;
+
+    assert!(jjagent::jj::is_current_commit_precommit_with_config_in(
+        session_id,
+        &namespaced,
+        Some(repo.path())
+    )?);
+    // The default key doesn't match a namespaced precommit
+    assert!(!jjagent::jj::is_current_commit_precommit_with_config_in(
+        session_id,
+        &jjagent::session::PrecommitConfig::default(),
+        Some(repo.path())
+    )?);
+
+    Ok(())
+}
+
+#[test]
+fn test_check_pretool_invariants_reports_all_facts_about_at() -> Result<()> {
+    let repo = TestRepo::new()?;
+
+    let invariants = jjagent::jj::check_pretool_invariants_in(Some(repo.path()))?;
+    assert_eq!(invariants.session_id, None);
+    assert!(invariants.at_head);
+    assert!(!invariants.has_conflicts);
+
+    // A session change with a descendant: both invariants are violated
+    repo.jj(&[
+        "describe",
+        "-m",
+        "work\n\nClaude-session-id: invariants-1234",
+    ])?;
+    repo.jj(&["new", "-m", "child"])?;
+    repo.jj(&["prev", "--edit"])?;
+
+    let invariants = jjagent::jj::check_pretool_invariants_in(Some(repo.path()))?;
+    assert_eq!(invariants.session_id.as_deref(), Some("invariants-1234"));
+    assert!(!invariants.at_head);
+    assert!(!invariants.has_conflicts);
+
+    Ok(())
+}